@@ -0,0 +1,73 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use webgl_grid::document::{Color, Document, ShapeId, ShapeKind, Style, Transform};
+use webgl_grid::geometry::{Point, Rect};
+use webgl_grid::spatial::SpatialIndex;
+
+/// Scatters `count` filled rectangles over a square board with roughly 100
+/// shapes per 1000x1000 area, so a viewport query returns a similar number of
+/// shapes at every size.
+fn board(count: usize) -> Document {
+    let mut seed = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed >> 11) as f32 / (1u64 << 53) as f32
+    };
+    let side = (count as f32 / 100.0).sqrt() * 1000.0;
+    let style = Style {
+        fill: Some(Color::WHITE),
+        ..Style::default()
+    };
+    let mut document = Document::new();
+    for _ in 0..count {
+        let (x, y) = (next() * side, next() * side);
+        let (w, h) = (10.0 + next() * 90.0, 10.0 + next() * 90.0);
+        document.insert(ShapeKind::Rectangle, Transform::new(x, y, w, h), style.clone());
+    }
+    document.drain_events();
+    document
+}
+
+fn spatial_benchmarks(c: &mut Criterion) {
+    for count in [100_000, 1_000_000] {
+        let document = board(count);
+        let mut index = SpatialIndex::from_document(&document);
+        let side = (count as f32 / 100.0).sqrt() * 1000.0;
+        let center = Point::new(side * 0.5, side * 0.5);
+        let viewport = Rect::new(center.x - 960.0, center.y - 540.0, center.x + 960.0, center.y + 540.0);
+
+        let mut group = c.benchmark_group("spatial");
+        group.sample_size(20);
+
+        group.bench_function(BenchmarkId::new("build", count), |b| {
+            b.iter(|| SpatialIndex::from_document(black_box(&document)))
+        });
+        group.bench_function(BenchmarkId::new("visible_rect", count), |b| {
+            b.iter(|| index.query_rect(black_box(&viewport)))
+        });
+        group.bench_function(BenchmarkId::new("topmost_at", count), |b| {
+            b.iter(|| index.topmost_at(&document, black_box(center), 4.0))
+        });
+        group.bench_function(BenchmarkId::new("marquee", count), |b| {
+            let marquee = Rect::new(center.x - 300.0, center.y - 300.0, center.x + 300.0, center.y + 300.0);
+            b.iter(|| index.query_contained(black_box(&marquee)))
+        });
+        group.bench_function(BenchmarkId::new("move_shape", count), |b| {
+            let id = ShapeId(count as u64 / 2);
+            let bounds = index.bounds_of(id).unwrap();
+            let mut step = 0.0;
+            b.iter(|| {
+                step += 1.0;
+                let moved = Rect::new(bounds.min_x + step, bounds.min_y, bounds.max_x + step, bounds.max_y);
+                index.insert(id, black_box(moved));
+            })
+        });
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, spatial_benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,90 @@
+//! Renders a saved board to PNG without a browser or GPU.
+//!
+//! cargo run --example export_png -- board.json out.png [--scale 2] [--size 800x600] [--transparent]
+//!     [--font Primary.ttf --font Fallback.ttf ...]
+
+use std::process::ExitCode;
+
+use webgl_grid::board::Board;
+use webgl_grid::document::Document;
+use webgl_grid::export::{ExportSize, PngOptions};
+use webgl_grid::raster::render_png;
+use webgl_grid::selection::Selection;
+use webgl_grid::text::{Font, FontStack};
+
+const USAGE: &str = "usage: export_png <board.json> <out.png> [--scale N | --size WxH] [--transparent] [--font FILE]...";
+
+struct Args {
+    input: String,
+    output: String,
+    options: PngOptions,
+    /// Primary first, then fallbacks.
+    fonts: Vec<String>,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut paths = Vec::new();
+    let mut options = PngOptions::default();
+    let mut fonts = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scale" => {
+                let value = args.next().ok_or("--scale needs a value")?;
+                let scale = value.parse().map_err(|_| format!("invalid scale '{}'", value))?;
+                options.size = ExportSize::Scale(scale);
+            }
+            "--size" => {
+                let value = args.next().ok_or("--size needs a value")?;
+                let (width, height) = value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .ok_or_else(|| format!("invalid size '{}', expected WxH", value))?;
+                options.size = ExportSize::Fit { width, height };
+            }
+            "--transparent" => options.transparent = true,
+            "--font" => fonts.push(args.next().ok_or("--font needs a file")?.clone()),
+            _ => paths.push(arg.clone()),
+        }
+    }
+    match <[String; 2]>::try_from(paths) {
+        Ok([input, output]) => Ok(Args {
+            input,
+            output,
+            options,
+            fonts,
+        }),
+        Err(_) => Err(USAGE.to_string()),
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Args {
+        input,
+        output,
+        options,
+        fonts: font_paths,
+    } = parse_args(&args)?;
+    let mut fonts = FontStack::new();
+    for path in font_paths {
+        let data = std::fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+        fonts.push(Font::from_bytes(data).map_err(|e| format!("{}: {}", path, e))?);
+    }
+    let json = std::fs::read_to_string(&input).map_err(|e| format!("{}: {}", input, e))?;
+    let board = Board::from_json(&json).map_err(|e| format!("{}: {}", input, e))?;
+    let mut document = Document::new();
+    board.restore(&mut document);
+    let png = render_png(&document, &Selection::new(), &fonts, &options)?;
+    std::fs::write(&output, png).map_err(|e| format!("{}: {}", output, e))
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
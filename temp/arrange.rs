@@ -0,0 +1,417 @@
+//! Arranging shapes. Z-order commands move shapes among their siblings:
+//! the shapes in the same group or frame or, at the top level, on the same
+//! layer. Align, distribute and tidy move shapes by their bounds, so
+//! rotated shapes line up by what they cover.
+
+use crate::document::{Document, LayerId, Shape, ShapeId};
+use crate::fractional_index::FractionalIndex;
+use crate::geometry::Rect;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arrange {
+    /// Up past the next sibling above.
+    Forward,
+    /// Down past the next sibling below.
+    Backward,
+    ToFront,
+    ToBack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+    Top,
+    Middle,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// Where a shape sits in the paint order: its container, or its layer at
+/// the top level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Container(ShapeId),
+    Layer(LayerId),
+}
+
+fn scope(document: &Document, shape: &Shape) -> Scope {
+    match shape.parent {
+        Some(parent) => Scope::Container(parent),
+        None => Scope::Layer(document.layer_of(shape).id),
+    }
+}
+
+/// The shapes in `scope`, bottom to top.
+fn siblings(document: &Document, scope: Scope) -> Vec<&Shape> {
+    let mut siblings: Vec<&Shape> = match scope {
+        Scope::Container(parent) => document.shapes().filter(|s| s.parent == Some(parent)).collect(),
+        Scope::Layer(layer) => document
+            .shapes()
+            .filter(|s| s.parent.is_none() && document.layer_of(s).id == layer)
+            .collect(),
+    };
+    siblings.sort_by(|a, b| (&a.z_index, a.id).cmp(&(&b.z_index, b.id)));
+    siblings
+}
+
+/// Keys for `count` new shapes painted directly above `shape` among its
+/// siblings, e.g. the pieces of a split stroke.
+pub(crate) fn keys_above(document: &Document, shape: &Shape, count: usize) -> Vec<FractionalIndex> {
+    let above = siblings(document, scope(document, shape))
+        .into_iter()
+        .map(|sibling| &sibling.z_index)
+        .find(|z| **z > shape.z_index)
+        .cloned();
+    FractionalIndex::n_between(Some(&shape.z_index), above.as_ref(), count).unwrap_or_else(|_| vec![shape.z_index.clone(); count])
+}
+
+/// Moves `ids` in the paint order, keeping their order among themselves.
+/// Only the moved shapes get new z-indices, between their new neighbours.
+pub fn arrange(document: &mut Document, ids: &[ShapeId], how: Arrange) {
+    let mut scopes = Vec::new();
+    for shape in ids.iter().filter_map(|id| document.get(*id)) {
+        let scope = scope(document, shape);
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    for scope in scopes {
+        let order: Vec<(ShapeId, FractionalIndex)> =
+            siblings(document, scope).iter().map(|s| (s.id, s.z_index.clone())).collect();
+        let picked = |id: &ShapeId| ids.contains(id);
+        let mut reordered: Vec<ShapeId> = order.iter().map(|(id, _)| *id).collect();
+        match how {
+            Arrange::ToFront => reordered.sort_by_key(|id| picked(id)),
+            Arrange::ToBack => reordered.sort_by_key(|id| !picked(id)),
+            Arrange::Forward => {
+                for i in (0..reordered.len().saturating_sub(1)).rev() {
+                    if picked(&reordered[i]) && !picked(&reordered[i + 1]) {
+                        reordered.swap(i, i + 1);
+                    }
+                }
+            }
+            Arrange::Backward => {
+                for i in 1..reordered.len() {
+                    if picked(&reordered[i]) && !picked(&reordered[i - 1]) {
+                        reordered.swap(i, i - 1);
+                    }
+                }
+            }
+        }
+        let moved: Vec<bool> = reordered
+            .iter()
+            .zip(&order)
+            .map(|(id, (slot, _))| picked(id) && id != slot)
+            .collect();
+        for (id, z_index) in rekey(&order, &reordered, &moved) {
+            if document.get(id).is_some_and(|s| s.z_index != z_index) {
+                let _ = document.update(id, |shape| shape.z_index = z_index);
+            }
+        }
+    }
+}
+
+/// Z-indices putting `reordered` in paint order: each run of `moved` shapes
+/// gets keys between the shapes around it, which keep theirs. Siblings
+/// sharing a z-index, as concurrent edits can leave them, may leave no room
+/// between; then every sibling is given a new key.
+fn rekey(order: &[(ShapeId, FractionalIndex)], reordered: &[ShapeId], moved: &[bool]) -> Vec<(ShapeId, FractionalIndex)> {
+    let key = |id: ShapeId| order.iter().find(|(other, _)| *other == id).map(|(_, z)| z);
+    let mut keys = Vec::new();
+    let mut start = 0;
+    while start < reordered.len() {
+        if !moved[start] {
+            start += 1;
+            continue;
+        }
+        let end = (start..reordered.len()).find(|i| !moved[*i]).unwrap_or(reordered.len());
+        let below = start.checked_sub(1).and_then(|i| key(reordered[i]));
+        let above = reordered.get(end).and_then(|id| key(*id));
+        match FractionalIndex::n_between(below, above, end - start) {
+            Ok(run) => keys.extend(reordered[start..end].iter().copied().zip(run)),
+            Err(_) => {
+                // Cannot fail: there are no bounds.
+                let spread = FractionalIndex::n_between(None, None, reordered.len()).unwrap();
+                return reordered.iter().copied().zip(spread).collect();
+            }
+        }
+        start = end;
+    }
+    keys
+}
+
+/// Moves the outermost of `ids` onto `layer`, above its shapes. Shapes
+/// inside groups and frames leave them.
+pub fn move_to_layer(document: &mut Document, ids: &[ShapeId], layer: LayerId) -> Result<(), String> {
+    document.layer(layer).ok_or_else(|| format!("Layer {} not found", layer.0))?;
+    let mut moving = outermost(document, ids);
+    document.sort_in_paint_order(&mut moving);
+    let moving: Vec<ShapeId> = moving.into_iter().map(|s| s.id).collect();
+    let top = siblings(document, Scope::Layer(layer)).last().map(|s| s.z_index.clone());
+    let z_indices = FractionalIndex::n_between(top.as_ref(), None, moving.len())?;
+    for (id, z_index) in moving.into_iter().zip(z_indices) {
+        document.update(id, |shape| {
+            shape.parent = None;
+            shape.layer = layer;
+            shape.z_index = z_index;
+        })?;
+    }
+    Ok(())
+}
+
+/// The shapes of `ids` not inside another of them; contents follow their
+/// containers.
+fn outermost<'a>(document: &'a Document, ids: &[ShapeId]) -> Vec<&'a Shape> {
+    ids.iter()
+        .filter(|id| !ids.iter().any(|other| document.is_descendant(**id, *other)))
+        .filter_map(|id| document.get(*id))
+        .collect()
+}
+
+/// The outermost of `ids` with their bounds.
+fn bounded(document: &Document, ids: &[ShapeId]) -> Vec<(ShapeId, Rect)> {
+    outermost(document, ids)
+        .into_iter()
+        .map(|shape| (shape.id, shape.transform.bounds()))
+        .collect()
+}
+
+fn translate(document: &mut Document, id: ShapeId, dx: f32, dy: f32) {
+    if dx != 0.0 || dy != 0.0 {
+        let _ = document.update(id, |shape| {
+            shape.transform.x += dx;
+            shape.transform.y += dy;
+        });
+    }
+}
+
+/// Lines up the bounds of `ids` with an edge or the centre of their
+/// combined bounds. Needs at least two shapes.
+pub fn align(document: &mut Document, ids: &[ShapeId], how: Align) {
+    let shapes = bounded(document, ids);
+    if shapes.len() < 2 {
+        return;
+    }
+    let Some(all) = shapes.iter().map(|(_, b)| *b).reduce(|a, b| a.union(&b)) else {
+        return;
+    };
+    for (id, b) in shapes {
+        let (dx, dy) = match how {
+            Align::Left => (all.min_x - b.min_x, 0.0),
+            Align::Center => (all.center().x - b.center().x, 0.0),
+            Align::Right => (all.max_x - b.max_x, 0.0),
+            Align::Top => (0.0, all.min_y - b.min_y),
+            Align::Middle => (0.0, all.center().y - b.center().y),
+            Align::Bottom => (0.0, all.max_y - b.max_y),
+        };
+        translate(document, id, dx, dy);
+    }
+}
+
+/// Spaces `ids` out along `axis` with equal gaps between their bounds,
+/// keeping the first and last in place. Needs at least three shapes.
+pub fn distribute(document: &mut Document, ids: &[ShapeId], axis: Axis) {
+    let mut shapes = bounded(document, ids);
+    if shapes.len() < 3 {
+        return;
+    }
+    let span = |b: &Rect| match axis {
+        Axis::Horizontal => (b.min_x, b.max_x),
+        Axis::Vertical => (b.min_y, b.max_y),
+    };
+    shapes.sort_by(|(_, a), (_, b)| {
+        let (a, b) = (span(a), span(b));
+        (a.0 + a.1).total_cmp(&(b.0 + b.1))
+    });
+    let start = span(&shapes[0].1).0;
+    let end = span(&shapes[shapes.len() - 1].1).1;
+    let total: f32 = shapes.iter().map(|(_, b)| span(b).1 - span(b).0).sum();
+    let gap = (end - start - total) / (shapes.len() - 1) as f32;
+    let mut at = start;
+    for (id, b) in shapes {
+        let (min, max) = span(&b);
+        match axis {
+            Axis::Horizontal => translate(document, id, at - min, 0.0),
+            Axis::Vertical => translate(document, id, 0.0, at - min),
+        }
+        at += max - min + gap;
+    }
+}
+
+/// Lays `ids` out in a near-square grid of equal cells `gap` apart, from
+/// the top-left of their combined bounds. Shapes keep their reading order,
+/// row by row, and are centred in their cells.
+pub fn tidy(document: &mut Document, ids: &[ShapeId], gap: f32) {
+    let mut shapes = bounded(document, ids);
+    let Some(all) = shapes.iter().map(|(_, b)| *b).reduce(|a, b| a.union(&b)) else {
+        return;
+    };
+    let columns = (shapes.len() as f32).sqrt().ceil() as usize;
+    let cell_width = shapes.iter().map(|(_, b)| b.width()).fold(0.0, f32::max);
+    let cell_height = shapes.iter().map(|(_, b)| b.height()).fold(0.0, f32::max);
+    shapes.sort_by(|(_, a), (_, b)| a.center().y.total_cmp(&b.center().y));
+    for row in shapes.chunks_mut(columns) {
+        row.sort_by(|(_, a), (_, b)| a.center().x.total_cmp(&b.center().x));
+    }
+    for (i, (id, b)) in shapes.into_iter().enumerate() {
+        let (column, row) = ((i % columns) as f32, (i / columns) as f32);
+        let center_x = all.min_x + column * (cell_width + gap) + cell_width * 0.5;
+        let center_y = all.min_y + row * (cell_height + gap) + cell_height * 0.5;
+        translate(document, id, center_x - b.center().x, center_y - b.center().y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{ShapeKind, Style, Transform};
+    use crate::editor::Editor;
+
+    fn stack(document: &mut Document, count: usize) -> Vec<ShapeId> {
+        (0..count)
+            .map(|_| document.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 10.0, 10.0), Style::default()))
+            .collect()
+    }
+
+    fn order(document: &Document) -> Vec<ShapeId> {
+        document.shapes_in_z_order().iter().map(|s| s.id).collect()
+    }
+
+    #[test]
+    fn test_forward_backward_front_back() {
+        let mut document = Document::new();
+        let ids = stack(&mut document, 4);
+        let [a, b, c, d] = ids[..] else { unreachable!() };
+
+        arrange(&mut document, &[a], Arrange::Forward);
+        assert_eq!(order(&document), vec![b, a, c, d]);
+        // Only the moved shape is rewritten.
+        assert_eq!(document.take_operations().len(), 5);
+
+        arrange(&mut document, &[b, c], Arrange::ToFront);
+        assert_eq!(order(&document), vec![a, d, b, c]);
+        arrange(&mut document, &[c], Arrange::ToBack);
+        assert_eq!(order(&document), vec![c, a, d, b]);
+        arrange(&mut document, &[d, b], Arrange::Backward);
+        assert_eq!(order(&document), vec![c, d, b, a]);
+
+        // Already in place: nothing changes.
+        document.take_operations();
+        arrange(&mut document, &[c], Arrange::ToBack);
+        arrange(&mut document, &[b, a], Arrange::Forward);
+        assert!(document.take_operations().is_empty());
+    }
+
+    #[test]
+    fn test_tied_z_indices_are_spread() {
+        let mut document = Document::new();
+        let ids = stack(&mut document, 3);
+        let [a, b, c] = ids[..] else { unreachable!() };
+        let tied = document.get(a).unwrap().z_index.clone();
+        for id in [b, c] {
+            document.update(id, |shape| shape.z_index = tied.clone()).unwrap();
+        }
+        assert_eq!(order(&document), vec![a, b, c]);
+
+        arrange(&mut document, &[a], Arrange::Forward);
+        assert_eq!(order(&document), vec![b, a, c]);
+        arrange(&mut document, &[c], Arrange::Backward);
+        assert_eq!(order(&document), vec![b, c, a]);
+    }
+
+    #[test]
+    fn test_layers_order_and_scope_arranging() {
+        let mut document = Document::new();
+        let bottom = stack(&mut document, 2);
+        let upper = document.add_layer("Upper");
+        let top = stack(&mut document, 1);
+        assert_eq!(document.get(top[0]).unwrap().layer, upper);
+
+        // Sending to the back stays within the upper layer.
+        arrange(&mut document, &top, Arrange::ToBack);
+        assert_eq!(order(&document), vec![bottom[0], bottom[1], top[0]]);
+
+        document.move_layer(upper, 0).unwrap();
+        assert_eq!(order(&document), vec![top[0], bottom[0], bottom[1]]);
+
+        move_to_layer(&mut document, &[bottom[0]], upper).unwrap();
+        assert_eq!(order(&document), vec![top[0], bottom[0], bottom[1]]);
+        assert_eq!(document.layer_of(document.get(bottom[0]).unwrap()).id, upper);
+        assert!(move_to_layer(&mut document, &[bottom[1]], LayerId(9)).is_err());
+    }
+
+    fn bounds(document: &Document, id: ShapeId) -> Rect {
+        document.get(id).unwrap().transform.bounds()
+    }
+
+    #[test]
+    fn test_align_and_distribute_by_bounds() {
+        let mut document = Document::new();
+        let a = document.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 10.0, 10.0), Style::default());
+        let b = document.insert(ShapeKind::Rectangle, Transform::new(50.0, 20.0, 20.0, 10.0), Style::default());
+        // A 10×10 square turned 45°, so its bounds are about 14 across.
+        let c = document.insert(
+            ShapeKind::Rectangle,
+            Transform {
+                rotation: std::f32::consts::FRAC_PI_4,
+                ..Transform::new(100.0, 40.0, 10.0, 10.0)
+            },
+            Style::default(),
+        );
+        align(&mut document, &[a, b, c], Align::Right);
+        let right = bounds(&document, c).max_x;
+        for id in [a, b] {
+            assert!((bounds(&document, id).max_x - right).abs() < 1e-3);
+        }
+        align(&mut document, &[a, b, c], Align::Top);
+        for id in [a, b, c] {
+            assert!(bounds(&document, id).min_y.abs() < 1e-3);
+        }
+
+        // a and b now overlap; spread them out along x between the others.
+        document.update(a, |s| s.transform.x = -100.0).unwrap();
+        distribute(&mut document, &[a, b, c], Axis::Horizontal);
+        let [ba, bb, bc] = [a, b, c].map(|id| bounds(&document, id));
+        assert_eq!(ba.min_x, -100.0);
+        assert!((bc.max_x - right).abs() < 1e-3);
+        assert!(((bb.min_x - ba.max_x) - (bc.min_x - bb.max_x)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_tidy_is_one_undo_step() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let ids: Vec<ShapeId> = [(300.0, 0.0), (0.0, 200.0), (150.0, 10.0), (40.0, 300.0), (500.0, 500.0)]
+            .into_iter()
+            .map(|(x, y)| editor.document.insert(ShapeKind::Ellipse, Transform::new(x, y, 20.0, 20.0), Style::default()))
+            .collect();
+        editor.sync_index();
+        editor.selection.set(ids.iter().copied());
+        let before: Vec<Rect> = ids.iter().map(|id| bounds(&editor.document, *id)).collect();
+
+        editor.tidy_selection();
+        // Three columns, filled row by row in reading order.
+        let cells: Vec<(f32, f32)> = ids
+            .iter()
+            .map(|id| {
+                let b = bounds(&editor.document, *id);
+                (b.min_x, b.min_y)
+            })
+            .collect();
+        assert_eq!(cells[1], (0.0, 0.0));
+        assert_eq!((cells[2].1, cells[0].1), (0.0, 0.0));
+        assert!(cells[1].0 < cells[2].0 && cells[2].0 < cells[0].0);
+        assert_eq!(cells[3].0, 0.0);
+        assert!(cells[3].1 > 0.0 && cells[4].1 == cells[3].1);
+
+        assert!(editor.undo().unwrap());
+        let after: Vec<Rect> = ids.iter().map(|id| bounds(&editor.document, *id)).collect();
+        assert_eq!(after, before);
+    }
+}
@@ -0,0 +1,470 @@
+use std::ops::Range;
+
+use crate::document::{Color, Document, Paint, Shape, ShapeId, ShapeKind};
+use crate::freehand::{stroke_widths, FreehandOptions};
+use crate::geometry::{Point, Rect};
+use crate::tessellate::{stroke_polyline, LineCap, LineJoin, Mesh, StrokeOptions};
+
+/// Floats per SDF instance: center(2), half_size(2), rotation, stroke_width,
+/// kind, fill(4), stroke(4).
+pub const SDF_INSTANCE_FLOATS: usize = 15;
+
+/// Floats per mesh vertex: position(2), color(4).
+pub const MESH_VERTEX_FLOATS: usize = 6;
+
+/// Curve flattening tolerance for tessellated strokes, in world units.
+const TOLERANCE: f32 = 0.25;
+
+pub const SDF_KIND_RECT: f32 = 0.0;
+pub const SDF_KIND_ELLIPSE: f32 = 1.0;
+
+/// How a run of primitives is drawn. Each material maps to one shader program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Material {
+    /// Instanced quads shaded with a signed distance function.
+    Sdf,
+    /// Indexed triangle meshes with per-vertex color.
+    Mesh,
+}
+
+/// A contiguous range of instances (for [`Material::Sdf`]) or indices (for
+/// [`Material::Mesh`]) that can be drawn with a single call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawBatch {
+    pub material: Material,
+    pub start: usize,
+    pub count: usize,
+    /// World rect the batch is cut to, for shapes inside frames.
+    pub clip: Option<Rect>,
+}
+
+/// CPU-side geometry for the shape pass, rebuilt whenever the document changes.
+///
+/// Shapes are visited in z-order and consecutive shapes sharing a material
+/// and clip are merged into one [`DrawBatch`], so paint order is preserved
+/// while typical documents collapse into a handful of draw calls.
+#[derive(Debug, Default)]
+pub struct ShapeBatches {
+    pub instances: Vec<f32>,
+    pub vertices: Vec<f32>,
+    pub indices: Vec<u32>,
+    pub batches: Vec<DrawBatch>,
+    /// Number of batches after each shape, for [`paint_steps`].
+    pub ends: Vec<usize>,
+    /// Clip and layer opacity of the shape being pushed.
+    paint: Paint,
+    /// Batches before this one are closed: a shape drawn by another pass
+    /// came after them, so later shapes must not be merged into them.
+    sealed: usize,
+}
+
+impl ShapeBatches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rebuilds all buffers from `document`, reusing existing allocations.
+    pub fn rebuild(&mut self, document: &Document) {
+        let shapes = document.shapes_in_z_order().into_iter().filter(|shape| document.is_visible(shape));
+        self.rebuild_painted(shapes, |shape| document.paint(shape));
+    }
+
+    /// Rebuilds from shapes already in paint order.
+    pub fn rebuild_from<'a, I: IntoIterator<Item = &'a Shape>>(&mut self, shapes: I) {
+        self.rebuild_painted(shapes, |_| Paint::default());
+    }
+
+    /// Like [`ShapeBatches::rebuild_from`], drawing each shape with the clip
+    /// and opacity `paint` returns for it.
+    pub fn rebuild_painted<'a, I, F>(&mut self, shapes: I, paint: F)
+    where
+        I: IntoIterator<Item = &'a Shape>,
+        F: Fn(&Shape) -> Paint,
+    {
+        self.instances.clear();
+        self.vertices.clear();
+        self.indices.clear();
+        self.batches.clear();
+        self.ends.clear();
+        self.sealed = 0;
+        for shape in shapes {
+            self.paint = paint(shape);
+            self.push_shape(shape);
+            self.ends.push(self.batches.len());
+        }
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.instances.len() / SDF_INSTANCE_FLOATS
+    }
+
+    fn opacity(&self, shape: &Shape) -> f32 {
+        shape.style.opacity * self.paint.opacity
+    }
+
+    fn push_shape(&mut self, shape: &Shape) {
+        match &shape.kind {
+            ShapeKind::Rectangle | ShapeKind::Frame { .. } => self.push_closed(shape, SDF_KIND_RECT, LineJoin::Miter),
+            ShapeKind::Ellipse => self.push_closed(shape, SDF_KIND_ELLIPSE, LineJoin::Round),
+            ShapeKind::Line { .. } => {
+                if let Some(points) = shape.path_points() {
+                    self.push_stroke(&points, None, false, shape, LineJoin::Round);
+                }
+            }
+            ShapeKind::Freehand { points } => {
+                let widths = stroke_widths(points, shape.style.stroke_width, &FreehandOptions::default());
+                if let Some(path) = shape.path_points() {
+                    self.push_stroke(&path, Some(&widths), false, shape, LineJoin::Round);
+                }
+            }
+            ShapeKind::Arrow { .. } => {
+                if let Some(points) = shape.path_points() {
+                    self.push_stroke(&points, None, false, shape, LineJoin::Round);
+                    self.push_arrow_head(points[0], points[1], shape);
+                }
+            }
+            ShapeKind::Connector { .. } => {
+                if let Some(points) = shape.path_points() {
+                    self.push_stroke(&points, None, false, shape, LineJoin::Round);
+                    if let [.., from, tip] = points.as_slice() {
+                        self.push_arrow_head(*from, *tip, shape);
+                    }
+                }
+            }
+            // Text and images get their own passes, drawn between the
+            // batches around them; groups are not painted.
+            ShapeKind::Text { .. } | ShapeKind::Image { .. } => self.sealed = self.batches.len(),
+            ShapeKind::Group => {}
+        }
+    }
+
+    /// Rectangles and ellipses go through the SDF shader unless they are
+    /// dashed, in which case the stroke is tessellated and only the fill is
+    /// left to the SDF.
+    fn push_closed(&mut self, shape: &Shape, kind: f32, join: LineJoin) {
+        if shape.style.dash.is_empty() {
+            self.push_sdf(shape, kind, shape.style.stroke_width);
+            return;
+        }
+        if shape.style.fill.is_some() {
+            self.push_sdf(shape, kind, 0.0);
+        }
+        if let Some(outline) = shape.outline(TOLERANCE) {
+            self.push_stroke(&outline, None, true, shape, join);
+        }
+    }
+
+    fn extend_batch(&mut self, material: Material, start: usize, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let open = self.batches.len() > self.sealed;
+        match self.batches.last_mut() {
+            Some(last) if open && last.material == material && last.clip == self.paint.clip && last.start + last.count == start => {
+                last.count += count;
+            }
+            _ => self.batches.push(DrawBatch {
+                material,
+                start,
+                count,
+                clip: self.paint.clip,
+            }),
+        }
+    }
+
+    fn push_sdf(&mut self, shape: &Shape, kind: f32, stroke_width: f32) {
+        let t = &shape.transform;
+        let center = t.center();
+        let fill = shape
+            .style
+            .fill
+            .map(|c| with_opacity(c, self.opacity(shape)))
+            .unwrap_or(Color::TRANSPARENT);
+        let stroke = with_opacity(shape.style.stroke, self.opacity(shape));
+        let start = self.instance_count();
+        self.instances.extend_from_slice(&[
+            center.x,
+            center.y,
+            t.width.abs() * 0.5,
+            t.height.abs() * 0.5,
+            t.rotation,
+            stroke_width,
+            kind,
+            fill.r,
+            fill.g,
+            fill.b,
+            fill.a,
+            stroke.r,
+            stroke.g,
+            stroke.b,
+            stroke.a,
+        ]);
+        self.extend_batch(Material::Sdf, start, 1);
+    }
+
+    fn push_vertex(&mut self, p: Point, color: Color) -> u32 {
+        let index = (self.vertices.len() / MESH_VERTEX_FLOATS) as u32;
+        self.vertices
+            .extend_from_slice(&[p.x, p.y, color.r, color.g, color.b, color.a]);
+        index
+    }
+
+    fn push_stroke(&mut self, points: &[Point], widths: Option<&[f32]>, closed: bool, shape: &Shape, join: LineJoin) {
+        let options = StrokeOptions {
+            width: shape.style.stroke_width,
+            join,
+            cap: if closed { LineCap::Butt } else { LineCap::Round },
+            dash: shape.style.dash.clone(),
+            tolerance: TOLERANCE,
+            ..StrokeOptions::default()
+        };
+        let mesh = stroke_polyline(points, widths, closed, &options);
+        self.push_mesh(&mesh, with_opacity(shape.style.stroke, self.opacity(shape)));
+    }
+
+    fn push_mesh(&mut self, mesh: &Mesh, color: Color) {
+        let base = (self.vertices.len() / MESH_VERTEX_FLOATS) as u32;
+        for &p in &mesh.vertices {
+            self.push_vertex(p, color);
+        }
+        let start = self.indices.len();
+        self.indices.extend(mesh.indices.iter().map(|i| i + base));
+        self.extend_batch(Material::Mesh, start, mesh.indices.len());
+    }
+
+    fn push_arrow_head(&mut self, from: Point, tip: Point, shape: &Shape) {
+        let dir = (tip - from).normalize();
+        if dir == Point::ZERO {
+            return;
+        }
+        let color = with_opacity(shape.style.stroke, self.opacity(shape));
+        let size = shape.style.stroke_width * 4.0;
+        let base = tip - dir * size;
+        let side = dir.perp() * (size * 0.5);
+        let start = self.indices.len();
+        let a = self.push_vertex(tip, color);
+        let b = self.push_vertex(base + side, color);
+        let c = self.push_vertex(base - side, color);
+        self.indices.extend_from_slice(&[a, b, c]);
+        self.extend_batch(Material::Mesh, start, 3);
+    }
+}
+
+/// One draw of a paint-ordered list of shapes, by whichever pass draws it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaintStep {
+    /// A range of [`ShapeBatches::batches`].
+    Batches(Range<usize>),
+    /// A range of glyph instances sharing a clip.
+    Glyphs(Range<usize>, Option<Rect>),
+    /// One image shape.
+    Image(ShapeId),
+}
+
+/// Orders the draws for `shapes`, which are in paint order, so that shapes,
+/// text and images overlap as their z-order and layers say rather than by
+/// kind.
+/// `batch_ends` and `glyph_ends` give the batch and glyph instance counts
+/// after each shape, from [`ShapeBatches::ends`] and
+/// [`crate::glyph_atlas::glyph_instances`].
+pub fn paint_steps<F: Fn(&Shape) -> Paint>(shapes: &[&Shape], batch_ends: &[usize], glyph_ends: &[usize], paint: F) -> Vec<PaintStep> {
+    let mut steps = Vec::new();
+    let (mut batch, mut glyph) = (0, 0);
+    for ((shape, &batch_end), &glyph_end) in shapes.iter().zip(batch_ends).zip(glyph_ends) {
+        if batch_end > batch {
+            match steps.last_mut() {
+                Some(PaintStep::Batches(range)) => range.end = batch_end,
+                _ => steps.push(PaintStep::Batches(batch..batch_end)),
+            }
+        }
+        if glyph_end > glyph {
+            let clip = paint(shape).clip;
+            match steps.last_mut() {
+                Some(PaintStep::Glyphs(range, last)) if *last == clip => range.end = glyph_end,
+                _ => steps.push(PaintStep::Glyphs(glyph..glyph_end, clip)),
+            }
+        }
+        if let ShapeKind::Image { .. } = shape.kind {
+            steps.push(PaintStep::Image(shape.id));
+        }
+        (batch, glyph) = (batch_end, glyph_end);
+    }
+    steps
+}
+
+fn with_opacity(color: Color, opacity: f32) -> Color {
+    Color::rgba(color.r, color.g, color.b, color.a * opacity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Style, Transform};
+
+    fn line() -> ShapeKind {
+        ShapeKind::Line {
+            start: Point::new(0.0, 0.0),
+            end: Point::new(10.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn test_consecutive_shapes_share_a_batch() {
+        let mut doc = Document::new();
+        for i in 0..3 {
+            doc.insert(
+                ShapeKind::Rectangle,
+                Transform::new(i as f32, 0.0, 10.0, 10.0),
+                Style::default(),
+            );
+        }
+        doc.insert(ShapeKind::Ellipse, Transform::new(0.0, 0.0, 4.0, 2.0), Style::default());
+
+        let mut batches = ShapeBatches::new();
+        batches.rebuild(&doc);
+        assert_eq!(batches.instance_count(), 4);
+        assert_eq!(
+            batches.batches,
+            vec![DrawBatch {
+                material: Material::Sdf,
+                start: 0,
+                count: 4,
+                clip: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_batches_preserve_z_order() {
+        let mut doc = Document::new();
+        let t = Transform::new(0.0, 0.0, 10.0, 10.0);
+        doc.insert(ShapeKind::Rectangle, t, Style::default());
+        doc.insert(line(), t, Style::default());
+        doc.insert(ShapeKind::Rectangle, t, Style::default());
+
+        let mut batches = ShapeBatches::new();
+        batches.rebuild(&doc);
+        let materials: Vec<Material> = batches.batches.iter().map(|b| b.material).collect();
+        assert_eq!(materials, vec![Material::Sdf, Material::Mesh, Material::Sdf]);
+        assert_eq!(batches.batches[2].start, 1);
+    }
+
+    #[test]
+    fn test_text_between_shapes_splits_the_steps() {
+        let mut doc = Document::new();
+        let t = Transform::new(0.0, 0.0, 10.0, 10.0);
+        doc.insert(ShapeKind::Rectangle, t, Style::default());
+        let text = ShapeKind::Text {
+            content: "A".to_string(),
+            font_size: 10.0,
+            align: crate::text::TextAlign::Left,
+        };
+        doc.insert(text, t, Style::default());
+        doc.insert(ShapeKind::Rectangle, t, Style::default());
+
+        let mut batches = ShapeBatches::new();
+        batches.rebuild(&doc);
+        // The rectangles stay apart so the text can be drawn between them.
+        assert_eq!(batches.batches.len(), 2);
+        assert_eq!(batches.ends, vec![1, 1, 2]);
+        let shapes = doc.shapes_in_z_order();
+        let steps = paint_steps(&shapes, &batches.ends, &[0, 3, 3], |_| Paint::default());
+        assert_eq!(steps, vec![PaintStep::Batches(0..1), PaintStep::Glyphs(0..3, None), PaintStep::Batches(1..2)]);
+    }
+
+    #[test]
+    fn test_steps_follow_paint_order_across_kinds() {
+        let mut doc = Document::new();
+        let t = Transform::new(0.0, 0.0, 10.0, 10.0);
+        doc.insert(ShapeKind::Rectangle, t, Style::default());
+        let image = doc.insert(ShapeKind::Image { source: "a.png".to_string() }, t, Style::default());
+        let text = ShapeKind::Text {
+            content: "A".to_string(),
+            font_size: 10.0,
+            align: crate::text::TextAlign::Left,
+        };
+        doc.insert(text, t, Style::default());
+        doc.insert(ShapeKind::Ellipse, t, Style::default());
+
+        let mut batches = ShapeBatches::new();
+        batches.rebuild(&doc);
+        assert_eq!(batches.ends, vec![1, 1, 1, 2]);
+        let shapes = doc.shapes_in_z_order();
+        let steps = paint_steps(&shapes, &batches.ends, &[0, 0, 1, 1], |_| Paint::default());
+        assert_eq!(
+            steps,
+            vec![PaintStep::Batches(0..1), PaintStep::Image(image), PaintStep::Glyphs(0..1, None), PaintStep::Batches(1..2)]
+        );
+    }
+
+    #[test]
+    fn test_frame_contents_get_their_own_clipped_batch() {
+        let mut doc = Document::new();
+        let t = Transform::new(0.0, 0.0, 10.0, 10.0);
+        let frame = doc.insert(ShapeKind::Frame { name: "A".to_string() }, Transform::new(0.0, 0.0, 5.0, 5.0), Style::default());
+        let inside = doc.insert(ShapeKind::Rectangle, t, Style::default());
+        doc.update(inside, |s| s.parent = Some(frame)).unwrap();
+        doc.insert(ShapeKind::Rectangle, t, Style::default());
+
+        let mut batches = ShapeBatches::new();
+        batches.rebuild(&doc);
+        let clips: Vec<Option<Rect>> = batches.batches.iter().map(|b| b.clip).collect();
+        assert_eq!(clips, vec![None, Some(Rect::new(0.0, 0.0, 5.0, 5.0)), None]);
+    }
+
+    #[test]
+    fn test_opacity_is_applied_to_colors() {
+        let mut doc = Document::new();
+        let style = Style {
+            fill: Some(Color::WHITE),
+            opacity: 0.5,
+            ..Style::default()
+        };
+        doc.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 2.0, 2.0), style);
+
+        let mut batches = ShapeBatches::new();
+        batches.rebuild(&doc);
+        assert_eq!(batches.instances[10], 0.5);
+        assert_eq!(batches.instances[14], 0.5);
+    }
+
+    #[test]
+    fn test_dashed_rectangle_is_tessellated() {
+        let mut doc = Document::new();
+        let style = Style {
+            fill: Some(Color::WHITE),
+            dash: vec![4.0, 2.0],
+            ..Style::default()
+        };
+        doc.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 20.0, 10.0), style);
+
+        let mut batches = ShapeBatches::new();
+        batches.rebuild(&doc);
+        let materials: Vec<Material> = batches.batches.iter().map(|b| b.material).collect();
+        assert_eq!(materials, vec![Material::Sdf, Material::Mesh]);
+        // The SDF instance only carries the fill.
+        assert_eq!(batches.instances[5], 0.0);
+    }
+
+    #[test]
+    fn test_mesh_indices_are_in_range() {
+        let mut doc = Document::new();
+        let t = Transform::new(0.0, 0.0, 10.0, 10.0);
+        doc.insert(line(), t, Style::default());
+        doc.insert(
+            ShapeKind::Arrow {
+                start: Point::new(0.0, 0.0),
+                end: Point::new(10.0, 10.0),
+            },
+            t,
+            Style::default(),
+        );
+
+        let mut batches = ShapeBatches::new();
+        batches.rebuild(&doc);
+        let vertex_count = (batches.vertices.len() / MESH_VERTEX_FLOATS) as u32;
+        assert!(batches.indices.iter().all(|&i| i < vertex_count));
+        assert_eq!(batches.batches.len(), 1);
+        assert_eq!(batches.batches[0].count, batches.indices.len());
+    }
+}
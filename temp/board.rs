@@ -0,0 +1,515 @@
+//! The saved board format: the document and camera as versioned JSON.
+//!
+//! Loading parses into a `serde_json::Value`, upgrades it through
+//! [`MIGRATIONS`] to [`FORMAT_VERSION`], then decodes it strictly. Errors
+//! name the offending field, e.g. `shapes[12].style.stroke: expected color`.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::document::{Document, Layer, Routing, Shape, ShapeKind, PREVIEW_SHAPE_ID};
+use crate::fractional_index::FractionalIndex;
+use crate::state::State;
+
+/// Version written by [`Board::to_json`].
+pub const FORMAT_VERSION: u64 = 2;
+
+/// Upgrades a file in place from one version to the next.
+pub type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// `MIGRATIONS[i]` upgrades version `i + 1` to `i + 2`, so the chain must
+/// have `FORMAT_VERSION - 1` entries.
+pub const MIGRATIONS: &[Migration] = &[fractional_z_indices];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Board {
+    pub version: u64,
+    pub camera: State,
+    /// Bottom to top. Boards saved before layers existed get the default one.
+    #[serde(default = "default_layers")]
+    pub layers: Vec<Layer>,
+    /// Bottom to top.
+    pub shapes: Vec<Shape>,
+}
+
+fn default_layers() -> Vec<Layer> {
+    Document::new().layers().to_vec()
+}
+
+impl Board {
+    pub fn new(document: &Document, camera: &State) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            camera: camera.clone(),
+            layers: document.layers().to_vec(),
+            shapes: document.shapes_in_z_order().into_iter().cloned().collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    /// Parses, migrates and validates a saved board.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(json).map_err(|e| format!("invalid JSON: {}", e))?;
+        Self::from_value(value, MIGRATIONS)
+    }
+
+    fn from_value(mut value: Value, migrations: &[Migration]) -> Result<Self, String> {
+        migrate(&mut value, migrations)?;
+        let board: Board = serde_path_to_error::deserialize(value).map_err(|e| {
+            let path = e.path().to_string();
+            format!("{}: {}", path, e.into_inner())
+        })?;
+        board.validate()?;
+        Ok(board)
+    }
+
+    /// Checks what the types alone cannot: ranges, unique ids and references.
+    fn validate(&self) -> Result<(), String> {
+        if self.camera.zoom <= 0.0 {
+            return Err("camera.zoom: expected a positive number".to_string());
+        }
+        if self.layers.is_empty() {
+            return Err("layers: expected at least one layer".to_string());
+        }
+        let mut layers = HashSet::new();
+        for (i, layer) in self.layers.iter().enumerate() {
+            if !layers.insert(layer.id) {
+                return Err(format!("layers[{}].id: duplicate id {}", i, layer.id.0));
+            }
+            if !(0.0..=1.0).contains(&layer.opacity) {
+                return Err(format!("layers[{}].opacity: expected a number between 0 and 1", i));
+            }
+        }
+        let all: HashMap<_, _> = self.shapes.iter().map(|shape| (shape.id, shape)).collect();
+        let mut ids = HashSet::new();
+        for (i, shape) in self.shapes.iter().enumerate() {
+            let at = |field: &str, message: &str| Err(format!("shapes[{}].{}: {}", i, field, message));
+            if shape.id == PREVIEW_SHAPE_ID {
+                return at("id", "reserved id");
+            }
+            if !ids.insert(shape.id) {
+                return at("id", &format!("duplicate id {}", shape.id.0));
+            }
+            if shape.transform.width < 0.0 {
+                return at("transform.width", "expected a non-negative number");
+            }
+            if shape.transform.height < 0.0 {
+                return at("transform.height", "expected a non-negative number");
+            }
+            if shape.style.stroke_width < 0.0 {
+                return at("style.stroke_width", "expected a non-negative number");
+            }
+            if shape.style.dash.iter().any(|d| *d < 0.0) {
+                return at("style.dash", "expected non-negative lengths");
+            }
+            if !(0.0..=1.0).contains(&shape.style.opacity) {
+                return at("style.opacity", "expected a number between 0 and 1");
+            }
+            if !layers.contains(&shape.layer) {
+                return at("layer", &format!("unknown layer {}", shape.layer.0));
+            }
+            if let Some(parent) = shape.parent {
+                match all.get(&parent) {
+                    None => return at("parent", &format!("unknown shape {}", parent.0)),
+                    Some(parent) if !parent.is_container() => {
+                        return at("parent", &format!("shape {} is not a group or frame", parent.id.0));
+                    }
+                    _ => {}
+                }
+                // A chain longer than the board must revisit a shape.
+                let mut ancestor = shape.parent;
+                for _ in 0..self.shapes.len() {
+                    ancestor = ancestor.and_then(|id| all.get(&id)).and_then(|shape| shape.parent);
+                }
+                if ancestor.is_some() {
+                    return at("parent", "cycle in parents");
+                }
+            }
+            match &shape.kind {
+                ShapeKind::Freehand { points } => {
+                    if let Some(j) = points.iter().position(|p| !(0.0..=1.0).contains(&p.pressure)) {
+                        return at(&format!("kind.points[{}].pressure", j), "expected a number between 0 and 1");
+                    }
+                }
+                ShapeKind::Text { font_size, .. } if *font_size <= 0.0 => {
+                    return at("kind.font_size", "expected a positive number");
+                }
+                ShapeKind::Connector { route, routing, start_binding, end_binding } => {
+                    if *routing == Routing::Curved && route.len() != 4 {
+                        return at("kind.route", "expected four control points");
+                    }
+                    if route.len() < 2 {
+                        return at("kind.route", "expected at least two points");
+                    }
+                    for (field, binding) in [("kind.start_binding", start_binding), ("kind.end_binding", end_binding)] {
+                        match binding {
+                            Some(binding) if binding.shape == shape.id => return at(field, "cannot bind to itself"),
+                            Some(binding) if !all.contains_key(&binding.shape) => {
+                                return at(field, &format!("unknown shape {}", binding.shape.0));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the document's layers and shapes with the board's. The
+    /// document keeps its revision counter, so renderers notice the change.
+    pub fn restore(self, document: &mut Document) -> State {
+        let existing: Vec<_> = document.shapes().map(|s| s.id).collect();
+        for id in existing {
+            let _ = document.delete(id);
+        }
+        // Cannot fail: there is at least one layer.
+        let _ = document.set_layers(self.layers);
+        for shape in self.shapes {
+            // Cannot fail: ids were checked to be unique.
+            let _ = document.insert_shape(shape);
+        }
+        self.camera
+    }
+}
+
+/// Upgrades `value` to [`FORMAT_VERSION`] one step at a time.
+fn migrate(value: &mut Value, migrations: &[Migration]) -> Result<(), String> {
+    let current = migrations.len() as u64 + 1;
+    let version = match value.get("version") {
+        Some(version) => version.as_u64().filter(|v| *v >= 1).ok_or("version: expected a positive integer")?,
+        None if value.is_object() => return Err("version: missing field".to_string()),
+        None => return Err("expected an object".to_string()),
+    };
+    if version > current {
+        return Err(format!("version: {} is newer than the supported version {}", version, current));
+    }
+    for (from, migration) in migrations.iter().enumerate().skip(version as usize - 1) {
+        migration(value).map_err(|e| format!("migrating from version {}: {}", from + 1, e))?;
+        value["version"] = Value::from(from as u64 + 2);
+    }
+    Ok(())
+}
+
+/// Version 1 stored z-indices as integers. Each distinct integer becomes a
+/// key, in the same order.
+fn fractional_z_indices(value: &mut Value) -> Result<(), String> {
+    let Some(shapes) = value.get_mut("shapes").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    let mut integers = Vec::new();
+    for (i, shape) in shapes.iter().enumerate() {
+        let z_index = shape.get("z_index").and_then(Value::as_i64);
+        integers.push(z_index.ok_or_else(|| format!("shapes[{}].z_index: expected an integer", i))?);
+    }
+    let mut distinct = integers.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    let keys = FractionalIndex::n_between(None, None, distinct.len())?;
+    for (shape, integer) in shapes.iter_mut().zip(integers) {
+        // Cannot fail: every integer is in `distinct`.
+        let key = &keys[distinct.binary_search(&integer).unwrap()];
+        shape["z_index"] = Value::from(key.as_str());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Anchor, Binding, Color, LayerId, ShapeId, StrokePoint, Style, Transform};
+    use crate::geometry::Point;
+    use crate::text::TextAlign;
+    use proptest::prelude::*;
+
+    fn sample() -> Board {
+        let mut document = Document::new();
+        document.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 10.0, 20.0), Style::default());
+        document.insert(
+            ShapeKind::Text {
+                content: "hi".to_string(),
+                font_size: 24.0,
+                align: TextAlign::Center,
+            },
+            Transform::new(5.0, 5.0, 40.0, 30.0),
+            Style::default(),
+        );
+        Board::new(&document, &State::default())
+    }
+
+    fn error_for(edit: impl FnOnce(&mut Value)) -> String {
+        let mut value = serde_json::to_value(sample()).unwrap();
+        edit(&mut value);
+        Board::from_json(&value.to_string()).unwrap_err()
+    }
+
+    #[test]
+    fn test_errors_name_the_field() {
+        assert_eq!(
+            error_for(|v| v["shapes"][1]["style"]["stroke"] = Value::from("red")),
+            "shapes[1].style.stroke: expected color"
+        );
+        assert!(error_for(|v| v["shapes"][0]["transform"]["x"] = Value::from("0")).starts_with("shapes[0].transform.x:"));
+        assert!(error_for(|v| v["shapes"][0]["extra"] = Value::from(1)).starts_with("shapes[0]"));
+        assert_eq!(
+            error_for(|v| v["shapes"][1]["id"] = v["shapes"][0]["id"].clone()),
+            "shapes[1].id: duplicate id 0"
+        );
+        assert_eq!(
+            error_for(|v| v["camera"]["zoom"] = Value::from(0.0)),
+            "camera.zoom: expected a positive number"
+        );
+        assert_eq!(
+            error_for(|v| {
+                v["shapes"][0]["kind"] = serde_json::json!({
+                    "type": "connector",
+                    "route": [{ "x": 0.0, "y": 0.0 }, { "x": 1.0, "y": 1.0 }],
+                    "routing": "elbow",
+                    "end_binding": { "shape": 7, "anchor": { "type": "outline" } },
+                })
+            }),
+            "shapes[0].kind.end_binding: unknown shape 7"
+        );
+        assert_eq!(error_for(|v| v["shapes"][0]["parent"] = Value::from(9)), "shapes[0].parent: unknown shape 9");
+        assert_eq!(error_for(|v| v["shapes"][1]["layer"] = Value::from(4)), "shapes[1].layer: unknown layer 4");
+        assert_eq!(error_for(|v| v["layers"] = serde_json::json!([])), "layers: expected at least one layer");
+        assert_eq!(
+            error_for(|v| v["layers"][0]["opacity"] = Value::from(2.0)),
+            "layers[0].opacity: expected a number between 0 and 1"
+        );
+        // Boards from before layers put everything on the default one.
+        let mut value = serde_json::to_value(sample()).unwrap();
+        value.as_object_mut().unwrap().remove("layers");
+        for shape in value["shapes"].as_array_mut().unwrap() {
+            shape.as_object_mut().unwrap().remove("layer");
+        }
+        assert_eq!(Board::from_json(&value.to_string()).unwrap(), sample());
+        assert_eq!(
+            error_for(|v| v["shapes"][1]["parent"] = Value::from(0)),
+            "shapes[1].parent: shape 0 is not a group or frame"
+        );
+        assert_eq!(
+            error_for(|v| {
+                for (i, parent) in [(0, 1), (1, 0)] {
+                    v["shapes"][i]["kind"] = serde_json::json!({ "type": "group" });
+                    v["shapes"][i]["parent"] = Value::from(parent);
+                }
+            }),
+            "shapes[0].parent: cycle in parents"
+        );
+        assert_eq!(
+            error_for(|v| v["shapes"][0]["z_index"] = Value::from("a00")),
+            "shapes[0].z_index: invalid fractional index 'a00'"
+        );
+        assert_eq!(error_for(|v| v["version"] = Value::from(99)), "version: 99 is newer than the supported version 2");
+        assert!(Board::from_json("[1, 2").unwrap_err().starts_with("invalid JSON"));
+    }
+
+    #[test]
+    fn test_migrations_run_in_order() {
+        // A pretend history: version 1 called the camera "view", version 2
+        // stored zoom as a percentage.
+        fn rename_view(value: &mut Value) -> Result<(), String> {
+            let view = value.as_object_mut().unwrap().remove("view").ok_or("missing view")?;
+            value["camera"] = view;
+            Ok(())
+        }
+        fn zoom_from_percent(value: &mut Value) -> Result<(), String> {
+            let percent = value["camera"]["zoom"].as_f64().ok_or("zoom is not a number")?;
+            value["camera"]["zoom"] = Value::from(percent / 100.0);
+            Ok(())
+        }
+        let migrations: &[Migration] = &[rename_view, zoom_from_percent];
+        let old = serde_json::json!({
+            "version": 1,
+            "view": { "zoom": 250.0, "offset_x": 0.0, "offset_y": 0.0 },
+            "shapes": [],
+        });
+        let board = Board::from_value(old, migrations).unwrap();
+        assert_eq!(board.version, 3);
+        assert_eq!(board.camera.zoom, 2.5);
+
+        let broken = serde_json::json!({ "version": 1, "shapes": [] });
+        assert_eq!(
+            Board::from_value(broken, migrations).unwrap_err(),
+            "migrating from version 1: missing view"
+        );
+    }
+
+    #[test]
+    fn test_integer_z_indices_migrate_in_order() {
+        let mut value = serde_json::to_value(sample()).unwrap();
+        value["version"] = Value::from(1);
+        let shapes = value["shapes"].as_array_mut().unwrap();
+        let mut third = shapes[0].clone();
+        third["id"] = Value::from(2);
+        shapes.push(third);
+        for (shape, z_index) in shapes.iter_mut().zip([7, -3, 7]) {
+            shape["z_index"] = Value::from(z_index);
+        }
+        let board = Board::from_json(&value.to_string()).unwrap();
+        assert_eq!(board.version, FORMAT_VERSION);
+        let z_index = |i: usize| &board.shapes[i].z_index;
+        assert!(z_index(1) < z_index(0));
+        assert_eq!(z_index(0), z_index(2));
+
+        value["shapes"][0]["z_index"] = Value::from(1.5);
+        assert_eq!(
+            Board::from_json(&value.to_string()).unwrap_err(),
+            "migrating from version 1: shapes[0].z_index: expected an integer"
+        );
+    }
+
+    #[test]
+    fn test_restore_replaces_shapes() {
+        let board = sample();
+        let mut document = Document::new();
+        document.insert(ShapeKind::Ellipse, Transform::new(0.0, 0.0, 1.0, 1.0), Style::default());
+        let revision = document.revision();
+        let camera = board.clone().restore(&mut document);
+        assert_eq!(camera, board.camera);
+        assert!(document.revision() > revision);
+        assert_eq!(Board::new(&document, &camera), board);
+    }
+
+    fn unit() -> impl Strategy<Value = f32> {
+        0.0f32..=1.0
+    }
+
+    fn coordinate() -> impl Strategy<Value = f32> {
+        -1e6f32..1e6
+    }
+
+    fn color() -> impl Strategy<Value = Color> {
+        (unit(), unit(), unit(), unit()).prop_map(|(r, g, b, a)| Color::rgba(r, g, b, a))
+    }
+
+    fn point() -> impl Strategy<Value = Point> {
+        (coordinate(), coordinate()).prop_map(|(x, y)| Point::new(x, y))
+    }
+
+    fn align() -> impl Strategy<Value = TextAlign> {
+        prop_oneof![Just(TextAlign::Left), Just(TextAlign::Center), Just(TextAlign::Right)]
+    }
+
+    fn kind() -> impl Strategy<Value = ShapeKind> {
+        prop_oneof![
+            Just(ShapeKind::Rectangle),
+            Just(ShapeKind::Ellipse),
+            (point(), point()).prop_map(|(start, end)| ShapeKind::Line { start, end }),
+            (point(), point()).prop_map(|(start, end)| ShapeKind::Arrow { start, end }),
+            prop::collection::vec((coordinate(), coordinate(), unit()), 0..8).prop_map(|points| ShapeKind::Freehand {
+                points: points.into_iter().map(|(x, y, pressure)| StrokePoint { x, y, pressure }).collect(),
+            }),
+            (".*", 1.0f32..200.0, align()).prop_map(|(content, font_size, align)| ShapeKind::Text {
+                content,
+                font_size,
+                align,
+            }),
+            ".*".prop_map(|source| ShapeKind::Image { source }),
+            Just(ShapeKind::Group),
+            ".*".prop_map(|name| ShapeKind::Frame { name }),
+            (routing(), prop::collection::vec(point(), 2..6), prop::option::of(binding()), prop::option::of(binding()))
+                .prop_map(|(routing, mut route, start_binding, end_binding)| {
+                    if routing == Routing::Curved {
+                        route.resize(4, Point::ZERO);
+                    }
+                    ShapeKind::Connector { route, routing, start_binding, end_binding }
+                }),
+        ]
+    }
+
+    fn routing() -> impl Strategy<Value = Routing> {
+        prop_oneof![Just(Routing::Straight), Just(Routing::Curved), Just(Routing::Elbow)]
+    }
+
+    /// Binds to shape `n` of the board under test; see [`retarget`].
+    fn binding() -> impl Strategy<Value = Binding> {
+        let anchor = prop_oneof![
+            Just(Anchor::Outline),
+            (unit(), unit()).prop_map(|(x, y)| Anchor::Point { x, y }),
+        ];
+        (0u64..12, anchor).prop_map(|(n, anchor)| Binding { shape: ShapeId(n), anchor })
+    }
+
+    /// Points generated bindings at real shapes other than the connector itself.
+    fn retarget(binding: &mut Option<Binding>, own: ShapeId, count: u64, spacing: u64) {
+        if let Some(b) = binding {
+            b.shape = ShapeId(b.shape.0 % count * spacing);
+        }
+        if binding.is_some_and(|b| b.shape == own) {
+            *binding = None;
+        }
+    }
+
+    fn shape() -> impl Strategy<Value = Shape> {
+        let transform = (coordinate(), coordinate(), 0.0f32..1e5, 0.0f32..1e5, -7.0f32..7.0)
+            .prop_map(|(x, y, width, height, rotation)| Transform { x, y, width, height, rotation });
+        let style = (color(), prop::option::of(color()), 0.0f32..100.0, prop::collection::vec(0.0f32..50.0, 0..4), unit())
+            .prop_map(|(stroke, fill, stroke_width, dash, opacity)| Style {
+                stroke,
+                fill,
+                stroke_width,
+                dash,
+                opacity,
+            });
+        // Parent `n` is the board's nth shape; see the property below.
+        let parent = prop::option::of(0u64..12).prop_map(|n| n.map(ShapeId));
+        let layer = (0u64..3).prop_map(LayerId);
+        (kind(), transform, style, layer, parent).prop_map(|(kind, transform, style, layer, parent)| Shape {
+            id: ShapeId(0),
+            kind,
+            transform,
+            style,
+            z_index: FractionalIndex::default(),
+            layer,
+            parent,
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_save_load_is_lossless(
+            layers in prop::collection::vec((".*", any::<bool>(), any::<bool>(), unit()), 0..3),
+            shapes in prop::collection::vec(shape(), 0..12),
+            zoom in 0.01f32..100.0,
+            offset_x in coordinate(),
+            offset_y in coordinate(),
+        ) {
+            let mut document = Document::new();
+            for (name, visible, locked, opacity) in layers {
+                let id = document.add_layer(&name);
+                document.update_layer(id, |layer| *layer = Layer { id, name, visible, locked, opacity }).unwrap();
+            }
+            let count = shapes.len() as u64;
+            let z_indices = FractionalIndex::n_between(None, None, shapes.len()).unwrap();
+            let containers: Vec<bool> = shapes.iter().map(Shape::is_container).collect();
+            for ((i, mut shape), z_index) in shapes.into_iter().enumerate().zip(z_indices) {
+                shape.id = ShapeId(i as u64 * 3);
+                shape.z_index = z_index;
+                shape.layer.0 %= document.layers().len() as u64;
+                // Only earlier containers, so parents never form a cycle.
+                shape.parent = shape
+                    .parent
+                    .map(|n| n.0 % count)
+                    .filter(|&n| n < i as u64 && containers[n as usize])
+                    .map(|n| ShapeId(n * 3));
+                if let ShapeKind::Connector { start_binding, end_binding, .. } = &mut shape.kind {
+                    retarget(start_binding, shape.id, count, 3);
+                    retarget(end_binding, shape.id, count, 3);
+                }
+                document.insert_shape(shape).unwrap();
+            }
+            let board = Board::new(&document, &State { zoom, offset_x, offset_y });
+            let loaded = Board::from_json(&board.to_json().unwrap()).unwrap();
+            prop_assert_eq!(loaded, board);
+        }
+    }
+}
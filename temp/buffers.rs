@@ -0,0 +1,48 @@
+use web_sys::{WebGl2RenderingContext, WebGlBuffer};
+
+/// A GPU buffer that is re-filled from CPU data and grows on demand.
+///
+/// Capacity doubles when exceeded so steady editing doesn't reallocate every frame.
+pub struct DynamicBuffer {
+    pub buffer: WebGlBuffer,
+    target: u32,
+    capacity_bytes: i32,
+}
+
+impl DynamicBuffer {
+    pub fn new(context: &WebGl2RenderingContext, target: u32) -> Result<Self, String> {
+        let buffer = context.create_buffer()
+            .ok_or("Failed to create buffer")?;
+
+        Ok(Self {
+            buffer,
+            target,
+            capacity_bytes: 0,
+        })
+    }
+
+    fn reserve(&mut self, context: &WebGl2RenderingContext, bytes: i32) {
+        context.bind_buffer(self.target, Some(&self.buffer));
+        if bytes > self.capacity_bytes {
+            let capacity = (bytes as u32).next_power_of_two().max(1024) as i32;
+            context.buffer_data_with_i32(self.target, capacity, WebGl2RenderingContext::DYNAMIC_DRAW);
+            self.capacity_bytes = capacity;
+        }
+    }
+
+    pub fn upload_f32(&mut self, context: &WebGl2RenderingContext, data: &[f32]) {
+        self.reserve(context, (data.len() * 4) as i32);
+        unsafe {
+            let view = js_sys::Float32Array::view(data);
+            context.buffer_sub_data_with_i32_and_array_buffer_view(self.target, 0, &view);
+        }
+    }
+
+    pub fn upload_u32(&mut self, context: &WebGl2RenderingContext, data: &[u32]) {
+        self.reserve(context, (data.len() * 4) as i32);
+        unsafe {
+            let view = js_sys::Uint32Array::view(data);
+            context.buffer_sub_data_with_i32_and_array_buffer_view(self.target, 0, &view);
+        }
+    }
+}
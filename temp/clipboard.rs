@@ -0,0 +1,163 @@
+//! Copy and paste. Copied shapes travel as a board, see [`crate::board`],
+//! so a paste is migrated and validated like a load. The platform glue adds
+//! SVG and PNG renderings for other apps.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::board::{Board, FORMAT_VERSION};
+use crate::document::{Document, ShapeId, ShapeKind};
+use crate::geometry::Point;
+use crate::state::State;
+
+/// Clipboard type of the board JSON.
+pub const MIME_TYPE: &str = "application/x-webgl-grid+json";
+
+/// `ids` and everything inside them, bottom to top. Containers and bound
+/// shapes left behind are dropped from the copies.
+pub fn copy(document: &Document, ids: &[ShapeId]) -> Option<Board> {
+    let mut copied: HashSet<ShapeId> = ids.iter().copied().filter(|id| document.contains(*id)).collect();
+    for id in ids {
+        copied.extend(document.descendants(*id));
+    }
+    if copied.is_empty() {
+        return None;
+    }
+    let shapes = document
+        .shapes_in_z_order()
+        .into_iter()
+        .filter(|shape| copied.contains(&shape.id))
+        .map(|shape| {
+            let mut shape = shape.clone();
+            shape.parent = shape.parent.filter(|parent| copied.contains(parent));
+            if let ShapeKind::Connector { start_binding, end_binding, .. } = &mut shape.kind {
+                for binding in [start_binding, end_binding] {
+                    if binding.is_some_and(|b| !copied.contains(&b.shape)) {
+                        *binding = None;
+                    }
+                }
+            }
+            shape
+        })
+        .collect();
+    Some(Board {
+        version: FORMAT_VERSION,
+        camera: State::default(),
+        layers: document.layers().to_vec(),
+        shapes,
+    })
+}
+
+/// Adds `board`'s shapes on top of the active layer, or the topmost
+/// editable one if the active layer is locked or hidden, centred on `at`.
+/// The copies get fresh ids, and connectors bound within the board stay
+/// bound to the copies. Returns the outermost pasted shapes.
+pub fn paste(document: &mut Document, board: &Board, at: Point) -> Result<Vec<ShapeId>, String> {
+    let layer = document.editable_layer().ok_or("Every layer is locked or hidden")?;
+    let outermost = board.shapes.iter().filter(|shape| shape.parent.is_none());
+    let Some(bounds) = outermost.map(|shape| shape.transform.bounds()).reduce(|a, b| a.union(&b)) else {
+        return Ok(Vec::new());
+    };
+    let offset = at - bounds.center();
+    let mut ids = HashMap::new();
+    for shape in &board.shapes {
+        let mut transform = shape.transform;
+        transform.x += offset.x;
+        transform.y += offset.y;
+        ids.insert(shape.id, document.insert(shape.kind.clone(), transform, shape.style.clone()));
+    }
+    // Parents and bindings once every copy has its id.
+    for shape in &board.shapes {
+        let parent = shape.parent.and_then(|parent| ids.get(&parent).copied());
+        let mut kind = shape.kind.clone();
+        if let ShapeKind::Connector { start_binding, end_binding, .. } = &mut kind {
+            for binding in [start_binding, end_binding] {
+                *binding = binding.and_then(|mut b| {
+                    b.shape = *ids.get(&b.shape)?;
+                    Some(b)
+                });
+            }
+        }
+        if parent.is_some() || kind != shape.kind || layer != document.active_layer() {
+            let _ = document.update(ids[&shape.id], |copy| {
+                copy.parent = parent;
+                copy.kind = kind;
+                copy.layer = layer;
+            });
+        }
+    }
+    Ok(board
+        .shapes
+        .iter()
+        .filter(|shape| shape.parent.is_none())
+        .map(|shape| ids[&shape.id])
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Anchor, Binding, Routing, Style, Transform};
+    use crate::hierarchy::group;
+
+    #[test]
+    fn test_copy_and_paste_remaps_ids() {
+        let mut document = Document::new();
+        let a = document.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 10.0, 10.0), Style::default());
+        let b = document.insert(ShapeKind::Ellipse, Transform::new(90.0, 0.0, 10.0, 10.0), Style::default());
+        let outside = document.insert(ShapeKind::Ellipse, Transform::new(0.0, 90.0, 10.0, 10.0), Style::default());
+        let bound = |shape| Some(Binding { shape, anchor: Anchor::Outline });
+        let connector = |start, end| ShapeKind::Connector {
+            route: vec![Point::new(0.0, 0.0), Point::new(80.0, 0.0)],
+            routing: Routing::Straight,
+            start_binding: bound(start),
+            end_binding: bound(end),
+        };
+        let inside = document.insert(connector(a, b), Transform::new(10.0, 5.0, 80.0, 0.0), Style::default());
+        let leaving = document.insert(connector(a, outside), Transform::new(5.0, 10.0, 0.0, 80.0), Style::default());
+        let g = group(&mut document, &[a, b]).unwrap();
+
+        let board = copy(&document, &[g, inside, leaving]).unwrap();
+        assert_eq!(board.shapes.len(), 5);
+        // The board round-trips through the clipboard format.
+        let board = Board::from_json(&board.to_json().unwrap()).unwrap();
+
+        let pasted = paste(&mut document, &board, Point::new(500.0, 500.0)).unwrap();
+        assert_eq!(pasted.len(), 3);
+        assert!(pasted.iter().all(|id| ![g, inside, leaving].contains(id)));
+        let new_group = pasted.iter().copied().find(|id| document.get(*id).unwrap().kind == ShapeKind::Group).unwrap();
+        let children = document.children(new_group);
+        assert_eq!(children.len(), 2);
+        // The bounds of what was pasted are centred on the target.
+        let bounds = pasted
+            .iter()
+            .map(|id| document.get(*id).unwrap().transform.bounds())
+            .reduce(|a, b| a.union(&b))
+            .unwrap();
+        assert!(bounds.center().distance(Point::new(500.0, 500.0)) < 1e-3);
+
+        let bindings = |id: ShapeId| match &document.get(id).unwrap().kind {
+            ShapeKind::Connector { start_binding, end_binding, .. } => (start_binding.map(|b| b.shape), end_binding.map(|b| b.shape)),
+            _ => unreachable!(),
+        };
+        let connectors: Vec<_> = pasted.iter().copied().filter(|id| *id != new_group).map(bindings).collect();
+        assert!(connectors.contains(&(Some(children[0]), Some(children[1]))));
+        // The binding to a shape left behind is dropped.
+        assert!(connectors.contains(&(Some(children[0]), None)));
+    }
+
+    #[test]
+    fn test_paste_skips_locked_and_hidden_layers() {
+        let mut document = Document::new();
+        let a = document.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 10.0, 10.0), Style::default());
+        let board = copy(&document, &[a]).unwrap();
+        let bottom = document.active_layer();
+        let top = document.add_layer("Locked");
+        document.update_layer(top, |layer| layer.locked = true).unwrap();
+
+        let pasted = paste(&mut document, &board, Point::ZERO).unwrap();
+        assert_eq!(document.get(pasted[0]).unwrap().layer, bottom);
+
+        document.update_layer(bottom, |layer| layer.visible = false).unwrap();
+        assert!(paste(&mut document, &board, Point::ZERO).is_err());
+    }
+}
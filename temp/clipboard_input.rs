@@ -0,0 +1,237 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use js_sys::{Array, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{Blob, BlobPropertyBag, ClipboardEvent, ClipboardItem, DataTransfer, File, HtmlTextAreaElement, Window};
+
+use crate::clipboard::MIME_TYPE;
+use crate::editor::Editor;
+use crate::image_input::{insert_files, settle};
+
+const SVG_TYPE: &str = "image/svg+xml";
+const PNG_TYPE: &str = "image/png";
+/// Pixels per world unit of the PNG put on the clipboard.
+const PNG_SCALE: f32 = 2.0;
+
+/// The async Clipboard API only carries custom types under this prefix.
+fn web_type() -> String {
+    format!("web {MIME_TYPE}")
+}
+
+/// Editing text is the text field's business, not the canvas's.
+fn in_text_field(event: &ClipboardEvent) -> bool {
+    event.target().is_some_and(|target| target.has_type::<HtmlTextAreaElement>())
+}
+
+fn blob(parts: &JsValue, type_: &str) -> Result<Blob, JsValue> {
+    let options = BlobPropertyBag::new();
+    options.set_type(type_);
+    match parts.dyn_ref::<Uint8Array>() {
+        Some(bytes) => Blob::new_with_u8_array_sequence_and_options(&Array::of1(bytes), &options),
+        None => Blob::new_with_str_sequence_and_options(&Array::of1(parts), &options),
+    }
+}
+
+/// The selection as it goes on the clipboard: our JSON, plus SVG and PNG
+/// renderings for other apps.
+struct Copied {
+    json: String,
+    svg: Option<String>,
+    png: Option<Vec<u8>>,
+}
+
+impl Copied {
+    /// Renders the selection; `None` when nothing is selected.
+    fn selection(editor: &Rc<RefCell<Editor>>) -> Option<Self> {
+        let json = editor.borrow().copy_selection()?;
+        // The exports borrow the editor themselves.
+        Some(Self {
+            json,
+            svg: crate::export_svg("selection", false).ok(),
+            png: crate::export_png("selection", PNG_SCALE, true).ok(),
+        })
+    }
+
+    /// Fills the event's clipboard, which every browser reads but which
+    /// only holds text, so no PNG.
+    fn set_data(&self, data: &DataTransfer) -> Result<(), JsValue> {
+        data.set_data(MIME_TYPE, &self.json)?;
+        if let Some(svg) = &self.svg {
+            data.set_data(SVG_TYPE, svg)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the clipboard with every format, PNG included, where the
+    /// async Clipboard API allows it. Failing leaves the event's data.
+    fn write(&self, window: &Window) -> Result<(), JsValue> {
+        let mut blobs = vec![(web_type(), blob(&self.json.as_str().into(), MIME_TYPE)?)];
+        if let Some(svg) = &self.svg {
+            blobs.push((SVG_TYPE.to_string(), blob(&svg.as_str().into(), SVG_TYPE)?));
+        }
+        if let Some(png) = &self.png {
+            blobs.push((PNG_TYPE.to_string(), blob(&Uint8Array::from(png.as_slice()).into(), PNG_TYPE)?));
+        }
+        let record = Object::new();
+        for (type_, blob) in blobs {
+            Reflect::set(&record, &type_.into(), &blob)?;
+        }
+        let item = ClipboardItem::new_with_record_from_str_to_blob_promise(&record)?;
+        let written = window.navigator().clipboard().write(&Array::of1(&item));
+        settle(&written, |_| {}, |error| web_sys::console::warn_2(&"Clipboard write failed".into(), &error));
+        Ok(())
+    }
+}
+
+fn copy(window: &Window, event: &ClipboardEvent, editor: &Rc<RefCell<Editor>>) -> bool {
+    if in_text_field(event) {
+        return false;
+    }
+    let (Some(copied), Some(data)) = (Copied::selection(editor), event.clipboard_data()) else {
+        return false;
+    };
+    event.prevent_default();
+    if let Err(e) = copied.set_data(&data).and_then(|()| copied.write(window)) {
+        web_sys::console::warn_1(&e);
+    }
+    true
+}
+
+fn paste_json(editor: &Rc<RefCell<Editor>>, json: &str) {
+    let mut editor = editor.borrow_mut();
+    let at = editor.pointer();
+    if let Err(e) = editor.paste(json, at) {
+        web_sys::console::warn_1(&format!("Cannot paste: {e}").into());
+    }
+}
+
+/// What the paste event carried besides our JSON, kept for when the async
+/// clipboard has none either.
+struct Fallback {
+    window: Window,
+    editor: Rc<RefCell<Editor>>,
+    files: Vec<File>,
+    text: String,
+}
+
+impl Fallback {
+    fn paste(self) {
+        let at = self.editor.borrow().pointer();
+        if !self.files.is_empty() {
+            insert_files(&self.window, self.files, at, &self.editor);
+        } else {
+            self.editor.borrow_mut().paste_text(&self.text, at);
+        }
+    }
+
+    /// Looks for our JSON among the async clipboard's items, which is the
+    /// only place a browser exposes the `web ` type written by [`Copied::write`].
+    fn read(self) {
+        let read = self.window.navigator().clipboard().read();
+        let fallback = Rc::new(RefCell::new(Some(self)));
+        let fallback_clone = fallback.clone();
+        let give_up = move || {
+            if let Some(fallback) = fallback_clone.borrow_mut().take() {
+                fallback.paste();
+            }
+        };
+        let give_up_clone = give_up.clone();
+        settle(
+            &read,
+            move |items| {
+                let item = Array::from(&items)
+                    .iter()
+                    .map(|item| item.unchecked_into::<ClipboardItem>())
+                    .find(|item| item.types().includes(&web_type().into(), 0));
+                let Some(item) = item else {
+                    return give_up();
+                };
+                let give_up_clone = give_up.clone();
+                settle(
+                    &item.get_type(&web_type()),
+                    move |blob| {
+                        let text = blob.unchecked_into::<Blob>().text();
+                        settle(
+                            &text,
+                            move |json| {
+                                let json = json.as_string().unwrap_or_default();
+                                if let Some(fallback) = fallback.borrow_mut().take() {
+                                    paste_json(&fallback.editor, &json);
+                                }
+                            },
+                            move |_| give_up_clone(),
+                        );
+                    },
+                    move |_| give_up(),
+                );
+            },
+            move |_| give_up_clone(),
+        );
+    }
+}
+
+/// Copies, cuts and pastes shapes anywhere on the page. Images and plain
+/// text from other apps paste as new shapes at the pointer.
+pub fn setup_clipboard_events(window: &Window, editor: Rc<RefCell<Editor>>) -> Result<(), JsValue> {
+    let window_clone = window.clone();
+    let editor_clone = editor.clone();
+    let copy_callback = Closure::wrap(Box::new(move |event: ClipboardEvent| {
+        copy(&window_clone, &event, &editor_clone);
+    }) as Box<dyn FnMut(ClipboardEvent)>);
+
+    let window_clone = window.clone();
+    let editor_clone = editor.clone();
+    let cut_callback = Closure::wrap(Box::new(move |event: ClipboardEvent| {
+        // A busy tool keeps its shapes, so there would be nothing to cut.
+        if !editor_clone.borrow().tools.is_idle() {
+            return;
+        }
+        // Rendered before deleting, so the PNG still has the shapes.
+        if copy(&window_clone, &event, &editor_clone) {
+            editor_clone.borrow_mut().delete_selection();
+        }
+    }) as Box<dyn FnMut(ClipboardEvent)>);
+
+    let window_clone = window.clone();
+    let paste_callback = Closure::wrap(Box::new(move |event: ClipboardEvent| {
+        if in_text_field(&event) {
+            return;
+        }
+        let Some(data) = event.clipboard_data() else {
+            return;
+        };
+        event.prevent_default();
+        match data.get_data(MIME_TYPE) {
+            Ok(json) if !json.is_empty() => paste_json(&editor, &json),
+            // The event's data is gone once it returns, so keep what the
+            // async read may fall back to.
+            _ => Fallback {
+                window: window_clone.clone(),
+                editor: editor.clone(),
+                files: data
+                    .files()
+                    .map(|files| {
+                        (0..files.length())
+                            .filter_map(|i| files.get(i))
+                            .filter(|file| file.type_().starts_with("image/"))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                text: data.get_data("text/plain").unwrap_or_default(),
+            }
+            .read(),
+        }
+    }) as Box<dyn FnMut(ClipboardEvent)>);
+
+    window.add_event_listener_with_callback("copy", copy_callback.as_ref().unchecked_ref())?;
+    window.add_event_listener_with_callback("cut", cut_callback.as_ref().unchecked_ref())?;
+    window.add_event_listener_with_callback("paste", paste_callback.as_ref().unchecked_ref())?;
+
+    copy_callback.forget();
+    cut_callback.forget();
+    paste_callback.forget();
+
+    Ok(())
+}
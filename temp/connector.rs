@@ -0,0 +1,619 @@
+//! Keeping connectors attached to the shapes they are bound to.
+//!
+//! A connector stores its route, so drawing, hit testing and export never
+//! route anything themselves. Whenever shapes change, [`reroute_affected`]
+//! recomputes the connectors bound to them and writes back only routes that
+//! actually moved. Routing depends on nothing but the document, so replaying
+//! history, which restores routes together with their targets, records no
+//! new edits.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+use crate::document::{Anchor, Binding, Document, DocumentEvent, Routing, Shape, ShapeId, ShapeKind, Transform};
+use crate::geometry::{point_in_polygon, Point, Rect};
+use crate::spatial::SpatialIndex;
+
+/// Space left between a bound end and its target, in world units.
+pub const BINDING_GAP: f32 = 4.0;
+
+/// Anchors offered on every target, as fractions of its box: the middle of
+/// each side.
+pub const ANCHORS: [(f32, f32); 4] = [(0.5, 0.0), (1.0, 0.5), (0.5, 1.0), (0.0, 0.5)];
+
+/// How far elbow routes stand off from the shapes they go around, in world
+/// units.
+const ELBOW_MARGIN: f32 = 20.0;
+
+/// What each turn of an elbow route costs, as extra length in world units.
+const BEND_PENALTY: f32 = 40.0;
+
+/// Most obstacles an elbow route goes around. The routing grid grows with
+/// the square of their number, so past this the route cuts straight
+/// through rather than stall a drag on a crowded board.
+const MAX_ELBOW_OBSTACLES: usize = 40;
+
+/// How far the control points of a curved route reach out, as a fraction of
+/// the distance between its ends.
+const CURVE_REACH: f32 = 0.4;
+
+/// Sampling tolerance for ellipse outlines, in world units.
+const TOLERANCE: f32 = 0.25;
+
+/// Closed shapes a connector end can bind to.
+pub fn can_bind(shape: &Shape) -> bool {
+    matches!(
+        shape.kind,
+        ShapeKind::Rectangle | ShapeKind::Ellipse | ShapeKind::Text { .. } | ShapeKind::Image { .. } | ShapeKind::Frame { .. }
+    )
+}
+
+fn outline(shape: &Shape) -> Vec<Point> {
+    shape.outline(TOLERANCE).unwrap_or_else(|| shape.transform.corners().to_vec())
+}
+
+/// World position of the anchor `(x, y)`, given as fractions of `shape`'s box.
+pub fn anchor_position(shape: &Shape, x: f32, y: f32) -> Point {
+    let t = &shape.transform;
+    t.to_world(Point::new(x * t.width, y * t.height))
+}
+
+/// The binding for a connector end at `point`: the topmost bindable shape
+/// containing it or within `tolerance` of its outline, at one of its
+/// [`ANCHORS`] if one is within `tolerance` too.
+pub fn binding_at(document: &Document, index: &SpatialIndex, point: Point, tolerance: f32) -> Option<Binding> {
+    let probe = Rect::new(point.x, point.y, point.x, point.y).expand(tolerance);
+    let target = index
+        .query_rect(&probe)
+        .into_iter()
+        .filter_map(|id| document.get(id))
+        .filter(|shape| can_bind(shape) && document.is_hittable(shape) && touches(shape, point, tolerance))
+        .max_by_key(|shape| document.paint_key(shape))?;
+    let anchor = ANCHORS
+        .into_iter()
+        .map(|(x, y)| (anchor_position(target, x, y).distance(point), x, y))
+        .filter(|(distance, ..)| *distance <= tolerance)
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map_or(Anchor::Outline, |(_, x, y)| Anchor::Point { x, y });
+    Some(Binding { shape: target.id, anchor })
+}
+
+fn touches(shape: &Shape, point: Point, tolerance: f32) -> bool {
+    let outline = outline(shape);
+    point_in_polygon(point, &outline)
+        || (0..outline.len()).any(|i| point.distance_to_segment(outline[i], outline[(i + 1) % outline.len()]) <= tolerance)
+}
+
+/// The target a binding points at, if it still exists and can be bound.
+fn target(document: &Document, binding: Option<Binding>, connector: ShapeId) -> Option<(&Shape, Anchor)> {
+    let binding = binding?;
+    let shape = document.get(binding.shape).filter(|shape| shape.id != connector && can_bind(shape))?;
+    Some((shape, binding.anchor))
+}
+
+/// Where an end is aimed from by the other end: its anchor, its target's
+/// centre, or the end itself when it is free.
+fn reference(target: Option<(&Shape, Anchor)>, free: Point) -> Point {
+    match target {
+        Some((shape, Anchor::Point { x, y })) => anchor_position(shape, x, y),
+        Some((shape, Anchor::Outline)) => shape.transform.center(),
+        None => free,
+    }
+}
+
+/// Where a bound end sits, [`BINDING_GAP`] outside its target. Outline
+/// bindings sit where the line from the target's centre to `toward` leaves
+/// the outline.
+fn bound_end(shape: &Shape, anchor: Anchor, toward: Point) -> Point {
+    let center = shape.transform.center();
+    match anchor {
+        Anchor::Point { x, y } => {
+            let point = anchor_position(shape, x, y);
+            point + (point - center).normalize() * BINDING_GAP
+        }
+        Anchor::Outline => {
+            let direction = (toward - center).normalize();
+            if direction == Point::ZERO {
+                return center;
+            }
+            let outline = outline(shape);
+            let reach = (0..outline.len())
+                .filter_map(|i| ray_hit(center, direction, outline[i], outline[(i + 1) % outline.len()]))
+                .fold(0.0, f32::max);
+            center + direction * (reach + BINDING_GAP)
+        }
+    }
+}
+
+/// How far along the ray from `origin` in `direction` it crosses segment `ab`.
+fn ray_hit(origin: Point, direction: Point, a: Point, b: Point) -> Option<f32> {
+    let edge = b - a;
+    let denominator = direction.cross(edge);
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+    let offset = a - origin;
+    let t = offset.cross(edge) / denominator;
+    let u = offset.cross(direction) / denominator;
+    (t >= 0.0 && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+/// The way a route leaves `end`: straight out of its target, judged
+/// relative to the target's proportions, or towards `other` when free.
+fn exit_direction(target: Option<&Shape>, end: Point, other: Point) -> Point {
+    let Some(shape) = target else {
+        return other - end;
+    };
+    let bounds = shape.transform.bounds();
+    let out = end - bounds.center();
+    if out == Point::ZERO {
+        return other - end;
+    }
+    Point::new(out.x / bounds.width().max(f32::EPSILON), out.y / bounds.height().max(f32::EPSILON))
+}
+
+/// The axis-aligned unit vector closest to `direction`.
+fn axis(direction: Point) -> Point {
+    if direction.x.abs() >= direction.y.abs() {
+        Point::new(if direction.x < 0.0 { -1.0 } else { 1.0 }, 0.0)
+    } else {
+        Point::new(0.0, if direction.y < 0.0 { -1.0 } else { 1.0 })
+    }
+}
+
+/// `connector` re-routed between its ends as bound in `document`. Bound
+/// ends follow their targets and free ends stay put; bindings to shapes
+/// that are gone are dropped. Other shapes are returned unchanged.
+///
+/// The result is axis-aligned: the transform is the route's bounds.
+pub fn reroute(document: &Document, index: &SpatialIndex, connector: &Shape) -> Shape {
+    let ShapeKind::Connector { route, routing, start_binding, end_binding } = &connector.kind else {
+        return connector.clone();
+    };
+    let (Some(first), Some(last)) = (route.first(), route.last()) else {
+        return connector.clone();
+    };
+    let t = &connector.transform;
+    let start_target = target(document, *start_binding, connector.id);
+    let end_target = target(document, *end_binding, connector.id);
+    let (free_start, free_end) = (t.to_world(*first), t.to_world(*last));
+    let start = start_target.map_or(free_start, |(shape, anchor)| bound_end(shape, anchor, reference(end_target, free_end)));
+    let end = end_target.map_or(free_end, |(shape, anchor)| bound_end(shape, anchor, reference(start_target, free_start)));
+    let start_exit = exit_direction(start_target.map(|(shape, _)| shape), start, end);
+    let end_exit = exit_direction(end_target.map(|(shape, _)| shape), end, start);
+
+    let waypoints = match routing {
+        Routing::Straight => vec![start, end],
+        Routing::Curved => {
+            let reach = start.distance(end) * CURVE_REACH;
+            vec![
+                start,
+                start + start_exit.normalize() * reach,
+                end + end_exit.normalize() * reach,
+                end,
+            ]
+        }
+        Routing::Elbow => {
+            // Only shapes near the line between the ends are worth avoiding.
+            let region = Rect::from_points([start, end]).unwrap().expand(ELBOW_MARGIN * 2.0);
+            let mut obstacles: Vec<Rect> = index
+                .query_rect(&region)
+                .into_iter()
+                .filter_map(|id| document.get(id))
+                .filter(|shape| can_bind(shape))
+                .map(|shape| shape.bounds())
+                .collect();
+            if obstacles.len() > MAX_ELBOW_OBSTACLES {
+                obstacles.clear();
+            }
+            elbow_route(start, axis(start_exit), end, axis(end_exit), &obstacles)
+        }
+    };
+
+    let bounds = Rect::from_points(waypoints.iter().copied()).unwrap();
+    let corner = Point::new(bounds.min_x, bounds.min_y);
+    let mut rerouted = connector.clone();
+    rerouted.transform = Transform::new(bounds.min_x, bounds.min_y, bounds.width(), bounds.height());
+    rerouted.kind = ShapeKind::Connector {
+        route: waypoints.into_iter().map(|p| p - corner).collect(),
+        routing: *routing,
+        start_binding: start_binding.filter(|_| start_target.is_some()),
+        end_binding: end_binding.filter(|_| end_target.is_some()),
+    };
+    rerouted
+}
+
+/// Whether two versions of a shape match up to float noise, so re-routing
+/// an up-to-date connector is never recorded as an edit.
+fn same_route(a: &Shape, b: &Shape) -> bool {
+    let close = |x: f32, y: f32| (x - y).abs() <= 1e-4 * (1.0 + x.abs().max(y.abs()));
+    let (ta, tb) = (&a.transform, &b.transform);
+    let transforms = close(ta.x, tb.x)
+        && close(ta.y, tb.y)
+        && close(ta.width, tb.width)
+        && close(ta.height, tb.height)
+        && close(ta.rotation, tb.rotation);
+    match (&a.kind, &b.kind) {
+        (
+            ShapeKind::Connector { route: ra, routing: ma, start_binding: sa, end_binding: ea },
+            ShapeKind::Connector { route: rb, routing: mb, start_binding: sb, end_binding: eb },
+        ) => {
+            transforms
+                && ma == mb
+                && sa == sb
+                && ea == eb
+                && ra.len() == rb.len()
+                && ra.iter().zip(rb).all(|(p, q)| close(p.x, q.x) && close(p.y, q.y))
+        }
+        _ => a == b,
+    }
+}
+
+/// Re-routes the connectors touched by `events`: those that changed and
+/// those bound to a shape that changed or was deleted. `index` must already
+/// reflect the events.
+pub fn reroute_affected(document: &mut Document, index: &SpatialIndex, events: &[DocumentEvent]) {
+    if events.is_empty() {
+        return;
+    }
+    let changed: HashSet<ShapeId> = events
+        .iter()
+        .map(|event| match *event {
+            DocumentEvent::Inserted(id) | DocumentEvent::Updated(id) | DocumentEvent::Deleted(id) => id,
+        })
+        .collect();
+    let mut affected: Vec<ShapeId> = changed
+        .iter()
+        .copied()
+        .filter(|id| document.get(*id).is_some_and(|shape| matches!(shape.kind, ShapeKind::Connector { .. })))
+        .chain(changed.iter().flat_map(|id| document.attached(*id)))
+        .collect();
+    affected.sort();
+    affected.dedup();
+    for id in affected {
+        let Some(shape) = document.get(id) else {
+            continue;
+        };
+        let rerouted = reroute(document, index, shape);
+        if !same_route(shape, &rerouted) {
+            let _ = document.update(id, |shape| *shape = rerouted);
+        }
+    }
+}
+
+/// Whether `point` is strictly inside `rect`.
+fn inside(rect: &Rect, point: Point) -> bool {
+    rect.min_x < point.x && point.x < rect.max_x && rect.min_y < point.y && point.y < rect.max_y
+}
+
+/// Whether the axis-aligned segment `ab` passes through the inside of `rect`.
+fn crosses(rect: &Rect, a: Point, b: Point) -> bool {
+    let (min_x, max_x) = (a.x.min(b.x), a.x.max(b.x));
+    let (min_y, max_y) = (a.y.min(b.y), a.y.max(b.y));
+    if min_y == max_y {
+        rect.min_y < min_y && min_y < rect.max_y && rect.min_x < max_x && min_x < rect.max_x
+    } else {
+        rect.min_x < min_x && min_x < rect.max_x && rect.min_y < max_y && min_y < rect.max_y
+    }
+}
+
+const DIRECTIONS: [Point; 4] = [
+    Point { x: 1.0, y: 0.0 },
+    Point { x: -1.0, y: 0.0 },
+    Point { x: 0.0, y: 1.0 },
+    Point { x: 0.0, y: -1.0 },
+];
+
+fn direction_index(direction: Point) -> usize {
+    DIRECTIONS.iter().position(|d| *d == direction).unwrap_or(0)
+}
+
+/// An orthogonal route from `start`, leaving along `start_exit`, to `end`,
+/// arriving against `end_exit`, around `obstacles`.
+///
+/// Both ends first step [`ELBOW_MARGIN`] straight out. Between those stubs
+/// the route runs on a grid of lines through the stubs and along the
+/// margins of every obstacle, taking the path that is shortest once each
+/// turn is charged [`BEND_PENALTY`]. Obstacles covering a stub are ignored,
+/// and if the stubs cannot be joined at all the route cuts through.
+fn elbow_route(start: Point, start_exit: Point, end: Point, end_exit: Point, obstacles: &[Rect]) -> Vec<Point> {
+    let a = start + start_exit * ELBOW_MARGIN;
+    let b = end + end_exit * ELBOW_MARGIN;
+    let blocking: Vec<Rect> = obstacles
+        .iter()
+        .map(|rect| rect.expand(ELBOW_MARGIN * 0.5))
+        .filter(|rect| !inside(rect, a) && !inside(rect, b))
+        .collect();
+
+    let mut xs = vec![a.x, b.x, (a.x + b.x) * 0.5];
+    let mut ys = vec![a.y, b.y, (a.y + b.y) * 0.5];
+    for rect in obstacles.iter().map(|rect| rect.expand(ELBOW_MARGIN)) {
+        xs.extend([rect.min_x, rect.max_x]);
+        ys.extend([rect.min_y, rect.max_y]);
+    }
+    for lines in [&mut xs, &mut ys] {
+        lines.sort_by(f32::total_cmp);
+        lines.dedup_by(|a, b| (*a - *b).abs() < 1e-3);
+    }
+
+    let path = grid_path(&xs, &ys, a, start_exit, b, end_exit * -1.0, &blocking).unwrap_or_else(|| {
+        if start_exit.x != 0.0 {
+            let middle = (a.x + b.x) * 0.5;
+            vec![a, Point::new(middle, a.y), Point::new(middle, b.y), b]
+        } else {
+            let middle = (a.y + b.y) * 0.5;
+            vec![a, Point::new(a.x, middle), Point::new(b.x, middle), b]
+        }
+    });
+    let mut points = vec![start];
+    points.extend(path);
+    points.push(end);
+    simplify(points)
+}
+
+/// Dijkstra over the grid of `xs` by `ys`, where a state is a node and the
+/// direction it was entered in. Returns the nodes from `a` to `b`.
+fn grid_path(xs: &[f32], ys: &[f32], a: Point, a_direction: Point, b: Point, b_direction: Point, blocking: &[Rect]) -> Option<Vec<Point>> {
+    let find = |lines: &[f32], value: f32| lines.iter().position(|line| (line - value).abs() < 1e-3);
+    let node = |i: usize, j: usize| i * ys.len() + j;
+    let position = |n: usize| Point::new(xs[n / ys.len()], ys[n % ys.len()]);
+    let start = node(find(xs, a.x)?, find(ys, a.y)?);
+    let goal = node(find(xs, b.x)?, find(ys, b.y)?);
+    let neighbour = |n: usize, direction: usize| {
+        let (i, j) = (n / ys.len(), n % ys.len());
+        let (i, j) = match direction {
+            0 => (i + 1, j),
+            1 => (i.checked_sub(1)?, j),
+            2 => (i, j + 1),
+            _ => (i, j.checked_sub(1)?),
+        };
+        (i < xs.len() && j < ys.len()).then(|| node(i, j))
+    };
+
+    let states = xs.len() * ys.len() * DIRECTIONS.len();
+    let mut cost = vec![f32::INFINITY; states];
+    let mut previous = vec![usize::MAX; states];
+    let mut queue = BinaryHeap::new();
+    let first = start * DIRECTIONS.len() + direction_index(a_direction);
+    cost[first] = 0.0;
+    // Costs are non-negative, so their bit patterns sort like the values.
+    queue.push(Reverse((0f32.to_bits(), first)));
+    let arrival = direction_index(b_direction);
+    let mut best: Option<(f32, usize)> = None;
+    while let Some(Reverse((bits, state))) = queue.pop() {
+        let so_far = f32::from_bits(bits);
+        if so_far > cost[state] || best.is_some_and(|(total, _)| so_far >= total) {
+            continue;
+        }
+        let (n, direction) = (state / DIRECTIONS.len(), state % DIRECTIONS.len());
+        if n == goal {
+            let total = so_far + if direction == arrival { 0.0 } else { BEND_PENALTY };
+            if best.is_none_or(|(best, _)| total < best) {
+                best = Some((total, state));
+            }
+            continue;
+        }
+        for next_direction in 0..DIRECTIONS.len() {
+            // Never double back along the way just taken.
+            if next_direction ^ 1 == direction {
+                continue;
+            }
+            let Some(next) = neighbour(n, next_direction) else {
+                continue;
+            };
+            let (from, to) = (position(n), position(next));
+            if blocking.iter().any(|rect| crosses(rect, from, to)) {
+                continue;
+            }
+            let bend = if next_direction == direction { 0.0 } else { BEND_PENALTY };
+            let next_cost = so_far + from.distance(to) + bend;
+            let next_state = next * DIRECTIONS.len() + next_direction;
+            if next_cost < cost[next_state] {
+                cost[next_state] = next_cost;
+                previous[next_state] = state;
+                queue.push(Reverse((next_cost.to_bits(), next_state)));
+            }
+        }
+    }
+
+    let (_, mut state) = best?;
+    let mut path = vec![position(state / DIRECTIONS.len())];
+    while previous[state] != usize::MAX {
+        state = previous[state];
+        path.push(position(state / DIRECTIONS.len()));
+    }
+    path.reverse();
+    Some(path)
+}
+
+/// Drops repeated points and points in the middle of straight runs.
+fn simplify(points: Vec<Point>) -> Vec<Point> {
+    let mut out: Vec<Point> = Vec::with_capacity(points.len());
+    for point in points {
+        if out.last().is_some_and(|last| last.distance(point) < 1e-3) {
+            continue;
+        }
+        if let [.., before, last] = out.as_slice() {
+            let (run, next) = (*last - *before, point - *last);
+            if run.cross(next).abs() < 1e-3 * run.length() * next.length() && run.dot(next) > 0.0 {
+                out.pop();
+            }
+        }
+        out.push(point);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Style;
+
+    fn rect(document: &mut Document, x: f32, y: f32) -> ShapeId {
+        document.insert(ShapeKind::Rectangle, Transform::new(x, y, 100.0, 100.0), Style::default())
+    }
+
+    fn connector(document: &mut Document, routing: Routing, from: ShapeId, to: ShapeId) -> ShapeId {
+        let kind = ShapeKind::Connector {
+            route: vec![Point::ZERO, Point::new(1.0, 1.0)],
+            routing,
+            start_binding: Some(Binding { shape: from, anchor: Anchor::Outline }),
+            end_binding: Some(Binding { shape: to, anchor: Anchor::Outline }),
+        };
+        document.insert(kind, Transform::new(0.0, 0.0, 1.0, 1.0), Style::default())
+    }
+
+    fn sync(document: &mut Document, index: &mut SpatialIndex) {
+        let events = document.drain_events();
+        index.apply_events(document, &events);
+        reroute_affected(document, index, &events);
+        let events = document.drain_events();
+        index.apply_events(document, &events);
+    }
+
+    fn route(document: &Document, id: ShapeId) -> Vec<Point> {
+        document.get(id).unwrap().path_points().unwrap()
+    }
+
+    fn close(a: Point, b: Point) -> bool {
+        a.distance(b) < 1e-3
+    }
+
+    #[test]
+    fn test_straight_route_follows_outlines() {
+        let mut document = Document::new();
+        let mut index = SpatialIndex::new();
+        let a = rect(&mut document, 0.0, 0.0);
+        let b = rect(&mut document, 300.0, 0.0);
+        let c = connector(&mut document, Routing::Straight, a, b);
+        sync(&mut document, &mut index);
+        let points = route(&document, c);
+        assert!(close(points[0], Point::new(100.0 + BINDING_GAP, 50.0)));
+        assert!(close(points[1], Point::new(300.0 - BINDING_GAP, 50.0)));
+
+        // Moving either end re-routes; rerouting again changes nothing.
+        document.update(b, |shape| shape.transform = Transform::new(0.0, 300.0, 100.0, 100.0)).unwrap();
+        sync(&mut document, &mut index);
+        let points = route(&document, c);
+        assert!(close(points[0], Point::new(50.0, 100.0 + BINDING_GAP)));
+        assert!(close(points[1], Point::new(50.0, 300.0 - BINDING_GAP)));
+        document.take_operations();
+        reroute_affected(&mut document, &index, &[DocumentEvent::Updated(a)]);
+        assert!(document.take_operations().is_empty());
+
+        // Deleting a target frees that end where it was.
+        document.delete(b).unwrap();
+        sync(&mut document, &mut index);
+        let ShapeKind::Connector { start_binding, end_binding, .. } = &document.get(c).unwrap().kind else {
+            panic!("expected a connector");
+        };
+        assert!(start_binding.is_some() && end_binding.is_none());
+        assert!(close(route(&document, c)[1], points[1]));
+    }
+
+    #[test]
+    fn test_anchor_bindings_and_curves() {
+        let mut document = Document::new();
+        let mut index = SpatialIndex::new();
+        let a = rect(&mut document, 0.0, 0.0);
+        let b = rect(&mut document, 300.0, 300.0);
+        sync(&mut document, &mut index);
+        // Near the top middle of `b`, and inside `a` away from any anchor.
+        let top = binding_at(&document, &index, Point::new(352.0, 298.0), 5.0).unwrap();
+        assert_eq!(top, Binding { shape: b, anchor: Anchor::Point { x: 0.5, y: 0.0 } });
+        let inner = binding_at(&document, &index, Point::new(30.0, 30.0), 5.0).unwrap();
+        assert_eq!(inner, Binding { shape: a, anchor: Anchor::Outline });
+        assert!(binding_at(&document, &index, Point::new(200.0, 200.0), 5.0).is_none());
+
+        let c = connector(&mut document, Routing::Curved, a, b);
+        document
+            .update(c, |shape| {
+                if let ShapeKind::Connector { end_binding, .. } = &mut shape.kind {
+                    *end_binding = Some(top);
+                }
+            })
+            .unwrap();
+        sync(&mut document, &mut index);
+        let ShapeKind::Connector { route: controls, .. } = &document.get(c).unwrap().kind else {
+            panic!("expected a connector");
+        };
+        assert_eq!(controls.len(), 4);
+        let points = route(&document, c);
+        assert!(points.len() > 4);
+        assert!(close(*points.last().unwrap(), Point::new(350.0, 300.0 - BINDING_GAP)));
+        // The curve arrives from above, straight into the anchor.
+        let [.., before, last] = points.as_slice() else { unreachable!() };
+        assert!((last.x - before.x).abs() < (last.y - before.y).abs());
+    }
+
+    #[test]
+    fn test_elbow_route_avoids_obstacles() {
+        let mut document = Document::new();
+        let mut index = SpatialIndex::new();
+        let a = rect(&mut document, 0.0, 0.0);
+        let b = rect(&mut document, 500.0, 0.0);
+        let wall = document.insert(ShapeKind::Rectangle, Transform::new(250.0, -100.0, 50.0, 300.0), Style::default());
+        let c = connector(&mut document, Routing::Elbow, a, b);
+        sync(&mut document, &mut index);
+
+        let points = route(&document, c);
+        assert!(close(points[0], Point::new(100.0 + BINDING_GAP, 50.0)));
+        assert!(close(*points.last().unwrap(), Point::new(500.0 - BINDING_GAP, 50.0)));
+        let obstacle = document.get(wall).unwrap().bounds();
+        for segment in points.windows(2) {
+            assert!(segment[0].x == segment[1].x || segment[0].y == segment[1].y, "not orthogonal: {:?}", segment);
+            assert!(!crosses(&obstacle, segment[0], segment[1]), "crosses the wall: {:?}", segment);
+        }
+        // Around the wall, not through it: out, over, across, down, in.
+        assert_eq!(points.len(), 6);
+    }
+
+    #[test]
+    fn test_crowded_elbow_route_cuts_through() {
+        let mut document = Document::new();
+        let mut index = SpatialIndex::new();
+        let a = rect(&mut document, 0.0, 0.0);
+        let b = rect(&mut document, 2000.0, 0.0);
+        for i in 0..MAX_ELBOW_OBSTACLES {
+            let x = 150.0 + i as f32 * 40.0;
+            document.insert(ShapeKind::Rectangle, Transform::new(x, 40.0, 10.0, 20.0), Style::default());
+        }
+        let c = connector(&mut document, Routing::Elbow, a, b);
+        sync(&mut document, &mut index);
+        let points = route(&document, c);
+        assert!(close(points[0], Point::new(100.0 + BINDING_GAP, 50.0)));
+        assert!(close(points[1], Point::new(2000.0 - BINDING_GAP, 50.0)));
+    }
+
+    #[test]
+    fn test_attachments_follow_bindings() {
+        let mut document = Document::new();
+        let a = rect(&mut document, 0.0, 0.0);
+        let b = rect(&mut document, 300.0, 0.0);
+        let c = connector(&mut document, Routing::Straight, a, b);
+        assert_eq!(document.attached(a).collect::<Vec<_>>(), vec![c]);
+        document
+            .update(c, |shape| {
+                if let ShapeKind::Connector { start_binding, .. } = &mut shape.kind {
+                    *start_binding = None;
+                }
+            })
+            .unwrap();
+        assert_eq!(document.attached(a).count(), 0);
+        assert_eq!(document.attached(b).collect::<Vec<_>>(), vec![c]);
+        document.delete(c).unwrap();
+        assert_eq!(document.attached(b).count(), 0);
+    }
+
+    #[test]
+    fn test_simplify_merges_straight_runs() {
+        let points = vec![
+            Point::ZERO,
+            Point::new(5.0, 0.0),
+            Point::new(5.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(10.0, 10.0),
+        ];
+        assert_eq!(simplify(points), vec![Point::ZERO, Point::new(10.0, 0.0), Point::new(10.0, 10.0)]);
+    }
+}
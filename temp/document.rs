@@ -0,0 +1,910 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::fractional_index::FractionalIndex;
+use crate::geometry::{Point, Rect};
+use crate::tessellate::flatten_cubic;
+use crate::text::TextAlign;
+
+/// Flattening tolerance for curved connectors, in world units.
+const CURVE_TOLERANCE: f32 = 0.25;
+
+/// Stable identifier of a shape. Ids are never reused within a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ShapeId(pub u64);
+
+/// Id given to shapes a tool draws before committing them. Never stored in a document.
+pub const PREVIEW_SHAPE_ID: ShapeId = ShapeId(u64::MAX);
+
+/// Stable identifier of a layer. Every document starts with layer 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LayerId(pub u64);
+
+/// A named slice of the paint order. Top-level shapes name their layer;
+/// shapes inside groups and frames are on their outermost container's.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layer {
+    pub id: LayerId,
+    pub name: String,
+    /// Hidden layers are not drawn, exported or hit.
+    pub visible: bool,
+    /// Locked layers are drawn but cannot be hit, so their shapes cannot be
+    /// picked or edited with the pointer.
+    pub locked: bool,
+    /// Multiplies the opacity of every shape on the layer.
+    pub opacity: f32,
+}
+
+impl Layer {
+    pub fn new(id: LayerId, name: &str) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            visible: true,
+            locked: false,
+            opacity: 1.0,
+        }
+    }
+}
+
+/// What a shape's containers and layer do to its drawing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Paint {
+    /// World rect the shape is cut to, see [`Document::clip`].
+    pub clip: Option<Rect>,
+    /// Multiplies the shape's own opacity.
+    pub opacity: f32,
+}
+
+impl Default for Paint {
+    fn default() -> Self {
+        Self { clip: None, opacity: 1.0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgba(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: Color = Color::rgba(1.0, 1.0, 1.0, 1.0);
+    pub const TRANSPARENT: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+/// Colours are saved as `[r, g, b, a]` with every channel in `0..=1`.
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        [self.r, self.g, self.b, self.a].serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Read loosely first so any malformed value gets the same message.
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let channels: Option<Vec<f32>> = value.as_array().filter(|a| a.len() == 4).and_then(|a| {
+            a.iter()
+                .map(|c| c.as_f64().map(|c| c as f32).filter(|c| (0.0..=1.0).contains(c)))
+                .collect()
+        });
+        match channels.as_deref() {
+            Some(&[r, g, b, a]) => Ok(Color::rgba(r, g, b, a)),
+            _ => Err(D::Error::custom("expected color")),
+        }
+    }
+}
+
+/// Placement of a shape in world space.
+///
+/// `x`/`y` is the top-left corner of the unrotated box and `rotation` is in
+/// radians around the box centre.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transform {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub rotation: f32,
+}
+
+impl Transform {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            rotation: 0.0,
+        }
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new(self.x + self.width * 0.5, self.y + self.height * 0.5)
+    }
+
+    /// Maps a point in the shape's local box (`0..width`, `0..height`) to world space.
+    pub fn to_world(&self, local: Point) -> Point {
+        Point::new(self.x + local.x, self.y + local.y).rotate_around(self.center(), self.rotation)
+    }
+
+    /// Inverse of [`Transform::to_world`].
+    pub fn to_local(&self, world: Point) -> Point {
+        let p = world.rotate_around(self.center(), -self.rotation);
+        Point::new(p.x - self.x, p.y - self.y)
+    }
+
+    /// World-space corners, clockwise from the top-left.
+    pub fn corners(&self) -> [Point; 4] {
+        [
+            self.to_world(Point::new(0.0, 0.0)),
+            self.to_world(Point::new(self.width, 0.0)),
+            self.to_world(Point::new(self.width, self.height)),
+            self.to_world(Point::new(0.0, self.height)),
+        ]
+    }
+
+    /// Axis-aligned bounds of the (possibly rotated) box.
+    pub fn bounds(&self) -> Rect {
+        Rect::from_points(self.corners()).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Style {
+    pub stroke: Color,
+    pub fill: Option<Color>,
+    pub stroke_width: f32,
+    /// Alternating dash/gap lengths in world units; empty for a solid stroke.
+    pub dash: Vec<f32>,
+    pub opacity: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            stroke: Color::BLACK,
+            fill: None,
+            stroke_width: 2.0,
+            dash: Vec::new(),
+            opacity: 1.0,
+        }
+    }
+}
+
+/// A sampled point of a freehand stroke, in the shape's local space.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StrokePoint {
+    pub x: f32,
+    pub y: f32,
+    pub pressure: f32,
+}
+
+/// Where on its target a connector end attaches.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Anchor {
+    /// Wherever the connector, aimed at the target's centre, meets its outline.
+    Outline,
+    /// A fixed point of the target's box, as fractions of its width and height.
+    Point { x: f32, y: f32 },
+}
+
+/// A connector end attached to another shape.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Binding {
+    pub shape: ShapeId,
+    pub anchor: Anchor,
+}
+
+/// How a connector travels between its ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Routing {
+    #[default]
+    Straight,
+    Curved,
+    /// Horizontal and vertical runs around the shapes in the way.
+    Elbow,
+}
+
+/// Geometry specific to each kind of shape. Points are in the shape's local
+/// space, so moving a shape only touches its [`Transform`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ShapeKind {
+    Rectangle,
+    Ellipse,
+    Line { start: Point, end: Point },
+    Arrow { start: Point, end: Point },
+    Freehand { points: Vec<StrokePoint> },
+    /// Wraps to the transform's width.
+    Text {
+        content: String,
+        font_size: f32,
+        #[serde(default)]
+        align: TextAlign,
+    },
+    Image { source: String },
+    /// An arrow whose ends may be bound to other shapes. The route is kept
+    /// up to date by [`crate::connector`] whenever either end moves.
+    Connector {
+        /// Waypoints from start to end; for [`Routing::Curved`], the four
+        /// control points of a cubic bezier.
+        route: Vec<Point>,
+        routing: Routing,
+        #[serde(default)]
+        start_binding: Option<Binding>,
+        #[serde(default)]
+        end_binding: Option<Binding>,
+    },
+    /// Shapes that move and scale together. Not painted itself; its box is
+    /// kept around its children by [`crate::hierarchy`].
+    Group,
+    /// A fixed-size, axis-aligned region that clips its children.
+    Frame { name: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Shape {
+    pub id: ShapeId,
+    pub kind: ShapeKind,
+    pub transform: Transform,
+    pub style: Style,
+    /// Paint order among siblings; higher keys are drawn on top.
+    pub z_index: FractionalIndex,
+    /// The layer a top-level shape is on.
+    #[serde(default)]
+    pub layer: LayerId,
+    /// The group or frame this shape belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<ShapeId>,
+}
+
+impl Shape {
+    /// Whether other shapes can belong to this one.
+    pub fn is_container(&self) -> bool {
+        matches!(self.kind, ShapeKind::Group | ShapeKind::Frame { .. })
+    }
+
+    /// World-space bounds including half the stroke width.
+    pub fn bounds(&self) -> Rect {
+        self.transform.bounds().expand(self.style.stroke_width * 0.5)
+    }
+
+    /// World-space centreline of path-like shapes (lines, arrows, connectors,
+    /// freehand).
+    pub fn path_points(&self) -> Option<Vec<Point>> {
+        let t = &self.transform;
+        match &self.kind {
+            ShapeKind::Line { start, end } | ShapeKind::Arrow { start, end } => {
+                Some(vec![t.to_world(*start), t.to_world(*end)])
+            }
+            ShapeKind::Freehand { points } => Some(
+                points
+                    .iter()
+                    .map(|p| t.to_world(Point::new(p.x, p.y)))
+                    .collect(),
+            ),
+            ShapeKind::Connector { route, routing, .. } => {
+                let route: Vec<Point> = route.iter().map(|p| t.to_world(*p)).collect();
+                match (routing, route.as_slice()) {
+                    (Routing::Curved, [p0, c1, c2, p1]) => {
+                        let mut points = vec![*p0];
+                        flatten_cubic(*p0, *c1, *c2, *p1, CURVE_TOLERANCE, &mut points);
+                        Some(points)
+                    }
+                    _ => Some(route),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// World-space outline of closed shapes (rectangles, ellipses), with
+    /// ellipses sampled so no point strays more than `tolerance` from the curve.
+    pub fn outline(&self, tolerance: f32) -> Option<Vec<Point>> {
+        let t = &self.transform;
+        match &self.kind {
+            ShapeKind::Rectangle | ShapeKind::Frame { .. } => Some(t.corners().to_vec()),
+            ShapeKind::Ellipse => {
+                let (rx, ry) = (t.width.abs() * 0.5, t.height.abs() * 0.5);
+                let radius = rx.max(ry).max(tolerance * 2.0);
+                let step = 2.0 * (1.0 - tolerance / radius).acos();
+                let segments = ((std::f32::consts::TAU / step).ceil() as usize).clamp(8, 256);
+                Some(
+                    (0..segments)
+                        .map(|i| {
+                            let angle = std::f32::consts::TAU * i as f32 / segments as f32;
+                            t.to_world(Point::new(
+                                t.width * 0.5 + rx * angle.cos(),
+                                t.height * 0.5 + ry * angle.sin(),
+                            ))
+                        })
+                        .collect(),
+                )
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentEvent {
+    Inserted(ShapeId),
+    Updated(ShapeId),
+    Deleted(ShapeId),
+}
+
+/// A recorded edit, carrying enough of the shape to be reversed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Insert(Shape),
+    Update { before: Box<Shape>, after: Box<Shape> },
+    Delete(Shape),
+    /// Any change to the layers, which are few enough to keep whole.
+    Layers { before: Vec<Layer>, after: Vec<Layer> },
+}
+
+impl Operation {
+    /// The shape edited, or `None` for layer changes.
+    pub fn id(&self) -> Option<ShapeId> {
+        match self {
+            Self::Insert(shape) | Self::Delete(shape) => Some(shape.id),
+            Self::Update { after, .. } => Some(after.id),
+            Self::Layers { .. } => None,
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        match self {
+            Self::Insert(shape) => Self::Delete(shape.clone()),
+            Self::Update { before, after } => Self::Update {
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Self::Delete(shape) => Self::Insert(shape.clone()),
+            Self::Layers { before, after } => Self::Layers {
+                before: after.clone(),
+                after: before.clone(),
+            },
+        }
+    }
+}
+
+/// The set of shapes on the canvas.
+///
+/// Every mutation queues a [`DocumentEvent`]; consumers such as the renderer
+/// call [`Document::drain_events`] once per frame to pick up changes. Readers
+/// that only need to know *whether* anything changed can compare
+/// [`Document::revision`] instead. Each mutation is also journaled as an
+/// [`Operation`] for the undo history, see [`Document::take_operations`].
+///
+/// Shapes are painted layer by layer. Layer changes bump the revision and
+/// are journaled but queue no event, as no shape changes.
+#[derive(Debug)]
+pub struct Document {
+    shapes: HashMap<ShapeId, Shape>,
+    next_id: u64,
+    /// The highest z-index seen, so new shapes land on top.
+    top_z: Option<FractionalIndex>,
+    /// Bottom to top; never empty.
+    layers: Vec<Layer>,
+    active_layer: LayerId,
+    events: Vec<DocumentEvent>,
+    operations: Vec<Operation>,
+    revision: u64,
+    /// The connectors bound to each shape, by the shape they are bound to.
+    /// Entries for deleted shapes stay until their connectors let go.
+    attachments: HashMap<ShapeId, HashSet<ShapeId>>,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self {
+            shapes: HashMap::new(),
+            next_id: 0,
+            top_z: None,
+            layers: vec![Layer::new(LayerId(0), "Layer 1")],
+            active_layer: LayerId(0),
+            events: Vec::new(),
+            operations: Vec::new(),
+            revision: 0,
+            attachments: HashMap::new(),
+        }
+    }
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a new shape on top of all others, on the active layer, and
+    /// returns its id.
+    pub fn insert(&mut self, kind: ShapeKind, transform: Transform, style: Style) -> ShapeId {
+        let id = ShapeId(self.next_id);
+        let shape = Shape {
+            id,
+            kind,
+            transform,
+            style,
+            z_index: self.top_z.as_ref().map_or_else(FractionalIndex::default, FractionalIndex::after),
+            layer: self.active_layer,
+            parent: None,
+        };
+        // Cannot fail: the id was just allocated.
+        self.insert_shape(shape).unwrap();
+        id
+    }
+
+    /// Inserts a fully formed shape, keeping its id and z-index. Used when
+    /// restoring shapes, e.g. on load.
+    pub fn insert_shape(&mut self, shape: Shape) -> Result<ShapeId, String> {
+        let id = shape.id;
+        if self.shapes.contains_key(&id) {
+            return Err(format!("Shape {} already exists", id.0));
+        }
+        self.next_id = self.next_id.max(id.0 + 1);
+        self.raise_top_z(&shape.z_index);
+        self.operations.push(Operation::Insert(shape.clone()));
+        self.attach(&shape);
+        self.shapes.insert(id, shape);
+        self.push_event(DocumentEvent::Inserted(id));
+        Ok(id)
+    }
+
+    pub fn get(&self, id: ShapeId) -> Option<&Shape> {
+        self.shapes.get(&id)
+    }
+
+    pub fn contains(&self, id: ShapeId) -> bool {
+        self.shapes.contains_key(&id)
+    }
+
+    /// Applies `f` to the shape. The shape's id cannot be changed.
+    pub fn update<F: FnOnce(&mut Shape)>(&mut self, id: ShapeId, f: F) -> Result<(), String> {
+        let shape = self
+            .shapes
+            .get_mut(&id)
+            .ok_or_else(|| format!("Shape {} not found", id.0))?;
+        let before = shape.clone();
+        f(shape);
+        shape.id = id;
+        let after = shape.clone();
+        self.raise_top_z(&after.z_index);
+        self.detach(&before);
+        self.attach(&after);
+        self.operations.push(Operation::Update {
+            before: Box::new(before),
+            after: Box::new(after),
+        });
+        self.push_event(DocumentEvent::Updated(id));
+        Ok(())
+    }
+
+    fn raise_top_z(&mut self, z_index: &FractionalIndex) {
+        if self.top_z.as_ref().is_none_or(|top| top < z_index) {
+            self.top_z = Some(z_index.clone());
+        }
+    }
+
+    pub fn delete(&mut self, id: ShapeId) -> Result<Shape, String> {
+        let shape = self
+            .shapes
+            .remove(&id)
+            .ok_or_else(|| format!("Shape {} not found", id.0))?;
+        self.detach(&shape);
+        self.operations.push(Operation::Delete(shape.clone()));
+        self.push_event(DocumentEvent::Deleted(id));
+        Ok(shape)
+    }
+
+    /// The connectors with an end bound to `id`, in no particular order.
+    pub fn attached(&self, id: ShapeId) -> impl Iterator<Item = ShapeId> + '_ {
+        self.attachments.get(&id).into_iter().flatten().copied()
+    }
+
+    fn attach(&mut self, connector: &Shape) {
+        for target in bound_shapes(connector) {
+            self.attachments.entry(target).or_default().insert(connector.id);
+        }
+    }
+
+    fn detach(&mut self, connector: &Shape) {
+        for target in bound_shapes(connector) {
+            if let Some(connectors) = self.attachments.get_mut(&target) {
+                connectors.remove(&connector.id);
+                if connectors.is_empty() {
+                    self.attachments.remove(&target);
+                }
+            }
+        }
+    }
+
+    /// Replays `operation`, e.g. the inverse of one taken from the journal.
+    pub fn apply(&mut self, operation: &Operation) -> Result<(), String> {
+        match operation {
+            Operation::Insert(shape) => self.insert_shape(shape.clone()).map(|_| ()),
+            Operation::Update { after, .. } => self.update(after.id, |shape| *shape = after.as_ref().clone()),
+            Operation::Delete(shape) => self.delete(shape.id).map(|_| ()),
+            Operation::Layers { after, .. } => self.set_layers(after.clone()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    /// Shapes in arbitrary order.
+    pub fn shapes(&self) -> impl Iterator<Item = &Shape> {
+        self.shapes.values()
+    }
+
+    /// Shapes from bottom to top, see [`Document::paint_key`].
+    pub fn shapes_in_z_order(&self) -> Vec<&Shape> {
+        let mut shapes: Vec<&Shape> = self.shapes.values().collect();
+        self.sort_in_paint_order(&mut shapes);
+        shapes
+    }
+
+    /// Ids of `id`'s ancestors, nearest first. Stops at a missing parent and
+    /// at cycles, which loading rejects but edits could still create.
+    pub fn ancestors(&self, id: ShapeId) -> Vec<ShapeId> {
+        let mut ancestors = Vec::new();
+        let mut current = self.get(id).and_then(|shape| shape.parent);
+        while let Some(parent) = current.filter(|p| *p != id && !ancestors.contains(p)) {
+            let Some(shape) = self.get(parent) else {
+                break;
+            };
+            ancestors.push(parent);
+            current = shape.parent;
+        }
+        ancestors
+    }
+
+    /// Whether `ancestor` contains `id`, directly or through other containers.
+    pub fn is_descendant(&self, id: ShapeId, ancestor: ShapeId) -> bool {
+        self.ancestors(id).contains(&ancestor)
+    }
+
+    /// The shapes directly inside `id`, bottom to top.
+    pub fn children(&self, id: ShapeId) -> Vec<ShapeId> {
+        let mut children: Vec<&Shape> = self.shapes.values().filter(|s| s.parent == Some(id)).collect();
+        children.sort_by(|a, b| (&a.z_index, a.id).cmp(&(&b.z_index, b.id)));
+        children.into_iter().map(|s| s.id).collect()
+    }
+
+    /// Every shape inside `id`, at any depth, parents before children.
+    pub fn descendants(&self, id: ShapeId) -> Vec<ShapeId> {
+        let mut descendants = self.children(id);
+        let mut i = 0;
+        while i < descendants.len() {
+            let nested = self.children(descendants[i]);
+            descendants.extend(nested.into_iter().filter(|c| *c != id));
+            i += 1;
+        }
+        descendants
+    }
+
+    /// Sort key for painting: the position of the shape's layer, then the
+    /// `(z_index, id)` of every ancestor from the root down and the shape's
+    /// own. Children are drawn right after their container and before its
+    /// next sibling, and ties break by id.
+    pub fn paint_key(&self, shape: &Shape) -> (usize, Vec<(FractionalIndex, ShapeId)>) {
+        let mut key: Vec<(FractionalIndex, ShapeId)> = self
+            .ancestors(shape.id)
+            .into_iter()
+            .filter_map(|id| self.get(id))
+            .map(|s| (s.z_index.clone(), s.id))
+            .collect();
+        key.reverse();
+        key.push((shape.z_index.clone(), shape.id));
+        (self.layer_position(self.layer_of(shape).id), key)
+    }
+
+    pub fn sort_in_paint_order(&self, shapes: &mut [&Shape]) {
+        shapes.sort_by_cached_key(|s| self.paint_key(s));
+    }
+
+    /// The region `id` is clipped to: the intersection of its enclosing
+    /// frames, or `None` outside any frame.
+    pub fn clip(&self, id: ShapeId) -> Option<Rect> {
+        self.ancestors(id)
+            .into_iter()
+            .filter_map(|ancestor| self.get(ancestor))
+            .filter(|shape| matches!(shape.kind, ShapeKind::Frame { .. }))
+            .map(|frame| frame.transform.bounds())
+            .reduce(|a, b| a.intersection(&b))
+    }
+
+    /// The clip and opacity `shape` is drawn with.
+    pub fn paint(&self, shape: &Shape) -> Paint {
+        Paint {
+            clip: self.clip(shape.id),
+            opacity: self.layer_of(shape).opacity,
+        }
+    }
+
+    /// Layers from bottom to top. There is always at least one.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn layer(&self, id: LayerId) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.id == id)
+    }
+
+    fn layer_position(&self, id: LayerId) -> usize {
+        self.layers.iter().position(|layer| layer.id == id).unwrap_or(0)
+    }
+
+    /// The layer `shape` is on: its outermost container's, or the bottom
+    /// layer if that one no longer exists.
+    pub fn layer_of(&self, shape: &Shape) -> &Layer {
+        let root = self.ancestors(shape.id).last().and_then(|id| self.get(*id)).unwrap_or(shape);
+        self.layer(root.layer).unwrap_or(&self.layers[0])
+    }
+
+    /// Whether `shape` is drawn and exported.
+    pub fn is_visible(&self, shape: &Shape) -> bool {
+        self.layer_of(shape).visible
+    }
+
+    /// Whether the pointer can hit `shape`: its layer is visible and unlocked.
+    pub fn is_hittable(&self, shape: &Shape) -> bool {
+        let layer = self.layer_of(shape);
+        layer.visible && !layer.locked
+    }
+
+    /// The layer new shapes go on.
+    pub fn active_layer(&self) -> LayerId {
+        self.active_layer
+    }
+
+    /// Where pasted shapes go: the active layer if it is visible and
+    /// unlocked, or else the topmost layer that is.
+    pub fn editable_layer(&self) -> Option<LayerId> {
+        let editable = |layer: &&Layer| layer.visible && !layer.locked;
+        self.layer(self.active_layer)
+            .filter(editable)
+            .or_else(|| self.layers.iter().rev().find(editable))
+            .map(|layer| layer.id)
+    }
+
+    pub fn set_active_layer(&mut self, id: LayerId) -> Result<(), String> {
+        self.layer(id).ok_or_else(|| format!("Layer {} not found", id.0))?;
+        self.active_layer = id;
+        Ok(())
+    }
+
+    /// Adds a layer on top and makes it the active one.
+    pub fn add_layer(&mut self, name: &str) -> LayerId {
+        let id = LayerId(self.layers.iter().map(|layer| layer.id.0 + 1).max().unwrap_or(0));
+        let mut layers = self.layers.clone();
+        layers.push(Layer::new(id, name));
+        // Cannot fail: the list is not empty.
+        self.set_layers(layers).unwrap();
+        self.active_layer = id;
+        id
+    }
+
+    /// Applies `f` to the layer. The layer's id cannot be changed.
+    pub fn update_layer<F: FnOnce(&mut Layer)>(&mut self, id: LayerId, f: F) -> Result<(), String> {
+        let mut layers = self.layers.clone();
+        let layer = layers
+            .iter_mut()
+            .find(|layer| layer.id == id)
+            .ok_or_else(|| format!("Layer {} not found", id.0))?;
+        f(layer);
+        layer.id = id;
+        self.set_layers(layers)
+    }
+
+    /// Moves the layer to `index` from the bottom, clamped to the top.
+    pub fn move_layer(&mut self, id: LayerId, index: usize) -> Result<(), String> {
+        let mut layers = self.layers.clone();
+        let from = layers
+            .iter()
+            .position(|layer| layer.id == id)
+            .ok_or_else(|| format!("Layer {} not found", id.0))?;
+        let layer = layers.remove(from);
+        layers.insert(index.min(layers.len()), layer);
+        self.set_layers(layers)
+    }
+
+    /// Deletes the layer and every shape on it. The last layer cannot be
+    /// deleted.
+    pub fn delete_layer(&mut self, id: LayerId) -> Result<(), String> {
+        self.layer(id).ok_or_else(|| format!("Layer {} not found", id.0))?;
+        if self.layers.len() == 1 {
+            return Err("Cannot delete the last layer".to_string());
+        }
+        let doomed: Vec<ShapeId> = self.shapes.values().filter(|s| self.layer_of(s).id == id).map(|s| s.id).collect();
+        for shape in doomed {
+            self.delete(shape)?;
+        }
+        self.set_layers(self.layers.iter().filter(|layer| layer.id != id).cloned().collect())
+    }
+
+    /// Replaces every layer at once, e.g. when loading or replaying history.
+    /// If the active layer is gone, the top one becomes active.
+    pub fn set_layers(&mut self, layers: Vec<Layer>) -> Result<(), String> {
+        if layers.is_empty() {
+            return Err("A document needs at least one layer".to_string());
+        }
+        let before = std::mem::replace(&mut self.layers, layers);
+        if self.layer(self.active_layer).is_none() {
+            self.active_layer = self.layers[self.layers.len() - 1].id;
+        }
+        self.operations.push(Operation::Layers {
+            before,
+            after: self.layers.clone(),
+        });
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// Counter bumped by every mutation.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    fn push_event(&mut self, event: DocumentEvent) {
+        self.revision += 1;
+        self.events.push(event);
+    }
+
+    /// Returns and clears the events queued since the last call.
+    pub fn drain_events(&mut self) -> Vec<DocumentEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Returns and clears the operations journaled since the last call.
+    pub fn take_operations(&mut self) -> Vec<Operation> {
+        std::mem::take(&mut self.operations)
+    }
+}
+
+/// The shapes a connector's ends are bound to.
+fn bound_shapes(shape: &Shape) -> impl Iterator<Item = ShapeId> {
+    let bindings = match &shape.kind {
+        ShapeKind::Connector { start_binding, end_binding, .. } => [*start_binding, *end_binding],
+        _ => [None, None],
+    };
+    bindings.into_iter().flatten().map(|binding| binding.shape)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(doc: &mut Document, x: f32) -> ShapeId {
+        doc.insert(
+            ShapeKind::Rectangle,
+            Transform::new(x, 0.0, 10.0, 10.0),
+            Style::default(),
+        )
+    }
+
+    #[test]
+    fn test_insert_update_delete() {
+        let mut doc = Document::new();
+        let a = rect(&mut doc, 0.0);
+        let b = rect(&mut doc, 20.0);
+        assert_ne!(a, b);
+        assert_eq!(doc.len(), 2);
+
+        doc.update(a, |s| s.transform.x = 5.0).unwrap();
+        assert_eq!(doc.get(a).unwrap().transform.x, 5.0);
+
+        let removed = doc.delete(b).unwrap();
+        assert_eq!(removed.id, b);
+        assert!(doc.get(b).is_none());
+        assert!(doc.delete(b).is_err());
+        assert!(doc.update(b, |_| {}).is_err());
+
+        assert_eq!(
+            doc.drain_events(),
+            vec![
+                DocumentEvent::Inserted(a),
+                DocumentEvent::Inserted(b),
+                DocumentEvent::Updated(a),
+                DocumentEvent::Deleted(b),
+            ]
+        );
+        assert!(doc.drain_events().is_empty());
+        assert_eq!(doc.revision(), 4);
+    }
+
+    #[test]
+    fn test_ids_are_not_reused() {
+        let mut doc = Document::new();
+        let a = rect(&mut doc, 0.0);
+        doc.delete(a).unwrap();
+        let b = rect(&mut doc, 0.0);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_insert_shape_rejects_duplicate_id() {
+        let mut doc = Document::new();
+        let a = rect(&mut doc, 0.0);
+        let shape = doc.get(a).unwrap().clone();
+        assert!(doc.insert_shape(shape).is_err());
+    }
+
+    #[test]
+    fn test_z_order() {
+        let mut doc = Document::new();
+        let a = rect(&mut doc, 0.0);
+        let b = rect(&mut doc, 0.0);
+        let order: Vec<ShapeId> = doc.shapes_in_z_order().iter().map(|s| s.id).collect();
+        assert_eq!(order, vec![a, b]);
+
+        let top = doc.get(b).unwrap().z_index.after();
+        doc.update(a, |s| s.z_index = top).unwrap();
+        let order: Vec<ShapeId> = doc.shapes_in_z_order().iter().map(|s| s.id).collect();
+        assert_eq!(order, vec![b, a]);
+
+        // New shapes still land on top of the raised one.
+        let c = rect(&mut doc, 0.0);
+        assert_eq!(doc.shapes_in_z_order().last().unwrap().id, c);
+    }
+
+    #[test]
+    fn test_inverse_operations_undo_the_journal() {
+        let mut doc = Document::new();
+        let a = rect(&mut doc, 0.0);
+        doc.take_operations();
+        let before = doc.get(a).unwrap().clone();
+
+        doc.update(a, |s| s.transform.x = 5.0).unwrap();
+        let b = rect(&mut doc, 20.0);
+        doc.delete(a).unwrap();
+        let operations = doc.take_operations();
+        assert_eq!(operations.iter().map(Operation::id).collect::<Vec<_>>(), vec![Some(a), Some(b), Some(a)]);
+
+        for operation in operations.iter().rev() {
+            doc.apply(&operation.inverse()).unwrap();
+        }
+        assert_eq!(doc.get(a), Some(&before));
+        assert!(!doc.contains(b));
+        assert!(doc.apply(&Operation::Delete(before.clone())).is_ok());
+        assert!(doc.apply(&Operation::Update { before: Box::new(before.clone()), after: Box::new(before) }).is_err());
+    }
+
+    #[test]
+    fn test_rotated_bounds() {
+        let mut t = Transform::new(0.0, 0.0, 10.0, 10.0);
+        t.rotation = std::f32::consts::FRAC_PI_4;
+        let b = t.bounds();
+        let half_diag = 50f32.sqrt();
+        assert!((b.width() - 2.0 * half_diag).abs() < 1e-4);
+        assert_eq!(t.center(), Point::new(5.0, 5.0));
+
+        let world = t.to_world(Point::new(10.0, 0.0));
+        let local = t.to_local(world);
+        assert!((local.x - 10.0).abs() < 1e-4 && local.y.abs() < 1e-4);
+    }
+}
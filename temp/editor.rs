@@ -0,0 +1,598 @@
+use crate::arrange::{self, arrange, move_to_layer, Align, Arrange, Axis};
+use crate::connector::reroute_affected;
+use crate::document::{Document, DocumentEvent, Layer, LayerId, Shape, ShapeId, ShapeKind, Style, Transform};
+use crate::geometry::Point;
+use crate::board::Board;
+use crate::clipboard;
+use crate::grid::GridStyle;
+use crate::handles::selection_frame;
+use crate::hierarchy::{self, propagate};
+use crate::minimap::Minimap;
+use crate::history::History;
+use crate::overlay::Overlay;
+use crate::selection::Selection;
+use crate::spatial::SpatialIndex;
+use crate::state::State;
+use crate::text::{layout, FontStack};
+use crate::tools::{InputEvent, PointerButton, TextEdit, TextTool, ToolContext, ToolKind, ToolManager};
+
+/// Largest fraction of the viewport a newly added image covers.
+const IMAGE_FIT: f32 = 0.8;
+
+/// Space between cells when tidying, in world units.
+const TIDY_GAP: f32 = 20.0;
+
+/// Everything an editing session owns: camera, document, selection, spatial
+/// index, undo history, fonts and tools. Input goes in through
+/// [`Editor::handle_input`]; the renderer reads the public fields.
+pub struct Editor {
+    pub state: State,
+    pub document: Document,
+    pub selection: Selection,
+    pub index: SpatialIndex,
+    pub history: History,
+    /// The background grid, for export and snapping.
+    pub grid: GridStyle,
+    /// Primary font first, then fallbacks; text measures with these.
+    pub fonts: FontStack,
+    pub tools: ToolManager,
+    /// Where the pointer was last seen, in screen pixels.
+    pointer: Option<Point>,
+    pub show_minimap: bool,
+    /// The minimap as it was when a drag in it began.
+    minimap_drag: Option<Minimap>,
+    /// Why the last input failed, if it did, until [`Editor::take_error`].
+    last_error: Option<String>,
+    width: f32,
+    height: f32,
+}
+
+impl Editor {
+    /// `width` and `height` are the canvas size in pixels.
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            state: State::default(),
+            document: Document::new(),
+            selection: Selection::new(),
+            index: SpatialIndex::new(),
+            history: History::default(),
+            grid: GridStyle::default(),
+            fonts: FontStack::new(),
+            tools: ToolManager::new(),
+            pointer: None,
+            show_minimap: false,
+            minimap_drag: None,
+            last_error: None,
+            width,
+            height,
+        }
+    }
+
+    /// Takes the error left by the last input that failed, such as an undo
+    /// that could not be applied.
+    pub fn take_error(&mut self) -> Option<String> {
+        self.last_error.take()
+    }
+
+    pub fn viewport(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+
+    pub fn set_viewport(&mut self, width: f32, height: f32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Ctrl+Z undoes and Ctrl+Shift+Z redoes, Ctrl+G groups and
+    /// Ctrl+Shift+G ungroups, Ctrl+] and Ctrl+[ bring forward and send
+    /// backward, or to the front and back with Shift (Cmd on macOS);
+    /// everything else goes to the tools.
+    pub fn handle_input(&mut self, event: InputEvent) {
+        if let InputEvent::PointerDown(pointer) | InputEvent::PointerMove(pointer) = &event {
+            self.pointer = Some(pointer.screen);
+        }
+        if self.handle_minimap(&event) {
+            return;
+        }
+        if let InputEvent::KeyDown { key, modifiers, .. } = &event {
+            if modifiers.command() && key.eq_ignore_ascii_case("z") {
+                if let Err(e) = if modifiers.shift { self.redo() } else { self.undo() } {
+                    self.last_error = Some(format!("Cannot undo or redo: {e}"));
+                }
+                return;
+            }
+            if modifiers.command() && key.eq_ignore_ascii_case("g") && self.tools.is_idle() {
+                if modifiers.shift {
+                    self.ungroup_selection();
+                } else {
+                    self.group_selection();
+                }
+                return;
+            }
+            let how = match key.as_str() {
+                "]" | "}" if modifiers.shift => Some(Arrange::ToFront),
+                "]" => Some(Arrange::Forward),
+                "[" | "{" if modifiers.shift => Some(Arrange::ToBack),
+                "[" => Some(Arrange::Backward),
+                _ => None,
+            };
+            if let Some(how) = how.filter(|_| modifiers.command() && self.tools.is_idle()) {
+                self.arrange_selection(how);
+                return;
+            }
+        }
+        self.with_tools(|tools, context| tools.handle(&event, context));
+    }
+
+    pub fn set_tool(&mut self, kind: ToolKind) {
+        self.with_tools(|tools, context| tools.set_active(kind, context));
+    }
+
+    /// The board as versioned JSON.
+    pub fn save(&self) -> Result<String, String> {
+        Board::new(&self.document, &self.state).to_json()
+    }
+
+    /// Replaces the board with a saved one. Loading is not undoable and
+    /// starts a fresh history; on error nothing changes.
+    pub fn load(&mut self, json: &str) -> Result<(), String> {
+        let board = Board::from_json(json)?;
+        self.with_tools(|tools, context| tools.cancel(context));
+        self.state = board.restore(&mut self.document);
+        self.document.take_operations();
+        self.history.clear();
+        self.selection.clear();
+        self.sync_index();
+        Ok(())
+    }
+
+    /// Adds an image of `natural` pixel size, shown at one world unit per
+    /// screen pixel but shrunk to fit the viewport, centred on the screen
+    /// point `at` or the viewport centre. It becomes the selection.
+    pub fn insert_image(&mut self, source: String, natural: (u32, u32), at: Option<Point>) -> ShapeId {
+        let per_pixel = self.state.world_units_per_pixel(self.width, self.height);
+        let visible = self.state.visible_world_rect(self.width, self.height);
+        let (width, height) = (natural.0.max(1) as f32 * per_pixel, natural.1.max(1) as f32 * per_pixel);
+        let fit = (IMAGE_FIT * visible.width() / width).min(IMAGE_FIT * visible.height() / height).min(1.0);
+        let (width, height) = (width * fit, height * fit);
+        let center = match at {
+            Some(screen) => self.state.screen_to_world(self.width, self.height, screen),
+            None => visible.center(),
+        };
+        let transform = Transform::new(center.x - width * 0.5, center.y - height * 0.5, width, height);
+        self.transaction(|editor| {
+            let id = editor.document.insert(ShapeKind::Image { source }, transform, Style::default());
+            editor.selection.select_only(id);
+            id
+        })
+    }
+
+    /// The minimap, if shown and the canvas has room for it.
+    pub fn minimap(&self) -> Option<Minimap> {
+        if !self.show_minimap {
+            return None;
+        }
+        match &self.minimap_drag {
+            Some(minimap) => Some(minimap.with_viewport(self.state.visible_world_rect(self.width, self.height))),
+            None => Minimap::new(&self.index, &self.state, self.width, self.height),
+        }
+    }
+
+    /// Moves the camera to wherever the primary button is pressed or dragged
+    /// in the minimap. Returns whether the minimap took the event.
+    fn handle_minimap(&mut self, event: &InputEvent) -> bool {
+        let minimap = match (event, self.minimap_drag.take()) {
+            (InputEvent::PointerDown(pointer), None) if pointer.button == PointerButton::Primary && self.tools.is_idle() => {
+                match self.minimap().filter(|minimap| minimap.contains(pointer.screen)) {
+                    Some(minimap) => minimap,
+                    None => return false,
+                }
+            }
+            (InputEvent::PointerMove(_), Some(minimap)) => minimap,
+            (InputEvent::PointerUp(_) | InputEvent::Cancel, Some(_)) => return true,
+            (_, dragging) => {
+                self.minimap_drag = dragging;
+                return false;
+            }
+        };
+        if let InputEvent::PointerDown(pointer) | InputEvent::PointerMove(pointer) = event {
+            self.state.center_on(minimap.to_world(pointer.screen));
+        }
+        self.minimap_drag = Some(minimap);
+        true
+    }
+
+    /// The pointer's last screen position, if it is over the canvas.
+    pub fn pointer(&self) -> Option<Point> {
+        self.pointer
+            .filter(|p| (0.0..=self.width).contains(&p.x) && (0.0..=self.height).contains(&p.y))
+    }
+
+    /// Where pasted content goes: the screen point `at`, or the viewport
+    /// centre.
+    fn paste_target(&self, at: Option<Point>) -> Point {
+        match at {
+            Some(screen) => self.state.screen_to_world(self.width, self.height, screen),
+            None => self.state.visible_world_rect(self.width, self.height).center(),
+        }
+    }
+
+    /// The selection as clipboard JSON, see [`clipboard::copy`].
+    pub fn copy_selection(&self) -> Option<String> {
+        let ids: Vec<ShapeId> = self.selection.iter().collect();
+        clipboard::copy(&self.document, &ids)?.to_json().ok()
+    }
+
+    /// Copies the selection, then deletes it as one undo step. `None` when
+    /// nothing is selected or a tool is busy, as nothing can be cut then.
+    pub fn cut_selection(&mut self) -> Option<String> {
+        if !self.tools.is_idle() {
+            return None;
+        }
+        let json = self.copy_selection()?;
+        self.delete_selection().then_some(json)
+    }
+
+    /// Deletes the selected shapes and their contents as one undo step.
+    /// Returns false, deleting nothing, when nothing is selected or a tool
+    /// is busy.
+    pub fn delete_selection(&mut self) -> bool {
+        let ids: Vec<ShapeId> = self.selection.iter().collect();
+        if ids.is_empty() || !self.tools.is_idle() {
+            return false;
+        }
+        self.transaction(|editor| {
+            for id in ids {
+                let _ = editor.document.delete(id);
+            }
+            editor.selection.clear();
+        });
+        true
+    }
+
+    /// Pastes clipboard JSON centred on the screen point `at` or the
+    /// viewport centre, as one undo step, and selects it.
+    pub fn paste(&mut self, json: &str, at: Option<Point>) -> Result<(), String> {
+        let board = Board::from_json(json)?;
+        let at = self.paste_target(at);
+        self.with_tools(|tools, context| tools.cancel(context));
+        self.transaction(|editor| {
+            let pasted = clipboard::paste(&mut editor.document, &board, at)?;
+            editor.selection.set(pasted);
+            Ok(())
+        })
+    }
+
+    /// Pastes plain text as a text shape in the text tool's style, centred
+    /// on the screen point `at` or the viewport centre, and selects it. Like
+    /// [`Self::paste`], it skips a locked or hidden active layer.
+    pub fn paste_text(&mut self, text: &str, at: Option<Point>) -> Option<ShapeId> {
+        if text.trim().is_empty() {
+            return None;
+        }
+        let layer = self.document.editable_layer()?;
+        let at = self.paste_target(at);
+        let text_tool = &self.tools.text;
+        let kind = ShapeKind::Text {
+            content: text.to_string(),
+            font_size: text_tool.font_size,
+            align: text_tool.align,
+        };
+        let style = text_tool.style.clone();
+        let mut transform = TextTool::fitted_transform(at, text, text_tool.font_size, &self.fonts);
+        transform.x -= transform.width * 0.5;
+        transform.y -= transform.height * 0.5;
+        self.with_tools(|tools, context| tools.cancel(context));
+        Some(self.transaction(|editor| {
+            let id = editor.document.insert(kind, transform, style);
+            if layer != editor.document.active_layer() {
+                let _ = editor.document.update(id, |shape| shape.layer = layer);
+            }
+            editor.selection.select_only(id);
+            id
+        }))
+    }
+
+    /// Groups the selected shapes and selects the group.
+    pub fn group_selection(&mut self) -> Option<ShapeId> {
+        let ids: Vec<ShapeId> = self.selection.iter().collect();
+        if ids.is_empty() {
+            return None;
+        }
+        self.transaction(|editor| {
+            let id = hierarchy::group(&mut editor.document, &ids)?;
+            editor.selection.select_only(id);
+            Some(id)
+        })
+    }
+
+    /// Dissolves the selected groups and selects what they held.
+    pub fn ungroup_selection(&mut self) {
+        let ids: Vec<ShapeId> = self.selection.iter().collect();
+        self.transaction(|editor| {
+            let children: Vec<ShapeId> = ids
+                .iter()
+                .flat_map(|id| hierarchy::ungroup(&mut editor.document, *id))
+                .collect();
+            if !children.is_empty() {
+                editor.selection.set(children);
+            }
+        });
+    }
+
+    /// Moves the selected shapes in the paint order within their container
+    /// or layer.
+    pub fn arrange_selection(&mut self, how: Arrange) {
+        let ids: Vec<ShapeId> = self.selection.iter().collect();
+        self.transaction(|editor| arrange(&mut editor.document, &ids, how));
+    }
+
+    pub fn align_selection(&mut self, how: Align) {
+        let ids: Vec<ShapeId> = self.selection.iter().collect();
+        self.transaction(|editor| arrange::align(&mut editor.document, &ids, how));
+    }
+
+    pub fn distribute_selection(&mut self, axis: Axis) {
+        let ids: Vec<ShapeId> = self.selection.iter().collect();
+        self.transaction(|editor| arrange::distribute(&mut editor.document, &ids, axis));
+    }
+
+    /// Lays the selection out in a grid, see [`arrange::tidy`].
+    pub fn tidy_selection(&mut self) {
+        let ids: Vec<ShapeId> = self.selection.iter().collect();
+        self.transaction(|editor| arrange::tidy(&mut editor.document, &ids, TIDY_GAP));
+    }
+
+    /// Moves the selected shapes onto `layer`, above its shapes.
+    pub fn move_selection_to_layer(&mut self, layer: LayerId) -> Result<(), String> {
+        let ids: Vec<ShapeId> = self.selection.iter().collect();
+        self.transaction(|editor| move_to_layer(&mut editor.document, &ids, layer))
+    }
+
+    /// Changes a layer as one undo step. Shapes hidden or locked by the
+    /// change leave the selection.
+    pub fn update_layer<F: FnOnce(&mut Layer)>(&mut self, id: LayerId, f: F) -> Result<(), String> {
+        self.transaction(|editor| editor.document.update_layer(id, f))?;
+        let document = &self.document;
+        let kept: Vec<ShapeId> = self
+            .selection
+            .iter()
+            .filter(|id| document.get(*id).is_some_and(|shape| document.is_hittable(shape)))
+            .collect();
+        self.selection.set(kept);
+        Ok(())
+    }
+
+    /// Runs `f` as one undo step, however many edits it makes.
+    pub fn transaction<T, F: FnOnce(&mut Self) -> T>(&mut self, f: F) -> T {
+        self.history.begin(&self.state);
+        let result = f(self);
+        self.sync_index();
+        self.history.commit(&self.state);
+        result
+    }
+
+    /// Undoes the last step, first cancelling any gesture in progress.
+    /// Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> Result<bool, String> {
+        self.with_tools(|tools, context| tools.cancel(context));
+        let undone = self.history.undo(&mut self.document, &mut self.state);
+        self.sync_index();
+        undone
+    }
+
+    pub fn redo(&mut self) -> Result<bool, String> {
+        self.with_tools(|tools, context| tools.cancel(context));
+        let redone = self.history.redo(&mut self.document, &mut self.state);
+        self.sync_index();
+        redone
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// A gesture is one transaction: it opens when input reaches an idle
+    /// tool and commits once the tool is idle again.
+    fn with_tools<F: FnOnce(&mut ToolManager, &mut ToolContext)>(&mut self, f: F) {
+        if self.tools.is_idle() {
+            self.history.begin(&self.state);
+        }
+        let mut context = ToolContext {
+            state: &mut self.state,
+            document: &mut self.document,
+            selection: &mut self.selection,
+            index: &self.index,
+            fonts: &self.fonts,
+            grid: &self.grid,
+            width: self.width,
+            height: self.height,
+        };
+        f(&mut self.tools, &mut context);
+        self.sync_index();
+        if self.tools.is_idle() {
+            self.history.commit(&self.state);
+        }
+    }
+
+    /// Carries edits over to the contents of groups and frames, applies
+    /// pending document changes to the spatial index, re-routes connectors
+    /// bound to changed shapes, drops deleted shapes from the selection and
+    /// records the changes in the history. The editor is the document's only
+    /// event consumer; call this after editing the document directly.
+    pub fn sync_index(&mut self) {
+        let mut operations = self.document.take_operations();
+        propagate(&mut self.document, &operations);
+        let mut events = self.document.drain_events();
+        self.index.apply_events(&self.document, &events);
+        reroute_affected(&mut self.document, &self.index, &events);
+        let rerouted = self.document.drain_events();
+        self.index.apply_events(&self.document, &rerouted);
+        events.extend(rerouted);
+        operations.extend(self.document.take_operations());
+        self.history.record(operations, &self.state);
+        for event in &events {
+            if let DocumentEvent::Deleted(id) = event {
+                self.selection.remove(*id);
+            }
+        }
+    }
+
+    /// The shape the active tool is drawing, if any.
+    pub fn preview_shape(&self) -> Option<Shape> {
+        self.tools.preview()
+    }
+
+    /// Selection outlines and bounds, plus the active tool's feedback.
+    pub fn overlay(&self) -> Overlay {
+        let mut overlay = Overlay::new(self.state.world_units_per_pixel(self.width, self.height));
+        if let Some(group) = self.tools.select.entered().and_then(|id| self.document.get(id)) {
+            overlay.bounding_box(group.transform.bounds());
+        }
+        for shape in self.selection.iter().filter_map(|id| self.document.get(id)) {
+            overlay.outline(shape);
+        }
+        if let Some(frame) = selection_frame(&self.document, &self.selection) {
+            overlay.frame(&frame);
+            if self.tools.active() == ToolKind::Select {
+                overlay.handles(&frame);
+            }
+        }
+        if let Some((shape, edit)) = self.text_edit() {
+            if let ShapeKind::Text { content, font_size, align } = &shape.kind {
+                let laid_out = layout(&self.fonts, content, *font_size, Some(shape.transform.width), *align);
+                let caret = edit.selection.is_empty().then(|| laid_out.caret(edit.selection.end));
+                let composition = edit.composition.clone().map(|range| laid_out.selection_rects(range)).unwrap_or_default();
+                overlay.text_edit(shape, caret, &laid_out.selection_rects(edit.selection.clone()), &composition);
+            }
+        }
+        self.tools.overlay(&self.document, &mut overlay);
+        overlay
+    }
+
+    /// The text shape being edited in place, if any.
+    pub fn text_edit(&self) -> Option<(&Shape, &TextEdit)> {
+        if self.tools.active() != ToolKind::Text {
+            return None;
+        }
+        let edit = self.tools.text.editing()?;
+        Some((self.document.get(edit.id)?, edit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dragging_in_the_minimap_moves_the_camera() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.document.insert(ShapeKind::Rectangle, Transform::new(4000.0, 3000.0, 100.0, 100.0), Style::default());
+        editor.sync_index();
+        assert_eq!(editor.minimap(), None);
+        editor.show_minimap = true;
+        let minimap = editor.minimap().unwrap();
+
+        let pointer = |x, y| crate::tools::PointerInput::new(Point::new(x, y));
+        let target = minimap.frame.center();
+        editor.handle_input(InputEvent::PointerDown(pointer(target.x, target.y)));
+        let center = |editor: &Editor| editor.state.screen_to_world(800.0, 600.0, Point::new(400.0, 300.0));
+        assert!(center(&editor).distance(minimap.to_world(target)) < 1e-1);
+        // The map holds still while dragging, though the camera moves.
+        editor.handle_input(InputEvent::PointerMove(pointer(target.x + 20.0, target.y)));
+        assert!(center(&editor).distance(minimap.to_world(Point::new(target.x + 20.0, target.y))) < 1e-1);
+        assert_eq!(editor.minimap().unwrap().view, minimap.view);
+        editor.handle_input(InputEvent::PointerUp(pointer(target.x + 20.0, target.y)));
+        // Nothing was drawn or selected along the way.
+        assert_eq!(editor.document.len(), 1);
+        assert!(editor.selection.is_empty());
+    }
+
+    #[test]
+    fn test_cut_and_paste_at_the_pointer() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let id = editor.document.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 50.0, 50.0), Style::default());
+        editor.sync_index();
+        assert_eq!(editor.copy_selection(), None);
+        editor.selection.select_only(id);
+        let json = editor.cut_selection().unwrap();
+        assert!(!editor.document.contains(id));
+
+        editor.handle_input(InputEvent::PointerMove(crate::tools::PointerInput::new(Point::new(600.0, 200.0))));
+        editor.paste(&json, editor.pointer()).unwrap();
+        let pasted = editor.selection.iter().next().unwrap();
+        assert_ne!(pasted, id);
+        let center = editor.document.get(pasted).unwrap().transform.center();
+        let screen = editor.state.world_to_screen(800.0, 600.0, center);
+        assert!(screen.distance(Point::new(600.0, 200.0)) < 1e-2);
+        assert!(editor.paste("{}", None).is_err());
+
+        // Text lands in the middle of the view; each paste is one undo step.
+        let text = editor.paste_text("hello", None).unwrap();
+        assert!(editor.document.get(text).unwrap().transform.center().distance(Point::ZERO) < 1e-3);
+        assert!(editor.undo().unwrap());
+        assert!(!editor.document.contains(text));
+        assert!(editor.undo().unwrap());
+        assert!(!editor.document.contains(pasted));
+        assert!(editor.undo().unwrap());
+        assert!(editor.document.contains(id));
+    }
+
+    #[test]
+    fn test_insert_image_fits_viewport_and_undoes() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let per_pixel = editor.state.world_units_per_pixel(800.0, 600.0);
+        let small = editor.insert_image("a.png".to_string(), (100, 50), Some(Point::new(200.0, 100.0)));
+        let shape = editor.document.get(small).unwrap();
+        assert!((shape.transform.width - 100.0 * per_pixel).abs() < 1e-3);
+        let center = editor.state.world_to_screen(800.0, 600.0, shape.transform.center());
+        assert!((center.x - 200.0).abs() < 1e-2 && (center.y - 100.0).abs() < 1e-2);
+        assert!(editor.selection.contains(small));
+
+        let large = editor.insert_image("b.png".to_string(), (4000, 1000), None);
+        let t = editor.document.get(large).unwrap().transform;
+        assert!((t.width / per_pixel - 640.0).abs() < 0.1);
+        assert!((t.width / t.height - 4.0).abs() < 1e-3);
+
+        assert!(editor.undo().unwrap());
+        assert!(!editor.document.contains(large));
+        assert!(editor.document.contains(small));
+    }
+
+    #[test]
+    fn test_layers_gate_hits_and_undo() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let square = Transform::new(-50.0, -50.0, 100.0, 100.0);
+        let style = Style {
+            fill: Some(crate::document::Color::WHITE),
+            ..Style::default()
+        };
+        let below = editor.transaction(|editor| editor.document.insert(ShapeKind::Rectangle, square, style.clone()));
+        let upper = editor.transaction(|editor| editor.document.add_layer("Upper"));
+        let above = editor.transaction(|editor| editor.document.insert(ShapeKind::Ellipse, square, style));
+        let hit = |editor: &Editor| editor.index.topmost_at(&editor.document, Point::ZERO, 1.0);
+        assert_eq!(hit(&editor), Some(above));
+
+        editor.selection.select_only(above);
+        editor.update_layer(upper, |layer| layer.locked = true).unwrap();
+        assert_eq!(hit(&editor), Some(below));
+        assert!(editor.selection.is_empty());
+        editor.update_layer(LayerId(0), |layer| layer.visible = false).unwrap();
+        assert_eq!(hit(&editor), None);
+
+        assert!(editor.undo().unwrap());
+        assert!(editor.undo().unwrap());
+        assert_eq!(hit(&editor), Some(above));
+
+        editor.transaction(|editor| editor.document.delete_layer(upper)).unwrap();
+        assert!(!editor.document.contains(above));
+        assert!(editor.undo().unwrap());
+        assert_eq!(editor.document.layers().len(), 2);
+        assert_eq!(hit(&editor), Some(above));
+        assert!(editor.document.delete_layer(LayerId(7)).is_err());
+    }
+}
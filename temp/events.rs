@@ -1,55 +1,181 @@
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::{WebGl2RenderingContext, HtmlCanvasElement, MouseEvent, Window};
+use web_sys::{WebGl2RenderingContext, HtmlCanvasElement, HtmlTextAreaElement, KeyboardEvent, MouseEvent, PointerEvent, Window};
 use std::rc::Rc;
 use std::cell::RefCell;
-use crate::state::State;
+use crate::editor::Editor;
+use crate::geometry::Point;
+use crate::tools::{InputEvent, Modifiers, PointerButton, PointerInput, PointerKind};
 
-pub fn setup_mouse_events(
-    canvas: &HtmlCanvasElement,
-    state: Rc<RefCell<State>>,
-) -> Result<(), JsValue> {
-    let state_clone = state.clone();
-    let mousedown_callback = Closure::wrap(Box::new(move |event: MouseEvent| {
-        let mut state = state_clone.borrow_mut();
-        state.start_drag(event.client_x() as f32, event.client_y() as f32);
-    }) as Box<dyn FnMut(MouseEvent)>);
+fn pointer_input(event: &PointerEvent) -> PointerInput {
+    let kind = match event.pointer_type().as_str() {
+        "pen" => PointerKind::Pen,
+        "touch" => PointerKind::Touch,
+        _ => PointerKind::Mouse,
+    };
+    // Moves report button -1; treat them as the primary pointer.
+    let button = match event.button() {
+        1 => PointerButton::Middle,
+        2 => PointerButton::Secondary,
+        _ => PointerButton::Primary,
+    };
+    PointerInput {
+        screen: Point::new(event.offset_x() as f32, event.offset_y() as f32),
+        pressure: event.pressure(),
+        kind,
+        button,
+        modifiers: Modifiers {
+            shift: event.shift_key(),
+            alt: event.alt_key(),
+            ctrl: event.ctrl_key(),
+            meta: event.meta_key(),
+        },
+    }
+}
 
-    let state_clone = state.clone();
-    let mouseup_callback = Closure::wrap(Box::new(move |_event: MouseEvent| {
-        state_clone.borrow_mut().stop_drag();
-    }) as Box<dyn FnMut(MouseEvent)>);
+fn mouse_input(event: &MouseEvent) -> PointerInput {
+    PointerInput {
+        modifiers: Modifiers {
+            shift: event.shift_key(),
+            alt: event.alt_key(),
+            ctrl: event.ctrl_key(),
+            meta: event.meta_key(),
+        },
+        ..PointerInput::new(Point::new(event.offset_x() as f32, event.offset_y() as f32))
+    }
+}
+
+fn keyboard_modifiers(event: &KeyboardEvent) -> Modifiers {
+    Modifiers {
+        shift: event.shift_key(),
+        alt: event.alt_key(),
+        ctrl: event.ctrl_key(),
+        meta: event.meta_key(),
+    }
+}
 
-    let state_clone = state;
+/// Forwards pointer input on the canvas to the editor's tools.
+pub fn setup_pointer_events(canvas: &HtmlCanvasElement, editor: Rc<RefCell<Editor>>) -> Result<(), JsValue> {
+    let editor_clone = editor.clone();
     let canvas_clone = canvas.clone();
-    let mousemove_callback = Closure::wrap(Box::new(move |event: MouseEvent| {
-        let mut state = state_clone.borrow_mut();
-        state.update_drag(
-            canvas_clone.width() as f32,
-            canvas_clone.height() as f32,
-            event.client_x() as f32,
-            event.client_y() as f32,
-        );
+    let pointerdown_callback = Closure::wrap(Box::new(move |event: PointerEvent| {
+        // Suppress the compatibility mouse events (and middle-click autoscroll).
+        event.prevent_default();
+        let _ = canvas_clone.set_pointer_capture(event.pointer_id());
+        editor_clone.borrow_mut().handle_input(InputEvent::PointerDown(pointer_input(&event)));
+    }) as Box<dyn FnMut(PointerEvent)>);
+
+    let editor_clone = editor.clone();
+    let pointermove_callback = Closure::wrap(Box::new(move |event: PointerEvent| {
+        let mut editor = editor_clone.borrow_mut();
+        // Browsers deliver one pointermove per frame; the coalesced list
+        // holds every sample the device reported since the last one.
+        let coalesced = event.get_coalesced_events();
+        if coalesced.length() == 0 {
+            editor.handle_input(InputEvent::PointerMove(pointer_input(&event)));
+        }
+        for sample in coalesced.iter() {
+            let sample: PointerEvent = sample.unchecked_into();
+            editor.handle_input(InputEvent::PointerMove(pointer_input(&sample)));
+        }
+    }) as Box<dyn FnMut(PointerEvent)>);
+
+    let editor_clone = editor.clone();
+    let pointerup_callback = Closure::wrap(Box::new(move |event: PointerEvent| {
+        editor_clone.borrow_mut().handle_input(InputEvent::PointerUp(pointer_input(&event)));
+    }) as Box<dyn FnMut(PointerEvent)>);
+
+    let editor_clone = editor.clone();
+    let pointercancel_callback = Closure::wrap(Box::new(move |_event: PointerEvent| {
+        editor_clone.borrow_mut().handle_input(InputEvent::Cancel);
+    }) as Box<dyn FnMut(PointerEvent)>);
+
+    let editor_clone = editor;
+    let dblclick_callback = Closure::wrap(Box::new(move |event: MouseEvent| {
+        editor_clone.borrow_mut().handle_input(InputEvent::DoubleClick(mouse_input(&event)));
     }) as Box<dyn FnMut(MouseEvent)>);
 
     canvas.add_event_listener_with_callback(
-        "mousedown",
-        mousedown_callback.as_ref().unchecked_ref(),
+        "pointerdown",
+        pointerdown_callback.as_ref().unchecked_ref(),
+    )?;
+    canvas.add_event_listener_with_callback(
+        "pointermove",
+        pointermove_callback.as_ref().unchecked_ref(),
     )?;
     canvas.add_event_listener_with_callback(
-        "mouseup",
-        mouseup_callback.as_ref().unchecked_ref(),
+        "pointerup",
+        pointerup_callback.as_ref().unchecked_ref(),
     )?;
     canvas.add_event_listener_with_callback(
-        "mousemove",
-        mousemove_callback.as_ref().unchecked_ref(),
+        "pointercancel",
+        pointercancel_callback.as_ref().unchecked_ref(),
+    )?;
+    canvas.add_event_listener_with_callback(
+        "dblclick",
+        dblclick_callback.as_ref().unchecked_ref(),
     )?;
 
     // Prevent memory leaks by forgetting the callbacks
     // (they'll be cleaned up when the page is unloaded)
-    mousedown_callback.forget();
-    mouseup_callback.forget();
-    mousemove_callback.forget();
+    pointerdown_callback.forget();
+    pointermove_callback.forget();
+    pointerup_callback.forget();
+    pointercancel_callback.forget();
+    dblclick_callback.forget();
+
+    Ok(())
+}
+
+/// Forwards key presses to the editor's tools. Losing focus cancels the
+/// gesture in progress, since the matching key-up will never arrive.
+pub fn setup_keyboard_events(window: &Window, editor: Rc<RefCell<Editor>>) -> Result<(), JsValue> {
+    let editor_clone = editor.clone();
+    let keydown_callback = Closure::wrap(Box::new(move |event: KeyboardEvent| {
+        // Typing into the text field, including its undo, is its own business.
+        if event.target().is_some_and(|target| target.has_type::<HtmlTextAreaElement>()) {
+            return;
+        }
+        if event.key() == " " {
+            // Keep the page from scrolling while space pans the canvas.
+            event.prevent_default();
+        }
+        let modifiers = keyboard_modifiers(&event);
+        if modifiers.command() && event.key().eq_ignore_ascii_case("z") {
+            // Undo and redo belong to the canvas, not the browser.
+            event.prevent_default();
+        }
+        let mut editor = editor_clone.borrow_mut();
+        editor.handle_input(InputEvent::KeyDown {
+            key: event.key(),
+            modifiers,
+            repeat: event.repeat(),
+        });
+        if let Some(e) = editor.take_error() {
+            web_sys::console::warn_1(&e.into());
+        }
+    }) as Box<dyn FnMut(KeyboardEvent)>);
+
+    let editor_clone = editor.clone();
+    let keyup_callback = Closure::wrap(Box::new(move |event: KeyboardEvent| {
+        editor_clone.borrow_mut().handle_input(InputEvent::KeyUp {
+            key: event.key(),
+            modifiers: keyboard_modifiers(&event),
+        });
+    }) as Box<dyn FnMut(KeyboardEvent)>);
+
+    let editor_clone = editor;
+    let blur_callback = Closure::wrap(Box::new(move || {
+        editor_clone.borrow_mut().handle_input(InputEvent::Cancel);
+    }) as Box<dyn FnMut()>);
+
+    window.add_event_listener_with_callback("keydown", keydown_callback.as_ref().unchecked_ref())?;
+    window.add_event_listener_with_callback("keyup", keyup_callback.as_ref().unchecked_ref())?;
+    window.add_event_listener_with_callback("blur", blur_callback.as_ref().unchecked_ref())?;
+
+    keydown_callback.forget();
+    keyup_callback.forget();
+    blur_callback.forget();
 
     Ok(())
 }
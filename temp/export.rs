@@ -0,0 +1,257 @@
+//! Raster export: sizing a region in pixels, splitting it into tiles the
+//! GPU can render, and encoding the result as PNG. The pixels come from
+//! either `WebGLRenderer` in the browser or [`crate::raster`] headless.
+
+use crate::document::Color;
+use crate::geometry::Rect;
+use crate::state::{State, WORLD_UNITS_PER_GRID_UNIT};
+use crate::svg::ExportArea;
+
+/// Largest export along either side, in pixels.
+pub const MAX_EXPORT_SIZE: u32 = 16384;
+
+/// How large the exported image is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportSize {
+    /// Pixels per world unit: 1.0, 2.0 and 4.0 give 1x, 2x and 4x.
+    Scale(f32),
+    /// Exactly this many pixels. The region is centred and widened along one
+    /// axis to match the aspect ratio, never stretched.
+    Fit { width: u32, height: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PngOptions {
+    pub area: ExportArea,
+    pub size: ExportSize,
+    /// Margin around the shapes, in world units; ignored for
+    /// [`ExportArea::Rect`].
+    pub padding: f32,
+    /// Leaves uncovered pixels transparent instead of filling `background`.
+    pub transparent: bool,
+    pub background: Color,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self {
+            area: ExportArea::Document,
+            size: ExportSize::Scale(1.0),
+            padding: 16.0,
+            transparent: false,
+            background: Color::WHITE,
+        }
+    }
+}
+
+impl PngOptions {
+    /// The colour to clear to, if any.
+    pub fn clear_color(&self) -> Option<Color> {
+        (!self.transparent).then_some(self.background)
+    }
+}
+
+/// A block of the output image, in pixels from the top-left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A world rect mapped onto a whole number of pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportPlan {
+    /// The exact world rect covered, grown from the requested one so each
+    /// pixel stays square.
+    pub view: Rect,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ExportPlan {
+    pub fn new(view: Rect, size: ExportSize) -> Result<Self, String> {
+        let (width, height, scale) = match size {
+            ExportSize::Scale(scale) if scale > 0.0 => (
+                (view.width() * scale).ceil().max(1.0),
+                (view.height() * scale).ceil().max(1.0),
+                scale,
+            ),
+            ExportSize::Fit { width, height } if width > 0 && height > 0 => {
+                let (width, height) = (width as f32, height as f32);
+                (width, height, (width / view.width()).min(height / view.height()))
+            }
+            _ => return Err("Export size must be positive".to_string()),
+        };
+        if width > MAX_EXPORT_SIZE as f32 || height > MAX_EXPORT_SIZE as f32 {
+            return Err(format!(
+                "Export of {}x{} pixels exceeds the limit of {} per side",
+                width, height, MAX_EXPORT_SIZE
+            ));
+        }
+        let center = view.center();
+        let (half_width, half_height) = (width * 0.5 / scale, height * 0.5 / scale);
+        let view = match size {
+            ExportSize::Scale(_) => Rect::new(view.min_x, view.min_y, view.min_x + width / scale, view.min_y + height / scale),
+            ExportSize::Fit { .. } => Rect::new(
+                center.x - half_width,
+                center.y - half_height,
+                center.x + half_width,
+                center.y + half_height,
+            ),
+        };
+        Ok(Self {
+            view,
+            width: width as u32,
+            height: height as u32,
+        })
+    }
+
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.width as f32 / self.view.width()
+    }
+
+    /// Row-major tiles no larger than `max_size` on a side, e.g. the GPU's
+    /// `MAX_TEXTURE_SIZE`.
+    pub fn tiles(&self, max_size: u32) -> Vec<Tile> {
+        let max_size = max_size.max(1);
+        let mut tiles = Vec::new();
+        for y in (0..self.height).step_by(max_size as usize) {
+            for x in (0..self.width).step_by(max_size as usize) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: max_size.min(self.width - x),
+                    height: max_size.min(self.height - y),
+                });
+            }
+        }
+        tiles
+    }
+
+    /// The world rect `tile` covers.
+    pub fn tile_view(&self, tile: &Tile) -> Rect {
+        let per_pixel = 1.0 / self.pixels_per_unit();
+        let min_x = self.view.min_x + tile.x as f32 * per_pixel;
+        let min_y = self.view.min_y + tile.y as f32 * per_pixel;
+        Rect::new(
+            min_x,
+            min_y,
+            min_x + tile.width as f32 * per_pixel,
+            min_y + tile.height as f32 * per_pixel,
+        )
+    }
+
+    /// A camera that shows exactly `tile` on a canvas of the tile's size.
+    pub fn camera(&self, tile: &Tile) -> State {
+        let center = self.tile_view(tile).center();
+        let zoom = self.pixels_per_unit() * 2.0 * WORLD_UNITS_PER_GRID_UNIT / tile.width.min(tile.height) as f32;
+        State {
+            zoom,
+            offset_x: -center.x / WORLD_UNITS_PER_GRID_UNIT * zoom,
+            offset_y: center.y / WORLD_UNITS_PER_GRID_UNIT * zoom,
+        }
+    }
+}
+
+/// Copies a tile's RGBA rows into the full image. `bottom_up` is for
+/// pixels read back from GL, whose rows start at the bottom.
+pub fn blit_tile(image: &mut [u8], image_width: u32, tile: &Tile, pixels: &[u8], bottom_up: bool) {
+    let row_bytes = tile.width as usize * 4;
+    for row in 0..tile.height as usize {
+        let source = if bottom_up { tile.height as usize - 1 - row } else { row };
+        let target = ((tile.y as usize + row) * image_width as usize + tile.x as usize) * 4;
+        image[target..target + row_bytes].copy_from_slice(&pixels[source * row_bytes..(source + 1) * row_bytes]);
+    }
+}
+
+/// Converts premultiplied RGBA, as rendered, to the straight alpha PNG wants.
+pub fn unpremultiply(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 || alpha == 255 {
+            continue;
+        }
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+/// Encodes straight-alpha RGBA8 pixels, rows top to bottom.
+pub fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Result<Vec<u8>, String> {
+    if pixels.len() != width as usize * height as usize * 4 {
+        return Err(format!("Expected {}x{} RGBA pixels, got {} bytes", width, height, pixels.len()));
+    }
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Point;
+
+    #[test]
+    fn test_plan_sizes() {
+        let view = Rect::new(0.0, 0.0, 100.5, 50.0);
+        let plan = ExportPlan::new(view, ExportSize::Scale(2.0)).unwrap();
+        assert_eq!((plan.width, plan.height), (201, 100));
+        assert_eq!(plan.pixels_per_unit(), 2.0);
+
+        let plan = ExportPlan::new(Rect::new(0.0, 0.0, 100.0, 50.0), ExportSize::Fit { width: 300, height: 300 }).unwrap();
+        assert_eq!((plan.width, plan.height), (300, 300));
+        assert_eq!(plan.view, Rect::new(0.0, -25.0, 100.0, 75.0));
+
+        assert!(ExportPlan::new(view, ExportSize::Scale(0.0)).is_err());
+        assert!(ExportPlan::new(view, ExportSize::Scale(1000.0)).is_err());
+    }
+
+    #[test]
+    fn test_tiles_cover_the_image_once() {
+        let plan = ExportPlan::new(Rect::new(0.0, 0.0, 250.0, 130.0), ExportSize::Scale(1.0)).unwrap();
+        let tiles = plan.tiles(100);
+        assert_eq!(tiles.len(), 6);
+        assert!(tiles.iter().all(|t| t.width <= 100 && t.height <= 100));
+        let area: u32 = tiles.iter().map(|t| t.width * t.height).sum();
+        assert_eq!(area, 250 * 130);
+        assert_eq!(tiles[5], Tile { x: 200, y: 100, width: 50, height: 30 });
+    }
+
+    #[test]
+    fn test_tile_camera_frames_the_tile() {
+        let plan = ExportPlan::new(Rect::new(-40.0, 10.0, 360.0, 210.0), ExportSize::Scale(2.0)).unwrap();
+        for tile in plan.tiles(300) {
+            let camera = plan.camera(&tile);
+            let view = plan.tile_view(&tile);
+            let (w, h) = (tile.width as f32, tile.height as f32);
+            let top_left = camera.world_to_screen(w, h, Point::new(view.min_x, view.min_y));
+            let bottom_right = camera.world_to_screen(w, h, Point::new(view.max_x, view.max_y));
+            assert!(top_left.distance(Point::ZERO) < 1e-2);
+            assert!(bottom_right.distance(Point::new(w, h)) < 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_blit_and_unpremultiply() {
+        let mut image = vec![0u8; 4 * 2 * 4];
+        let tile = Tile { x: 2, y: 0, width: 2, height: 2 };
+        let pixels: Vec<u8> = (0..16).collect();
+        blit_tile(&mut image, 4, &tile, &pixels, true);
+        assert_eq!(&image[8..16], &pixels[8..16]);
+        assert_eq!(&image[24..32], &pixels[0..8]);
+
+        let mut pixel = [64, 0, 128, 128];
+        unpremultiply(&mut pixel);
+        assert_eq!(pixel, [128, 0, 255, 128]);
+        assert!(encode_png(2, 2, &pixels).unwrap().starts_with(b"\x89PNG"));
+        assert!(encode_png(3, 2, &pixels).is_err());
+    }
+}
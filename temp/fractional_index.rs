@@ -0,0 +1,385 @@
+//! Z-order keys that sort as plain strings.
+//!
+//! A key is an integer part, a head letter giving its length followed by
+//! base-62 digits, and then a fraction without trailing zeros. There is
+//! always room between two keys, so reordering rewrites only the shapes that
+//! move and edits made concurrently rarely touch the same shape. The
+//! smallest integer part is never a key on its own, which leaves room below
+//! every key too.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Base-62 digits in ASCII order, so keys compare bytewise.
+const DIGITS: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Integer part of the first key handed out.
+const FIRST: &[u8] = b"a0";
+
+/// The smallest integer part: `A` and 26 zeros.
+const SMALLEST_INTEGER: &[u8] = b"A00000000000000000000000000";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct FractionalIndex(String);
+
+impl Default for FractionalIndex {
+    fn default() -> Self {
+        Self(String::from_utf8(FIRST.to_vec()).unwrap())
+    }
+}
+
+impl fmt::Display for FractionalIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for FractionalIndex {
+    type Error = String;
+
+    fn try_from(key: String) -> Result<Self, String> {
+        let bytes = key.as_bytes();
+        // Trailing zeros are only allowed in the integer part.
+        let valid = bytes.first().and_then(|head| integer_length(*head)).is_some_and(|length| {
+            length <= bytes.len()
+                && bytes.iter().all(|c| digit(*c).is_some())
+                && (length == bytes.len() || !bytes.ends_with(b"0"))
+                && bytes != SMALLEST_INTEGER
+        });
+        if !valid {
+            return Err(format!("invalid fractional index '{}'", key));
+        }
+        Ok(Self(key))
+    }
+}
+
+impl From<FractionalIndex> for String {
+    fn from(index: FractionalIndex) -> String {
+        index.0
+    }
+}
+
+impl FractionalIndex {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// A key above `self`.
+    pub fn after(&self) -> Self {
+        // Cannot fail: there is no upper bound.
+        Self::between(Some(self), None).unwrap()
+    }
+
+    /// A key below `self`.
+    pub fn before(&self) -> Self {
+        // Cannot fail: there is no lower bound.
+        Self::between(None, Some(self)).unwrap()
+    }
+
+    /// A key strictly between `below` and `above`; `None` is unbounded. Only
+    /// the shared prefix of the bounds and a digit or two past it are
+    /// looked at, so this never needs to renumber other keys.
+    pub fn between(below: Option<&Self>, above: Option<&Self>) -> Result<Self, String> {
+        let key = match (below, above) {
+            (Some(a), Some(b)) if a >= b => return Err(format!("'{}' is not below '{}'", a, b)),
+            (None, None) => FIRST.to_vec(),
+            (None, Some(b)) => {
+                let (integer, fraction) = b.split();
+                if integer == SMALLEST_INTEGER {
+                    [integer, &midpoint(b"", Some(fraction))].concat()
+                } else if !fraction.is_empty() {
+                    integer.to_vec()
+                } else {
+                    // Only the smallest integer has no predecessor.
+                    decrement_integer(integer).unwrap()
+                }
+            }
+            (Some(a), None) => {
+                let (integer, fraction) = a.split();
+                increment_integer(integer).unwrap_or_else(|| [integer, &midpoint(fraction, None)].concat())
+            }
+            (Some(a), Some(b)) => {
+                let (integer_a, fraction_a) = a.split();
+                let (integer_b, fraction_b) = b.split();
+                if integer_a == integer_b {
+                    [integer_a, &midpoint(fraction_a, Some(fraction_b))].concat()
+                } else {
+                    match increment_integer(integer_a) {
+                        Some(next) if next.as_slice() < b.0.as_bytes() => next,
+                        _ => [integer_a, &midpoint(fraction_a, None)].concat(),
+                    }
+                }
+            }
+        };
+        // Cannot fail: keys are built from ASCII digits.
+        Ok(Self(String::from_utf8(key).unwrap()))
+    }
+
+    /// `count` ascending keys strictly between `below` and `above`, spread
+    /// so they stay short.
+    pub fn n_between(below: Option<&Self>, above: Option<&Self>, count: usize) -> Result<Vec<Self>, String> {
+        match count {
+            0 => Ok(Vec::new()),
+            1 => Ok(vec![Self::between(below, above)?]),
+            _ if above.is_none() => {
+                let mut keys = vec![Self::between(below, None)?];
+                for _ in 1..count {
+                    keys.push(keys[keys.len() - 1].after());
+                }
+                Ok(keys)
+            }
+            _ if below.is_none() => {
+                let mut keys = vec![Self::between(None, above)?];
+                for _ in 1..count {
+                    keys.push(keys[keys.len() - 1].before());
+                }
+                keys.reverse();
+                Ok(keys)
+            }
+            _ => {
+                let half = count / 2;
+                let middle = Self::between(below, above)?;
+                let mut keys = Self::n_between(below, Some(&middle), half)?;
+                let upper = Self::n_between(Some(&middle), above, count - half - 1)?;
+                keys.push(middle);
+                keys.extend(upper);
+                Ok(keys)
+            }
+        }
+    }
+
+    /// The integer part and the fraction.
+    fn split(&self) -> (&[u8], &[u8]) {
+        let bytes = self.0.as_bytes();
+        // Cannot fail: keys are validated on construction.
+        bytes.split_at(integer_length(bytes[0]).unwrap())
+    }
+}
+
+fn digit(c: u8) -> Option<usize> {
+    match c {
+        b'0'..=b'9' => Some((c - b'0') as usize),
+        b'A'..=b'Z' => Some((c - b'A') as usize + 10),
+        b'a'..=b'z' => Some((c - b'a') as usize + 36),
+        _ => None,
+    }
+}
+
+/// Length of an integer part, head included: `a` is 2, `b` 3 and so on up
+/// to `z`; `Z` is 2, `Y` 3 and so on down to `A`.
+fn integer_length(head: u8) -> Option<usize> {
+    match head {
+        b'a'..=b'z' => Some((head - b'a') as usize + 2),
+        b'A'..=b'Z' => Some((b'Z' - head) as usize + 2),
+        _ => None,
+    }
+}
+
+/// Digits strictly between fractions `a` and `b`, where `None` is one past
+/// the largest fraction. Neither may end in a zero.
+fn midpoint(a: &[u8], b: Option<&[u8]>) -> Vec<u8> {
+    if let Some(b) = b {
+        // Skip the shared prefix, reading missing digits of `a` as zeros.
+        let shared = b
+            .iter()
+            .enumerate()
+            .take_while(|(i, c)| a.get(*i).copied().unwrap_or(b'0') == **c)
+            .count();
+        if shared > 0 {
+            let rest = midpoint(a.get(shared..).unwrap_or(&[]), Some(&b[shared..]));
+            return [&b[..shared], &rest[..]].concat();
+        }
+    }
+    let digit_a = a.first().map_or(0, |c| digit(*c).unwrap());
+    let digit_b = b.map_or(DIGITS.len(), |b| digit(b[0]).unwrap());
+    if digit_b - digit_a > 1 {
+        return vec![DIGITS[(digit_a + digit_b).div_ceil(2)]];
+    }
+    match b {
+        Some(b) if b.len() > 1 => vec![b[0]],
+        // The first digits are consecutive: keep `a`'s and look further.
+        _ => [&[DIGITS[digit_a]], &midpoint(a.get(1..).unwrap_or(&[]), None)[..]].concat(),
+    }
+}
+
+fn increment_integer(integer: &[u8]) -> Option<Vec<u8>> {
+    let (head, mut digits) = (integer[0], integer[1..].to_vec());
+    for d in digits.iter_mut().rev() {
+        let next = digit(*d).unwrap() + 1;
+        if next < DIGITS.len() {
+            *d = DIGITS[next];
+            return Some([&[head], &digits[..]].concat());
+        }
+        *d = DIGITS[0];
+    }
+    // Carried out of every digit: move to the next length.
+    let head = match head {
+        b'Z' => return Some(FIRST.to_vec()),
+        b'z' => return None,
+        _ => head + 1,
+    };
+    if head > b'a' {
+        digits.push(DIGITS[0]);
+    } else {
+        digits.pop();
+    }
+    Some([&[head], &digits[..]].concat())
+}
+
+fn decrement_integer(integer: &[u8]) -> Option<Vec<u8>> {
+    let (head, mut digits) = (integer[0], integer[1..].to_vec());
+    let last = DIGITS[DIGITS.len() - 1];
+    for d in digits.iter_mut().rev() {
+        let value = digit(*d).unwrap();
+        if value > 0 {
+            *d = DIGITS[value - 1];
+            return Some([&[head], &digits[..]].concat());
+        }
+        *d = last;
+    }
+    let head = match head {
+        b'a' => return Some(vec![b'Z', last]),
+        b'A' => return None,
+        _ => head - 1,
+    };
+    if head < b'Z' {
+        digits.push(last);
+    } else {
+        digits.pop();
+    }
+    Some([&[head], &digits[..]].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn key(s: &str) -> FractionalIndex {
+        FractionalIndex::try_from(s.to_string()).unwrap()
+    }
+
+    fn assert_between(below: Option<&FractionalIndex>, above: Option<&FractionalIndex>) -> FractionalIndex {
+        let middle = FractionalIndex::between(below, above).unwrap();
+        assert!(below.is_none_or(|b| *b < middle), "{:?} < {}", below, middle);
+        assert!(above.is_none_or(|a| middle < *a), "{} < {:?}", middle, above);
+        assert_eq!(FractionalIndex::try_from(middle.to_string()).as_ref(), Ok(&middle));
+        middle
+    }
+
+    #[test]
+    fn test_keys_between() {
+        let first = FractionalIndex::default();
+        assert_eq!(first.as_str(), "a0");
+        assert_eq!(first.after().as_str(), "a1");
+        assert_eq!(first.before().as_str(), "Zz");
+        assert_eq!(key("az").after().as_str(), "b00");
+        assert_eq!(key("b00").before().as_str(), "az");
+        assert_eq!(FractionalIndex::between(Some(&key("a0")), Some(&key("a1"))).unwrap().as_str(), "a0V");
+        assert_eq!(FractionalIndex::between(Some(&key("a0V")), Some(&key("a1"))).unwrap().as_str(), "a0l");
+        assert_eq!(FractionalIndex::between(Some(&key("a0")), Some(&key("a0V"))).unwrap().as_str(), "a0G");
+        assert!(FractionalIndex::between(Some(&key("a1")), Some(&key("a0"))).is_err());
+        assert!(FractionalIndex::between(Some(&key("a1")), Some(&key("a1"))).is_err());
+    }
+
+    #[test]
+    fn test_invalid_keys_are_rejected() {
+        for bad in ["", "a", "a~", "a00", "b0", "a0.5", "A00000000000000000000000000", "~"] {
+            assert!(FractionalIndex::try_from(bad.to_string()).is_err(), "{}", bad);
+        }
+        for good in ["a0", "b00", "Zz", "A000000000000000000000000001", "zzzzzzzzzzzzzzzzzzzzzzzzzzzV"] {
+            assert!(FractionalIndex::try_from(good.to_string()).is_ok(), "{}", good);
+        }
+    }
+
+    #[test]
+    fn test_extremes_keep_room() {
+        // Past the largest and smallest integers keys grow a fraction.
+        let mut top = key("zzzzzzzzzzzzzzzzzzzzzzzzzzz");
+        let mut bottom = key("A000000000000000000000000001");
+        for _ in 0..500 {
+            top = assert_between(Some(&top), None);
+            bottom = assert_between(None, Some(&bottom));
+        }
+        // Appending and prepending many times crosses integer lengths.
+        let (mut high, mut low) = (FractionalIndex::default(), FractionalIndex::default());
+        for _ in 0..10_000 {
+            high = assert_between(Some(&high), None);
+            low = assert_between(None, Some(&low));
+        }
+        assert!(high.as_str().len() <= 4 && low.as_str().len() <= 4);
+        // Repeatedly splitting the same gap only grows keys slowly.
+        let (a, mut b) = (key("a0"), key("a1"));
+        for _ in 0..1000 {
+            b = assert_between(Some(&a), Some(&b));
+        }
+        assert!(b.as_str().len() < 1010);
+    }
+
+    #[test]
+    fn test_n_between() {
+        for (below, above) in [(None, None), (Some(key("a0")), None), (None, Some(key("a0"))), (Some(key("a0")), Some(key("a0V")))] {
+            let keys = FractionalIndex::n_between(below.as_ref(), above.as_ref(), 20).unwrap();
+            assert_eq!(keys.len(), 20);
+            assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(below.is_none_or(|b| b < keys[0]) && above.is_none_or(|a| keys[19] < a));
+        }
+    }
+
+    /// Valid keys, weighted toward the shortest and longest integer parts.
+    fn any_key() -> impl Strategy<Value = FractionalIndex> {
+        let head = prop_oneof![
+            Just(b'A'),
+            Just(b'Z'),
+            Just(b'a'),
+            Just(b'z'),
+            (b'A'..=b'Z'),
+            (b'a'..=b'z'),
+        ];
+        let digits = prop_oneof![Just(0usize), Just(61usize), 0usize..62];
+        (head, prop::collection::vec(digits.clone(), 26), prop::collection::vec(digits, 0..6)).prop_map(
+            |(head, integer, mut fraction)| {
+                let length = integer_length(head).unwrap();
+                while fraction.last() == Some(&0) {
+                    fraction.pop();
+                }
+                let mut key = vec![head];
+                key.extend(integer[..length - 1].iter().map(|d| DIGITS[*d]));
+                if key == SMALLEST_INTEGER && fraction.is_empty() {
+                    fraction.push(1);
+                }
+                key.extend(fraction.iter().map(|d| DIGITS[*d]));
+                FractionalIndex::try_from(String::from_utf8(key).unwrap()).unwrap()
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn prop_between_any_two_keys(a in any_key(), b in any_key()) {
+            let (below, above) = if a <= b { (a, b) } else { (b, a) };
+            if below == above {
+                prop_assert!(FractionalIndex::between(Some(&below), Some(&above)).is_err());
+            } else {
+                assert_between(Some(&below), Some(&above));
+            }
+            assert_between(Some(&below), None);
+            assert_between(None, Some(&below));
+            assert_between(Some(&above), None);
+            assert_between(None, Some(&above));
+        }
+
+        #[test]
+        fn prop_random_inserts_stay_sorted(positions in prop::collection::vec(any::<prop::sample::Index>(), 1..200)) {
+            let mut keys: Vec<FractionalIndex> = Vec::new();
+            for position in positions {
+                let i = position.index(keys.len() + 1);
+                let key = assert_between(i.checked_sub(1).map(|j| &keys[j]), keys.get(i));
+                keys.insert(i, key);
+            }
+            prop_assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    }
+}
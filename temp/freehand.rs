@@ -0,0 +1,433 @@
+use crate::document::{LayerId, Shape, ShapeKind, StrokePoint, Style, Transform, PREVIEW_SHAPE_ID};
+use crate::fractional_index::FractionalIndex;
+use crate::geometry::{Point, Rect};
+
+/// How freehand strokes are captured and shaped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreehandOptions {
+    /// Stabilizer strength in `0..1`; higher values trail further behind the
+    /// pointer for smoother lines.
+    pub smoothing: f32,
+    /// How much pressure affects width, in `0..1`. At 0.5 pressure the stroke
+    /// is always its nominal width.
+    pub thinning: f32,
+    /// Distance over which the start and end of a stroke taper to a point.
+    pub taper_start: f32,
+    pub taper_end: f32,
+    /// Ramer–Douglas–Peucker tolerance applied on commit, in screen pixels.
+    pub simplify_tolerance: f32,
+}
+
+impl Default for FreehandOptions {
+    fn default() -> Self {
+        Self {
+            smoothing: 0.5,
+            thinning: 0.6,
+            taper_start: 0.0,
+            taper_end: 0.0,
+            simplify_tolerance: 0.5,
+        }
+    }
+}
+
+/// Exponential moving average over pointer samples.
+#[derive(Debug, Clone)]
+pub struct Stabilizer {
+    strength: f32,
+    last: Option<StrokePoint>,
+}
+
+impl Stabilizer {
+    pub fn new(strength: f32) -> Self {
+        Self {
+            strength: strength.clamp(0.0, 0.99),
+            last: None,
+        }
+    }
+
+    pub fn push(&mut self, sample: StrokePoint) -> StrokePoint {
+        let smoothed = match self.last {
+            None => sample,
+            Some(last) => {
+                let t = 1.0 - self.strength;
+                StrokePoint {
+                    x: last.x + (sample.x - last.x) * t,
+                    y: last.y + (sample.y - last.y) * t,
+                    pressure: last.pressure + (sample.pressure - last.pressure) * t,
+                }
+            }
+        };
+        self.last = Some(smoothed);
+        smoothed
+    }
+}
+
+/// Ramer–Douglas–Peucker simplification. Endpoints are always kept.
+pub fn simplify(points: &[StrokePoint], tolerance: f32) -> Vec<StrokePoint> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let position = |p: &StrokePoint| Point::new(p.x, p.y);
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let (a, b) = (position(&points[first]), position(&points[last]));
+        let mut farthest = first;
+        let mut max_distance = 0.0;
+        for (i, p) in points.iter().enumerate().take(last).skip(first + 1) {
+            let d = position(p).distance_to_segment(a, b);
+            if d > max_distance {
+                max_distance = d;
+                farthest = i;
+            }
+        }
+        if max_distance > tolerance {
+            keep[farthest] = true;
+            stack.push((first, farthest));
+            stack.push((farthest, last));
+        }
+    }
+
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(p, k)| k.then_some(*p))
+        .collect()
+}
+
+/// What is left of a stroke after erasing, and what goes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StrokeSplit {
+    /// Runs that stay, each at least two points long.
+    pub kept: Vec<Vec<StrokePoint>>,
+    /// Runs that go, reaching to the kept points on either side.
+    pub erased: Vec<Vec<StrokePoint>>,
+}
+
+/// Resamples a stroke so no two consecutive points are more than `spacing`
+/// apart, so testing each point is enough to find a cut in a long segment.
+pub fn densify(points: &[StrokePoint], spacing: f32) -> Vec<StrokePoint> {
+    let position = |p: &StrokePoint| Point::new(p.x, p.y);
+    let mut dense: Vec<StrokePoint> = points.first().copied().into_iter().collect();
+    for w in points.windows(2) {
+        let steps = (position(&w[0]).distance(position(&w[1])) / spacing).ceil().max(1.0) as usize;
+        dense.extend((1..=steps).map(|i| {
+            let t = i as f32 / steps as f32;
+            StrokePoint {
+                x: w[0].x + (w[1].x - w[0].x) * t,
+                y: w[0].y + (w[1].y - w[0].y) * t,
+                pressure: w[0].pressure + (w[1].pressure - w[0].pressure) * t,
+            }
+        }));
+    }
+    dense
+}
+
+/// Splits a [`densify`]d stroke where `gone` is set for its points. Kept
+/// runs are simplified within `tolerance`. Returns `None` when nothing is
+/// erased.
+pub fn split_stroke(dense: &[StrokePoint], gone: &[bool], tolerance: f32) -> Option<StrokeSplit> {
+    if !gone.contains(&true) {
+        return None;
+    }
+    let mut split = StrokeSplit::default();
+    let mut start = 0;
+    while start < dense.len() {
+        let end = (start..dense.len()).find(|i| gone[*i] != gone[start]).unwrap_or(dense.len());
+        if gone[start] {
+            split.erased.push(dense[start.saturating_sub(1)..(end + 1).min(dense.len())].to_vec());
+        } else if end - start >= 2 {
+            split.kept.push(simplify(&dense[start..end], tolerance));
+        }
+        start = end;
+    }
+    Some(split)
+}
+
+fn ease_out(t: f32) -> f32 {
+    t * (2.0 - t)
+}
+
+/// Full stroke width at each point, from pressure, thinning and tapering.
+pub fn stroke_widths(points: &[StrokePoint], size: f32, options: &FreehandOptions) -> Vec<f32> {
+    let mut distances = Vec::with_capacity(points.len());
+    let mut total = 0.0;
+    for (i, p) in points.iter().enumerate() {
+        if i > 0 {
+            let prev = &points[i - 1];
+            total += Point::new(p.x, p.y).distance(Point::new(prev.x, prev.y));
+        }
+        distances.push(total);
+    }
+
+    points
+        .iter()
+        .zip(distances)
+        .map(|(p, distance)| {
+            let pressure = p.pressure.clamp(0.0, 1.0);
+            let mut width = size * (1.0 - options.thinning * (1.0 - 2.0 * pressure));
+            if options.taper_start > 0.0 {
+                width *= ease_out((distance / options.taper_start).min(1.0));
+            }
+            if options.taper_end > 0.0 {
+                width *= ease_out(((total - distance) / options.taper_end).min(1.0));
+            }
+            width.max(size * 0.05)
+        })
+        .collect()
+}
+
+/// Closed polygon around a variable-width stroke: the left edge, a round
+/// end cap, the right edge reversed and a round start cap. Used where a
+/// filled outline is needed instead of a triangle mesh, e.g. SVG export.
+pub fn outline(points: &[Point], widths: &[f32]) -> Vec<Point> {
+    const CAP_STEPS: usize = 6;
+    if points.is_empty() {
+        return Vec::new();
+    }
+    let normal_at = |i: usize| {
+        let prev = points[i.saturating_sub(1)];
+        let next = points[(i + 1).min(points.len() - 1)];
+        (next - prev).normalize().perp()
+    };
+
+    let mut left = Vec::with_capacity(points.len());
+    let mut right = Vec::with_capacity(points.len());
+    for (i, &p) in points.iter().enumerate() {
+        let offset = normal_at(i) * (widths[i] * 0.5);
+        left.push(p + offset);
+        right.push(p - offset);
+    }
+
+    let cap = |center: Point, from: Point, out: &mut Vec<Point>| {
+        for step in 1..CAP_STEPS {
+            let angle = -std::f32::consts::PI * step as f32 / CAP_STEPS as f32;
+            out.push(center + (from - center).rotate_around(Point::ZERO, angle));
+        }
+    };
+
+    let last = points.len() - 1;
+    let mut polygon = left.clone();
+    cap(points[last], left[last], &mut polygon);
+    polygon.extend(right.iter().rev());
+    cap(points[0], right[0], &mut polygon);
+    polygon
+}
+
+/// Simulates pressure for devices that don't report it: fast movement thins
+/// the line, slow movement thickens it.
+fn simulated_pressure(previous: f32, distance: f32, size: f32) -> f32 {
+    let speed = (distance / size.max(f32::EPSILON)).min(1.0);
+    let target = 1.0 - speed;
+    (previous + (target - previous) * speed * 0.275).clamp(0.0, 1.0)
+}
+
+/// Collects pointer samples for a stroke that is still being drawn.
+#[derive(Debug, Clone)]
+pub struct StrokeBuilder {
+    points: Vec<StrokePoint>,
+    last_raw: Option<StrokePoint>,
+    stabilizer: Stabilizer,
+    simulate_pressure: bool,
+    style: Style,
+    options: FreehandOptions,
+}
+
+impl StrokeBuilder {
+    /// `simulate_pressure` should be set for mice and other devices that
+    /// report a constant pressure.
+    pub fn new(style: Style, options: FreehandOptions, simulate_pressure: bool) -> Self {
+        Self {
+            points: Vec::new(),
+            last_raw: None,
+            stabilizer: Stabilizer::new(options.smoothing),
+            simulate_pressure,
+            style,
+            options,
+        }
+    }
+
+    /// Adds a world-space sample. Call once per coalesced pointer event.
+    pub fn add_sample(&mut self, point: Point, pressure: f32) {
+        let pressure = if self.simulate_pressure {
+            let previous = self.points.last().map_or(0.5, |p| p.pressure);
+            let distance = self
+                .last_raw
+                .map_or(0.0, |p| Point::new(p.x, p.y).distance(point));
+            simulated_pressure(previous, distance, self.style.stroke_width)
+        } else {
+            pressure
+        };
+        let raw = StrokePoint {
+            x: point.x,
+            y: point.y,
+            pressure,
+        };
+        self.last_raw = Some(raw);
+        let smoothed = self.stabilizer.push(raw);
+        self.points.push(smoothed);
+    }
+
+    pub fn points(&self) -> &[StrokePoint] {
+        &self.points
+    }
+
+    /// The stroke so far, including the stabilizer's lag back to the pointer.
+    fn settled_points(&self) -> Vec<StrokePoint> {
+        let mut points = self.points.clone();
+        if let (Some(raw), Some(last)) = (self.last_raw, points.last()) {
+            if *last != raw {
+                points.push(raw);
+            }
+        }
+        points
+    }
+
+    /// A freehand shape through world-space `points`, boxed by their bounds.
+    pub(crate) fn to_shape_parts(points: &[StrokePoint]) -> Option<(ShapeKind, Transform)> {
+        let bounds = Rect::from_points(points.iter().map(|p| Point::new(p.x, p.y)))?;
+        let local = points
+            .iter()
+            .map(|p| StrokePoint {
+                x: p.x - bounds.min_x,
+                y: p.y - bounds.min_y,
+                pressure: p.pressure,
+            })
+            .collect();
+        Some((
+            ShapeKind::Freehand { points: local },
+            Transform::new(bounds.min_x, bounds.min_y, bounds.width(), bounds.height()),
+        ))
+    }
+
+    /// A throwaway shape for drawing the stroke before it is committed.
+    pub fn preview_shape(&self) -> Option<Shape> {
+        let (kind, transform) = Self::to_shape_parts(&self.settled_points())?;
+        Some(Shape {
+            id: PREVIEW_SHAPE_ID,
+            kind,
+            transform,
+            style: self.style.clone(),
+            z_index: FractionalIndex::default(),
+            layer: LayerId::default(),
+            parent: None,
+        })
+    }
+
+    /// Simplifies the stroke and returns the parts for a document shape.
+    /// `world_units_per_pixel` converts the simplify tolerance to world space.
+    pub fn finish(self, world_units_per_pixel: f32) -> Option<(ShapeKind, Transform, Style)> {
+        let tolerance = self.options.simplify_tolerance * world_units_per_pixel;
+        let points = simplify(&self.settled_points(), tolerance);
+        let (kind, transform) = Self::to_shape_parts(&points)?;
+        Some((kind, transform, self.style))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sp(x: f32, y: f32) -> StrokePoint {
+        StrokePoint { x, y, pressure: 0.5 }
+    }
+
+    #[test]
+    fn test_simplify_removes_collinear_points() {
+        let points: Vec<StrokePoint> = (0..=10).map(|i| sp(i as f32, 0.0)).collect();
+        let simplified = simplify(&points, 0.1);
+        assert_eq!(simplified, vec![sp(0.0, 0.0), sp(10.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_simplify_keeps_corners() {
+        let points = vec![sp(0.0, 0.0), sp(5.0, 0.1), sp(10.0, 0.0), sp(10.0, 10.0)];
+        let simplified = simplify(&points, 0.5);
+        assert_eq!(simplified, vec![sp(0.0, 0.0), sp(10.0, 0.0), sp(10.0, 10.0)]);
+    }
+
+    #[test]
+    fn test_stabilizer_lags_behind() {
+        let mut stabilizer = Stabilizer::new(0.5);
+        stabilizer.push(sp(0.0, 0.0));
+        let p = stabilizer.push(sp(10.0, 0.0));
+        assert_eq!(p.x, 5.0);
+
+        let mut off = Stabilizer::new(0.0);
+        off.push(sp(0.0, 0.0));
+        assert_eq!(off.push(sp(10.0, 0.0)).x, 10.0);
+    }
+
+    #[test]
+    fn test_widths_follow_pressure_and_taper() {
+        let points = vec![
+            StrokePoint { x: 0.0, y: 0.0, pressure: 0.5 },
+            StrokePoint { x: 10.0, y: 0.0, pressure: 1.0 },
+            StrokePoint { x: 20.0, y: 0.0, pressure: 0.0 },
+        ];
+        let options = FreehandOptions {
+            thinning: 0.5,
+            ..FreehandOptions::default()
+        };
+        let widths = stroke_widths(&points, 4.0, &options);
+        assert_eq!(widths, vec![4.0, 6.0, 2.0]);
+
+        let tapered = stroke_widths(
+            &points,
+            4.0,
+            &FreehandOptions {
+                taper_start: 20.0,
+                ..options
+            },
+        );
+        assert_eq!(tapered[0], 4.0 * 0.05);
+        assert!(tapered[1] < widths[1]);
+        assert_eq!(tapered[2], widths[2]);
+    }
+
+    #[test]
+    fn test_builder_finishes_in_local_space() {
+        let style = Style::default();
+        let mut builder = StrokeBuilder::new(style, FreehandOptions::default(), false);
+        for i in 0..=10 {
+            builder.add_sample(Point::new(100.0 + i as f32, 50.0), 0.7);
+        }
+        assert!(builder.preview_shape().is_some());
+
+        let (kind, transform, _) = builder.finish(1.0).unwrap();
+        assert_eq!((transform.x, transform.y), (100.0, 50.0));
+        match kind {
+            ShapeKind::Freehand { points } => {
+                // A straight line simplifies to its endpoints, and the last
+                // point catches up with the pointer.
+                assert_eq!(points.len(), 2);
+                assert_eq!(points[0].x, 0.0);
+                assert_eq!(points[1].x, 10.0);
+            }
+            other => panic!("unexpected kind {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_simulated_pressure_thins_fast_strokes() {
+        let mut slow = StrokeBuilder::new(Style::default(), FreehandOptions::default(), true);
+        let mut fast = StrokeBuilder::new(Style::default(), FreehandOptions::default(), true);
+        for i in 0..20 {
+            slow.add_sample(Point::new(i as f32 * 0.1, 0.0), 0.5);
+            fast.add_sample(Point::new(i as f32 * 10.0, 0.0), 0.5);
+        }
+        assert!(slow.points().last().unwrap().pressure > fast.points().last().unwrap().pressure);
+    }
+
+    #[test]
+    fn test_outline_is_closed_around_the_stroke() {
+        let points = [Point::new(0.0, 0.0), Point::new(10.0, 0.0)];
+        let polygon = outline(&points, &[2.0, 2.0]);
+        let bounds = Rect::from_points(polygon.iter().copied()).unwrap();
+        assert!((bounds.min_x + 1.0).abs() < 0.1 && (bounds.max_x - 11.0).abs() < 0.1);
+        assert!((bounds.height() - 2.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,305 @@
+use std::ops::{Add, Mul, Sub};
+
+use serde::{Deserialize, Serialize};
+
+/// A point or vector in world space.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub const ZERO: Point = Point { x: 0.0, y: 0.0 };
+
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn dot(self, other: Point) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn cross(self, other: Point) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn distance(self, other: Point) -> f32 {
+        (self - other).length()
+    }
+
+    /// Unit vector in the same direction, or zero for a zero-length vector.
+    pub fn normalize(self) -> Point {
+        let len = self.length();
+        if len > f32::EPSILON {
+            self * (1.0 / len)
+        } else {
+            Point::ZERO
+        }
+    }
+
+    /// Counter-clockwise perpendicular (in a y-up frame).
+    pub fn perp(self) -> Point {
+        Point::new(-self.y, self.x)
+    }
+
+    pub fn lerp(self, other: Point, t: f32) -> Point {
+        self + (other - self) * t
+    }
+
+    /// Shortest distance from this point to the segment `a`-`b`.
+    pub fn distance_to_segment(self, a: Point, b: Point) -> f32 {
+        let ab = b - a;
+        let len_sq = ab.dot(ab);
+        if len_sq <= f32::EPSILON {
+            return self.distance(a);
+        }
+        let t = ((self - a).dot(ab) / len_sq).clamp(0.0, 1.0);
+        self.distance(a + ab * t)
+    }
+
+    /// Rotates the point by `angle` radians around `origin`.
+    pub fn rotate_around(self, origin: Point, angle: f32) -> Point {
+        if angle == 0.0 {
+            return self;
+        }
+        let (sin, cos) = angle.sin_cos();
+        let d = self - origin;
+        Point::new(
+            origin.x + d.x * cos - d.y * sin,
+            origin.y + d.x * sin + d.y * cos,
+        )
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+    fn add(self, rhs: Point) -> Point {
+        Point::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+    fn sub(self, rhs: Point) -> Point {
+        Point::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<f32> for Point {
+    type Output = Point;
+    fn mul(self, rhs: f32) -> Point {
+        Point::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+/// Axis-aligned rectangle in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl Rect {
+    pub fn new(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Self {
+        Self {
+            min_x: min_x.min(max_x),
+            min_y: min_y.min(max_y),
+            max_x: min_x.max(max_x),
+            max_y: min_y.max(max_y),
+        }
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Point>>(points: I) -> Option<Rect> {
+        let mut iter = points.into_iter();
+        let first = iter.next()?;
+        let mut rect = Rect::new(first.x, first.y, first.x, first.y);
+        for p in iter {
+            rect.min_x = rect.min_x.min(p.x);
+            rect.min_y = rect.min_y.min(p.y);
+            rect.max_x = rect.max_x.max(p.x);
+            rect.max_y = rect.max_y.max(p.y);
+        }
+        Some(rect)
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f32 {
+        self.max_y - self.min_y
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new(
+            (self.min_x + self.max_x) * 0.5,
+            (self.min_y + self.max_y) * 0.5,
+        )
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.min_x.is_finite() && self.min_y.is_finite() && self.max_x.is_finite() && self.max_y.is_finite()
+    }
+
+    pub fn contains_point(&self, p: Point) -> bool {
+        p.x >= self.min_x && p.x <= self.max_x && p.y >= self.min_y && p.y <= self.max_y
+    }
+
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        other.min_x >= self.min_x
+            && other.max_x <= self.max_x
+            && other.min_y >= self.min_y
+            && other.max_y <= self.max_y
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.min_x <= other.max_x
+            && self.max_x >= other.min_x
+            && self.min_y <= other.max_y
+            && self.max_y >= other.min_y
+    }
+
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    /// The overlap of the two rectangles. Disjoint rectangles give an
+    /// inverted one, which contains no point.
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        Rect {
+            min_x: self.min_x.max(other.min_x),
+            min_y: self.min_y.max(other.min_y),
+            max_x: self.max_x.min(other.max_x),
+            max_y: self.max_y.min(other.max_y),
+        }
+    }
+
+    /// Whether the segment from `a` to `b` touches the rectangle.
+    pub fn intersects_segment(&self, a: Point, b: Point) -> bool {
+        // Liang-Barsky: narrow the segment's parameter range slab by slab.
+        let d = b - a;
+        let (mut t0, mut t1) = (0.0f32, 1.0f32);
+        let slabs = [
+            (-d.x, a.x - self.min_x),
+            (d.x, self.max_x - a.x),
+            (-d.y, a.y - self.min_y),
+            (d.y, self.max_y - a.y),
+        ];
+        for (p, q) in slabs {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return false;
+                }
+                continue;
+            }
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+            if t0 > t1 {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Grows the rectangle by `amount` on every side.
+    pub fn expand(&self, amount: f32) -> Rect {
+        Rect {
+            min_x: self.min_x - amount,
+            min_y: self.min_y - amount,
+            max_x: self.max_x + amount,
+            max_y: self.max_y + amount,
+        }
+    }
+}
+
+/// Even-odd point-in-polygon test. The polygon is implicitly closed.
+pub fn point_in_polygon(point: Point, polygon: &[Point]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[j];
+        if (a.y > point.y) != (b.y > point.y) {
+            let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Whether the segments `a`–`b` and `c`–`d` cross or touch.
+pub fn segments_intersect(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let (ab, cd) = (b - a, d - c);
+    let denominator = ab.cross(cd);
+    if denominator == 0.0 {
+        // Parallel; they only meet if collinear and overlapping.
+        return (c - a).cross(ab) == 0.0 && Rect::from_points([a, b]).is_some_and(|r| r.intersects(&Rect::from_points([c, d]).unwrap()));
+    }
+    let t = (c - a).cross(cd) / denominator;
+    let u = (c - a).cross(ab) / denominator;
+    (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate_around() {
+        let p = Point::new(1.0, 0.0).rotate_around(Point::ZERO, std::f32::consts::FRAC_PI_2);
+        assert!((p.x - 0.0).abs() < 1e-6);
+        assert!((p.y - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rect_queries() {
+        let a = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let b = Rect::new(5.0, 5.0, 15.0, 15.0);
+        assert!(a.intersects(&b));
+        assert!(!a.contains_rect(&b));
+        assert_eq!(a.union(&b), Rect::new(0.0, 0.0, 15.0, 15.0));
+        assert_eq!(
+            Rect::from_points([Point::new(3.0, -1.0), Point::new(-2.0, 4.0)]),
+            Some(Rect::new(-2.0, -1.0, 3.0, 4.0))
+        );
+    }
+
+    #[test]
+    fn test_segment_and_polygon_queries() {
+        let r = Rect::new(0.0, 0.0, 10.0, 10.0);
+        assert!(r.intersects_segment(Point::new(-5.0, 5.0), Point::new(15.0, 5.0)));
+        assert!(r.intersects_segment(Point::new(2.0, 2.0), Point::new(3.0, 3.0)));
+        assert!(!r.intersects_segment(Point::new(-5.0, 0.0), Point::new(0.0, -5.0) - Point::new(0.1, 0.1)));
+        assert!(!r.intersects_segment(Point::new(11.0, -5.0), Point::new(11.0, 15.0)));
+
+        let triangle = [Point::new(0.0, 0.0), Point::new(10.0, 0.0), Point::new(0.0, 10.0)];
+        assert!(point_in_polygon(Point::new(2.0, 2.0), &triangle));
+        assert!(!point_in_polygon(Point::new(6.0, 6.0), &triangle));
+        assert!(!point_in_polygon(Point::new(1.0, 1.0), &[]));
+
+        let (a, b) = (Point::new(0.0, 0.0), Point::new(10.0, 10.0));
+        assert!(segments_intersect(a, b, Point::new(0.0, 10.0), Point::new(10.0, 0.0)));
+        assert!(!segments_intersect(a, b, Point::new(6.0, 0.0), Point::new(10.0, 4.0)));
+        assert!(segments_intersect(a, b, Point::new(5.0, 5.0), Point::new(20.0, 20.0)));
+        assert!(!segments_intersect(a, b, Point::new(11.0, 11.0), Point::new(20.0, 20.0)));
+    }
+}
@@ -0,0 +1,341 @@
+//! Signed distance field glyphs packed into one single-channel texture.
+//!
+//! Each glyph is rasterized once at [`ATLAS_EM_SIZE`] texels per em. Texels
+//! store the distance to the outline, so the text shader can threshold at
+//! 0.5 with a screen-space smoothing width and stay sharp at any zoom.
+
+use std::collections::HashMap;
+
+use crate::document::{Color, Paint, Shape, ShapeKind};
+use crate::geometry::{Point, Rect};
+use crate::text::{layout, FontStack, GlyphKey};
+
+/// Texels per em in the atlas.
+pub const ATLAS_EM_SIZE: f32 = 48.0;
+
+/// Distance in texels encoded on each side of the outline; also the padding
+/// around each glyph.
+pub const SDF_SPREAD: f32 = 6.0;
+
+pub const ATLAS_SIZE: u32 = 1024;
+
+/// Floats per glyph instance: rect(4), uv(4), center(2), rotation, color(4).
+/// The rect is the glyph quad's min corner and size relative to the text
+/// box centre, which the box rotates about.
+pub const GLYPH_INSTANCE_FLOATS: usize = 15;
+
+/// Where a glyph's distance field sits in the atlas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasGlyph {
+    /// Texel rect in the atlas.
+    pub texels: Rect,
+    /// The same area in em units, relative to the pen position on the
+    /// baseline, y down.
+    pub plane: Rect,
+}
+
+/// A glyph's distance field before packing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphField {
+    pub width: u32,
+    pub height: u32,
+    /// 128 on the outline, above inside, below outside.
+    pub pixels: Vec<u8>,
+    pub plane: Rect,
+}
+
+/// Computes the distance field of an outline given as closed polygons in em
+/// units, or `None` if it is blank.
+pub fn glyph_field(contours: &[Vec<Point>]) -> Option<GlyphField> {
+    let bounds = Rect::from_points(contours.iter().flatten().copied())?;
+    let min_x = (bounds.min_x * ATLAS_EM_SIZE).floor() - SDF_SPREAD;
+    let min_y = (bounds.min_y * ATLAS_EM_SIZE).floor() - SDF_SPREAD;
+    let width = ((bounds.max_x * ATLAS_EM_SIZE).ceil() + SDF_SPREAD - min_x) as u32;
+    let height = ((bounds.max_y * ATLAS_EM_SIZE).ceil() + SDF_SPREAD - min_y) as u32;
+
+    let segments: Vec<(Point, Point)> = contours
+        .iter()
+        .flat_map(|contour| {
+            let scaled: Vec<Point> = contour.iter().map(|p| *p * ATLAS_EM_SIZE).collect();
+            (0..scaled.len()).map(move |i| (scaled[i], scaled[(i + 1) % scaled.len()]))
+        })
+        .collect();
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let p = Point::new(min_x + x as f32 + 0.5, min_y + y as f32 + 0.5);
+            let mut distance = f32::MAX;
+            let mut winding = 0;
+            for &(a, b) in &segments {
+                distance = distance.min(p.distance_to_segment(a, b));
+                if a.y <= p.y {
+                    if b.y > p.y && (b - a).cross(p - a) > 0.0 {
+                        winding += 1;
+                    }
+                } else if b.y <= p.y && (b - a).cross(p - a) < 0.0 {
+                    winding -= 1;
+                }
+            }
+            let signed = if winding != 0 { distance } else { -distance };
+            let value = (0.5 + signed / (2.0 * SDF_SPREAD)).clamp(0.0, 1.0);
+            pixels.push((value * 255.0).round() as u8);
+        }
+    }
+    Some(GlyphField {
+        width,
+        height,
+        pixels,
+        plane: Rect::new(
+            min_x / ATLAS_EM_SIZE,
+            min_y / ATLAS_EM_SIZE,
+            (min_x + width as f32) / ATLAS_EM_SIZE,
+            (min_y + height as f32) / ATLAS_EM_SIZE,
+        ),
+    })
+}
+
+/// Packs glyph fields into rows ("shelves") of a fixed-size texture.
+pub struct GlyphAtlas {
+    size: u32,
+    pixels: Vec<u8>,
+    /// `None` for blank glyphs such as spaces.
+    glyphs: HashMap<GlyphKey, Option<AtlasGlyph>>,
+    /// (top, height, next free x) of each shelf.
+    shelves: Vec<(u32, u32, u32)>,
+    revision: u64,
+}
+
+impl GlyphAtlas {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            pixels: vec![0; size as usize * size as usize],
+            glyphs: HashMap::new(),
+            shelves: Vec::new(),
+            revision: 0,
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Row-major single-channel texels, `size` x `size`.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Bumped whenever texels change, so the texture can be re-uploaded.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Drops every glyph, e.g. when the atlas is full or the fonts changed.
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+        self.glyphs.clear();
+        self.shelves.clear();
+        self.revision += 1;
+    }
+
+    /// The glyph's place in the atlas, rasterizing it on first use. Errors
+    /// when it no longer fits; clear the atlas and lay out again.
+    pub fn glyph(&mut self, fonts: &FontStack, key: GlyphKey) -> Result<Option<AtlasGlyph>, String> {
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return Ok(*glyph);
+        }
+        let Some(field) = glyph_field(&fonts.outline(key)) else {
+            self.glyphs.insert(key, None);
+            return Ok(None);
+        };
+        let (x, y) = self.allocate(field.width, field.height).ok_or("Glyph atlas is full")?;
+        for row in 0..field.height as usize {
+            let target = (y as usize + row) * self.size as usize + x as usize;
+            let source = row * field.width as usize;
+            self.pixels[target..target + field.width as usize]
+                .copy_from_slice(&field.pixels[source..source + field.width as usize]);
+        }
+        let glyph = AtlasGlyph {
+            texels: Rect::new(x as f32, y as f32, (x + field.width) as f32, (y + field.height) as f32),
+            plane: field.plane,
+        };
+        self.glyphs.insert(key, Some(glyph));
+        self.revision += 1;
+        Ok(Some(glyph))
+    }
+
+    /// Finds room on the first shelf that fits, else opens a new one. Keeps
+    /// a texel of gap so linear filtering never bleeds between glyphs.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (padded_width, padded_height) = (width + 1, height + 1);
+        if padded_width > self.size {
+            return None;
+        }
+        for shelf in &mut self.shelves {
+            let (top, shelf_height, free_x) = *shelf;
+            if padded_height <= shelf_height && free_x + padded_width <= self.size {
+                shelf.2 += padded_width;
+                return Some((free_x, top));
+            }
+        }
+        let top = self.shelves.last().map_or(0, |&(top, height, _)| top + height);
+        if top + padded_height > self.size {
+            return None;
+        }
+        self.shelves.push((top, padded_height, padded_width));
+        Some((0, top))
+    }
+}
+
+/// Lays out the text shapes among `shapes` into glyph instances for the
+/// text shader or the CPU rasterizer. If the atlas fills up it is cleared
+/// and the layout redone once, which drops glyphs no longer needed. Colors
+/// take the opacity `paint` returns for each shape.
+///
+/// Returns the instance count after each shape, for
+/// [`crate::batch::paint_steps`].
+pub fn glyph_instances<F: Fn(&Shape) -> Paint>(
+    atlas: &mut GlyphAtlas,
+    fonts: &FontStack,
+    shapes: &[&Shape],
+    paint: F,
+    data: &mut Vec<f32>,
+) -> Vec<usize> {
+    let mut ends = Vec::with_capacity(shapes.len());
+    for attempt in 0..2 {
+        data.clear();
+        ends.clear();
+        let result = shapes.iter().try_for_each(|shape| {
+            let pushed = push_text(atlas, fonts, shape, paint(shape).opacity, data);
+            ends.push(data.len() / GLYPH_INSTANCE_FLOATS);
+            pushed
+        });
+        if result.is_ok() || attempt == 1 {
+            break;
+        }
+        atlas.clear();
+    }
+    ends
+}
+
+/// Appends one instance per visible glyph of a text shape, its opacity
+/// multiplied by `opacity`. Fails only when the atlas is full.
+fn push_text(atlas: &mut GlyphAtlas, fonts: &FontStack, shape: &Shape, opacity: f32, data: &mut Vec<f32>) -> Result<(), String> {
+    let ShapeKind::Text { content, font_size, align } = &shape.kind else {
+        return Ok(());
+    };
+    let t = &shape.transform;
+    let center = t.center();
+    let color = shape.style.stroke;
+    let color = Color::rgba(color.r, color.g, color.b, color.a * shape.style.opacity * opacity);
+    let texel = 1.0 / atlas.size() as f32;
+    let laid_out = layout(fonts, content, *font_size, Some(t.width), *align);
+    for glyph in &laid_out.glyphs {
+        let Some(placed) = atlas.glyph(fonts, glyph.key)? else {
+            continue;
+        };
+        let plane = placed.plane;
+        let origin = Point::new(t.x + glyph.x, t.y + glyph.y);
+        let min = origin + Point::new(plane.min_x, plane.min_y) * *font_size - center;
+        data.extend_from_slice(&[
+            min.x,
+            min.y,
+            plane.width() * font_size,
+            plane.height() * font_size,
+            placed.texels.min_x * texel,
+            placed.texels.min_y * texel,
+            placed.texels.max_x * texel,
+            placed.texels.max_y * texel,
+            center.x,
+            center.y,
+            t.rotation,
+            color.r,
+            color.g,
+            color.b,
+            color.a,
+        ]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::{layout, test_fonts, TextAlign};
+
+    fn key(fonts: &FontStack, ch: char) -> GlyphKey {
+        layout(fonts, &ch.to_string(), 10.0, None, TextAlign::Left).glyphs[0].key
+    }
+
+    fn texel(field: &GlyphField, em: Point) -> u8 {
+        let x = ((em.x - field.plane.min_x) * ATLAS_EM_SIZE) as usize;
+        let y = ((em.y - field.plane.min_y) * ATLAS_EM_SIZE) as usize;
+        field.pixels[y * field.width as usize + x]
+    }
+
+    #[test]
+    fn test_field_sign_and_ramp() {
+        // A unit square one em wide, wound either way.
+        let square = vec![
+            Point::new(0.0, -1.0),
+            Point::new(1.0, -1.0),
+            Point::new(1.0, 0.0),
+            Point::new(0.0, 0.0),
+        ];
+        for contour in [square.clone(), square.into_iter().rev().collect()] {
+            let field = glyph_field(&[contour]).unwrap();
+            assert_eq!(field.width, ATLAS_EM_SIZE as u32 + 2 * SDF_SPREAD as u32);
+            assert_eq!(texel(&field, Point::new(0.5, -0.5)), 255);
+            assert_eq!(field.pixels[0], 0);
+            // Half a texel inside the left edge.
+            let edge = texel(&field, Point::new(0.5 / ATLAS_EM_SIZE, -0.5));
+            assert!((128..150).contains(&edge));
+        }
+        assert!(glyph_field(&[]).is_none());
+    }
+
+    #[test]
+    fn test_glyph_holes_are_outside() {
+        let fonts = test_fonts();
+        let o = key(&fonts, 'o');
+        let field = glyph_field(&fonts.outline(o)).unwrap();
+        // The middle of the counter is empty; the left of the bowl is ink.
+        let center = field.plane.center();
+        assert!(texel(&field, center) < 128);
+        let x_height = center.y;
+        let ink = (0..field.width).any(|x| {
+            let em = Point::new(field.plane.min_x + (x as f32 + 0.5) / ATLAS_EM_SIZE, x_height);
+            em.x < center.x && texel(&field, em) > 128
+        });
+        assert!(ink);
+    }
+
+    #[test]
+    fn test_atlas_packs_and_caches() {
+        let fonts = test_fonts();
+        let mut atlas = GlyphAtlas::new(256);
+        let a = atlas.glyph(&fonts, key(&fonts, 'A')).unwrap().unwrap();
+        let revision = atlas.revision();
+        assert_eq!(atlas.glyph(&fonts, key(&fonts, 'A')).unwrap(), Some(a));
+        assert_eq!(atlas.revision(), revision);
+        assert_eq!(atlas.glyph(&fonts, key(&fonts, ' ')).unwrap(), None);
+
+        let b = atlas.glyph(&fonts, key(&fonts, 'B')).unwrap().unwrap();
+        assert!(!a.texels.intersects(&b.texels.expand(-0.5)));
+        assert!(a.plane.min_y < 0.0 && a.plane.max_y > 0.0);
+
+        // Fill it up, then start over.
+        let mut full = false;
+        for ch in 'C'..='z' {
+            if atlas.glyph(&fonts, key(&fonts, ch)).is_err() {
+                full = true;
+                break;
+            }
+        }
+        assert!(full);
+        atlas.clear();
+        assert!(atlas.pixels().iter().all(|&p| p == 0));
+        assert_eq!(atlas.glyph(&fonts, key(&fonts, 'B')).unwrap().unwrap().texels.min_x, 0.0);
+    }
+}
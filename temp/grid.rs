@@ -0,0 +1,21 @@
+use crate::document::Color;
+
+/// How the background grid looks, in world units. The default matches the
+/// dots the renderer draws.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridStyle {
+    /// Distance between neighbouring dots.
+    pub spacing: f32,
+    pub dot_radius: f32,
+    pub color: Color,
+}
+
+impl Default for GridStyle {
+    fn default() -> Self {
+        Self {
+            spacing: 40.0,
+            dot_radius: 1.5,
+            color: Color::rgba(0.8, 0.8, 0.8, 1.0),
+        }
+    }
+}
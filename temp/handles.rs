@@ -0,0 +1,371 @@
+//! Transform handles: the selection frame, hit testing its handles, and the
+//! resize and rotate maths applied to the selected shapes.
+
+use std::f32::consts::PI;
+
+use crate::document::{Document, Shape, ShapeKind, StrokePoint, Transform};
+use crate::geometry::Point;
+use crate::selection::Selection;
+
+/// Drawn size of a handle, in screen pixels.
+pub const HANDLE_SIZE_PX: f32 = 8.0;
+
+/// Side of the square around a handle that grabs it, in screen pixels.
+pub const HANDLE_HIT_PX: f32 = 14.0;
+
+/// Distance from the top edge to the rotation handle, in screen pixels.
+pub const ROTATE_HANDLE_OFFSET_PX: f32 = 24.0;
+
+/// Rotation snaps to multiples of this while Shift is held.
+const ROTATION_STEP: f32 = PI / 12.0;
+
+/// Which side of the frame a handle sits on, per axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Min,
+    Mid,
+    Max,
+}
+
+impl Side {
+    fn position(self, size: f32) -> f32 {
+        match self {
+            Self::Min => 0.0,
+            Self::Mid => size * 0.5,
+            Self::Max => size,
+        }
+    }
+
+    fn opposite(self) -> Self {
+        match self {
+            Self::Min => Self::Max,
+            Self::Mid => Self::Mid,
+            Self::Max => Self::Min,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handle {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+    Rotate,
+}
+
+impl Handle {
+    /// Corners first, so they win when handles overlap on tiny frames.
+    pub const ALL: [Handle; 9] = [
+        Self::NorthEast,
+        Self::SouthEast,
+        Self::SouthWest,
+        Self::NorthWest,
+        Self::North,
+        Self::East,
+        Self::South,
+        Self::West,
+        Self::Rotate,
+    ];
+
+    fn sides(self) -> (Side, Side) {
+        match self {
+            Self::North | Self::Rotate => (Side::Mid, Side::Min),
+            Self::NorthEast => (Side::Max, Side::Min),
+            Self::East => (Side::Max, Side::Mid),
+            Self::SouthEast => (Side::Max, Side::Max),
+            Self::South => (Side::Mid, Side::Max),
+            Self::SouthWest => (Side::Min, Side::Max),
+            Self::West => (Side::Min, Side::Mid),
+            Self::NorthWest => (Side::Min, Side::Min),
+        }
+    }
+
+    /// Which of the frame's axes, x then y, dragging this handle resizes.
+    pub fn axes(self) -> (bool, bool) {
+        let (x, y) = self.sides();
+        (x != Side::Mid, y != Side::Mid)
+    }
+
+    /// World position of the handle on `frame`.
+    pub fn position(self, frame: &Transform, world_units_per_pixel: f32) -> Point {
+        let (x, y) = self.sides();
+        let mut local = Point::new(x.position(frame.width), y.position(frame.height));
+        if self == Self::Rotate {
+            local.y -= ROTATE_HANDLE_OFFSET_PX * world_units_per_pixel;
+        }
+        frame.to_world(local)
+    }
+}
+
+/// The box handles are drawn on: a lone shape's own rotated box, or the
+/// axis-aligned box around several shapes' boxes. Stroke width is left out,
+/// so handles sit on the geometry being resized.
+pub fn selection_frame(document: &Document, selection: &Selection) -> Option<Transform> {
+    let mut shapes = selection.iter().filter_map(|id| document.get(id));
+    let first = shapes.next()?;
+    let mut bounds = first.transform.bounds();
+    let mut count = 1;
+    for shape in shapes {
+        bounds = bounds.union(&shape.transform.bounds());
+        count += 1;
+    }
+    if count == 1 {
+        return Some(first.transform);
+    }
+    Some(Transform::new(bounds.min_x, bounds.min_y, bounds.width(), bounds.height()))
+}
+
+/// The handle under `point`. Hit areas are a fixed size on screen.
+pub fn handle_at(frame: &Transform, point: Point, world_units_per_pixel: f32) -> Option<Handle> {
+    let reach = HANDLE_HIT_PX * 0.5 * world_units_per_pixel;
+    Handle::ALL.into_iter().find(|handle| {
+        // Compare in the frame's axes so hit squares turn with the frame.
+        let offset = (point - handle.position(frame, world_units_per_pixel)).rotate_around(Point::ZERO, -frame.rotation);
+        offset.x.abs() <= reach && offset.y.abs() <= reach
+    })
+}
+
+/// A resize expressed in the frame's local space: points move away from
+/// `anchor` by `scale_x`, `scale_y`. Negative scales flip.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resize {
+    pub anchor: Point,
+    pub scale_x: f32,
+    pub scale_y: f32,
+}
+
+impl Resize {
+    /// The resize that brings `handle` to `pointer` (world space).
+    /// `keep_aspect` scales both axes together; `from_center` anchors at the
+    /// frame's centre instead of the opposite handle.
+    pub fn from_drag(frame: &Transform, handle: Handle, pointer: Point, keep_aspect: bool, from_center: bool) -> Self {
+        let (side_x, side_y) = handle.sides();
+        let local = frame.to_local(pointer);
+        let anchor_side = |side: Side| if from_center { Side::Mid } else { side.opposite() };
+        let anchor = Point::new(
+            anchor_side(side_x).position(frame.width),
+            anchor_side(side_y).position(frame.height),
+        );
+        let axis_scale = |side: Side, size: f32, anchor: f32, pointer: f32| {
+            let reach = side.position(size) - anchor;
+            if side == Side::Mid || reach.abs() < f32::EPSILON {
+                None
+            } else {
+                Some((pointer - anchor) / reach)
+            }
+        };
+        let sx = axis_scale(side_x, frame.width, anchor.x, local.x);
+        let sy = axis_scale(side_y, frame.height, anchor.y, local.y);
+
+        let (scale_x, scale_y) = match (sx, sy, keep_aspect) {
+            (Some(sx), Some(sy), true) => {
+                let s = sx.abs().max(sy.abs());
+                (s.copysign(sx), s.copysign(sy))
+            }
+            (Some(sx), Some(sy), false) => (sx, sy),
+            // Edge handles with Shift grow the other axis about its middle.
+            (Some(s), None, true) => (s, s.abs()),
+            (None, Some(s), true) => (s.abs(), s),
+            (Some(s), None, false) => (s, 1.0),
+            (None, Some(s), false) => (1.0, s),
+            (None, None, _) => (1.0, 1.0),
+        };
+        let anchor = Point::new(
+            if sx.is_none() && keep_aspect { frame.width * 0.5 } else { anchor.x },
+            if sy.is_none() && keep_aspect { frame.height * 0.5 } else { anchor.y },
+        );
+        Self { anchor, scale_x, scale_y }
+    }
+
+    fn apply(&self, local: Point) -> Point {
+        Point::new(
+            self.anchor.x + (local.x - self.anchor.x) * self.scale_x,
+            self.anchor.y + (local.y - self.anchor.y) * self.scale_y,
+        )
+    }
+
+    /// `shape` resized with `frame`.
+    ///
+    /// Shapes turned relative to the frame cannot shear, so their centre
+    /// follows the resize exactly and their box is stretched by how much
+    /// the resize lengthens each of its own axes. Flips become a mirrored
+    /// rotation and mirrored local geometry.
+    pub fn apply_to(&self, frame: &Transform, shape: &Shape) -> Shape {
+        let t = &shape.transform;
+        let relative = t.rotation - frame.rotation;
+        let (sin, cos) = relative.sin_cos();
+        let (sx, sy) = (self.scale_x, self.scale_y);
+        let width = t.width * ((sx * cos).powi(2) + (sy * sin).powi(2)).sqrt();
+        let height = t.height * ((sx * sin).powi(2) + (sy * cos).powi(2)).sqrt();
+        let (flip_x, flip_y) = (sx < 0.0, sy < 0.0);
+        let relative = if flip_x != flip_y { -relative } else { relative };
+
+        let center = frame.to_world(self.apply(frame.to_local(t.center())));
+        let mut resized = shape.clone();
+        resized.transform = Transform {
+            x: center.x - width * 0.5,
+            y: center.y - height * 0.5,
+            width,
+            height,
+            rotation: frame.rotation + relative,
+        };
+        let ratio = |new: f32, old: f32| if old.abs() < f32::EPSILON { 1.0 } else { new / old };
+        let (rx, ry) = (ratio(width, t.width), ratio(height, t.height));
+        let map = |p: Point| {
+            let x = p.x * rx;
+            let y = p.y * ry;
+            Point::new(if flip_x { width - x } else { x }, if flip_y { height - y } else { y })
+        };
+        match &mut resized.kind {
+            ShapeKind::Line { start, end } | ShapeKind::Arrow { start, end } => {
+                *start = map(*start);
+                *end = map(*end);
+            }
+            ShapeKind::Freehand { points } => {
+                for point in points.iter_mut() {
+                    let p = map(Point::new(point.x, point.y));
+                    *point = StrokePoint { x: p.x, y: p.y, ..*point };
+                }
+            }
+            ShapeKind::Connector { route, .. } => {
+                for point in route.iter_mut() {
+                    *point = map(*point);
+                }
+            }
+            _ => {}
+        }
+        resized
+    }
+}
+
+/// The rotation, in radians, from grabbing the rotation handle at `grab` to
+/// dragging it to `pointer` around `pivot`. `snap` rounds the frame's
+/// resulting angle to 15° steps.
+pub fn rotation_delta(frame: &Transform, pivot: Point, grab: Point, pointer: Point, snap: bool) -> f32 {
+    let angle = |p: Point| (p.y - pivot.y).atan2(p.x - pivot.x);
+    let delta = angle(pointer) - angle(grab);
+    if snap {
+        let target = ((frame.rotation + delta) / ROTATION_STEP).round() * ROTATION_STEP;
+        target - frame.rotation
+    } else {
+        delta
+    }
+}
+
+/// `shape` turned by `angle` around `pivot`.
+pub fn rotate_shape(shape: &Shape, pivot: Point, angle: f32) -> Shape {
+    let mut rotated = shape.clone();
+    let t = &mut rotated.transform;
+    let center = t.center().rotate_around(pivot, angle);
+    t.x = center.x - t.width * 0.5;
+    t.y = center.y - t.height * 0.5;
+    t.rotation += angle;
+    rotated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{LayerId, ShapeId, Style};
+    use crate::fractional_index::FractionalIndex;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn shape(kind: ShapeKind, transform: Transform) -> Shape {
+        Shape {
+            id: ShapeId(1),
+            kind,
+            transform,
+            style: Style::default(),
+            z_index: FractionalIndex::default(),
+            layer: LayerId::default(),
+            parent: None,
+        }
+    }
+
+    fn close(a: Point, b: Point) -> bool {
+        a.distance(b) < 1e-3
+    }
+
+    #[test]
+    fn test_handle_hits_are_screen_sized() {
+        let frame = Transform::new(0.0, 0.0, 100.0, 50.0);
+        assert_eq!(handle_at(&frame, Point::new(102.0, 52.0), 1.0), Some(Handle::SouthEast));
+        assert_eq!(handle_at(&frame, Point::new(50.0, -24.0), 1.0), Some(Handle::Rotate));
+        assert_eq!(handle_at(&frame, Point::new(50.0, 25.0), 1.0), None);
+        // Zoomed out 3x, the same screen distance covers 3x the world.
+        assert_eq!(handle_at(&frame, Point::new(115.0, 25.0), 3.0), Some(Handle::East));
+        assert_eq!(handle_at(&frame, Point::new(115.0, 25.0), 1.0), None);
+    }
+
+    #[test]
+    fn test_corner_resize_with_modifiers() {
+        let frame = Transform::new(0.0, 0.0, 100.0, 50.0);
+        let plain = Resize::from_drag(&frame, Handle::SouthEast, Point::new(200.0, 75.0), false, false);
+        assert_eq!((plain.anchor, plain.scale_x, plain.scale_y), (Point::ZERO, 2.0, 1.5));
+
+        let locked = Resize::from_drag(&frame, Handle::SouthEast, Point::new(200.0, 75.0), true, false);
+        assert_eq!((locked.scale_x, locked.scale_y), (2.0, 2.0));
+
+        let centered = Resize::from_drag(&frame, Handle::East, Point::new(150.0, 25.0), false, true);
+        assert_eq!((centered.anchor, centered.scale_x, centered.scale_y), (Point::new(50.0, 25.0), 2.0, 1.0));
+
+        let r = shape(ShapeKind::Rectangle, frame);
+        let out = centered.apply_to(&frame, &r).transform;
+        assert_eq!((out.x, out.y, out.width, out.height), (-50.0, 0.0, 200.0, 50.0));
+    }
+
+    #[test]
+    fn test_resize_rotated_shape_in_its_own_axes() {
+        let mut t = Transform::new(0.0, 0.0, 100.0, 20.0);
+        t.rotation = FRAC_PI_2;
+        let r = shape(ShapeKind::Rectangle, t);
+        // The east handle of a quarter-turned frame points down in the world.
+        let east = Handle::East.position(&t, 1.0);
+        assert!(close(east, Point::new(50.0, 60.0)));
+        let resize = Resize::from_drag(&t, Handle::East, Point::new(50.0, 110.0), false, false);
+        let out = resize.apply_to(&t, &r);
+        assert!((out.transform.width - 150.0).abs() < 1e-3);
+        assert_eq!(out.transform.height, 20.0);
+        assert_eq!(out.transform.rotation, FRAC_PI_2);
+        // The opposite (west) edge stays put.
+        assert!(close(out.transform.to_world(Point::new(0.0, 10.0)), t.to_world(Point::new(0.0, 10.0))));
+    }
+
+    #[test]
+    fn test_multi_selection_resize_and_flip() {
+        let frame = Transform::new(0.0, 0.0, 100.0, 100.0);
+        let line = shape(
+            ShapeKind::Line {
+                start: Point::new(0.0, 0.0),
+                end: Point::new(50.0, 50.0),
+            },
+            Transform::new(50.0, 50.0, 50.0, 50.0),
+        );
+        // Drag the east edge past the west one: a horizontal flip.
+        let resize = Resize::from_drag(&frame, Handle::East, Point::new(-100.0, 50.0), false, false);
+        assert_eq!(resize.scale_x, -1.0);
+        let out = resize.apply_to(&frame, &line);
+        let points = out.path_points().unwrap();
+        assert!(close(points[0], Point::new(-50.0, 50.0)));
+        assert!(close(points[1], Point::new(-100.0, 100.0)));
+    }
+
+    #[test]
+    fn test_rotation_snaps_and_orbits() {
+        let frame = Transform::new(0.0, 0.0, 100.0, 100.0);
+        let pivot = frame.center();
+        let grab = Point::new(50.0, -20.0);
+        let delta = rotation_delta(&frame, pivot, grab, Point::new(121.0, 48.0), true);
+        assert!((delta - FRAC_PI_2).abs() < 1e-5);
+
+        let r = shape(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 10.0, 10.0));
+        let out = rotate_shape(&r, pivot, FRAC_PI_2);
+        assert!(close(out.transform.center(), Point::new(95.0, 5.0)));
+        assert_eq!(out.transform.rotation, FRAC_PI_2);
+    }
+}
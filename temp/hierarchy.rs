@@ -0,0 +1,372 @@
+//! Groups and frames: shapes that contain other shapes.
+//!
+//! Containment is stored as each shape's `parent`. Editing a container
+//! through the document carries its contents along: [`propagate`] runs on
+//! every batch of edits before the history records it, so the follow-up
+//! changes land in the same undo step.
+
+use std::collections::HashSet;
+
+use crate::document::{Document, Operation, Shape, ShapeId, ShapeKind, Style, Transform};
+use crate::fractional_index::FractionalIndex;
+use crate::geometry::{Point, Rect};
+use crate::handles::{rotate_shape, Resize};
+use crate::spatial::SpatialIndex;
+
+/// Applies the consequences of `operations`, a batch of edits:
+///
+/// - contents follow their group's transform and their frame's position,
+/// - deleting a container deletes its contents,
+/// - groups refit around children edited on their own, and groups left
+///   empty are deleted.
+///
+/// Shapes edited directly in the same batch are left alone, so a tool may
+/// transform a group and its contents together.
+pub fn propagate(document: &mut Document, operations: &[Operation]) {
+    let edited: HashSet<ShapeId> = operations.iter().filter_map(Operation::id).collect();
+    let mut moved = HashSet::new();
+    let mut dirty = Vec::new();
+    for operation in operations {
+        match operation {
+            Operation::Update { before, after } if after.is_container() && before.transform != after.transform => {
+                moved.insert(after.id);
+                let mut after = after.clone();
+                if matches!(after.kind, ShapeKind::Frame { .. }) && after.transform.rotation != 0.0 {
+                    // Frames stay axis-aligned so they can clip with a scissor.
+                    after.transform.rotation = 0.0;
+                    let _ = document.update(after.id, |shape| shape.transform.rotation = 0.0);
+                }
+                for id in document.descendants(after.id) {
+                    if edited.contains(&id) {
+                        continue;
+                    }
+                    if let Some(followed) = document.get(id).map(|shape| follow(before, &after, shape)) {
+                        let _ = document.update(id, |shape| *shape = followed);
+                    }
+                }
+            }
+            Operation::Delete(shape) if shape.is_container() => {
+                for id in document.descendants(shape.id) {
+                    let _ = document.delete(id);
+                }
+            }
+            _ => {}
+        }
+        match operation {
+            Operation::Insert(shape) | Operation::Delete(shape) => dirty.extend(shape.parent),
+            Operation::Update { before, after } => dirty.extend(before.parent.into_iter().chain(after.parent)),
+            Operation::Layers { .. } => {}
+        }
+    }
+
+    dirty.retain(|id| !moved.contains(id));
+    // Deepest first, so a group refits after the groups inside it.
+    dirty.sort_by_cached_key(|id| (document.ancestors(*id).len(), *id));
+    dirty.dedup();
+    while let Some(id) = dirty.pop() {
+        let Some(group) = document.get(id).filter(|shape| shape.kind == ShapeKind::Group) else {
+            continue;
+        };
+        let parent = group.parent;
+        if document.children(id).is_empty() {
+            let _ = document.delete(id);
+        } else {
+            fit_group(document, id);
+        }
+        if let Some(parent) = parent.filter(|p| !moved.contains(p) && !dirty.contains(p)) {
+            dirty.push(parent);
+        }
+    }
+}
+
+/// `shape` carried along with its container's edit from `before` to
+/// `after`. Groups scale and rotate their contents; frames only move them.
+fn follow(before: &Shape, after: &Shape, shape: &Shape) -> Shape {
+    let (b, a) = (&before.transform, &after.transform);
+    if matches!(after.kind, ShapeKind::Frame { .. }) {
+        let mut moved = shape.clone();
+        moved.transform.x += a.x - b.x;
+        moved.transform.y += a.y - b.y;
+        return moved;
+    }
+    // Scale in the old box's frame from its top-left corner, then turn and
+    // move that scaled box onto the new one.
+    let ratio = |new: f32, old: f32| if old.abs() < f32::EPSILON { 1.0 } else { new / old };
+    let resize = Resize {
+        anchor: Point::ZERO,
+        scale_x: ratio(a.width, b.width),
+        scale_y: ratio(a.height, b.height),
+    };
+    let pivot = b.to_world(Point::new(a.width * 0.5, a.height * 0.5));
+    let mut followed = rotate_shape(&resize.apply_to(b, shape), pivot, a.rotation - b.rotation);
+    let offset = a.center() - pivot;
+    followed.transform.x += offset.x;
+    followed.transform.y += offset.y;
+    followed
+}
+
+/// Shrinks or grows group `id` around its children, keeping its rotation.
+pub fn fit_group(document: &mut Document, id: ShapeId) {
+    let Some(t) = document.get(id).map(|group| group.transform) else {
+        return;
+    };
+    let corners = document
+        .children(id)
+        .into_iter()
+        .filter_map(|child| document.get(child))
+        .flat_map(|child| child.transform.corners())
+        .map(|corner| t.to_local(corner));
+    let Some(local) = Rect::from_points(corners) else {
+        return;
+    };
+    let center = t.to_world(local.center());
+    let fitted = Transform {
+        x: center.x - local.width() * 0.5,
+        y: center.y - local.height() * 0.5,
+        width: local.width(),
+        height: local.height(),
+        rotation: t.rotation,
+    };
+    if fitted != t {
+        let _ = document.update(id, |group| group.transform = fitted);
+    }
+}
+
+/// Puts `ids` into a new group, stacked where the topmost of them was.
+/// Shapes already inside another of `ids` stay where they are. The group
+/// goes into the shapes' container if they share one.
+pub fn group(document: &mut Document, ids: &[ShapeId]) -> Option<ShapeId> {
+    let members: Vec<Shape> = ids
+        .iter()
+        .filter(|id| !ids.iter().any(|other| document.is_descendant(**id, *other)))
+        .filter_map(|id| document.get(*id).cloned())
+        .collect();
+    let first = members.first()?;
+    let parent = first.parent.filter(|p| members.iter().all(|m| m.parent == Some(*p)));
+    let z_index = members.iter().map(|m| m.z_index.clone()).max().unwrap_or_default();
+    let layer = document.layer_of(first).id;
+    let bounds = members
+        .iter()
+        .map(|m| m.transform.bounds())
+        .reduce(|a, b| a.union(&b))?;
+    let transform = Transform::new(bounds.min_x, bounds.min_y, bounds.width(), bounds.height());
+    let id = document.insert(ShapeKind::Group, transform, Style::default());
+    let _ = document.update(id, |group| {
+        group.z_index = z_index;
+        group.layer = layer;
+        group.parent = parent;
+    });
+    for member in &members {
+        let _ = document.update(member.id, |shape| shape.parent = Some(id));
+    }
+    Some(id)
+}
+
+/// Dissolves group `id`, handing its children to its container. Returns
+/// the children, or nothing if `id` is not a group.
+pub fn ungroup(document: &mut Document, id: ShapeId) -> Vec<ShapeId> {
+    let Some(group) = document.get(id).filter(|s| s.kind == ShapeKind::Group) else {
+        return Vec::new();
+    };
+    let (parent, layer) = (group.parent, document.layer_of(group).id);
+    let children = document.children(id);
+    for child in &children {
+        let _ = document.update(*child, |shape| {
+            shape.parent = parent;
+            shape.layer = layer;
+        });
+    }
+    let _ = document.delete(id);
+    children
+}
+
+/// Moves top-level shapes on the same layer lying entirely inside frame `id`
+/// into it.
+pub fn adopt_contained(document: &mut Document, index: &SpatialIndex, id: ShapeId) {
+    let Some((area, layer)) = document.get(id).map(|frame| (frame.transform.bounds(), frame.layer)) else {
+        return;
+    };
+    let inside: Vec<ShapeId> = index
+        .query_contained(&area)
+        .into_iter()
+        .filter(|other| *other != id)
+        .filter(|other| document.get(*other).is_some_and(|s| s.parent.is_none() && s.layer == layer))
+        .collect();
+    for other in inside {
+        let _ = document.update(other, |shape| shape.parent = Some(id));
+    }
+}
+
+/// Re-homes shapes dropped after a move: each goes into the topmost frame
+/// under its centre, or out to the top level when dropped outside its
+/// frame. Shapes inside groups stay put, and frames that cannot be hit take
+/// nothing. A re-homed shape lands on top of its new siblings.
+pub fn drop_into_frames(document: &mut Document, ids: &[ShapeId]) {
+    for &id in ids {
+        let Some(shape) = document.get(id) else {
+            continue;
+        };
+        let in_group = shape
+            .parent
+            .and_then(|p| document.get(p))
+            .is_some_and(|p| p.kind == ShapeKind::Group);
+        if in_group {
+            continue;
+        }
+        let center = shape.transform.center();
+        let layer = document.layer_of(shape).id;
+        let target = document
+            .shapes()
+            .filter(|frame| matches!(frame.kind, ShapeKind::Frame { .. }) && document.is_hittable(frame))
+            .filter(|frame| frame.id != id && !document.is_descendant(frame.id, id))
+            .filter(|frame| frame.transform.bounds().contains_point(center))
+            .max_by_key(|frame| document.paint_key(frame))
+            .map(|frame| frame.id);
+        if target == shape.parent {
+            continue;
+        }
+        let z_index = document
+            .shapes()
+            .filter(|s| s.parent == target)
+            .map(|s| &s.z_index)
+            .max()
+            .map_or_else(FractionalIndex::default, FractionalIndex::after);
+        let _ = document.update(id, |shape| {
+            shape.parent = target;
+            shape.z_index = z_index;
+            shape.layer = layer;
+        });
+    }
+}
+
+/// What clicking `hit` selects: its outermost group, unless that group has
+/// been entered, in which case the next group down, and so on. `entered` is
+/// the innermost entered group; its ancestors count as entered too. Frames
+/// never capture clicks.
+pub fn selection_target(document: &Document, hit: ShapeId, entered: Option<ShapeId>) -> ShapeId {
+    let open: Vec<ShapeId> = entered
+        .map(|e| document.ancestors(e).into_iter().chain([e]).collect())
+        .unwrap_or_default();
+    document
+        .ancestors(hit)
+        .into_iter()
+        .rev()
+        .find(|id| !open.contains(id) && document.get(*id).is_some_and(|s| s.kind == ShapeKind::Group))
+        .unwrap_or(hit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(document: &mut Document, x: f32, y: f32) -> ShapeId {
+        document.insert(ShapeKind::Rectangle, Transform::new(x, y, 10.0, 10.0), Style::default())
+    }
+
+    /// Propagates the pending edits and drops the follow-up ones, as
+    /// [`crate::editor::Editor::sync_index`] records them.
+    fn settle(document: &mut Document) {
+        let operations = document.take_operations();
+        propagate(document, &operations);
+        document.take_operations();
+    }
+
+    fn close(a: Transform, b: Transform) -> bool {
+        [a.x - b.x, a.y - b.y, a.width - b.width, a.height - b.height, a.rotation - b.rotation]
+            .iter()
+            .all(|d| d.abs() < 1e-3)
+    }
+
+    #[test]
+    fn test_group_moves_scales_and_refits() {
+        let mut document = Document::new();
+        let a = rect(&mut document, 0.0, 0.0);
+        let b = rect(&mut document, 30.0, 10.0);
+        let g = group(&mut document, &[a, b]).unwrap();
+        settle(&mut document);
+        assert_eq!(document.get(g).unwrap().transform, Transform::new(0.0, 0.0, 40.0, 20.0));
+        assert_eq!(document.children(g), vec![a, b]);
+
+        // Doubling the group's size from its top-left doubles the contents.
+        document.update(g, |s| s.transform = Transform::new(100.0, 0.0, 80.0, 40.0)).unwrap();
+        settle(&mut document);
+        assert!(close(document.get(b).unwrap().transform, Transform::new(160.0, 20.0, 20.0, 20.0)));
+
+        // Editing a child on its own refits the group around it.
+        document.update(a, |s| s.transform.x = 90.0).unwrap();
+        settle(&mut document);
+        assert!(close(document.get(g).unwrap().transform, Transform::new(90.0, 0.0, 90.0, 40.0)));
+    }
+
+    #[test]
+    fn test_rotating_a_group_turns_its_contents_around_its_centre() {
+        let mut document = Document::new();
+        let a = rect(&mut document, 0.0, 0.0);
+        let b = rect(&mut document, 30.0, 0.0);
+        let g = group(&mut document, &[a, b]).unwrap();
+        document.take_operations();
+        let before = document.get(g).unwrap().clone();
+        let turned = rotate_shape(&before, before.transform.center(), std::f32::consts::FRAC_PI_2);
+        document.update(g, |s| *s = turned).unwrap();
+        settle(&mut document);
+        let moved = document.get(b).unwrap().transform;
+        assert!((moved.center().x - 20.0).abs() < 1e-3);
+        assert!((moved.center().distance(Point::new(20.0, 5.0)) - 15.0).abs() < 1e-3);
+        assert!((moved.rotation - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+        // The group keeps its turned box rather than refitting.
+        assert_eq!(document.get(g).unwrap().transform.rotation, std::f32::consts::FRAC_PI_2);
+    }
+
+    #[test]
+    fn test_frames_move_contents_and_deletes_cascade() {
+        let mut document = Document::new();
+        let frame = document.insert(ShapeKind::Frame { name: "A".to_string() }, Transform::new(0.0, 0.0, 100.0, 100.0), Style::default());
+        let inner = rect(&mut document, 10.0, 10.0);
+        let outside = rect(&mut document, 200.0, 10.0);
+        let mut index = SpatialIndex::new();
+        let events = document.drain_events();
+        index.apply_events(&document, &events);
+        adopt_contained(&mut document, &index, frame);
+        assert_eq!(document.children(frame), vec![inner]);
+        assert_eq!(document.clip(inner), Some(Rect::new(0.0, 0.0, 100.0, 100.0)));
+        assert_eq!(document.clip(outside), None);
+
+        // Resizing a frame leaves its contents where they are.
+        document.take_operations();
+        document.update(frame, |s| s.transform.width = 50.0).unwrap();
+        settle(&mut document);
+        assert_eq!(document.get(inner).unwrap().transform.x, 10.0);
+        document.update(frame, |s| s.transform.x = 5.0).unwrap();
+        settle(&mut document);
+        assert_eq!(document.get(inner).unwrap().transform.x, 15.0);
+
+        document.delete(frame).unwrap();
+        settle(&mut document);
+        assert!(!document.contains(inner));
+        assert!(document.contains(outside));
+    }
+
+    #[test]
+    fn test_ungroup_and_empty_groups() {
+        let mut document = Document::new();
+        let a = rect(&mut document, 0.0, 0.0);
+        let b = rect(&mut document, 30.0, 0.0);
+        let c = rect(&mut document, 60.0, 0.0);
+        let inner = group(&mut document, &[a, b]).unwrap();
+        let outer = group(&mut document, &[inner, c]).unwrap();
+        settle(&mut document);
+        assert_eq!(selection_target(&document, a, None), outer);
+        assert_eq!(selection_target(&document, a, Some(outer)), inner);
+        assert_eq!(selection_target(&document, a, Some(inner)), a);
+
+        assert_eq!(ungroup(&mut document, outer), vec![inner, c]);
+        settle(&mut document);
+        assert_eq!(document.get(inner).unwrap().parent, None);
+        assert!(document.contains(a));
+
+        document.delete(a).unwrap();
+        document.delete(b).unwrap();
+        settle(&mut document);
+        assert!(!document.contains(inner));
+    }
+}
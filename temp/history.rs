@@ -0,0 +1,407 @@
+//! Undo and redo built on the document's operation journal.
+//!
+//! Operations are recorded into transactions; everything between the
+//! outermost [`History::begin`] and [`History::commit`] becomes one undo
+//! step, so a drag made of many moves is undone at once.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::document::{Document, Layer, Operation, Shape, ShapeId};
+use crate::state::State;
+
+/// Undo steps kept by [`History::default`].
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// One undo step: document operations plus, optionally, the camera move.
+#[derive(Debug, Clone, PartialEq)]
+struct Transaction {
+    operations: Vec<Operation>,
+    camera: Option<(State, State)>,
+}
+
+/// Per-shape net change inside an open transaction.
+#[derive(Debug, Clone)]
+struct Change {
+    before: Option<Shape>,
+    after: Option<Shape>,
+}
+
+#[derive(Debug, Clone)]
+pub struct History {
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+    limit: usize,
+    /// Whether camera moves made inside a transaction are undoable. Off by
+    /// default, so panning and zooming stay out of the undo stack.
+    pub record_camera: bool,
+    depth: usize,
+    camera_before: Option<State>,
+    /// Changes in the order their shapes were first touched, which is the
+    /// order they replay in.
+    pending: Vec<Change>,
+    /// Where each shape's change sits in `pending`.
+    pending_index: HashMap<ShapeId, usize>,
+    /// Layers before and after the open transaction, if it changed them.
+    pending_layers: Option<(Vec<Layer>, Vec<Layer>)>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl History {
+    /// Keeps at most `limit` undo steps, dropping the oldest first.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+            record_camera: false,
+            depth: 0,
+            camera_before: None,
+            pending: Vec::new(),
+            pending_index: HashMap::new(),
+            pending_layers: None,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.depth > 0
+    }
+
+    /// Opens a transaction. Transactions nest; only the outermost commit
+    /// creates an undo step. `camera` is the camera as the step starts.
+    pub fn begin(&mut self, camera: &State) {
+        if self.depth == 0 {
+            self.camera_before = Some(camera.clone());
+        }
+        self.depth += 1;
+    }
+
+    /// Closes a transaction. Returns true if this made a new undo step, which
+    /// only happens at the outermost level and if anything actually changed.
+    pub fn commit(&mut self, camera: &State) -> bool {
+        match self.depth {
+            0 => return false,
+            1 => self.depth = 0,
+            _ => {
+                self.depth -= 1;
+                return false;
+            }
+        }
+
+        let mut operations: Vec<Operation> = self
+            .pending_layers
+            .take()
+            .filter(|(before, after)| before != after)
+            .map(|(before, after)| Operation::Layers { before, after })
+            .into_iter()
+            .collect();
+        self.pending_index.clear();
+        operations.extend(std::mem::take(&mut self.pending).into_iter().filter_map(|change| {
+            match (change.before, change.after) {
+                (None, Some(after)) => Some(Operation::Insert(after)),
+                (Some(before), None) => Some(Operation::Delete(before)),
+                (Some(before), Some(after)) if before != after => Some(Operation::Update {
+                    before: Box::new(before),
+                    after: Box::new(after),
+                }),
+                _ => None,
+            }
+        }));
+        let camera = self
+            .camera_before
+            .take()
+            .filter(|before| self.record_camera && before != camera)
+            .map(|before| (before, camera.clone()));
+        if operations.is_empty() && camera.is_none() {
+            return false;
+        }
+
+        self.undo.push_back(Transaction { operations, camera });
+        self.redo.clear();
+        self.trim();
+        true
+    }
+
+    /// Adds journaled operations to the open transaction, or records them as
+    /// an undo step of their own if none is open.
+    pub fn record(&mut self, operations: Vec<Operation>, camera: &State) {
+        if operations.is_empty() {
+            return;
+        }
+        let standalone = self.depth == 0;
+        if standalone {
+            self.begin(camera);
+        }
+        for operation in operations {
+            self.merge(operation);
+        }
+        if standalone {
+            self.commit(camera);
+        }
+    }
+
+    /// Folds `operation` into the pending change for its shape, so a shape
+    /// touched many times keeps only its first and last state. Layer changes
+    /// fold the same way.
+    fn merge(&mut self, operation: Operation) {
+        let (id, before, after) = match operation {
+            Operation::Insert(shape) => (shape.id, None, Some(shape)),
+            Operation::Update { before, after } => (after.id, Some(*before), Some(*after)),
+            Operation::Delete(shape) => (shape.id, Some(shape), None),
+            Operation::Layers { before, after } => {
+                match &mut self.pending_layers {
+                    Some((_, pending)) => *pending = after,
+                    None => self.pending_layers = Some((before, after)),
+                }
+                return;
+            }
+        };
+        match self.pending_index.get(&id) {
+            Some(&i) => self.pending[i].after = after,
+            None => {
+                self.pending_index.insert(id, self.pending.len());
+                self.pending.push(Change { before, after });
+            }
+        }
+    }
+
+    /// Reverts the last undo step. Returns false if there was none. If an
+    /// operation fails, the step is rolled back and stays on the undo stack.
+    pub fn undo(&mut self, document: &mut Document, camera: &mut State) -> Result<bool, String> {
+        let Some(transaction) = self.undo.pop_back() else {
+            return Ok(false);
+        };
+        let inverse: Vec<Operation> = transaction.operations.iter().rev().map(Operation::inverse).collect();
+        let result = replay(document, &inverse);
+        // Replaying must not be recorded as a fresh edit.
+        document.take_operations();
+        if let Err(e) = result {
+            self.undo.push_back(transaction);
+            return Err(e);
+        }
+        if let Some((before, _)) = &transaction.camera {
+            *camera = before.clone();
+        }
+        self.redo.push(transaction);
+        Ok(true)
+    }
+
+    /// Re-applies the last undone step. Returns false if there was none. If
+    /// an operation fails, the step is rolled back and stays on the redo stack.
+    pub fn redo(&mut self, document: &mut Document, camera: &mut State) -> Result<bool, String> {
+        let Some(transaction) = self.redo.pop() else {
+            return Ok(false);
+        };
+        let result = replay(document, &transaction.operations);
+        document.take_operations();
+        if let Err(e) = result {
+            self.redo.push(transaction);
+            return Err(e);
+        }
+        if let Some((_, after)) = &transaction.camera {
+            *camera = after.clone();
+        }
+        self.undo.push_back(transaction);
+        self.trim();
+        Ok(true)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn trim(&mut self) {
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+}
+
+/// Applies `operations` in order, or none of them: on failure, the ones
+/// already applied are reverted before the error is returned.
+fn replay(document: &mut Document, operations: &[Operation]) -> Result<(), String> {
+    for (i, operation) in operations.iter().enumerate() {
+        if let Err(e) = document.apply(operation) {
+            for applied in operations[..i].iter().rev() {
+                let _ = document.apply(&applied.inverse());
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{ShapeKind, Style, Transform};
+
+    fn rect(document: &mut Document) -> ShapeId {
+        document.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 10.0, 10.0), Style::default())
+    }
+
+    #[test]
+    fn test_transaction_collapses_moves_into_one_step() {
+        let mut document = Document::new();
+        let mut camera = State::default();
+        let mut history = History::default();
+        let id = rect(&mut document);
+        history.record(document.take_operations(), &camera);
+
+        history.begin(&camera);
+        for x in 1..=10 {
+            document.update(id, |s| s.transform.x = x as f32).unwrap();
+            history.record(document.take_operations(), &camera);
+        }
+        assert!(history.commit(&camera));
+
+        assert!(history.undo(&mut document, &mut camera).unwrap());
+        assert_eq!(document.get(id).unwrap().transform.x, 0.0);
+        assert!(history.undo(&mut document, &mut camera).unwrap());
+        assert!(document.is_empty());
+        assert!(!history.can_undo());
+
+        history.redo(&mut document, &mut camera).unwrap();
+        history.redo(&mut document, &mut camera).unwrap();
+        assert_eq!(document.get(id).unwrap().transform.x, 10.0);
+        assert!(!history.can_redo());
+        assert!(document.take_operations().is_empty());
+    }
+
+    #[test]
+    fn test_net_no_op_and_nested_transactions() {
+        let mut document = Document::new();
+        let camera = State::default();
+        let mut history = History::default();
+        history.begin(&camera);
+        history.begin(&camera);
+        let id = rect(&mut document);
+        document.delete(id).unwrap();
+        history.record(document.take_operations(), &camera);
+        assert!(!history.commit(&camera));
+        assert!(history.in_transaction());
+        assert!(!history.commit(&camera));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_limit_and_redo_cleared_by_new_edit() {
+        let mut document = Document::new();
+        let mut camera = State::default();
+        let mut history = History::new(3);
+        for _ in 0..5 {
+            rect(&mut document);
+            history.record(document.take_operations(), &camera);
+        }
+        for _ in 0..3 {
+            assert!(history.undo(&mut document, &mut camera).unwrap());
+        }
+        assert!(!history.can_undo());
+        assert_eq!(document.len(), 2);
+
+        rect(&mut document);
+        history.record(document.take_operations(), &camera);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_failed_undo_rolls_back() {
+        let mut document = Document::new();
+        let mut camera = State::default();
+        let mut history = History::default();
+        let a = rect(&mut document);
+        let b = rect(&mut document);
+        history.record(document.take_operations(), &camera);
+
+        // Deleted behind the history's back, so undoing its insert fails
+        // after b is already gone.
+        document.delete(a).unwrap();
+        document.take_operations();
+        assert!(history.undo(&mut document, &mut camera).is_err());
+        assert!(document.get(b).is_some());
+        assert!(history.can_undo() && !history.can_redo());
+        assert!(document.take_operations().is_empty());
+    }
+
+    #[test]
+    fn test_camera_is_optional() {
+        let mut document = Document::new();
+        let mut camera = State::default();
+        let mut history = History::default();
+        history.begin(&camera);
+        camera.zoom = 3.0;
+        assert!(!history.commit(&camera));
+
+        history.record_camera = true;
+        history.begin(&camera);
+        camera.zoom = 2.0;
+        assert!(history.commit(&camera));
+        history.undo(&mut document, &mut camera).unwrap();
+        assert_eq!(camera.zoom, 3.0);
+    }
+
+    #[test]
+    fn test_editor_gestures_and_shortcuts() {
+        use crate::editor::Editor;
+        use crate::geometry::Point;
+        use crate::tools::{InputEvent, Modifiers, PointerInput, ToolKind};
+
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.set_tool(ToolKind::Rectangle);
+        let at = |x, y| PointerInput::new(Point::new(x, y));
+        editor.handle_input(InputEvent::PointerDown(at(400.0, 300.0)));
+        for x in [410.0, 420.0, 430.0] {
+            editor.handle_input(InputEvent::PointerMove(at(x, 340.0)));
+        }
+        editor.handle_input(InputEvent::PointerUp(at(430.0, 340.0)));
+        assert!(editor.can_undo());
+
+        let shortcut = |shift| InputEvent::KeyDown {
+            key: if shift { "Z" } else { "z" }.to_string(),
+            modifiers: Modifiers {
+                ctrl: true,
+                shift,
+                ..Modifiers::default()
+            },
+            repeat: false,
+        };
+        editor.handle_input(shortcut(false));
+        assert!(editor.document.is_empty());
+        assert!(!editor.can_undo());
+        editor.handle_input(shortcut(true));
+        assert_eq!(editor.document.len(), 1);
+        assert!(editor.can_undo() && !editor.can_redo());
+        assert_eq!(editor.take_error(), None);
+
+        // A step that no longer applies is kept, and the failure reported.
+        let id = editor.document.shapes().next().unwrap().id;
+        editor.document.delete(id).unwrap();
+        editor.document.take_operations();
+        editor.handle_input(shortcut(false));
+        assert!(editor.can_undo());
+        assert!(editor.take_error().is_some_and(|e| e.starts_with("Cannot undo")));
+        assert_eq!(editor.take_error(), None);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::document::{Shape, ShapeKind};
+use crate::geometry::{point_in_polygon, segments_intersect, Point, Rect};
+
+/// Whether `point` (world space) touches `shape`'s painted geometry, allowing
+/// `tolerance` world units of slack.
+///
+/// Filled closed shapes are hit anywhere inside; unfilled ones and paths only
+/// near their stroke. Groups are never hit themselves, only their contents.
+pub fn hit_test(shape: &Shape, point: Point, tolerance: f32) -> bool {
+    if !shape.bounds().expand(tolerance).contains_point(point) {
+        return false;
+    }
+    let t = &shape.transform;
+    let half_stroke = shape.style.stroke_width * 0.5;
+    let reach = half_stroke + tolerance;
+    match &shape.kind {
+        ShapeKind::Rectangle | ShapeKind::Frame { .. } => {
+            let local = t.to_local(point);
+            let (w, h) = (t.width.abs(), t.height.abs());
+            let inside = local.x >= 0.0 && local.x <= w && local.y >= 0.0 && local.y <= h;
+            if inside && shape.style.fill.is_some() {
+                return true;
+            }
+            // Distance to the border, from inside or outside.
+            let dx = (-local.x).max(local.x - w);
+            let dy = (-local.y).max(local.y - h);
+            let outside = Point::new(dx.max(0.0), dy.max(0.0)).length();
+            let distance = if inside { -dx.max(dy) } else { outside };
+            distance <= reach
+        }
+        ShapeKind::Ellipse => {
+            let local = t.to_local(point) - Point::new(t.width * 0.5, t.height * 0.5);
+            let (rx, ry) = ((t.width * 0.5).abs().max(1e-4), (t.height * 0.5).abs().max(1e-4));
+            let k = Point::new(local.x / rx, local.y / ry).length();
+            if k <= 1.0 && shape.style.fill.is_some() {
+                return true;
+            }
+            // Approximate distance to the outline, exact on circles.
+            ((k - 1.0) * rx.min(ry)).abs() <= reach
+        }
+        ShapeKind::Line { .. } | ShapeKind::Arrow { .. } | ShapeKind::Connector { .. } | ShapeKind::Freehand { .. } => {
+            let points = shape.path_points().unwrap_or_default();
+            match points.as_slice() {
+                [] => false,
+                [only] => only.distance(point) <= reach,
+                _ => points
+                    .windows(2)
+                    .any(|w| point.distance_to_segment(w[0], w[1]) <= reach),
+            }
+        }
+        ShapeKind::Text { .. } | ShapeKind::Image { .. } => {
+            let local = t.to_local(point);
+            local.x >= -tolerance
+                && local.x <= t.width.abs() + tolerance
+                && local.y >= -tolerance
+                && local.y <= t.height.abs() + tolerance
+        }
+        ShapeKind::Group => false,
+    }
+}
+
+/// World-space points tracing a shape's geometry, and whether they form a
+/// closed region. Ellipses are sampled within `tolerance`.
+fn geometry(shape: &Shape, tolerance: f32) -> (Vec<Point>, bool) {
+    match &shape.kind {
+        ShapeKind::Rectangle | ShapeKind::Ellipse | ShapeKind::Frame { .. } => (shape.outline(tolerance).unwrap_or_default(), true),
+        ShapeKind::Line { .. } | ShapeKind::Arrow { .. } | ShapeKind::Connector { .. } | ShapeKind::Freehand { .. } => {
+            (shape.path_points().unwrap_or_default(), false)
+        }
+        ShapeKind::Text { .. } | ShapeKind::Image { .. } => (shape.transform.corners().to_vec(), true),
+        ShapeKind::Group => (Vec::new(), false),
+    }
+}
+
+/// Whether any painted part of `shape` touches `rect`, as for a "touching" marquee.
+pub fn intersects_rect(shape: &Shape, rect: &Rect, tolerance: f32) -> bool {
+    let bounds = shape.bounds();
+    if !bounds.intersects(rect) {
+        return false;
+    }
+    if rect.contains_rect(&bounds) {
+        return true;
+    }
+    let (points, closed) = geometry(shape, tolerance);
+    let reach = rect.expand(shape.style.stroke_width * 0.5);
+    let touches_edge = match points.as_slice() {
+        [only] => reach.contains_point(*only),
+        _ => edges(&points, closed).any(|(a, b)| reach.intersects_segment(a, b)),
+    };
+    if touches_edge {
+        return true;
+    }
+    // No edge crosses the rect, so it is either wholly inside the shape or
+    // wholly outside; only filled regions count as touched from inside.
+    is_filled(shape, closed) && point_in_polygon(rect.center(), &points)
+}
+
+/// The segments joining `points`, back to the first if `closed`.
+fn edges(points: &[Point], closed: bool) -> impl Iterator<Item = (Point, Point)> + '_ {
+    let closing = (closed && points.len() > 2).then(|| (points[points.len() - 1], points[0]));
+    points.windows(2).map(|w| (w[0], w[1])).chain(closing)
+}
+
+fn is_filled(shape: &Shape, closed: bool) -> bool {
+    match shape.kind {
+        ShapeKind::Rectangle | ShapeKind::Ellipse | ShapeKind::Frame { .. } => shape.style.fill.is_some(),
+        _ => closed,
+    }
+}
+
+/// Whether any painted part of `shape` falls inside the closed `polygon`,
+/// as for a "touching" lasso. Sampled geometry is tested even-odd.
+pub fn intersects_polygon(shape: &Shape, polygon: &[Point], tolerance: f32) -> bool {
+    if !Rect::from_points(polygon.iter().copied()).is_some_and(|bounds| bounds.intersects(&shape.bounds())) {
+        return false;
+    }
+    let (points, closed) = geometry(shape, tolerance);
+    if points.iter().any(|p| point_in_polygon(*p, polygon)) || crosses(&points, closed, polygon) {
+        return true;
+    }
+    // A lasso drawn entirely inside a filled region still touches it.
+    is_filled(shape, closed) && point_in_polygon(polygon[0], &points)
+}
+
+/// Whether all of `shape`'s sampled geometry lies inside the closed `polygon`.
+pub fn contained_in_polygon(shape: &Shape, polygon: &[Point], tolerance: f32) -> bool {
+    let (points, closed) = geometry(shape, tolerance);
+    !points.is_empty() && points.iter().all(|p| point_in_polygon(*p, polygon)) && !crosses(&points, closed, polygon)
+}
+
+/// Whether any edge of the geometry crosses an edge of `polygon`; a concave
+/// lasso can cut between samples that are all inside it.
+fn crosses(points: &[Point], closed: bool, polygon: &[Point]) -> bool {
+    edges(points, closed).any(|(a, b)| edges(polygon, true).any(|(c, d)| segments_intersect(a, b, c, d)))
+}
+
+/// Whether all of `shape`'s painted geometry lies inside `rect`.
+pub fn contained_in_rect(shape: &Shape, rect: &Rect, tolerance: f32) -> bool {
+    let (points, _) = geometry(shape, tolerance);
+    Rect::from_points(points)
+        .is_some_and(|extent| rect.contains_rect(&extent.expand(shape.style.stroke_width * 0.5)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Color, Document, ShapeId, Style, Transform};
+
+    fn shape(doc: &Document, id: ShapeId) -> &Shape {
+        doc.get(id).unwrap()
+    }
+
+    #[test]
+    fn test_filled_and_outlined_rectangles() {
+        let mut doc = Document::new();
+        let t = Transform::new(0.0, 0.0, 100.0, 50.0);
+        let outlined = doc.insert(ShapeKind::Rectangle, t, Style::default());
+        let filled = doc.insert(
+            ShapeKind::Rectangle,
+            t,
+            Style {
+                fill: Some(Color::WHITE),
+                ..Style::default()
+            },
+        );
+
+        let center = Point::new(50.0, 25.0);
+        assert!(!hit_test(shape(&doc, outlined), center, 1.0));
+        assert!(hit_test(shape(&doc, filled), center, 1.0));
+        assert!(hit_test(shape(&doc, outlined), Point::new(50.0, 1.5), 1.0));
+        assert!(hit_test(shape(&doc, outlined), Point::new(-1.5, 25.0), 1.0));
+        assert!(!hit_test(shape(&doc, outlined), Point::new(-5.0, 25.0), 1.0));
+    }
+
+    #[test]
+    fn test_rotated_rectangle_uses_local_space() {
+        let mut doc = Document::new();
+        let mut t = Transform::new(0.0, 0.0, 100.0, 10.0);
+        t.rotation = std::f32::consts::FRAC_PI_2;
+        let style = Style {
+            fill: Some(Color::WHITE),
+            ..Style::default()
+        };
+        let id = doc.insert(ShapeKind::Rectangle, t, style);
+        // Rotated a quarter turn around (50, 5), the bar now runs vertically.
+        assert!(hit_test(shape(&doc, id), Point::new(50.0, 40.0), 0.0));
+        assert!(!hit_test(shape(&doc, id), Point::new(90.0, 5.0), 0.0));
+    }
+
+    #[test]
+    fn test_ellipse_and_paths() {
+        let mut doc = Document::new();
+        let circle = doc.insert(
+            ShapeKind::Ellipse,
+            Transform::new(0.0, 0.0, 20.0, 20.0),
+            Style::default(),
+        );
+        assert!(hit_test(shape(&doc, circle), Point::new(10.0, 0.5), 0.0));
+        assert!(!hit_test(shape(&doc, circle), Point::new(10.0, 10.0), 0.0));
+
+        let line = doc.insert(
+            ShapeKind::Line {
+                start: Point::new(0.0, 0.0),
+                end: Point::new(100.0, 0.0),
+            },
+            Transform::new(0.0, 100.0, 100.0, 0.0),
+            Style::default(),
+        );
+        assert!(hit_test(shape(&doc, line), Point::new(50.0, 102.0), 2.0));
+        assert!(!hit_test(shape(&doc, line), Point::new(50.0, 110.0), 2.0));
+    }
+
+    #[test]
+    fn test_marquee_touching_and_contained() {
+        let mut doc = Document::new();
+        let mut t = Transform::new(0.0, 0.0, 100.0, 100.0);
+        t.rotation = std::f32::consts::FRAC_PI_4;
+        let diamond = doc.insert(ShapeKind::Rectangle, t, Style::default());
+        let rotated = shape(&doc, diamond);
+
+        // The corner of the rotated bounds is empty, and the outline is unfilled.
+        let corner = Rect::new(-20.0, -20.0, -10.0, -10.0);
+        assert!(rotated.bounds().intersects(&corner));
+        assert!(!intersects_rect(rotated, &corner, 0.5));
+        assert!(!intersects_rect(rotated, &Rect::new(45.0, 45.0, 55.0, 55.0), 0.5));
+        assert!(intersects_rect(rotated, &Rect::new(45.0, -30.0, 55.0, 0.0), 0.5));
+
+        let ellipse = doc.insert(ShapeKind::Ellipse, t, Style::default());
+        let around = Rect::new(-10.0, -10.0, 110.0, 110.0);
+        assert!(contained_in_rect(shape(&doc, ellipse), &around, 0.5));
+        assert!(!contained_in_rect(shape(&doc, diamond), &around, 0.5));
+    }
+
+    #[test]
+    fn test_lasso_touching_and_contained() {
+        let mut doc = Document::new();
+        let filled = Style {
+            fill: Some(Color::WHITE),
+            ..Style::default()
+        };
+        let square = doc.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 10.0, 10.0), filled);
+        let square = shape(&doc, square);
+        // All four corners are inside, but a notch cuts into the top edge.
+        let notched = [
+            Point::new(-5.0, -5.0),
+            Point::new(4.0, -5.0),
+            Point::new(5.0, 3.0),
+            Point::new(6.0, -5.0),
+            Point::new(15.0, -5.0),
+            Point::new(15.0, 15.0),
+            Point::new(-5.0, 15.0),
+        ];
+        assert!(intersects_polygon(square, &notched, 0.5));
+        assert!(!contained_in_polygon(square, &notched, 0.5));
+        let around = [Point::new(-5.0, -5.0), Point::new(15.0, -5.0), Point::new(15.0, 15.0), Point::new(-5.0, 15.0)];
+        assert!(contained_in_polygon(square, &around, 0.5));
+        // Drawn wholly inside a filled shape, a lasso touches it.
+        let inside = [Point::new(2.0, 2.0), Point::new(8.0, 2.0), Point::new(5.0, 8.0)];
+        assert!(intersects_polygon(square, &inside, 0.5));
+        assert!(!contained_in_polygon(square, &inside, 0.5));
+        let beside = [Point::new(20.0, 0.0), Point::new(30.0, 0.0), Point::new(25.0, 10.0)];
+        assert!(!intersects_polygon(square, &beside, 0.5));
+    }
+}
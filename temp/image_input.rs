@@ -0,0 +1,215 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use js_sys::Promise;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    Blob, DragEvent, File, FileReader, HtmlCanvasElement, ImageBitmap,
+    ImageBitmapOptions, PremultiplyAlpha, ResizeQuality, Response, WebGl2RenderingContext, Window,
+};
+
+use crate::editor::Editor;
+use crate::geometry::Point;
+use crate::renderer::WebGLRenderer;
+
+/// Screen offset between images dropped together, so none hides another.
+const DROP_CASCADE_PX: f32 = 24.0;
+
+/// Calls `done` with the promise's value or `failed` with its error.
+///
+/// The two callbacks hold each other until the promise settles and are
+/// freed together then, so loads repeated on every zoom do not pile up.
+pub(crate) fn settle(promise: &Promise, done: impl FnOnce(JsValue) + 'static, failed: impl FnOnce(JsValue) + 'static) {
+    type Callbacks = (Closure<dyn FnMut(JsValue)>, Closure<dyn FnMut(JsValue)>);
+    let callbacks: Rc<RefCell<Option<Callbacks>>> = Rc::new(RefCell::new(None));
+    let mut done = Some(done);
+    let mut failed = Some(failed);
+    let callbacks_clone = callbacks.clone();
+    let resolve = Closure::wrap(Box::new(move |value: JsValue| {
+        // Released once this call returns.
+        let _callbacks = callbacks_clone.borrow_mut().take();
+        if let Some(done) = done.take() {
+            done(value);
+        }
+    }) as Box<dyn FnMut(JsValue)>);
+    let callbacks_clone = callbacks.clone();
+    let reject = Closure::wrap(Box::new(move |error: JsValue| {
+        let _callbacks = callbacks_clone.borrow_mut().take();
+        if let Some(failed) = failed.take() {
+            failed(error);
+        }
+    }) as Box<dyn FnMut(JsValue)>);
+    let _ = promise.then2(&resolve, &reject);
+    *callbacks.borrow_mut() = Some((resolve, reject));
+}
+
+fn bitmap_options(size: Option<(u32, u32)>) -> ImageBitmapOptions {
+    let options = ImageBitmapOptions::new();
+    // The image shader expects premultiplied texels.
+    options.set_premultiply_alpha(PremultiplyAlpha::Premultiply);
+    if let Some((width, height)) = size {
+        options.set_resize_width(width);
+        options.set_resize_height(height);
+        options.set_resize_quality(ResizeQuality::High);
+    }
+    options
+}
+
+/// Starts decoding `sources` for the renderer's texture manager.
+pub fn load_images(window: &Window, context: &WebGl2RenderingContext, renderer: &Rc<RefCell<WebGLRenderer>>, sources: Vec<String>) {
+    for source in sources {
+        ImageLoad {
+            window: window.clone(),
+            context: context.clone(),
+            renderer: renderer.clone(),
+            source,
+        }
+        .fetch();
+    }
+}
+
+/// One image on its way from its source URL to a texture.
+///
+/// `createImageBitmap` decodes and resizes off the main thread. The image
+/// is decoded at full size to learn its dimensions, then scaled down to
+/// what the texture manager asks for before it is uploaded, so thumbnails
+/// never hold full-resolution textures.
+#[derive(Clone)]
+struct ImageLoad {
+    window: Window,
+    context: WebGl2RenderingContext,
+    renderer: Rc<RefCell<WebGLRenderer>>,
+    source: String,
+}
+
+impl ImageLoad {
+    fn fail(self, error: JsValue) {
+        web_sys::console::warn_2(&format!("Failed to load image {:.80}", self.source).into(), &error);
+        self.renderer.borrow_mut().images.textures.failed(&self.source);
+    }
+
+    /// Continues with `next` once `promise` resolves, or fails.
+    fn then(self, promise: Result<Promise, JsValue>, next: fn(Self, JsValue)) {
+        match promise {
+            Ok(promise) => {
+                let load = self.clone();
+                settle(&promise, move |value| next(load, value), move |error| self.fail(error));
+            }
+            Err(error) => self.fail(error),
+        }
+    }
+
+    fn fetch(self) {
+        let fetched = self.window.fetch_with_str(&self.source);
+        self.then(Ok(fetched), Self::read);
+    }
+
+    fn read(self, response: JsValue) {
+        let response: Response = response.unchecked_into();
+        if !response.ok() {
+            let status = format!("HTTP {}", response.status());
+            return self.fail(status.into());
+        }
+        self.then(response.blob(), Self::decode);
+    }
+
+    fn decode(self, blob: JsValue) {
+        let blob: Blob = blob.unchecked_into();
+        let decoded = self.window.create_image_bitmap_with_blob_and_image_bitmap_options(&blob, &bitmap_options(None));
+        self.then(decoded, Self::resize);
+    }
+
+    fn resize(self, bitmap: JsValue) {
+        let bitmap: ImageBitmap = bitmap.unchecked_into();
+        let natural = (bitmap.width(), bitmap.height());
+        let size = self.renderer.borrow_mut().images.textures.upload_size(&self.source, natural);
+        if size == natural {
+            return self.upload(bitmap.into());
+        }
+        let resized = self
+            .window
+            .create_image_bitmap_with_image_bitmap_and_image_bitmap_options(&bitmap, &bitmap_options(Some(size)));
+        bitmap.close();
+        self.then(resized, Self::upload);
+    }
+
+    fn upload(self, bitmap: JsValue) {
+        let bitmap: ImageBitmap = bitmap.unchecked_into();
+        let uploaded = self.renderer.borrow_mut().images.upload(&self.context, &self.source, &bitmap);
+        bitmap.close();
+        if let Err(e) = uploaded {
+            web_sys::console::warn_1(&e.into());
+        }
+    }
+}
+
+/// Adds each image in `files` to the board, `at` a screen point or the
+/// viewport centre. The file becomes a data URL source, so the board stays
+/// self-contained when saved.
+pub(crate) fn insert_files(window: &Window, files: Vec<File>, at: Option<Point>, editor: &Rc<RefCell<Editor>>) {
+    let images = files.into_iter().filter(|file| file.type_().starts_with("image/"));
+    for (i, file) in images.enumerate() {
+        let at = at.map(|at| at + Point::new(i as f32, i as f32) * DROP_CASCADE_PX);
+        if let Err(e) = insert_file(window, file, at, editor.clone()) {
+            web_sys::console::warn_1(&e);
+        }
+    }
+}
+
+fn insert_file(window: &Window, file: File, at: Option<Point>, editor: Rc<RefCell<Editor>>) -> Result<(), JsValue> {
+    let reader = FileReader::new()?;
+    // Loading is asynchronous, so the handler set below still sees it end.
+    reader.read_as_data_url(&file)?;
+    let reader_clone = reader.clone();
+    let window = window.clone();
+    let onload = Closure::once_into_js(move || {
+        let Some(source) = reader_clone.result().ok().and_then(|result| result.as_string()) else {
+            return;
+        };
+        // Decoding only measures the image; the renderer loads its own copy.
+        let decoded = window.create_image_bitmap_with_blob(&file);
+        let Ok(decoded) = decoded else {
+            return;
+        };
+        settle(
+            &decoded,
+            move |bitmap| {
+                let bitmap: ImageBitmap = bitmap.unchecked_into();
+                let natural = (bitmap.width(), bitmap.height());
+                bitmap.close();
+                editor.borrow_mut().insert_image(source, natural, at);
+            },
+            |error| web_sys::console::warn_2(&"Unsupported image".into(), &error),
+        );
+    });
+    reader.set_onload(Some(onload.unchecked_ref()));
+    Ok(())
+}
+
+/// Adds images dropped on the canvas. Pasted ones arrive through
+/// [`crate::clipboard_input`].
+pub fn setup_image_events(window: &Window, canvas: &HtmlCanvasElement, editor: Rc<RefCell<Editor>>) -> Result<(), JsValue> {
+    // Without this the browser refuses the drop and opens the file instead.
+    let dragover_callback = Closure::wrap(Box::new(move |event: DragEvent| {
+        event.prevent_default();
+    }) as Box<dyn FnMut(DragEvent)>);
+
+    let window_clone = window.clone();
+    let drop_callback = Closure::wrap(Box::new(move |event: DragEvent| {
+        event.prevent_default();
+        if let Some(files) = event.data_transfer().and_then(|data| data.files()) {
+            let at = Point::new(event.offset_x() as f32, event.offset_y() as f32);
+            let files = (0..files.length()).filter_map(|i| files.get(i)).collect();
+            insert_files(&window_clone, files, Some(at), &editor);
+        }
+    }) as Box<dyn FnMut(DragEvent)>);
+
+    canvas.add_event_listener_with_callback("dragover", dragover_callback.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("drop", drop_callback.as_ref().unchecked_ref())?;
+
+    dragover_callback.forget();
+    drop_callback.forget();
+
+    Ok(())
+}
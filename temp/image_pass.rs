@@ -0,0 +1,159 @@
+use web_sys::{ImageBitmap, WebGl2RenderingContext, WebGlTexture, WebGlVertexArrayObject};
+
+use crate::document::{Paint, Shape, ShapeKind};
+use crate::shaders::ShaderProgram;
+use crate::shape_pass::{ShapePass, Target};
+use crate::state::State;
+use crate::textures::{TextureManager, PLACEHOLDER_RGBA};
+
+type Gl = WebGl2RenderingContext;
+
+/// Draws image shapes as textured quads, one draw call each, between the
+/// shape batches and text around them.
+///
+/// Decoding happens elsewhere: the pass reports what it draws to its
+/// [`TextureManager`], whose queued loads the page decodes and hands back
+/// through [`ImagePass::upload`]. Images without a texture yet draw as a
+/// grey placeholder.
+pub struct ImagePass {
+    program: ShaderProgram,
+    vao: WebGlVertexArrayObject,
+    placeholder: WebGlTexture,
+    pub textures: TextureManager<WebGlTexture>,
+}
+
+impl ImagePass {
+    pub fn new(context: &Gl) -> Result<Self, String> {
+        let program = ShaderProgram::image(context)?;
+        let quad = context.create_buffer()
+            .ok_or("Failed to create buffer")?;
+        let corners: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&quad));
+        unsafe {
+            let view = js_sys::Float32Array::view(&corners);
+            context.buffer_data_with_array_buffer_view(Gl::ARRAY_BUFFER, &view, Gl::STATIC_DRAW);
+        }
+        let vao = context.create_vertex_array()
+            .ok_or("Failed to create vertex array")?;
+        context.bind_vertex_array(Some(&vao));
+        context.enable_vertex_attrib_array(0);
+        context.vertex_attrib_pointer_with_i32(0, 2, Gl::FLOAT, false, 0, 0);
+        context.bind_vertex_array(None);
+
+        let placeholder = context.create_texture()
+            .ok_or("Failed to create texture")?;
+        context.bind_texture(Gl::TEXTURE_2D, Some(&placeholder));
+        context
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                Gl::TEXTURE_2D,
+                0,
+                Gl::RGBA8 as i32,
+                1,
+                1,
+                0,
+                Gl::RGBA,
+                Gl::UNSIGNED_BYTE,
+                Some(&PLACEHOLDER_RGBA),
+            )
+            .map_err(|e| format!("Failed to create placeholder texture: {:?}", e))?;
+        context.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MIN_FILTER, Gl::NEAREST as i32);
+        context.bind_texture(Gl::TEXTURE_2D, None);
+
+        Ok(Self {
+            program,
+            vao,
+            placeholder,
+            textures: TextureManager::default(),
+        })
+    }
+
+    /// Uploads a decoded image with mipmaps and makes it the texture for
+    /// `source`, deleting whatever that replaces or evicts.
+    pub fn upload(&mut self, context: &Gl, source: &str, bitmap: &ImageBitmap) -> Result<(), String> {
+        let texture = context.create_texture()
+            .ok_or("Failed to create texture")?;
+        context.bind_texture(Gl::TEXTURE_2D, Some(&texture));
+        let uploaded = context.tex_image_2d_with_u32_and_u32_and_image_bitmap(
+            Gl::TEXTURE_2D,
+            0,
+            Gl::RGBA8 as i32,
+            Gl::RGBA,
+            Gl::UNSIGNED_BYTE,
+            bitmap,
+        );
+        if let Err(e) = uploaded {
+            context.bind_texture(Gl::TEXTURE_2D, None);
+            context.delete_texture(Some(&texture));
+            self.textures.failed(source);
+            return Err(format!("Failed to upload image: {:?}", e));
+        }
+        context.generate_mipmap(Gl::TEXTURE_2D);
+        for (parameter, value) in [
+            (Gl::TEXTURE_MIN_FILTER, Gl::LINEAR_MIPMAP_LINEAR),
+            (Gl::TEXTURE_MAG_FILTER, Gl::LINEAR),
+            (Gl::TEXTURE_WRAP_S, Gl::CLAMP_TO_EDGE),
+            (Gl::TEXTURE_WRAP_T, Gl::CLAMP_TO_EDGE),
+        ] {
+            context.tex_parameteri(Gl::TEXTURE_2D, parameter, value as i32);
+        }
+        context.bind_texture(Gl::TEXTURE_2D, None);
+        for released in self.textures.loaded(source, texture, bitmap.width(), bitmap.height()) {
+            context.delete_texture(Some(&released));
+        }
+        Ok(())
+    }
+
+    /// Deletes textures evicted by a smaller budget.
+    pub fn set_budget(&mut self, context: &Gl, bytes: usize) {
+        for evicted in self.textures.set_budget(bytes) {
+            context.delete_texture(Some(&evicted));
+        }
+    }
+
+    /// Starts a frame of [`ImagePass::draw`] calls for the texture manager.
+    pub fn begin_frame(&mut self) {
+        self.textures.begin_frame();
+    }
+
+    pub fn set_camera(&self, context: &Gl, state: &State, width: f32, height: f32) {
+        ShapePass::set_camera_uniforms(&self.program, context, state, width, height);
+        self.program.set_uniform_1i(context, "u_image", 0);
+    }
+
+    /// Draws one image painted as `paint` says, with the camera from
+    /// [`ImagePass::set_camera`] and blending already set up. On screen
+    /// this skips images outside the view and tells the texture manager how
+    /// large the rest appear, so it can load or downsample them; exports use
+    /// whatever textures are resident.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(&mut self, context: &Gl, target: Target, state: &State, width: f32, height: f32, shape: &Shape, paint: Paint) {
+        let ShapeKind::Image { source } = &shape.kind else {
+            return;
+        };
+        let texture = match target {
+            Target::Screen => {
+                if !shape.bounds().intersects(&state.visible_world_rect(width, height)) {
+                    return;
+                }
+                let per_pixel = state.world_units_per_pixel(width, height);
+                let side = shape.transform.width.max(shape.transform.height) / per_pixel;
+                self.textures.use_image(source, side).cloned()
+            }
+            Target::Export => self.textures.texture(source).cloned(),
+        };
+
+        let t = &shape.transform;
+        context.use_program(Some(&self.program.program));
+        context.active_texture(Gl::TEXTURE0);
+        context.bind_texture(Gl::TEXTURE_2D, Some(texture.as_ref().unwrap_or(&self.placeholder)));
+        context.bind_vertex_array(Some(&self.vao));
+        ShapePass::set_clip(context, state, width, height, paint.clip);
+        self.program.set_uniform_4f(context, "u_box", [t.x, t.y, t.width, t.height]);
+        self.program.set_uniform_1f(context, "u_rotation", t.rotation);
+        self.program.set_uniform_1f(context, "u_opacity", shape.style.opacity * paint.opacity);
+        context.draw_arrays(Gl::TRIANGLE_STRIP, 0, 4);
+        ShapePass::set_clip(context, state, width, height, None);
+        context.bind_vertex_array(None);
+        context.bind_texture(Gl::TEXTURE_2D, None);
+    }
+}
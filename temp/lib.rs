@@ -1,17 +1,391 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::WebGl2RenderingContext;
 
+pub mod arrange;
+pub mod batch;
+pub mod board;
+mod buffers;
+pub mod clipboard;
+mod clipboard_input;
+pub mod connector;
+pub mod document;
+pub mod editor;
 mod events;
+pub mod export;
+pub mod fractional_index;
+pub mod freehand;
+pub mod geometry;
+pub mod glyph_atlas;
+pub mod grid;
+pub mod handles;
+pub mod hierarchy;
+pub mod history;
+pub mod hit_test;
+mod image_input;
+mod image_pass;
+pub mod minimap;
+pub mod overlay;
+pub mod raster;
 mod renderer;
+pub mod selection;
 mod shaders;
-mod state;
+mod shape_pass;
+pub mod snap;
+pub mod spatial;
+pub mod svg;
+pub mod state;
+pub mod tessellate;
+pub mod text;
+mod text_pass;
+mod text_input;
+pub mod textures;
+pub mod tools;
 mod utils;
 
-use events::{setup_mouse_events, setup_resize_events};
+use arrange::{Align, Arrange, Axis};
+use clipboard_input::setup_clipboard_events;
+use document::{LayerId, Routing, ShapeId};
+use editor::Editor;
+use export::{ExportPlan, ExportSize, PngOptions};
+use events::{setup_keyboard_events, setup_pointer_events, setup_resize_events};
+use image_input::{load_images, setup_image_events};
 use renderer::WebGLRenderer;
-use state::State;
-use utils::request_animation_frame;
+use selection::MarqueeMode;
+use svg::{ExportArea, SvgOptions};
+use text_input::{font_family, TextField};
+use tools::EraserMode;
+use utils::{request_animation_frame, to_js_result};
+
+thread_local! {
+    /// The running editor, for the functions exported to JS below.
+    static EDITOR: RefCell<Option<Rc<RefCell<Editor>>>> = const { RefCell::new(None) };
+    /// The canvas context and its renderer, for offscreen exports.
+    static RENDERER: RefCell<Option<(WebGl2RenderingContext, Rc<RefCell<WebGLRenderer>>)>> = const { RefCell::new(None) };
+}
+
+fn with_editor<T>(f: impl FnOnce(&mut Editor) -> T) -> Result<T, JsValue> {
+    EDITOR.with(|editor| match editor.borrow().as_ref() {
+        Some(editor) => Ok(f(&mut editor.borrow_mut())),
+        None => Err(JsValue::from_str("Editor not started")),
+    })
+}
+
+/// Adds a TrueType or OpenType font from its file's bytes. The first font
+/// loaded is the primary; later ones are fallbacks for characters it lacks.
+/// The font is also registered with the page, so text being edited lays out
+/// the same in the browser.
+#[wasm_bindgen(js_name = loadFont)]
+pub fn load_font(data: Vec<u8>) -> Result<(), JsValue> {
+    let face = to_js_result(text::Font::from_bytes(data.clone()))?;
+    let family = with_editor(|editor| {
+        editor.fonts.push(face);
+        font_family(editor.fonts.len() - 1)
+    })?;
+    if let Some(document) = web_sys::window().and_then(|window| window.document()) {
+        document.fonts().add(&web_sys::FontFace::new_with_u8_array(&family, &data)?)?;
+    }
+    Ok(())
+}
+
+/// Sets how much GPU memory image textures may use, in bytes. The least
+/// recently drawn images are evicted first and reloaded when seen again.
+#[wasm_bindgen(js_name = setTextureBudget)]
+pub fn set_texture_budget(bytes: usize) -> Result<(), JsValue> {
+    let (context, renderer) = RENDERER
+        .with(|renderer| renderer.borrow().clone())
+        .ok_or_else(|| JsValue::from_str("Renderer not started"))?;
+    renderer.borrow_mut().images.set_budget(&context, bytes);
+    Ok(())
+}
+
+/// Sets how new connectors are routed: `"straight"`, `"curved"` or `"elbow"`.
+#[wasm_bindgen(js_name = setConnectorRouting)]
+pub fn set_connector_routing(routing: &str) -> Result<(), JsValue> {
+    let routing = match routing {
+        "straight" => Routing::Straight,
+        "curved" => Routing::Curved,
+        "elbow" => Routing::Elbow,
+        other => return Err(JsValue::from_str(&format!("Unknown routing '{}'", other))),
+    };
+    with_editor(|editor| editor.tools.connector.routing = routing)
+}
+
+/// Sets what the eraser removes: `"whole"` shapes, or `"precise"` parts of
+/// freehand strokes.
+#[wasm_bindgen(js_name = setEraserMode)]
+pub fn set_eraser_mode(mode: &str) -> Result<(), JsValue> {
+    let mode = match mode {
+        "whole" => EraserMode::Whole,
+        "precise" => EraserMode::Precise,
+        other => return Err(JsValue::from_str(&format!("Unknown eraser mode '{}'", other))),
+    };
+    with_editor(|editor| editor.tools.eraser.mode = mode)
+}
+
+/// Sets the eraser radius in screen pixels.
+#[wasm_bindgen(js_name = setEraserRadius)]
+pub fn set_eraser_radius(radius: f32) -> Result<(), JsValue> {
+    with_editor(|editor| editor.tools.eraser.radius = radius)
+}
+
+/// Sets what the lasso picks up: shapes `"contained"` in it or `"touching"` it.
+#[wasm_bindgen(js_name = setLassoMode)]
+pub fn set_lasso_mode(mode: &str) -> Result<(), JsValue> {
+    let mode = match mode {
+        "contained" => MarqueeMode::Contained,
+        "touching" => MarqueeMode::Touching,
+        other => return Err(JsValue::from_str(&format!("Unknown lasso mode '{}'", other))),
+    };
+    with_editor(|editor| editor.tools.lasso.mode = mode)
+}
+
+/// Shows or hides the minimap in the bottom-right corner of the canvas.
+#[wasm_bindgen(js_name = setMinimap)]
+pub fn set_minimap(visible: bool) -> Result<(), JsValue> {
+    with_editor(|editor| editor.show_minimap = visible)
+}
+
+/// Turns snapping while moving and resizing on or off.
+#[wasm_bindgen(js_name = setSnapping)]
+pub fn set_snapping(enabled: bool) -> Result<(), JsValue> {
+    with_editor(|editor| editor.tools.select.snapping = enabled)
+}
+
+/// The layers, bottom to top, as JSON objects with `id`, `name`,
+/// `visible`, `locked` and `opacity`.
+#[wasm_bindgen]
+pub fn layers() -> Result<String, JsValue> {
+    with_editor(|editor| to_js_result(serde_json::to_string(editor.document.layers()).map_err(|e| e.to_string())))?
+}
+
+/// The layer new shapes are drawn on.
+#[wasm_bindgen(js_name = activeLayer)]
+pub fn active_layer() -> Result<u64, JsValue> {
+    with_editor(|editor| editor.document.active_layer().0)
+}
+
+#[wasm_bindgen(js_name = setActiveLayer)]
+pub fn set_active_layer(id: u64) -> Result<(), JsValue> {
+    with_editor(|editor| to_js_result(editor.document.set_active_layer(LayerId(id))))?
+}
+
+/// Adds a layer on top, makes it active and returns its id.
+#[wasm_bindgen(js_name = addLayer)]
+pub fn add_layer(name: &str) -> Result<u64, JsValue> {
+    with_editor(|editor| editor.transaction(|editor| editor.document.add_layer(name).0))
+}
+
+#[wasm_bindgen(js_name = renameLayer)]
+pub fn rename_layer(id: u64, name: &str) -> Result<(), JsValue> {
+    with_editor(|editor| to_js_result(editor.update_layer(LayerId(id), |layer| layer.name = name.to_string())))?
+}
+
+#[wasm_bindgen(js_name = setLayerVisible)]
+pub fn set_layer_visible(id: u64, visible: bool) -> Result<(), JsValue> {
+    with_editor(|editor| to_js_result(editor.update_layer(LayerId(id), |layer| layer.visible = visible)))?
+}
+
+#[wasm_bindgen(js_name = setLayerLocked)]
+pub fn set_layer_locked(id: u64, locked: bool) -> Result<(), JsValue> {
+    with_editor(|editor| to_js_result(editor.update_layer(LayerId(id), |layer| layer.locked = locked)))?
+}
+
+/// Sets a layer's opacity, clamped to 0..1.
+#[wasm_bindgen(js_name = setLayerOpacity)]
+pub fn set_layer_opacity(id: u64, opacity: f32) -> Result<(), JsValue> {
+    let opacity = if opacity.is_nan() { 1.0 } else { opacity.clamp(0.0, 1.0) };
+    with_editor(|editor| to_js_result(editor.update_layer(LayerId(id), |layer| layer.opacity = opacity)))?
+}
+
+/// Moves a layer to `index`, counted from the bottom.
+#[wasm_bindgen(js_name = moveLayer)]
+pub fn move_layer(id: u64, index: usize) -> Result<(), JsValue> {
+    with_editor(|editor| to_js_result(editor.transaction(|editor| editor.document.move_layer(LayerId(id), index))))?
+}
+
+/// Deletes a layer with everything on it. The last layer cannot be deleted.
+#[wasm_bindgen(js_name = deleteLayer)]
+pub fn delete_layer(id: u64) -> Result<(), JsValue> {
+    with_editor(|editor| to_js_result(editor.transaction(|editor| editor.document.delete_layer(LayerId(id)))))?
+}
+
+#[wasm_bindgen(js_name = moveSelectionToLayer)]
+pub fn move_selection_to_layer(id: u64) -> Result<(), JsValue> {
+    with_editor(|editor| to_js_result(editor.move_selection_to_layer(LayerId(id))))?
+}
+
+/// Reorders the selection: `"forward"`, `"backward"`, `"front"` or `"back"`.
+#[wasm_bindgen(js_name = arrangeSelection)]
+pub fn arrange_selection(how: &str) -> Result<(), JsValue> {
+    let how = match how {
+        "forward" => Arrange::Forward,
+        "backward" => Arrange::Backward,
+        "front" => Arrange::ToFront,
+        "back" => Arrange::ToBack,
+        other => return Err(JsValue::from_str(&format!("Unknown arrangement '{}'", other))),
+    };
+    with_editor(|editor| editor.arrange_selection(how))
+}
+
+/// Lines up the selection's bounds: `"left"`, `"center"`, `"right"`,
+/// `"top"`, `"middle"` or `"bottom"`.
+#[wasm_bindgen(js_name = alignSelection)]
+pub fn align_selection(how: &str) -> Result<(), JsValue> {
+    let how = match how {
+        "left" => Align::Left,
+        "center" => Align::Center,
+        "right" => Align::Right,
+        "top" => Align::Top,
+        "middle" => Align::Middle,
+        "bottom" => Align::Bottom,
+        other => return Err(JsValue::from_str(&format!("Unknown alignment '{}'", other))),
+    };
+    with_editor(|editor| editor.align_selection(how))
+}
+
+/// Spaces the selection out evenly: `"horizontal"` or `"vertical"`.
+#[wasm_bindgen(js_name = distributeSelection)]
+pub fn distribute_selection(axis: &str) -> Result<(), JsValue> {
+    let axis = match axis {
+        "horizontal" => Axis::Horizontal,
+        "vertical" => Axis::Vertical,
+        other => return Err(JsValue::from_str(&format!("Unknown axis '{}'", other))),
+    };
+    with_editor(|editor| editor.distribute_selection(axis))
+}
+
+#[wasm_bindgen(js_name = tidySelection)]
+pub fn tidy_selection() -> Result<(), JsValue> {
+    with_editor(|editor| editor.tidy_selection())
+}
+
+/// Undoes the last edit; returns false if there was nothing to undo.
+#[wasm_bindgen]
+pub fn undo() -> Result<bool, JsValue> {
+    with_editor(|editor| to_js_result(editor.undo()))?
+}
+
+#[wasm_bindgen]
+pub fn redo() -> Result<bool, JsValue> {
+    with_editor(|editor| to_js_result(editor.redo()))?
+}
+
+#[wasm_bindgen(js_name = canUndo)]
+pub fn can_undo() -> bool {
+    with_editor(|editor| editor.can_undo()).unwrap_or(false)
+}
+
+#[wasm_bindgen(js_name = canRedo)]
+pub fn can_redo() -> bool {
+    with_editor(|editor| editor.can_redo()).unwrap_or(false)
+}
+
+/// Whether panning and zooming become undo steps; off by default.
+#[wasm_bindgen(js_name = setUndoCamera)]
+pub fn set_undo_camera(enabled: bool) -> Result<(), JsValue> {
+    with_editor(|editor| editor.history.record_camera = enabled)
+}
+
+/// The board as JSON, for saving.
+#[wasm_bindgen(js_name = saveBoard)]
+pub fn save_board() -> Result<String, JsValue> {
+    with_editor(|editor| to_js_result(editor.save()))?
+}
+
+/// Replaces the board with saved JSON. Throws with the offending field's
+/// path if the file is invalid.
+#[wasm_bindgen(js_name = loadBoard)]
+pub fn load_board(json: &str) -> Result<(), JsValue> {
+    with_editor(|editor| to_js_result(editor.load(json)))?
+}
+
+fn parse_area(area: &str) -> Result<ExportArea, JsValue> {
+    match area {
+        "document" => Ok(ExportArea::Document),
+        "selection" => Ok(ExportArea::Selection),
+        other => match other.strip_prefix("frame:").and_then(|id| id.parse().ok()) {
+            Some(id) => Ok(ExportArea::Frame(ShapeId(id))),
+            None => Err(JsValue::from_str(&format!("Unknown export area '{}'", other))),
+        },
+    }
+}
+
+/// Exports `"document"`, `"selection"` or `"frame:<id>"` as SVG, optionally
+/// over the grid.
+#[wasm_bindgen(js_name = exportSvg)]
+pub fn export_svg(area: &str, include_grid: bool) -> Result<String, JsValue> {
+    let area = parse_area(area)?;
+    with_editor(|editor| to_js_result(editor_svg(editor, area, include_grid)))?
+}
+
+/// Exports a world-space rect as SVG, cropping shapes at its edges.
+#[wasm_bindgen(js_name = exportSvgRect)]
+pub fn export_svg_rect(x: f32, y: f32, width: f32, height: f32, include_grid: bool) -> Result<String, JsValue> {
+    let area = ExportArea::Rect(geometry::Rect::new(x, y, x + width, y + height));
+    with_editor(|editor| to_js_result(editor_svg(editor, area, include_grid)))?
+}
+
+fn editor_svg(editor: &Editor, area: ExportArea, include_grid: bool) -> Result<String, String> {
+    let options = SvgOptions {
+        area,
+        grid: include_grid.then_some(editor.grid),
+        background: include_grid.then_some(document::Color::WHITE),
+        ..SvgOptions::default()
+    };
+    svg::export_svg(&editor.document, &editor.selection, &editor.fonts, &options)
+}
+
+/// Exports `"document"`, `"selection"` or `"frame:<id>"` as PNG bytes at
+/// `scale` pixels per world unit, e.g. 1, 2 or 4.
+#[wasm_bindgen(js_name = exportPng)]
+pub fn export_png(area: &str, scale: f32, transparent: bool) -> Result<Vec<u8>, JsValue> {
+    let area = parse_area(area)?;
+    editor_png(area, ExportSize::Scale(scale), transparent)
+}
+
+/// Exports `"document"`, `"selection"` or `"frame:<id>"` as a PNG of
+/// exactly `width` x `height` pixels.
+#[wasm_bindgen(js_name = exportPngFit)]
+pub fn export_png_fit(area: &str, width: u32, height: u32, transparent: bool) -> Result<Vec<u8>, JsValue> {
+    let area = parse_area(area)?;
+    editor_png(area, ExportSize::Fit { width, height }, transparent)
+}
+
+/// Exports a world-space rect as PNG bytes at `scale` pixels per world unit.
+#[wasm_bindgen(js_name = exportPngRect)]
+pub fn export_png_rect(x: f32, y: f32, width: f32, height: f32, scale: f32, transparent: bool) -> Result<Vec<u8>, JsValue> {
+    let area = ExportArea::Rect(geometry::Rect::new(x, y, x + width, y + height));
+    editor_png(area, ExportSize::Scale(scale), transparent)
+}
+
+fn editor_png(area: ExportArea, size: ExportSize, transparent: bool) -> Result<Vec<u8>, JsValue> {
+    let options = PngOptions {
+        area,
+        size,
+        transparent,
+        ..PngOptions::default()
+    };
+    let (context, renderer) = RENDERER
+        .with(|renderer| renderer.borrow().clone())
+        .ok_or_else(|| JsValue::from_str("Renderer not started"))?;
+    with_editor(|editor| to_js_result(gpu_png(editor, &context, &mut renderer.borrow_mut(), &options)))?
+}
+
+fn gpu_png(
+    editor: &Editor,
+    context: &WebGl2RenderingContext,
+    renderer: &mut WebGLRenderer,
+    options: &PngOptions,
+) -> Result<Vec<u8>, String> {
+    let (shapes, view) = svg::export_region(&editor.document, &editor.selection, options.area, options.padding)?;
+    let plan = ExportPlan::new(view, options.size)?;
+    let pixels = renderer.export_pixels(context, &editor.document, &shapes, &editor.fonts, &plan, options.clear_color())?;
+    export::encode_png(plan.width, plan.height, &pixels)
+}
 
 #[wasm_bindgen(start)]
 pub fn start() -> Result<(), JsValue> {
@@ -23,28 +397,63 @@ pub fn start() -> Result<(), JsValue> {
         .unwrap()
         .dyn_into::<web_sys::HtmlCanvasElement>()?;
     
+    // A desynchronized context skips the compositor's extra frame of
+    // latency, so live strokes stay under the pointer.
+    let context_options = js_sys::Object::new();
+    js_sys::Reflect::set(&context_options, &"desynchronized".into(), &JsValue::TRUE)?;
     let context = canvas
-        .get_context("webgl2")?
+        .get_context_with_context_options("webgl2", &context_options)?
         .unwrap()
         .dyn_into::<WebGl2RenderingContext>()?;
 
     // Initialize state and renderer
-    let state = State::new();
-    let renderer = WebGLRenderer::new(&context)?;
+    let editor = Rc::new(RefCell::new(Editor::new(
+        canvas.width() as f32,
+        canvas.height() as f32,
+    )));
+    EDITOR.with(|global| *global.borrow_mut() = Some(editor.clone()));
+    let renderer = Rc::new(RefCell::new(WebGLRenderer::new(&context)?));
+    RENDERER.with(|global| *global.borrow_mut() = Some((context.clone(), renderer.clone())));
 
     // Setup events
-    setup_mouse_events(&canvas, state.clone())?;
+    let text_field = TextField::new(&document, &canvas, editor.clone())?;
+    setup_pointer_events(&canvas, editor.clone())?;
+    setup_keyboard_events(&window, editor.clone())?;
     setup_resize_events(&window, &canvas, &context)?;
+    setup_image_events(&window, &canvas, editor.clone())?;
+    setup_clipboard_events(&window, editor.clone())?;
 
     // Initial resize
-    renderer.resize_canvas(&canvas, &context);
+    renderer.borrow().resize_canvas(&canvas, &context);
 
     // Setup animation loop
-    let f = std::rc::Rc::new(std::cell::RefCell::new(None));
+    let f = Rc::new(RefCell::new(None));
     let g = f.clone();
 
     *g.borrow_mut() = Some(Closure::wrap(Box::new(move || {
-        renderer.render(&context, &state.borrow());
+        let mut editor = editor.borrow_mut();
+        editor.set_viewport(canvas.width() as f32, canvas.height() as f32);
+        editor.sync_index();
+
+        if let Err(e) = text_field.sync(&editor) {
+            web_sys::console::warn_1(&e);
+        }
+
+        let preview = editor.preview_shape();
+        let overlay = editor.overlay();
+        let minimap = editor.minimap();
+        renderer.borrow_mut().render(
+            &context,
+            &editor.state,
+            &editor.document,
+            &editor.index,
+            &editor.fonts,
+            preview.as_ref(),
+            overlay.shapes(),
+            minimap.as_ref(),
+        );
+        let loads = renderer.borrow_mut().images.textures.take_loads();
+        load_images(&window, &context, &renderer, loads);
         request_animation_frame(f.borrow().as_ref().unwrap());
     }) as Box<dyn FnMut()>));
 
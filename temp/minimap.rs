@@ -0,0 +1,178 @@
+//! A small map of the whole board in a corner of the canvas, showing the
+//! bounds of every shape and the part of the board on screen. Pressing or
+//! dragging in it moves the camera there.
+
+use crate::document::{Color, Document, ShapeKind, Style, Transform};
+use crate::geometry::{Point, Rect};
+use crate::overlay::{Overlay, ACCENT};
+use crate::spatial::SpatialIndex;
+use crate::state::{State, WORLD_UNITS_PER_GRID_UNIT};
+
+/// Size of the minimap in canvas pixels.
+pub const MINIMAP_WIDTH_PX: f32 = 200.0;
+pub const MINIMAP_HEIGHT_PX: f32 = 150.0;
+
+/// Gap between the minimap and the canvas edges, in pixels.
+const MINIMAP_MARGIN_PX: f32 = 16.0;
+
+/// Space left around the board, as a fraction of its larger side.
+const MINIMAP_PADDING: f32 = 0.1;
+
+const CONTENT: Color = Color::rgba(0.6, 0.6, 0.6, 1.0);
+const BORDER: Color = Color::rgba(0.8, 0.8, 0.8, 1.0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimap {
+    /// Where the minimap sits, in canvas pixels.
+    pub frame: Rect,
+    /// The world rect it shows, shaped like `frame` so pixels stay square.
+    pub view: Rect,
+    /// The part of the world on screen.
+    pub viewport: Rect,
+}
+
+impl Minimap {
+    /// Lays out the minimap in the bottom-right corner of a `width` x
+    /// `height` canvas, showing everything in `index` and what the camera
+    /// sees. `None` when the canvas is too small to hold it.
+    pub fn new(index: &SpatialIndex, state: &State, width: f32, height: f32) -> Option<Self> {
+        if width < MINIMAP_WIDTH_PX + MINIMAP_MARGIN_PX * 2.0 || height < MINIMAP_HEIGHT_PX + MINIMAP_MARGIN_PX * 2.0 {
+            return None;
+        }
+        let frame = Rect::new(
+            width - MINIMAP_MARGIN_PX - MINIMAP_WIDTH_PX,
+            height - MINIMAP_MARGIN_PX - MINIMAP_HEIGHT_PX,
+            width - MINIMAP_MARGIN_PX,
+            height - MINIMAP_MARGIN_PX,
+        );
+        let viewport = state.visible_world_rect(width, height);
+        let extent = index.bounds().map_or(viewport, |bounds| bounds.union(&viewport));
+        let extent = extent.expand(extent.width().max(extent.height()) * MINIMAP_PADDING);
+        let per_pixel = (extent.width() / frame.width()).max(extent.height() / frame.height());
+        let (half_width, half_height) = (frame.width() * per_pixel * 0.5, frame.height() * per_pixel * 0.5);
+        let center = extent.center();
+        let view = Rect::new(center.x - half_width, center.y - half_height, center.x + half_width, center.y + half_height);
+        Some(Self { frame, view, viewport })
+    }
+
+    /// The same map with the camera's view moved, e.g. while dragging in it,
+    /// so the map holds still under the pointer.
+    pub fn with_viewport(&self, viewport: Rect) -> Self {
+        Self { viewport, ..self.clone() }
+    }
+
+    pub fn contains(&self, screen: Point) -> bool {
+        self.frame.contains_point(screen)
+    }
+
+    fn world_units_per_pixel(&self) -> f32 {
+        self.view.width() / self.frame.width()
+    }
+
+    /// The world point under the canvas point `screen`.
+    pub fn to_world(&self, screen: Point) -> Point {
+        let per_pixel = self.world_units_per_pixel();
+        Point::new(
+            self.view.min_x + (screen.x - self.frame.min_x) * per_pixel,
+            self.view.min_y + (screen.y - self.frame.min_y) * per_pixel,
+        )
+    }
+
+    /// A camera that shows `view` on a canvas the size of `frame`.
+    pub fn camera(&self) -> State {
+        let zoom = 2.0 * WORLD_UNITS_PER_GRID_UNIT / (self.world_units_per_pixel() * self.frame.width().min(self.frame.height()));
+        let mut camera = State {
+            zoom,
+            ..State::default()
+        };
+        camera.center_on(self.view.center());
+        camera
+    }
+
+    /// What to draw through [`Self::camera`] under the camera's view: the
+    /// background and a box for each shape. Only changes with the document,
+    /// `frame` and `view`.
+    pub fn shapes(&self, document: &Document) -> Overlay {
+        let per_pixel = self.world_units_per_pixel();
+        let mut overlay = Overlay::new(per_pixel);
+        let background = Style {
+            stroke: BORDER,
+            fill: Some(Color::WHITE),
+            stroke_width: per_pixel,
+            ..Style::default()
+        };
+        overlay.push(ShapeKind::Rectangle, rect_transform(self.view.expand(-per_pixel * 0.5)), background);
+        // Shapes too thin to fill a pixel still show as a line.
+        let shape = Style {
+            stroke: CONTENT,
+            fill: Some(CONTENT),
+            stroke_width: per_pixel,
+            ..Style::default()
+        };
+        for bounds in content(document) {
+            overlay.push(ShapeKind::Rectangle, rect_transform(bounds), shape.clone());
+        }
+        overlay
+    }
+
+    /// The camera's view, drawn through [`Self::camera`] on top of
+    /// [`Self::shapes`].
+    pub fn viewport_shapes(&self) -> Overlay {
+        let per_pixel = self.world_units_per_pixel();
+        let mut overlay = Overlay::new(per_pixel);
+        let viewport = Style {
+            stroke: ACCENT,
+            fill: Some(Color::rgba(ACCENT.r, ACCENT.g, ACCENT.b, 0.1)),
+            stroke_width: per_pixel * 1.5,
+            ..Style::default()
+        };
+        overlay.push(ShapeKind::Rectangle, rect_transform(self.viewport), viewport);
+        overlay
+    }
+}
+
+/// Bounds of the top-level shapes on visible layers; contents of groups and
+/// frames lie within their container.
+fn content(document: &Document) -> impl Iterator<Item = Rect> + '_ {
+    document
+        .shapes()
+        .filter(|shape| shape.parent.is_none() && document.is_visible(shape))
+        .map(|shape| shape.bounds())
+}
+
+fn rect_transform(rect: Rect) -> Transform {
+    Transform::new(rect.min_x, rect.min_y, rect.width(), rect.height())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_covers_board_and_viewport() {
+        let mut document = Document::new();
+        document.insert(ShapeKind::Rectangle, Transform::new(5000.0, 5000.0, 100.0, 100.0), Style::default());
+        let index = SpatialIndex::from_document(&document);
+        let state = State::default();
+        assert_eq!(Minimap::new(&index, &state, 200.0, 150.0), None);
+
+        let minimap = Minimap::new(&index, &state, 800.0, 600.0).unwrap();
+        assert_eq!(minimap.frame, Rect::new(584.0, 434.0, 784.0, 584.0));
+        assert!(minimap.view.contains_rect(&Rect::new(5000.0, 5000.0, 5100.0, 5100.0)));
+        assert!(minimap.view.contains_rect(&minimap.viewport));
+        // Square pixels: the view has the frame's shape.
+        assert!((minimap.view.width() / minimap.view.height() - 4.0 / 3.0).abs() < 1e-4);
+
+        // The camera shows the view on a frame-sized canvas.
+        let camera = minimap.camera();
+        let (width, height) = (minimap.frame.width(), minimap.frame.height());
+        let corner = camera.screen_to_world(width, height, Point::new(0.0, 0.0));
+        assert!(corner.distance(Point::new(minimap.view.min_x, minimap.view.min_y)) < 1e-1);
+        let center = minimap.to_world(minimap.frame.center());
+        assert!(center.distance(minimap.view.center()) < 1e-2);
+
+        // Background and one shape, with the viewport on its own.
+        assert_eq!(minimap.shapes(&document).shapes().len(), 2);
+        assert_eq!(minimap.viewport_shapes().shapes().len(), 1);
+    }
+}
@@ -0,0 +1,303 @@
+use crate::document::{Color, LayerId, Shape, ShapeKind, StrokePoint, Style, Transform, PREVIEW_SHAPE_ID};
+use crate::fractional_index::FractionalIndex;
+use crate::geometry::{Point, Rect};
+use crate::handles::{Handle, HANDLE_SIZE_PX};
+use crate::snap::Guide;
+
+/// Colour of selection outlines, boxes and the marquee.
+pub const ACCENT: Color = Color::rgba(0.2, 0.5, 1.0, 1.0);
+
+/// Colour of snapping guides, set apart from the selection chrome.
+pub const GUIDE: Color = Color::rgba(1.0, 0.2, 0.5, 1.0);
+
+/// Colour of shapes, or parts of them, about to be erased.
+pub const ERASE: Color = Color::rgba(1.0, 0.3, 0.3, 0.6);
+
+/// Line width of overlay strokes, in screen pixels.
+const LINE_WIDTH_PX: f32 = 1.5;
+
+/// Diameter of connector anchors, in screen pixels.
+const ANCHOR_SIZE_PX: f32 = 8.0;
+
+/// Editor chrome drawn above the document: selection outlines, bounding
+/// boxes, transform handles, the marquee. Built fresh each frame as throwaway shapes, so it
+/// reuses the shape batching; sizes are given in screen pixels and converted
+/// with the current zoom, keeping the chrome the same size at any zoom.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    world_units_per_pixel: f32,
+    shapes: Vec<Shape>,
+}
+
+impl Overlay {
+    pub fn new(world_units_per_pixel: f32) -> Self {
+        Self {
+            world_units_per_pixel,
+            shapes: Vec::new(),
+        }
+    }
+
+    pub fn world_units_per_pixel(&self) -> f32 {
+        self.world_units_per_pixel
+    }
+
+    pub fn shapes(&self) -> &[Shape] {
+        &self.shapes
+    }
+
+    pub fn push(&mut self, kind: ShapeKind, transform: Transform, style: Style) {
+        self.shapes.push(Shape {
+            id: PREVIEW_SHAPE_ID,
+            kind,
+            transform,
+            style,
+            z_index: FractionalIndex::default(),
+            layer: LayerId::default(),
+            parent: None,
+        });
+    }
+
+    fn line_style(&self, dash_px: &[f32]) -> Style {
+        Style {
+            stroke: ACCENT,
+            fill: None,
+            stroke_width: LINE_WIDTH_PX * self.world_units_per_pixel,
+            dash: dash_px.iter().map(|d| d * self.world_units_per_pixel).collect(),
+            opacity: 1.0,
+        }
+    }
+
+    /// Traces `shape`'s geometry: boxes and ellipses by their outline,
+    /// paths along their centre line.
+    pub fn outline(&mut self, shape: &Shape) {
+        let style = self.line_style(&[]);
+        self.push(traced(shape), shape.transform, style);
+    }
+
+    /// Traces `shape` as about to be erased, at least as wide as its stroke.
+    pub fn erased(&mut self, shape: &Shape) {
+        let style = Style {
+            stroke: ERASE,
+            stroke_width: shape.style.stroke_width.max(LINE_WIDTH_PX * self.world_units_per_pixel),
+            ..self.line_style(&[])
+        };
+        self.push(traced(shape), shape.transform, style);
+    }
+
+    /// The eraser's reach around `at`, `radius` in world units.
+    pub fn eraser(&mut self, at: Point, radius: f32) {
+        let style = self.line_style(&[]);
+        let transform = Transform::new(at.x - radius, at.y - radius, radius * 2.0, radius * 2.0);
+        self.push(ShapeKind::Ellipse, transform, style);
+    }
+
+    /// An axis-aligned dashed box around `rect`.
+    pub fn bounding_box(&mut self, rect: Rect) {
+        let style = self.line_style(&[4.0, 4.0]);
+        self.push(ShapeKind::Rectangle, rect_transform(rect), style);
+    }
+
+    /// The selection frame, turned with `frame.rotation`.
+    pub fn frame(&mut self, frame: &Transform) {
+        let style = self.line_style(&[]);
+        self.push(ShapeKind::Rectangle, *frame, style);
+    }
+
+    /// Resize handles as squares and the rotation handle as a circle, all
+    /// [`HANDLE_SIZE_PX`] across.
+    pub fn handles(&mut self, frame: &Transform) {
+        let size = HANDLE_SIZE_PX * self.world_units_per_pixel;
+        let style = Style {
+            fill: Some(Color::WHITE),
+            ..self.line_style(&[])
+        };
+        for handle in Handle::ALL {
+            let center = handle.position(frame, self.world_units_per_pixel);
+            let transform = Transform {
+                rotation: frame.rotation,
+                ..Transform::new(center.x - size * 0.5, center.y - size * 0.5, size, size)
+            };
+            let kind = if handle == Handle::Rotate {
+                ShapeKind::Ellipse
+            } else {
+                ShapeKind::Rectangle
+            };
+            self.push(kind, transform, style.clone());
+        }
+    }
+
+    /// The caret or selection highlight of a text edit, and an underline
+    /// below uncommitted IME input. Rects are in the shape's local box.
+    pub fn text_edit(&mut self, shape: &Shape, caret: Option<Rect>, selection: &[Rect], composition: &[Rect]) {
+        let highlight = Style {
+            fill: Some(Color::rgba(ACCENT.r, ACCENT.g, ACCENT.b, 0.3)),
+            stroke_width: 0.0,
+            ..self.line_style(&[])
+        };
+        for rect in selection {
+            self.push(ShapeKind::Rectangle, local_transform(shape, *rect), highlight.clone());
+        }
+        // Caret and underline in the text colour.
+        let ink = Style {
+            fill: Some(shape.style.stroke),
+            stroke_width: 0.0,
+            ..self.line_style(&[])
+        };
+        let width = LINE_WIDTH_PX * self.world_units_per_pixel;
+        if let Some(caret) = caret {
+            let bar = Rect::new(caret.min_x - width * 0.5, caret.min_y, caret.min_x + width * 0.5, caret.max_y);
+            self.push(ShapeKind::Rectangle, local_transform(shape, bar), ink.clone());
+        }
+        for rect in composition {
+            let underline = Rect::new(rect.min_x, rect.max_y - width, rect.max_x, rect.max_y);
+            self.push(ShapeKind::Rectangle, local_transform(shape, underline), ink.clone());
+        }
+    }
+
+    /// A connector anchor as a small circle, filled when `active`.
+    pub fn anchor(&mut self, at: Point, active: bool) {
+        let size = ANCHOR_SIZE_PX * self.world_units_per_pixel;
+        let style = Style {
+            fill: Some(if active { ACCENT } else { Color::WHITE }),
+            ..self.line_style(&[])
+        };
+        self.push(ShapeKind::Ellipse, Transform::new(at.x - size * 0.5, at.y - size * 0.5, size, size), style);
+    }
+
+    /// A snapping guide line.
+    pub fn guide(&mut self, guide: &Guide) {
+        let style = Style {
+            stroke: GUIDE,
+            ..self.line_style(&[])
+        };
+        // Cannot fail: two points always have bounds.
+        let bounds = Rect::from_points([guide.start, guide.end]).unwrap();
+        let corner = Point::new(bounds.min_x, bounds.min_y);
+        let kind = ShapeKind::Line {
+            start: guide.start - corner,
+            end: guide.end - corner,
+        };
+        self.push(kind, rect_transform(bounds), style);
+    }
+
+    /// A lasso outline, closed back to where it started.
+    pub fn lasso(&mut self, points: &[Point]) {
+        let Some(bounds) = Rect::from_points(points.iter().copied()) else {
+            return;
+        };
+        let corner = Point::new(bounds.min_x, bounds.min_y);
+        let kind = ShapeKind::Freehand {
+            points: points
+                .iter()
+                .chain(points.first())
+                .map(|p| StrokePoint { x: p.x - corner.x, y: p.y - corner.y, pressure: 0.5 })
+                .collect(),
+        };
+        let style = self.line_style(&[4.0, 4.0]);
+        self.push(kind, rect_transform(bounds), style);
+    }
+
+    /// A translucent selection rectangle.
+    pub fn marquee(&mut self, rect: Rect) {
+        let style = Style {
+            fill: Some(Color::rgba(ACCENT.r, ACCENT.g, ACCENT.b, 0.1)),
+            ..self.line_style(&[])
+        };
+        self.push(ShapeKind::Rectangle, rect_transform(rect), style);
+    }
+}
+
+/// The kind that draws `shape`'s geometry as a plain line.
+fn traced(shape: &Shape) -> ShapeKind {
+    match &shape.kind {
+        ShapeKind::Text { .. } | ShapeKind::Image { .. } | ShapeKind::Group | ShapeKind::Frame { .. } => ShapeKind::Rectangle,
+        ShapeKind::Arrow { start, end } => ShapeKind::Line {
+            start: *start,
+            end: *end,
+        },
+        ShapeKind::Connector { .. } => ShapeKind::Freehand {
+            points: shape
+                .path_points()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|p| {
+                    let local = shape.transform.to_local(p);
+                    StrokePoint { x: local.x, y: local.y, pressure: 0.5 }
+                })
+                .collect(),
+        },
+        // Neutral pressure gives an even line instead of the stroke's widths.
+        ShapeKind::Freehand { points } => ShapeKind::Freehand {
+            points: points.iter().map(|p| StrokePoint { pressure: 0.5, ..*p }).collect(),
+        },
+        other => other.clone(),
+    }
+}
+
+fn rect_transform(rect: Rect) -> Transform {
+    Transform::new(rect.min_x, rect.min_y, rect.width(), rect.height())
+}
+
+/// `rect`, given in `shape`'s local box, turned with the shape.
+fn local_transform(shape: &Shape, rect: Rect) -> Transform {
+    let center = shape.transform.to_world(rect.center());
+    Transform {
+        rotation: shape.transform.rotation,
+        ..Transform::new(center.x - rect.width() * 0.5, center.y - rect.height() * 0.5, rect.width(), rect.height())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::ShapeId;
+    use crate::geometry::Point;
+
+    #[test]
+    fn test_overlay_widths_follow_zoom() {
+        let mut near = Overlay::new(0.5);
+        let mut far = Overlay::new(4.0);
+        let rect = Rect::new(0.0, 0.0, 100.0, 50.0);
+        near.bounding_box(rect);
+        far.bounding_box(rect);
+        assert_eq!(near.shapes()[0].style.stroke_width, 0.75);
+        assert_eq!(far.shapes()[0].style.stroke_width, 6.0);
+        assert_eq!(far.shapes()[0].style.dash, vec![16.0, 16.0]);
+    }
+
+    #[test]
+    fn test_handles_keep_screen_size() {
+        let frame = Transform::new(0.0, 0.0, 100.0, 100.0);
+        let mut overlay = Overlay::new(2.0);
+        overlay.handles(&frame);
+        assert_eq!(overlay.shapes().len(), Handle::ALL.len());
+        assert!(overlay.shapes().iter().all(|s| s.transform.width == HANDLE_SIZE_PX * 2.0));
+    }
+
+    #[test]
+    fn test_outline_drops_arrow_heads_and_pressure() {
+        let mut overlay = Overlay::new(1.0);
+        let mut shape = Shape {
+            id: ShapeId(1),
+            kind: ShapeKind::Freehand {
+                points: vec![StrokePoint { x: 0.0, y: 0.0, pressure: 1.0 }],
+            },
+            transform: Transform::new(0.0, 0.0, 1.0, 1.0),
+            style: Style::default(),
+            z_index: FractionalIndex::default(),
+            layer: LayerId::default(),
+            parent: None,
+        };
+        overlay.outline(&shape);
+        shape.kind = ShapeKind::Arrow {
+            start: Point::ZERO,
+            end: Point::new(1.0, 1.0),
+        };
+        overlay.outline(&shape);
+        match &overlay.shapes()[0].kind {
+            ShapeKind::Freehand { points } => assert_eq!(points[0].pressure, 0.5),
+            other => panic!("unexpected kind {:?}", other),
+        }
+        assert!(matches!(overlay.shapes()[1].kind, ShapeKind::Line { .. }));
+    }
+}
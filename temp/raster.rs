@@ -0,0 +1,630 @@
+//! Headless CPU rasterizer for PNG export without a GPU.
+//!
+//! It draws the same [`ShapeBatches`] and glyph instances the GPU passes
+//! upload, evaluating their shaders in software with 4x multisampling, so a
+//! server can produce the PNGs the browser would.
+
+use std::ops::Range;
+
+use crate::batch::{paint_steps, Material, PaintStep, ShapeBatches, MESH_VERTEX_FLOATS, SDF_INSTANCE_FLOATS, SDF_KIND_ELLIPSE, SDF_KIND_RECT};
+use crate::document::{Color, Document, Shape};
+use crate::export::{blit_tile, encode_png, ExportPlan, PngOptions};
+use crate::geometry::{Point, Rect};
+use crate::glyph_atlas::{glyph_instances, GlyphAtlas, ATLAS_SIZE, GLYPH_INSTANCE_FLOATS};
+use crate::selection::Selection;
+use crate::svg::export_region;
+use crate::text::FontStack;
+use crate::textures::PLACEHOLDER_RGBA;
+
+/// Tile size for CPU rendering; bounds the sample buffer to 64 MiB.
+pub const CPU_TILE_SIZE: u32 = 1024;
+
+/// Standard 4x MSAA sample positions within a pixel.
+const SAMPLES: [(f32, f32); 4] = [(0.375, 0.125), (0.875, 0.375), (0.125, 0.625), (0.625, 0.875)];
+
+/// Premultiplied RGBA samples for a block of pixels.
+pub struct Rasterizer {
+    view: Rect,
+    width: u32,
+    height: u32,
+    pixels_per_unit: f32,
+    samples: Vec<[f32; 4]>,
+    /// World rect drawing is cut to, whole pixels like a scissor.
+    clip: Option<Rect>,
+}
+
+impl Rasterizer {
+    /// A `width` x `height` image showing `view`, cleared to `background` or
+    /// to transparent.
+    pub fn new(view: Rect, width: u32, height: u32, background: Option<Color>) -> Self {
+        let clear = background.map_or([0.0; 4], |c| [c.r * c.a, c.g * c.a, c.b * c.a, c.a]);
+        Self {
+            view,
+            width,
+            height,
+            pixels_per_unit: width as f32 / view.width(),
+            samples: vec![clear; width as usize * height as usize * SAMPLES.len()],
+            clip: None,
+        }
+    }
+
+    fn to_pixels(&self, world: Point) -> Point {
+        Point::new(
+            (world.x - self.view.min_x) * self.pixels_per_unit,
+            (world.y - self.view.min_y) * self.pixels_per_unit,
+        )
+    }
+
+    fn to_world(&self, pixel: Point) -> Point {
+        Point::new(
+            self.view.min_x + pixel.x / self.pixels_per_unit,
+            self.view.min_y + pixel.y / self.pixels_per_unit,
+        )
+    }
+
+    /// Limits later drawing to `clip`, or lifts the limit.
+    pub fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+    }
+
+    /// Pixel columns and rows overlapping `min..max` in pixel space, within
+    /// the clip.
+    fn pixel_range(&self, mut min: Point, mut max: Point) -> Option<(u32, u32, u32, u32)> {
+        if let Some(clip) = self.clip {
+            let (low, high) = (self.to_pixels(Point::new(clip.min_x, clip.min_y)), self.to_pixels(Point::new(clip.max_x, clip.max_y)));
+            min = Point::new(min.x.max(low.x.floor()), min.y.max(low.y.floor()));
+            max = Point::new(max.x.min(high.x.ceil()), max.y.min(high.y.ceil()));
+        }
+        let x0 = min.x.floor().max(0.0) as u32;
+        let y0 = min.y.floor().max(0.0) as u32;
+        let x1 = (max.x.ceil().max(0.0) as u32).min(self.width);
+        let y1 = (max.y.ceil().max(0.0) as u32).min(self.height);
+        (x0 < x1 && y0 < y1).then_some((x0, y0, x1, y1))
+    }
+
+    fn blend(&mut self, x: u32, y: u32, sample: usize, color: [f32; 4]) {
+        let index = (y as usize * self.width as usize + x as usize) * SAMPLES.len() + sample;
+        let dst = &mut self.samples[index];
+        let keep = 1.0 - color[3];
+        for channel in 0..4 {
+            dst[channel] = color[channel] + dst[channel] * keep;
+        }
+    }
+
+    /// Blends `color` into the samples of a pixel set in `mask`.
+    fn blend_mask(&mut self, x: u32, y: u32, mask: u8, color: [f32; 4]) {
+        for sample in (0..SAMPLES.len()).filter(|s| mask & (1 << s) != 0) {
+            self.blend(x, y, sample, color);
+        }
+    }
+
+    /// Draws the batches in `range` in order, each within its clip, as the
+    /// shape pass does.
+    pub fn draw(&mut self, batches: &ShapeBatches, range: Range<usize>) {
+        for batch in &batches.batches[range] {
+            self.clip = batch.clip;
+            match batch.material {
+                Material::Sdf => {
+                    for i in batch.start..batch.start + batch.count {
+                        let instance = &batches.instances[i * SDF_INSTANCE_FLOATS..(i + 1) * SDF_INSTANCE_FLOATS];
+                        self.draw_sdf(instance);
+                    }
+                }
+                Material::Mesh => {
+                    for triangle in batches.indices[batch.start..batch.start + batch.count].chunks_exact(3) {
+                        let vertex = |i: u32| {
+                            let v = &batches.vertices[i as usize * MESH_VERTEX_FLOATS..(i as usize + 1) * MESH_VERTEX_FLOATS];
+                            (Point::new(v[0], v[1]), [v[2], v[3], v[4], v[5]])
+                        };
+                        self.draw_triangle([vertex(triangle[0]), vertex(triangle[1]), vertex(triangle[2])]);
+                    }
+                }
+            }
+        }
+        self.clip = None;
+    }
+
+    /// The SDF shader in software: shaded once per pixel, written to the
+    /// samples the instance quad covers.
+    fn draw_sdf(&mut self, instance: &[f32]) {
+        let center = Point::new(instance[0], instance[1]);
+        let half_size = Point::new(instance[2].max(1e-4), instance[3].max(1e-4));
+        let (rotation, stroke_width) = (instance[4], instance[5]);
+        let ellipse = instance[6] >= SDF_KIND_ELLIPSE - 0.5;
+        let fill = Color::rgba(instance[7], instance[8], instance[9], instance[10]);
+        let stroke = Color::rgba(instance[11], instance[12], instance[13], instance[14]);
+
+        let per_pixel = 1.0 / self.pixels_per_unit;
+        let pad = stroke_width * 0.5 + per_pixel;
+        let extent = Point::new(instance[2] + pad, instance[3] + pad);
+        let to_local = |world: Point| (world - center).rotate_around(Point::ZERO, -rotation);
+        let distance = |local: Point| {
+            if ellipse {
+                sd_ellipse(local, half_size)
+            } else {
+                sd_box(local, half_size)
+            }
+        };
+
+        let reach = self.pixels_per_unit * extent.length();
+        let middle = self.to_pixels(center);
+        let Some((x0, y0, x1, y1)) = self.pixel_range(middle - Point::new(reach, reach), middle + Point::new(reach, reach)) else {
+            return;
+        };
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let covered = sample_mask(|sx, sy| {
+                    let local = to_local(self.to_world(Point::new(x as f32 + sx, y as f32 + sy)));
+                    local.x.abs() <= extent.x && local.y.abs() <= extent.y
+                });
+                if covered == 0 {
+                    continue;
+                }
+                let pixel = Point::new(x as f32 + 0.5, y as f32 + 0.5);
+                let d = distance(to_local(self.to_world(pixel)));
+                // fwidth(d): change across one pixel in x plus one in y.
+                let dx = distance(to_local(self.to_world(pixel + Point::new(1.0, 0.0))));
+                let dy = distance(to_local(self.to_world(pixel + Point::new(0.0, 1.0))));
+                let aa = (dx - d).abs() + (dy - d).abs();
+
+                let fill_coverage = 1.0 - smoothstep(-aa, aa, d);
+                let stroke_coverage = if stroke_width > 1e-6 {
+                    1.0 - smoothstep(stroke_width * 0.5 - aa, stroke_width * 0.5 + aa, d.abs())
+                } else {
+                    0.0
+                };
+                let stroke_alpha = stroke.a * stroke_coverage;
+                let fill_alpha = fill.a * fill_coverage * (1.0 - stroke_alpha);
+                let color = [
+                    stroke.r * stroke_alpha + fill.r * fill_alpha,
+                    stroke.g * stroke_alpha + fill.g * fill_alpha,
+                    stroke.b * stroke_alpha + fill.b * fill_alpha,
+                    stroke_alpha + fill_alpha,
+                ];
+                self.blend_mask(x, y, covered, color);
+            }
+        }
+    }
+
+    /// An image as the GPU draws it before its texture loads: a grey quad,
+    /// faded by `opacity` on top of the shape's own.
+    pub fn draw_placeholder(&mut self, shape: &Shape, opacity: f32) {
+        let t = &shape.transform;
+        let center = t.center();
+        let [r, g, b, _] = PLACEHOLDER_RGBA.map(|c| c as f32 / 255.0);
+        let alpha = shape.style.opacity * opacity;
+        let instance = [
+            center.x, center.y, t.width.abs() * 0.5, t.height.abs() * 0.5, t.rotation, 0.0, SDF_KIND_RECT, // geometry
+            r, g, b, alpha, // fill
+            0.0, 0.0, 0.0, 0.0, // stroke
+        ];
+        self.draw_sdf(&instance);
+    }
+
+    /// The text shader in software: glyph quads sampling `atlas`.
+    pub fn draw_glyphs(&mut self, instances: &[f32], atlas: &GlyphAtlas) {
+        for instance in instances.chunks_exact(GLYPH_INSTANCE_FLOATS) {
+            self.draw_glyph(instance, atlas);
+        }
+    }
+
+    fn draw_glyph(&mut self, instance: &[f32], atlas: &GlyphAtlas) {
+        let (min, size) = (Point::new(instance[0], instance[1]), Point::new(instance[2], instance[3]));
+        let (uv_min, uv_max) = (Point::new(instance[4], instance[5]), Point::new(instance[6], instance[7]));
+        let (center, rotation) = (Point::new(instance[8], instance[9]), instance[10]);
+        let color = Color::rgba(instance[11], instance[12], instance[13], instance[14]);
+        if size.x <= 0.0 || size.y <= 0.0 {
+            return;
+        }
+
+        // Quad coordinates in 0..1 for a world point.
+        let to_quad = |world: Point| {
+            let local = (world - center).rotate_around(Point::ZERO, -rotation) - min;
+            Point::new(local.x / size.x, local.y / size.y)
+        };
+        let field = |quad: Point| {
+            let uv = Point::new(
+                uv_min.x + (uv_max.x - uv_min.x) * quad.x,
+                uv_min.y + (uv_max.y - uv_min.y) * quad.y,
+            );
+            sample_bilinear(atlas, uv)
+        };
+
+        let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+            .map(|(x, y)| self.to_pixels(center + (min + Point::new(x * size.x, y * size.y)).rotate_around(Point::ZERO, rotation)));
+        let Some(bounds) = Rect::from_points(corners) else {
+            return;
+        };
+        let Some((x0, y0, x1, y1)) = self.pixel_range(Point::new(bounds.min_x, bounds.min_y), Point::new(bounds.max_x, bounds.max_y)) else {
+            return;
+        };
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let covered = sample_mask(|sx, sy| {
+                    let quad = to_quad(self.to_world(Point::new(x as f32 + sx, y as f32 + sy)));
+                    (0.0..=1.0).contains(&quad.x) && (0.0..=1.0).contains(&quad.y)
+                });
+                if covered == 0 {
+                    continue;
+                }
+                let pixel = Point::new(x as f32 + 0.5, y as f32 + 0.5);
+                let d = field(to_quad(self.to_world(pixel)));
+                let dx = field(to_quad(self.to_world(pixel + Point::new(1.0, 0.0))));
+                let dy = field(to_quad(self.to_world(pixel + Point::new(0.0, 1.0))));
+                let aa = ((dx - d).abs() + (dy - d).abs()).max(1e-4);
+                let alpha = color.a * smoothstep(0.5 - aa, 0.5 + aa, d);
+                let premultiplied = [color.r * alpha, color.g * alpha, color.b * alpha, alpha];
+                self.blend_mask(x, y, covered, premultiplied);
+            }
+        }
+    }
+
+    /// Coverage is decided per sample with a consistent tie rule, so
+    /// triangles sharing an edge never both cover a sample on it.
+    fn draw_triangle(&mut self, vertices: [(Point, [f32; 4]); 3]) {
+        let [(a, ca), (mut b, mut cb), (mut c, mut cc)] = vertices.map(|(p, color)| (self.to_pixels(p), color));
+        let mut area = edge(a, b, c);
+        if area < 0.0 {
+            std::mem::swap(&mut b, &mut c);
+            std::mem::swap(&mut cb, &mut cc);
+            area = -area;
+        }
+        if area <= 0.0 {
+            return;
+        }
+        let min = Point::new(a.x.min(b.x).min(c.x), a.y.min(b.y).min(c.y));
+        let max = Point::new(a.x.max(b.x).max(c.x), a.y.max(b.y).max(c.y));
+        let Some((x0, y0, x1, y1)) = self.pixel_range(min, max) else {
+            return;
+        };
+        let inside = |from: Point, to: Point, p: Point| {
+            let w = edge(from, to, p);
+            let d = to - from;
+            w > 0.0 || (w == 0.0 && (d.y > 0.0 || (d.y == 0.0 && d.x < 0.0)))
+        };
+        for y in y0..y1 {
+            for x in x0..x1 {
+                // Colour is interpolated at the pixel centre, like the GPU.
+                let center = Point::new(x as f32 + 0.5, y as f32 + 0.5);
+                let weights = [edge(b, c, center) / area, edge(c, a, center) / area, edge(a, b, center) / area];
+                let mut color = [0.0; 4];
+                for channel in 0..4 {
+                    let value = weights[0] * ca[channel] + weights[1] * cb[channel] + weights[2] * cc[channel];
+                    color[channel] = value.clamp(0.0, 1.0);
+                }
+                let premultiplied = [color[0] * color[3], color[1] * color[3], color[2] * color[3], color[3]];
+                for (sample, (sx, sy)) in SAMPLES.iter().enumerate() {
+                    let p = Point::new(x as f32 + sx, y as f32 + sy);
+                    if inside(b, c, p) && inside(c, a, p) && inside(a, b, p) {
+                        self.blend(x, y, sample, premultiplied);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Averages the samples into straight-alpha RGBA8, rows top to bottom.
+    pub fn resolve(&self) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.width as usize * self.height as usize * 4);
+        for samples in self.samples.chunks_exact(SAMPLES.len()) {
+            let mut sum = [0.0f32; 4];
+            for sample in samples {
+                for channel in 0..4 {
+                    sum[channel] += sample[channel] / SAMPLES.len() as f32;
+                }
+            }
+            let alpha = sum[3];
+            for value in &sum[..3] {
+                let straight = if alpha > 0.0 { value / alpha } else { 0.0 };
+                pixels.push(to_byte(straight));
+            }
+            pixels.push(to_byte(alpha));
+        }
+        pixels
+    }
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Bit `i` set when `inside` holds at sample `i`, given its offset in the pixel.
+fn sample_mask(inside: impl Fn(f32, f32) -> bool) -> u8 {
+    SAMPLES
+        .iter()
+        .enumerate()
+        .filter(|(_, &(sx, sy))| inside(sx, sy))
+        .fold(0, |mask, (i, _)| mask | 1 << i)
+}
+
+/// Twice the signed area of `a b p`; positive when `p` is to the right of
+/// `a -> b` in y-down pixel space.
+fn edge(a: Point, b: Point, p: Point) -> f32 {
+    (b - a).cross(p - a)
+}
+
+/// `texture()` with linear filtering and clamp-to-edge on the atlas.
+fn sample_bilinear(atlas: &GlyphAtlas, uv: Point) -> f32 {
+    let size = atlas.size() as i64;
+    let x = uv.x * size as f32 - 0.5;
+    let y = uv.y * size as f32 - 0.5;
+    let (fx, fy) = (x - x.floor(), y - y.floor());
+    let texel = |tx: i64, ty: i64| {
+        let (tx, ty) = (tx.clamp(0, size - 1), ty.clamp(0, size - 1));
+        atlas.pixels()[(ty * size + tx) as usize] as f32 / 255.0
+    };
+    let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+    let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1, y0) * fx;
+    let bottom = texel(x0, y0 + 1) * (1.0 - fx) + texel(x0 + 1, y0 + 1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn sd_box(p: Point, b: Point) -> f32 {
+    let q = Point::new(p.x.abs() - b.x, p.y.abs() - b.y);
+    Point::new(q.x.max(0.0), q.y.max(0.0)).length() + q.x.max(q.y).min(0.0)
+}
+
+fn sd_ellipse(p: Point, r: Point) -> f32 {
+    let k0 = Point::new(p.x / r.x, p.y / r.y).length();
+    let k1 = Point::new(p.x / (r.x * r.x), p.y / (r.y * r.y)).length().max(1e-6);
+    k0 * (k0 - 1.0) / k1
+}
+
+/// Renders `shapes` over `plan` tile by tile, returning straight-alpha RGBA8.
+/// Shapes, text and images are drawn in paint order, as in the browser,
+/// images as the grey placeholder since there is nothing to decode them
+/// with. `document` provides the frames and layers `shapes` are drawn with.
+pub fn render_rgba(
+    document: &Document,
+    shapes: &[&Shape],
+    fonts: &FontStack,
+    plan: &ExportPlan,
+    background: Option<Color>,
+    tile_size: u32,
+) -> Vec<u8> {
+    let mut batches = ShapeBatches::new();
+    batches.rebuild_painted(shapes.iter().copied(), |shape| document.paint(shape));
+    let mut atlas = GlyphAtlas::new(ATLAS_SIZE);
+    let mut glyphs = Vec::new();
+    let ends = glyph_instances(&mut atlas, fonts, shapes, |shape| document.paint(shape), &mut glyphs);
+    let steps = paint_steps(shapes, &batches.ends, &ends, |shape| document.paint(shape));
+    let mut image = vec![0u8; plan.width as usize * plan.height as usize * 4];
+    for tile in plan.tiles(tile_size) {
+        let mut rasterizer = Rasterizer::new(plan.tile_view(&tile), tile.width, tile.height, background);
+        for step in &steps {
+            match step {
+                PaintStep::Batches(range) => rasterizer.draw(&batches, range.clone()),
+                PaintStep::Glyphs(range, clip) => {
+                    rasterizer.set_clip(*clip);
+                    rasterizer.draw_glyphs(&glyphs[range.start * GLYPH_INSTANCE_FLOATS..range.end * GLYPH_INSTANCE_FLOATS], &atlas);
+                    rasterizer.set_clip(None);
+                }
+                PaintStep::Image(id) => {
+                    let Some(shape) = document.get(*id) else {
+                        continue;
+                    };
+                    let paint = document.paint(shape);
+                    rasterizer.set_clip(paint.clip);
+                    rasterizer.draw_placeholder(shape, paint.opacity);
+                    rasterizer.set_clip(None);
+                }
+            }
+        }
+        blit_tile(&mut image, plan.width, &tile, &rasterizer.resolve(), false);
+    }
+    image
+}
+
+/// Exports a PNG without a GPU, e.g. from a command-line tool.
+pub fn render_png(
+    document: &Document,
+    selection: &Selection,
+    fonts: &FontStack,
+    options: &PngOptions,
+) -> Result<Vec<u8>, String> {
+    let (shapes, view) = export_region(document, selection, options.area, options.padding)?;
+    let plan = ExportPlan::new(view, options.size)?;
+    let pixels = render_rgba(document, &shapes, fonts, &plan, options.clear_color(), CPU_TILE_SIZE);
+    encode_png(plan.width, plan.height, &pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{ShapeKind, Style, Transform};
+    use crate::export::ExportSize;
+    use crate::text::{test_fonts, TextAlign};
+
+    fn document() -> Document {
+        let mut document = Document::new();
+        document.insert(
+            ShapeKind::Rectangle,
+            Transform::new(10.0, 10.0, 40.0, 20.0),
+            Style {
+                fill: Some(Color::rgba(1.0, 0.0, 0.0, 1.0)),
+                ..Style::default()
+            },
+        );
+        document.insert(
+            ShapeKind::Line {
+                start: Point::new(0.0, 0.0),
+                end: Point::new(60.0, 40.0),
+            },
+            Transform::new(0.0, 0.0, 60.0, 40.0),
+            Style {
+                stroke: Color::rgba(0.0, 0.0, 1.0, 1.0),
+                stroke_width: 4.0,
+                ..Style::default()
+            },
+        );
+        document
+    }
+
+    fn pixel(image: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * width + x) * 4) as usize;
+        [image[i], image[i + 1], image[i + 2], image[i + 3]]
+    }
+
+    #[test]
+    fn test_fill_stroke_and_background() {
+        let document = document();
+        let shapes = document.shapes_in_z_order();
+        let plan = ExportPlan::new(Rect::new(0.0, 0.0, 64.0, 48.0), ExportSize::Scale(1.0)).unwrap();
+        let image = render_rgba(&document, &shapes, &FontStack::new(), &plan, None, CPU_TILE_SIZE);
+        // Inside the rectangle, away from the line.
+        assert_eq!(pixel(&image, plan.width, 40, 14), [255, 0, 0, 255]);
+        // On the rectangle's black border, softened by antialiasing.
+        let border = pixel(&image, plan.width, 30, 10);
+        assert!(border[0] < 64 && border[1] == 0 && border[3] > 240);
+        // On the line, which is drawn on top.
+        assert_eq!(pixel(&image, plan.width, 30, 20), [0, 0, 255, 255]);
+        // Empty corner stays transparent.
+        assert_eq!(pixel(&image, plan.width, 62, 2)[3], 0);
+
+        let opaque = render_rgba(&document, &shapes, &FontStack::new(), &plan, Some(Color::WHITE), CPU_TILE_SIZE);
+        assert_eq!(pixel(&opaque, plan.width, 62, 2), [255, 255, 255, 255]);
+        // Antialiased edge of the line blends toward white.
+        assert!(opaque.chunks(4).any(|p| p[0] > 0 && p[0] < 255 && p[1] == p[0] && p[2] == 255));
+    }
+
+    #[test]
+    fn test_tiles_match_a_single_pass() {
+        let document = document();
+        let shapes = document.shapes_in_z_order();
+        let plan = ExportPlan::new(Rect::new(-3.0, -3.0, 70.0, 50.0), ExportSize::Scale(2.0)).unwrap();
+        let whole = render_rgba(&document, &shapes, &FontStack::new(), &plan, Some(Color::WHITE), CPU_TILE_SIZE);
+        let tiled = render_rgba(&document, &shapes, &FontStack::new(), &plan, Some(Color::WHITE), 17);
+        // Tiles see the same world through different origins; allow rounding.
+        assert!(whole.iter().zip(&tiled).all(|(a, b)| a.abs_diff(*b) <= 1));
+    }
+
+    #[test]
+    fn test_shared_edges_are_seamless() {
+        // Two triangles of one opaque square must not leave a lighter seam.
+        let mut batches = ShapeBatches::new();
+        batches.vertices = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]
+            .iter()
+            .flat_map(|&(x, y)| [x, y, 0.0, 0.0, 0.0, 1.0])
+            .collect();
+        batches.indices = vec![0, 1, 2, 0, 2, 3];
+        batches.batches.push(crate::batch::DrawBatch {
+            material: Material::Mesh,
+            start: 0,
+            count: 6,
+            clip: None,
+        });
+        let mut rasterizer = Rasterizer::new(Rect::new(0.0, 0.0, 10.0, 10.0), 10, 10, Some(Color::WHITE));
+        rasterizer.draw(&batches, 0..batches.batches.len());
+        assert!(rasterizer.resolve().chunks(4).all(|p| p == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_text_is_drawn() {
+        let mut document = Document::new();
+        document.insert(
+            ShapeKind::Text {
+                content: "H".to_string(),
+                font_size: 100.0,
+                align: TextAlign::Left,
+            },
+            Transform::new(0.0, 0.0, 200.0, 120.0),
+            Style::default(),
+        );
+        let shapes = document.shapes_in_z_order();
+        let plan = ExportPlan::new(Rect::new(0.0, 0.0, 100.0, 120.0), ExportSize::Scale(1.0)).unwrap();
+        let image = render_rgba(&document, &shapes, &test_fonts(), &plan, None, CPU_TILE_SIZE);
+        let ink = image.chunks(4).filter(|p| p == &[0, 0, 0, 255]).count();
+        let edges = image.chunks(4).filter(|p| p[3] > 0 && p[3] < 255).count();
+        assert!(ink > 500, "{} solid pixels", ink);
+        assert!(edges > 0 && edges < ink);
+        // Above the cap height stays clear.
+        assert_eq!(pixel(&image, plan.width, 30, 5)[3], 0);
+        // Without fonts there is nothing to draw.
+        let blank = render_rgba(&document, &shapes, &FontStack::new(), &plan, None, CPU_TILE_SIZE);
+        assert!(blank.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_shapes_above_text_cover_it() {
+        let mut document = Document::new();
+        let text = ShapeKind::Text {
+            content: "H".to_string(),
+            font_size: 100.0,
+            align: TextAlign::Left,
+        };
+        document.insert(text, Transform::new(0.0, 0.0, 200.0, 120.0), Style::default());
+        let cover = document.insert(
+            ShapeKind::Rectangle,
+            Transform::new(0.0, 0.0, 100.0, 120.0),
+            Style {
+                fill: Some(Color::WHITE),
+                stroke_width: 0.0,
+                ..Style::default()
+            },
+        );
+        let plan = ExportPlan::new(Rect::new(0.0, 0.0, 100.0, 120.0), ExportSize::Scale(1.0)).unwrap();
+        let render = |document: &Document| {
+            let shapes = document.shapes_in_z_order();
+            render_rgba(document, &shapes, &test_fonts(), &plan, None, CPU_TILE_SIZE)
+        };
+        // Inside the rectangle, away from its edges.
+        let ink = |image: &[u8]| (10..90).flat_map(|y| (10..90).map(move |x| (x, y))).filter(|&(x, y)| pixel(image, plan.width, x, y)[0] < 128).count();
+        assert_eq!(ink(&render(&document)), 0);
+
+        // Sent to the back, the rectangle is under the text.
+        crate::arrange::arrange(&mut document, &[cover], crate::arrange::Arrange::ToBack);
+        assert!(ink(&render(&document)) > 500);
+    }
+
+    #[test]
+    fn test_images_draw_as_placeholders_in_paint_order() {
+        let mut document = Document::new();
+        let image = document.insert(
+            ShapeKind::Image { source: "a.png".to_string() },
+            Transform::new(0.0, 0.0, 100.0, 100.0),
+            Style::default(),
+        );
+        document.insert(
+            ShapeKind::Rectangle,
+            Transform::new(50.0, 0.0, 50.0, 100.0),
+            Style {
+                fill: Some(Color::rgba(1.0, 0.0, 0.0, 1.0)),
+                stroke_width: 0.0,
+                ..Style::default()
+            },
+        );
+        let plan = ExportPlan::new(Rect::new(0.0, 0.0, 100.0, 100.0), ExportSize::Scale(1.0)).unwrap();
+        let render = |document: &Document| {
+            let shapes = document.shapes_in_z_order();
+            render_rgba(document, &shapes, &FontStack::new(), &plan, None, CPU_TILE_SIZE)
+        };
+        let image_pixels = render(&document);
+        assert_eq!(pixel(&image_pixels, plan.width, 25, 50), PLACEHOLDER_RGBA);
+        assert_eq!(pixel(&image_pixels, plan.width, 75, 50), [255, 0, 0, 255]);
+
+        // Brought to the front, the image hides the rectangle.
+        crate::arrange::arrange(&mut document, &[image], crate::arrange::Arrange::ToFront);
+        let image_pixels = render(&document);
+        assert_eq!(pixel(&image_pixels, plan.width, 75, 50), PLACEHOLDER_RGBA);
+    }
+
+    #[test]
+    fn test_render_png_scales() {
+        let document = document();
+        let options = PngOptions {
+            size: ExportSize::Scale(4.0),
+            ..PngOptions::default()
+        };
+        let png = render_png(&document, &Selection::new(), &FontStack::new(), &options).unwrap();
+        let decoder = png::Decoder::new(png.as_slice());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        // Bounds (-2..62, -2..42) plus 16 padding, four pixels per unit.
+        assert_eq!((info.width, info.height), (384, 304));
+    }
+}
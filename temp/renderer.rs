@@ -1,26 +1,76 @@
 use wasm_bindgen::JsCast;
-use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlBuffer, HtmlCanvasElement};
+use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlFramebuffer, WebGlRenderbuffer, WebGlVertexArrayObject, HtmlCanvasElement};
+use crate::batch::{paint_steps, PaintStep};
+use crate::document::{Color, Document, Shape};
+use crate::export::{blit_tile, unpremultiply, ExportPlan};
+use crate::geometry::Rect;
+use crate::image_pass::ImagePass;
+use crate::minimap::Minimap;
 use crate::shaders::ShaderProgram;
+use crate::shape_pass::{ShapePass, Target};
+use crate::spatial::SpatialIndex;
 use crate::state::State;
+use crate::text::FontStack;
+use crate::text_pass::TextPass;
 
 pub struct WebGLRenderer {
     program: ShaderProgram,
-    buffer: WebGlBuffer,
+    grid_vao: WebGlVertexArrayObject,
     grid_size: i32,
+    shapes: ShapePass,
+    text: TextPass,
+    pub images: ImagePass,
+    /// Draws of the uploaded document, in paint order.
+    steps: Vec<PaintStep>,
+    /// Document and font revisions the upload was made for.
+    uploaded_revision: Option<(u64, u64)>,
+    uploaded_area: Option<Rect>,
 }
 
 impl WebGLRenderer {
     pub fn new(context: &WebGl2RenderingContext) -> Result<Self, String> {
         let program = ShaderProgram::new(context)?;
         let buffer = Self::setup_vertex_buffer(context)?;
-        
+        let grid_vao = Self::setup_grid_vao(context, &buffer)?;
+        let shapes = ShapePass::new(context)?;
+        let text = TextPass::new(context)?;
+        let images = ImagePass::new(context)?;
+
         Ok(Self {
             program,
-            buffer,
+            grid_vao,
             grid_size: 51,
+            shapes,
+            text,
+            images,
+            steps: Vec::new(),
+            uploaded_revision: None,
+            uploaded_area: None,
         })
     }
 
+    /// Records the grid's attribute layout once so `render` only binds the VAO.
+    fn setup_grid_vao(context: &WebGl2RenderingContext, buffer: &WebGlBuffer) -> Result<WebGlVertexArrayObject, String> {
+        let vao = context.create_vertex_array()
+            .ok_or("Failed to create vertex array")?;
+
+        context.bind_vertex_array(Some(&vao));
+        context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(buffer));
+        // a_position is pinned to location 0 in the vertex shader.
+        context.enable_vertex_attrib_array(0);
+        context.vertex_attrib_pointer_with_i32(
+            0,
+            2,
+            WebGl2RenderingContext::FLOAT,
+            false,
+            0,
+            0,
+        );
+        context.bind_vertex_array(None);
+
+        Ok(vao)
+    }
+
     fn setup_vertex_buffer(context: &WebGl2RenderingContext) -> Result<WebGlBuffer, String> {
         let grid_size = 51;
         let mut positions = Vec::with_capacity(grid_size * grid_size * 2);
@@ -58,7 +108,26 @@ impl WebGLRenderer {
         context.viewport(0, 0, display_width as i32, display_height as i32);
     }
 
-    pub fn render(&self, context: &WebGl2RenderingContext, state: &State) {
+    /// Draws the grid, the document with its text and images, `preview` (a
+    /// shape still being drawn) and the editor `overlay`, with the `minimap`
+    /// on top.
+    ///
+    /// Only shapes near the viewport are uploaded, found through the spatial
+    /// index. The upload covers the visible rect plus a margin and is redone
+    /// when the document or fonts change or the camera leaves the covered
+    /// area.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        context: &WebGl2RenderingContext,
+        state: &State,
+        document: &Document,
+        index: &SpatialIndex,
+        fonts: &FontStack,
+        preview: Option<&Shape>,
+        overlay: &[Shape],
+        minimap: Option<&Minimap>,
+    ) {
         context.clear_color(1.0, 1.0, 1.0, 1.0);
         context.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
 
@@ -70,18 +139,7 @@ impl WebGLRenderer {
         let aspect_ratio = canvas.width() as f32 / canvas.height() as f32;
 
         context.use_program(Some(&self.program.program));
-        context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&self.buffer));
-
-        let position_loc = context.get_attrib_location(&self.program.program, "a_position");
-        context.enable_vertex_attrib_array(position_loc as u32);
-        context.vertex_attrib_pointer_with_i32(
-            position_loc as u32,
-            2,
-            WebGl2RenderingContext::FLOAT,
-            false,
-            0,
-            0,
-        );
+        context.bind_vertex_array(Some(&self.grid_vao));
 
         // Set uniforms
         self.program.set_uniform_1f(context, "u_aspect_ratio", aspect_ratio);
@@ -89,5 +147,190 @@ impl WebGLRenderer {
         self.program.set_uniform_2f(context, "u_offset", state.offset_x, state.offset_y);
 
         context.draw_arrays(WebGl2RenderingContext::POINTS, 0, self.grid_size * self.grid_size);
+        context.bind_vertex_array(None);
+
+        let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+
+        let visible = state.visible_world_rect(width, height);
+        let revision = (document.revision(), fonts.revision());
+        let area_covered = self.uploaded_area.is_some_and(|area| area.contains_rect(&visible));
+        if self.uploaded_revision != Some(revision) || !area_covered {
+            let area = visible.expand(visible.width().max(visible.height()) * 0.5);
+            let mut shapes: Vec<&Shape> = index
+                .query_rect(&area)
+                .into_iter()
+                .filter_map(|id| document.get(id))
+                .filter(|shape| document.is_visible(shape))
+                .collect();
+            document.sort_in_paint_order(&mut shapes);
+            let batch_ends = self.shapes.upload_document(context, document, &shapes);
+            let glyph_ends = self.text.upload_document(context, fonts, document, &shapes);
+            self.steps = paint_steps(&shapes, &batch_ends, &glyph_ends, |shape| document.paint(shape));
+            self.uploaded_revision = Some(revision);
+            self.uploaded_area = Some(area);
+        }
+        self.images.begin_frame();
+        let steps = std::mem::take(&mut self.steps);
+        self.draw_steps(context, Target::Screen, state, document, width, height, &steps);
+        self.steps = steps;
+        if let Some(shape) = preview {
+            self.shapes.render_preview(context, state, shape, width, height);
+        }
+        self.shapes.render_overlay(context, state, overlay, width, height);
+        if let Some(minimap) = minimap {
+            self.shapes.render_minimap(context, document, minimap, width, height);
+        }
+    }
+
+    /// Renders `shapes` over `plan` offscreen and reads the pixels back as
+    /// straight-alpha RGBA8, rows top to bottom. Large plans are drawn in
+    /// tiles no bigger than the GPU's texture and renderbuffer limits.
+    /// `document` provides the frames and layers `shapes` are drawn with.
+    pub fn export_pixels(
+        &mut self,
+        context: &WebGl2RenderingContext,
+        document: &Document,
+        shapes: &[&Shape],
+        fonts: &FontStack,
+        plan: &ExportPlan,
+        background: Option<Color>,
+    ) -> Result<Vec<u8>, String> {
+        type Gl = WebGl2RenderingContext;
+        let limit = |name: u32| {
+            context
+                .get_parameter(name)
+                .ok()
+                .and_then(|value| value.as_f64())
+                .map_or(1, |value| value as u32)
+        };
+        let tile_size = limit(Gl::MAX_TEXTURE_SIZE).min(limit(Gl::MAX_RENDERBUFFER_SIZE)).max(1);
+        let samples = limit(Gl::MAX_SAMPLES).min(4) as i32;
+        let tiles = plan.tiles(tile_size);
+        let (buffer_width, buffer_height) = tiles
+            .iter()
+            .fold((1, 1), |(w, h), tile| (tile.width.max(w), tile.height.max(h)));
+
+        let multisampled = Self::create_target(context, samples, buffer_width, buffer_height)?;
+        let resolved = match Self::create_target(context, 0, buffer_width, buffer_height) {
+            Ok(target) => target,
+            Err(e) => {
+                Self::delete_target(context, &multisampled);
+                return Err(e);
+            }
+        };
+
+        let batch_ends = self.shapes.upload_export(context, document, shapes);
+        let glyph_ends = self.text.upload_export(context, fonts, document, shapes);
+        let steps = paint_steps(shapes, &batch_ends, &glyph_ends, |shape| document.paint(shape));
+        // The export may have cleared the glyph atlas under the document.
+        self.uploaded_revision = None;
+        let clear = background.map_or([0.0; 4], |c| [c.r * c.a, c.g * c.a, c.b * c.a, c.a]);
+        let mut image = vec![0u8; plan.width as usize * plan.height as usize * 4];
+        let mut pixels = Vec::new();
+        let mut result = Ok(());
+        for tile in &tiles {
+            let (width, height) = (tile.width as i32, tile.height as i32);
+            context.bind_framebuffer(Gl::FRAMEBUFFER, Some(&multisampled.0));
+            context.viewport(0, 0, width, height);
+            context.clear_color(clear[0], clear[1], clear[2], clear[3]);
+            context.clear(Gl::COLOR_BUFFER_BIT);
+            let camera = plan.camera(tile);
+            self.draw_steps(context, Target::Export, &camera, document, tile.width as f32, tile.height as f32, &steps);
+
+            context.bind_framebuffer(Gl::READ_FRAMEBUFFER, Some(&multisampled.0));
+            context.bind_framebuffer(Gl::DRAW_FRAMEBUFFER, Some(&resolved.0));
+            context.blit_framebuffer(0, 0, width, height, 0, 0, width, height, Gl::COLOR_BUFFER_BIT, Gl::NEAREST);
+
+            context.bind_framebuffer(Gl::FRAMEBUFFER, Some(&resolved.0));
+            pixels.resize(tile.width as usize * tile.height as usize * 4, 0);
+            result = context
+                .read_pixels_with_opt_u8_array(0, 0, width, height, Gl::RGBA, Gl::UNSIGNED_BYTE, Some(&mut pixels))
+                .map_err(|e| format!("Failed to read export pixels: {:?}", e));
+            if result.is_err() {
+                break;
+            }
+            blit_tile(&mut image, plan.width, tile, &pixels, true);
+        }
+
+        context.bind_framebuffer(Gl::FRAMEBUFFER, None);
+        Self::delete_target(context, &multisampled);
+        Self::delete_target(context, &resolved);
+        // Hand the canvas back the way `render` expects it.
+        if let Some(canvas) = context.canvas().and_then(|c| c.dyn_into::<HtmlCanvasElement>().ok()) {
+            context.viewport(0, 0, canvas.width() as i32, canvas.height() as i32);
+        }
+
+        result?;
+        unpremultiply(&mut image);
+        Ok(image)
     }
-}
\ No newline at end of file
+
+    /// Draws `steps` of `target` in order, switching between the shape,
+    /// text and image programs as they interleave.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_steps(
+        &mut self,
+        context: &WebGl2RenderingContext,
+        target: Target,
+        state: &State,
+        document: &Document,
+        width: f32,
+        height: f32,
+        steps: &[PaintStep],
+    ) {
+        self.shapes.set_camera(context, state, width, height);
+        self.text.set_camera(context, state, width, height);
+        self.images.set_camera(context, state, width, height);
+        context.enable(WebGl2RenderingContext::BLEND);
+        context.blend_func(WebGl2RenderingContext::ONE, WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA);
+        for step in steps {
+            match step {
+                PaintStep::Batches(range) => self.shapes.draw(context, target, state, width, height, range.clone()),
+                PaintStep::Glyphs(range, clip) => self.text.draw(context, target, state, width, height, range.clone(), *clip),
+                PaintStep::Image(id) => {
+                    if let Some(shape) = document.get(*id) {
+                        self.images.draw(context, target, state, width, height, shape, document.paint(shape));
+                    }
+                }
+            }
+        }
+        context.disable(WebGl2RenderingContext::BLEND);
+    }
+
+    /// A framebuffer with one RGBA8 renderbuffer, multisampled when
+    /// `samples` is above zero.
+    fn create_target(
+        context: &WebGl2RenderingContext,
+        samples: i32,
+        width: u32,
+        height: u32,
+    ) -> Result<(WebGlFramebuffer, WebGlRenderbuffer), String> {
+        type Gl = WebGl2RenderingContext;
+        let renderbuffer = context.create_renderbuffer()
+            .ok_or("Failed to create renderbuffer")?;
+        context.bind_renderbuffer(Gl::RENDERBUFFER, Some(&renderbuffer));
+        context.renderbuffer_storage_multisample(Gl::RENDERBUFFER, samples, Gl::RGBA8, width as i32, height as i32);
+        context.bind_renderbuffer(Gl::RENDERBUFFER, None);
+
+        let Some(framebuffer) = context.create_framebuffer() else {
+            context.delete_renderbuffer(Some(&renderbuffer));
+            return Err("Failed to create framebuffer".to_string());
+        };
+        context.bind_framebuffer(Gl::FRAMEBUFFER, Some(&framebuffer));
+        context.framebuffer_renderbuffer(Gl::FRAMEBUFFER, Gl::COLOR_ATTACHMENT0, Gl::RENDERBUFFER, Some(&renderbuffer));
+        let status = context.check_framebuffer_status(Gl::FRAMEBUFFER);
+        context.bind_framebuffer(Gl::FRAMEBUFFER, None);
+
+        let target = (framebuffer, renderbuffer);
+        if status != Gl::FRAMEBUFFER_COMPLETE {
+            Self::delete_target(context, &target);
+            return Err(format!("Export framebuffer is incomplete (status {:#x})", status));
+        }
+        Ok(target)
+    }
+
+    fn delete_target(context: &WebGl2RenderingContext, target: &(WebGlFramebuffer, WebGlRenderbuffer)) {
+        context.delete_framebuffer(Some(&target.0));
+        context.delete_renderbuffer(Some(&target.1));
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::BTreeSet;
+
+use crate::document::{Document, ShapeId};
+use crate::geometry::{Point, Rect};
+use crate::hit_test::{contained_in_polygon, contained_in_rect, intersects_polygon, intersects_rect};
+use crate::spatial::SpatialIndex;
+
+/// Which shapes a marquee or lasso picks up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarqueeMode {
+    /// Shapes lying entirely inside the marquee.
+    #[default]
+    Contained,
+    /// Shapes with any painted part inside the marquee.
+    Touching,
+}
+
+impl MarqueeMode {
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Contained => Self::Touching,
+            Self::Touching => Self::Contained,
+        }
+    }
+}
+
+/// The set of selected shapes, kept in id order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    ids: BTreeSet<ShapeId>,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, id: ShapeId) -> bool {
+        self.ids.contains(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ShapeId> + '_ {
+        self.ids.iter().copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.ids.clear();
+    }
+
+    pub fn select_only(&mut self, id: ShapeId) {
+        self.ids.clear();
+        self.ids.insert(id);
+    }
+
+    /// Replaces the selection with `ids`.
+    pub fn set<I: IntoIterator<Item = ShapeId>>(&mut self, ids: I) {
+        self.ids = ids.into_iter().collect();
+    }
+
+    pub fn extend<I: IntoIterator<Item = ShapeId>>(&mut self, ids: I) {
+        self.ids.extend(ids);
+    }
+
+    /// Adds `id` if absent, removes it otherwise. Returns whether it is now selected.
+    pub fn toggle(&mut self, id: ShapeId) -> bool {
+        if self.ids.remove(&id) {
+            false
+        } else {
+            self.ids.insert(id);
+            true
+        }
+    }
+
+    pub fn remove(&mut self, id: ShapeId) -> bool {
+        self.ids.remove(&id)
+    }
+
+    /// Union of the selected shapes' world bounds.
+    pub fn bounds(&self, document: &Document) -> Option<Rect> {
+        self.iter()
+            .filter_map(|id| document.get(id))
+            .map(|shape| shape.bounds())
+            .reduce(|a, b| a.union(&b))
+    }
+}
+
+/// Shapes picked up by a marquee over `rect`, skipping hidden and locked
+/// layers. `tolerance` is how closely curved outlines are sampled, in world
+/// units.
+pub fn marquee_hits(
+    document: &Document,
+    index: &SpatialIndex,
+    rect: &Rect,
+    mode: MarqueeMode,
+    tolerance: f32,
+) -> Vec<ShapeId> {
+    index
+        .query_rect(rect)
+        .into_iter()
+        .filter_map(|id| document.get(id))
+        .filter(|shape| document.is_hittable(shape))
+        .filter(|shape| match mode {
+            MarqueeMode::Contained => contained_in_rect(shape, rect, tolerance),
+            MarqueeMode::Touching => intersects_rect(shape, rect, tolerance),
+        })
+        .map(|shape| shape.id)
+        .collect()
+}
+
+/// Shapes picked up by a lasso along the closed `polygon`, like
+/// [`marquee_hits`].
+pub fn lasso_hits(
+    document: &Document,
+    index: &SpatialIndex,
+    polygon: &[Point],
+    mode: MarqueeMode,
+    tolerance: f32,
+) -> Vec<ShapeId> {
+    let Some(bounds) = Rect::from_points(polygon.iter().copied()) else {
+        return Vec::new();
+    };
+    index
+        .query_rect(&bounds)
+        .into_iter()
+        .filter_map(|id| document.get(id))
+        .filter(|shape| document.is_hittable(shape))
+        .filter(|shape| match mode {
+            MarqueeMode::Contained => contained_in_polygon(shape, polygon, tolerance),
+            MarqueeMode::Touching => intersects_polygon(shape, polygon, tolerance),
+        })
+        .map(|shape| shape.id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{ShapeKind, Style, Transform};
+
+    #[test]
+    fn test_toggle_and_bounds() {
+        let mut doc = Document::new();
+        let a = doc.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 10.0, 10.0), Style::default());
+        let b = doc.insert(ShapeKind::Rectangle, Transform::new(20.0, 0.0, 10.0, 10.0), Style::default());
+
+        let mut selection = Selection::new();
+        assert!(selection.toggle(a));
+        assert!(selection.toggle(b));
+        assert!(!selection.toggle(a));
+        assert_eq!(selection.iter().collect::<Vec<_>>(), vec![b]);
+
+        selection.extend([a]);
+        assert_eq!(selection.bounds(&doc), Some(Rect::new(-1.0, -1.0, 31.0, 11.0)));
+    }
+
+    #[test]
+    fn test_marquee_modes() {
+        let mut doc = Document::new();
+        let inside = doc.insert(ShapeKind::Rectangle, Transform::new(10.0, 10.0, 20.0, 20.0), Style::default());
+        let straddling = doc.insert(ShapeKind::Rectangle, Transform::new(90.0, 10.0, 20.0, 20.0), Style::default());
+        doc.insert(ShapeKind::Rectangle, Transform::new(500.0, 500.0, 20.0, 20.0), Style::default());
+        let index = SpatialIndex::from_document(&doc);
+        let marquee = Rect::new(0.0, 0.0, 100.0, 100.0);
+
+        let contained = marquee_hits(&doc, &index, &marquee, MarqueeMode::Contained, 0.5);
+        assert_eq!(contained, vec![inside]);
+        let mut touching = marquee_hits(&doc, &index, &marquee, MarqueeMode::Touching, 0.5);
+        touching.sort();
+        assert_eq!(touching, vec![inside, straddling]);
+    }
+}
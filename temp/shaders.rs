@@ -27,14 +27,207 @@ void main() {
     outColor = vec4(0.8, 0.8, 0.8, 1.0);
 }"##;
 
+/// Maps y-down world coordinates into the grid's space, then to clip space.
+/// Shared by every shader that draws world-space geometry.
+const WORLD_TO_CLIP: &str = r##"
+uniform float u_aspect_ratio;
+uniform float u_zoom;
+uniform vec2 u_offset;
+uniform float u_world_scale;
+
+vec4 world_to_clip(vec2 world) {
+    vec2 grid = vec2(world.x, -world.y) * u_world_scale * u_zoom + u_offset;
+    vec2 adjusted = grid * vec2(min(1.0, 1.0 / u_aspect_ratio), min(1.0, u_aspect_ratio));
+    return vec4(adjusted, 0.0, 1.0);
+}
+"##;
+
+const SDF_VERTEX_SHADER: &str = r##"
+layout(location = 0) in vec2 a_corner;
+layout(location = 1) in vec2 a_center;
+layout(location = 2) in vec2 a_half_size;
+layout(location = 3) in float a_rotation;
+layout(location = 4) in float a_stroke_width;
+layout(location = 5) in float a_kind;
+layout(location = 6) in vec4 a_fill;
+layout(location = 7) in vec4 a_stroke;
+uniform float u_pixel_size;
+out vec2 v_local;
+out vec2 v_half_size;
+out float v_stroke_width;
+flat out float v_kind;
+out vec4 v_fill;
+out vec4 v_stroke;
+void main() {
+    // Grow the quad to fit the outer half of the stroke plus an AA pixel.
+    vec2 extent = a_half_size + vec2(a_stroke_width * 0.5 + u_pixel_size);
+    vec2 local = a_corner * extent;
+    float s = sin(a_rotation);
+    float c = cos(a_rotation);
+    vec2 world = a_center + vec2(local.x * c - local.y * s, local.x * s + local.y * c);
+
+    v_local = local;
+    v_half_size = a_half_size;
+    v_stroke_width = a_stroke_width;
+    v_kind = a_kind;
+    v_fill = a_fill;
+    v_stroke = a_stroke;
+    gl_Position = world_to_clip(world);
+}"##;
+
+const SDF_FRAGMENT_SHADER: &str = r##"#version 300 es
+precision highp float;
+in vec2 v_local;
+in vec2 v_half_size;
+in float v_stroke_width;
+flat in float v_kind;
+in vec4 v_fill;
+in vec4 v_stroke;
+out vec4 outColor;
+
+float sd_box(vec2 p, vec2 b) {
+    vec2 q = abs(p) - b;
+    return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0);
+}
+
+float sd_ellipse(vec2 p, vec2 r) {
+    // First-order approximation; exact enough for strokes a few pixels wide.
+    float k0 = length(p / r);
+    float k1 = max(length(p / (r * r)), 1e-6);
+    return k0 * (k0 - 1.0) / k1;
+}
+
+void main() {
+    vec2 half_size = max(v_half_size, vec2(1e-4));
+    float d = v_kind < 0.5 ? sd_box(v_local, half_size) : sd_ellipse(v_local, half_size);
+    float aa = fwidth(d);
+    float fill_coverage = 1.0 - smoothstep(-aa, aa, d);
+    float stroke_coverage = (1.0 - smoothstep(v_stroke_width * 0.5 - aa, v_stroke_width * 0.5 + aa, abs(d)))
+        * step(1e-6, v_stroke_width);
+
+    // Premultiplied stroke over fill.
+    float stroke_alpha = v_stroke.a * stroke_coverage;
+    float fill_alpha = v_fill.a * fill_coverage * (1.0 - stroke_alpha);
+    outColor = vec4(v_stroke.rgb * stroke_alpha + v_fill.rgb * fill_alpha, stroke_alpha + fill_alpha);
+}"##;
+
+const MESH_VERTEX_SHADER: &str = r##"
+layout(location = 0) in vec2 a_position;
+layout(location = 1) in vec4 a_color;
+out vec4 v_color;
+void main() {
+    v_color = a_color;
+    gl_Position = world_to_clip(a_position);
+}"##;
+
+const MESH_FRAGMENT_SHADER: &str = r##"#version 300 es
+precision mediump float;
+in vec4 v_color;
+out vec4 outColor;
+void main() {
+    outColor = vec4(v_color.rgb * v_color.a, v_color.a);
+}"##;
+
+const TEXT_VERTEX_SHADER: &str = r##"
+layout(location = 0) in vec2 a_corner;
+layout(location = 1) in vec4 a_rect;
+layout(location = 2) in vec4 a_uv;
+layout(location = 3) in vec3 a_transform;
+layout(location = 4) in vec4 a_color;
+out vec2 v_uv;
+out vec4 v_color;
+void main() {
+    // a_rect is the glyph quad relative to the text box centre, which is
+    // a_transform.xy; the box rotates about it by a_transform.z.
+    vec2 local = a_rect.xy + a_corner * a_rect.zw;
+    float s = sin(a_transform.z);
+    float c = cos(a_transform.z);
+    vec2 world = a_transform.xy + vec2(local.x * c - local.y * s, local.x * s + local.y * c);
+
+    v_uv = mix(a_uv.xy, a_uv.zw, a_corner);
+    v_color = a_color;
+    gl_Position = world_to_clip(world);
+}"##;
+
+const TEXT_FRAGMENT_SHADER: &str = r##"#version 300 es
+precision highp float;
+uniform sampler2D u_atlas;
+in vec2 v_uv;
+in vec4 v_color;
+out vec4 outColor;
+void main() {
+    // The field is 0.5 on the outline; smoothing over one screen pixel's
+    // worth of distance keeps edges sharp at every zoom.
+    float d = texture(u_atlas, v_uv).r;
+    float aa = max(fwidth(d), 1e-4);
+    float alpha = v_color.a * smoothstep(0.5 - aa, 0.5 + aa, d);
+    outColor = vec4(v_color.rgb * alpha, alpha);
+}"##;
+
+const IMAGE_VERTEX_SHADER: &str = r##"
+layout(location = 0) in vec2 a_corner;
+// Top-left and size of the image box, which turns by u_rotation about its centre.
+uniform vec4 u_box;
+uniform float u_rotation;
+out vec2 v_uv;
+void main() {
+    vec2 local = (a_corner - 0.5) * u_box.zw;
+    float s = sin(u_rotation);
+    float c = cos(u_rotation);
+    vec2 world = u_box.xy + u_box.zw * 0.5 + vec2(local.x * c - local.y * s, local.x * s + local.y * c);
+
+    v_uv = a_corner;
+    gl_Position = world_to_clip(world);
+}"##;
+
+const IMAGE_FRAGMENT_SHADER: &str = r##"#version 300 es
+precision mediump float;
+uniform sampler2D u_image;
+uniform float u_opacity;
+in vec2 v_uv;
+out vec4 outColor;
+void main() {
+    // Textures are uploaded premultiplied.
+    outColor = texture(u_image, v_uv) * u_opacity;
+}"##;
+
+/// Prepends the version line and the shared world-to-clip helpers.
+fn world_space_vertex_shader(body: &str) -> String {
+    format!("#version 300 es\nprecision highp float;\n{}{}", WORLD_TO_CLIP, body)
+}
+
 pub struct ShaderProgram {
     pub program: WebGlProgram,
 }
 
 impl ShaderProgram {
     pub fn new(context: &WebGl2RenderingContext) -> Result<Self, String> {
-        let vert_shader = compile_shader(context, WebGl2RenderingContext::VERTEX_SHADER, VERTEX_SHADER)?;
-        let frag_shader = compile_shader(context, WebGl2RenderingContext::FRAGMENT_SHADER, FRAGMENT_SHADER)?;
+        Self::from_sources(context, VERTEX_SHADER, FRAGMENT_SHADER)
+    }
+
+    /// Program for instanced rectangles and ellipses.
+    pub fn sdf(context: &WebGl2RenderingContext) -> Result<Self, String> {
+        Self::from_sources(context, &world_space_vertex_shader(SDF_VERTEX_SHADER), SDF_FRAGMENT_SHADER)
+    }
+
+    /// Program for colored triangle meshes.
+    pub fn mesh(context: &WebGl2RenderingContext) -> Result<Self, String> {
+        Self::from_sources(context, &world_space_vertex_shader(MESH_VERTEX_SHADER), MESH_FRAGMENT_SHADER)
+    }
+
+    /// Program for glyph quads sampling the signed distance field atlas.
+    pub fn text(context: &WebGl2RenderingContext) -> Result<Self, String> {
+        Self::from_sources(context, &world_space_vertex_shader(TEXT_VERTEX_SHADER), TEXT_FRAGMENT_SHADER)
+    }
+
+    /// Program for textured image quads.
+    pub fn image(context: &WebGl2RenderingContext) -> Result<Self, String> {
+        Self::from_sources(context, &world_space_vertex_shader(IMAGE_VERTEX_SHADER), IMAGE_FRAGMENT_SHADER)
+    }
+
+    pub fn from_sources(context: &WebGl2RenderingContext, vertex: &str, fragment: &str) -> Result<Self, String> {
+        let vert_shader = compile_shader(context, WebGl2RenderingContext::VERTEX_SHADER, vertex)?;
+        let frag_shader = compile_shader(context, WebGl2RenderingContext::FRAGMENT_SHADER, fragment)?;
         let program = link_program(context, &vert_shader, &frag_shader)?;
         
         Ok(Self { program })
@@ -46,11 +239,23 @@ impl ShaderProgram {
         }
     }
 
+    pub fn set_uniform_1i(&self, context: &WebGl2RenderingContext, name: &str, value: i32) {
+        if let Some(location) = context.get_uniform_location(&self.program, name) {
+            context.uniform1i(Some(&location), value);
+        }
+    }
+
     pub fn set_uniform_2f(&self, context: &WebGl2RenderingContext, name: &str, value1: f32, value2: f32) {
         if let Some(location) = context.get_uniform_location(&self.program, name) {
             context.uniform2f(Some(&location), value1, value2);
         }
     }
+
+    pub fn set_uniform_4f(&self, context: &WebGl2RenderingContext, name: &str, value: [f32; 4]) {
+        if let Some(location) = context.get_uniform_location(&self.program, name) {
+            context.uniform4f(Some(&location), value[0], value[1], value[2], value[3]);
+        }
+    }
 }
 
 fn compile_shader(
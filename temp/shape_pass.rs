@@ -0,0 +1,313 @@
+use std::ops::Range;
+
+use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlVertexArrayObject};
+
+use crate::batch::{Material, ShapeBatches, MESH_VERTEX_FLOATS, SDF_INSTANCE_FLOATS};
+use crate::buffers::DynamicBuffer;
+use crate::document::{Document, Shape};
+use crate::geometry::{Point, Rect};
+use crate::minimap::Minimap;
+use crate::shaders::ShaderProgram;
+use crate::state::{State, WORLD_UNITS_PER_GRID_UNIT};
+
+type Gl = WebGl2RenderingContext;
+
+/// Which upload a pass draws from: the document on screen, or an export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Screen,
+    Export,
+}
+
+/// GPU buffers and VAOs for one set of batched shapes.
+struct ShapeLayer {
+    sdf_vao: WebGlVertexArrayObject,
+    mesh_vao: WebGlVertexArrayObject,
+    instances: DynamicBuffer,
+    vertices: DynamicBuffer,
+    indices: DynamicBuffer,
+    batches: ShapeBatches,
+}
+
+impl ShapeLayer {
+    fn new(context: &Gl, quad: &WebGlBuffer) -> Result<Self, String> {
+        let instances = DynamicBuffer::new(context, Gl::ARRAY_BUFFER)?;
+        let vertices = DynamicBuffer::new(context, Gl::ARRAY_BUFFER)?;
+        let indices = DynamicBuffer::new(context, Gl::ELEMENT_ARRAY_BUFFER)?;
+
+        let sdf_vao = context.create_vertex_array()
+            .ok_or("Failed to create vertex array")?;
+        context.bind_vertex_array(Some(&sdf_vao));
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(quad));
+        context.enable_vertex_attrib_array(0);
+        context.vertex_attrib_pointer_with_i32(0, 2, Gl::FLOAT, false, 0, 0);
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&instances.buffer));
+        for location in 1..=7 {
+            context.enable_vertex_attrib_array(location);
+            context.vertex_attrib_divisor(location, 1);
+        }
+        Self::point_instance_attributes(context, 0);
+
+        let mesh_vao = context.create_vertex_array()
+            .ok_or("Failed to create vertex array")?;
+        context.bind_vertex_array(Some(&mesh_vao));
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&vertices.buffer));
+        let stride = (MESH_VERTEX_FLOATS * 4) as i32;
+        context.enable_vertex_attrib_array(0);
+        context.vertex_attrib_pointer_with_i32(0, 2, Gl::FLOAT, false, stride, 0);
+        context.enable_vertex_attrib_array(1);
+        context.vertex_attrib_pointer_with_i32(1, 4, Gl::FLOAT, false, stride, 8);
+        context.bind_buffer(Gl::ELEMENT_ARRAY_BUFFER, Some(&indices.buffer));
+
+        context.bind_vertex_array(None);
+
+        Ok(Self {
+            sdf_vao,
+            mesh_vao,
+            instances,
+            vertices,
+            indices,
+            batches: ShapeBatches::new(),
+        })
+    }
+
+    /// Points the per-instance attributes at `first_instance`. WebGL2 has no
+    /// base-instance draw, so each SDF batch re-points them before drawing.
+    /// Expects the SDF VAO and the instance buffer to be bound.
+    fn point_instance_attributes(context: &Gl, first_instance: usize) {
+        let stride = (SDF_INSTANCE_FLOATS * 4) as i32;
+        let base = (first_instance * SDF_INSTANCE_FLOATS * 4) as i32;
+        // (location, components, float offset)
+        let layout: [(u32, i32, i32); 7] = [(1, 2, 0), (2, 2, 2), (3, 1, 4), (4, 1, 5), (5, 1, 6), (6, 4, 7), (7, 4, 11)];
+        for (location, size, offset) in layout {
+            context.vertex_attrib_pointer_with_i32(location, size, Gl::FLOAT, false, stride, base + offset * 4);
+        }
+    }
+
+    fn upload(&mut self, context: &Gl) {
+        context.bind_vertex_array(None);
+        self.instances.upload_f32(context, &self.batches.instances);
+        self.vertices.upload_f32(context, &self.batches.vertices);
+        self.indices.upload_u32(context, &self.batches.indices);
+    }
+
+    /// Draws the batches in `range`, or all of them.
+    #[allow(clippy::too_many_arguments)]
+    fn draw(
+        &self,
+        context: &Gl,
+        sdf_program: &ShaderProgram,
+        mesh_program: &ShaderProgram,
+        state: &State,
+        width: f32,
+        height: f32,
+        range: Option<Range<usize>>,
+    ) {
+        let mut current = None;
+        let mut clip = None;
+        for batch in &self.batches.batches[range.unwrap_or(0..self.batches.batches.len())] {
+            if batch.clip != clip {
+                ShapePass::set_clip(context, state, width, height, batch.clip);
+                clip = batch.clip;
+            }
+            if current != Some(batch.material) {
+                match batch.material {
+                    Material::Sdf => {
+                        context.use_program(Some(&sdf_program.program));
+                        context.bind_vertex_array(Some(&self.sdf_vao));
+                        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&self.instances.buffer));
+                    }
+                    Material::Mesh => {
+                        context.use_program(Some(&mesh_program.program));
+                        context.bind_vertex_array(Some(&self.mesh_vao));
+                    }
+                }
+                current = Some(batch.material);
+            }
+            match batch.material {
+                Material::Sdf => {
+                    Self::point_instance_attributes(context, batch.start);
+                    context.draw_arrays_instanced(Gl::TRIANGLE_STRIP, 0, 4, batch.count as i32);
+                }
+                Material::Mesh => {
+                    context.draw_elements_with_i32(
+                        Gl::TRIANGLES,
+                        batch.count as i32,
+                        Gl::UNSIGNED_INT,
+                        (batch.start * 4) as i32,
+                    );
+                }
+            }
+        }
+        ShapePass::set_clip(context, state, width, height, None);
+        context.bind_vertex_array(None);
+    }
+}
+
+/// Draws document shapes: rectangles and ellipses as instanced SDF quads,
+/// paths as indexed triangle meshes.
+///
+/// The renderer uploads the shapes near the viewport and draws ranges of
+/// batches between the text and images around them, in paint order. A
+/// shape that is still being drawn goes into a separate small preview layer
+/// that is rebuilt every frame, so live strokes never pay for the whole
+/// document; the editor overlay gets a third layer, drawn separately so it
+/// can go above text. Exports draw from a fourth layer so they leave the
+/// on-screen upload alone. The minimap has two more: its shapes are cached
+/// by document revision, frame and view, while its viewport box is rebuilt
+/// every frame.
+pub struct ShapePass {
+    sdf_program: ShaderProgram,
+    mesh_program: ShaderProgram,
+    document_layer: ShapeLayer,
+    preview_layer: ShapeLayer,
+    overlay_layer: ShapeLayer,
+    export_layer: ShapeLayer,
+    minimap_layer: ShapeLayer,
+    minimap_viewport_layer: ShapeLayer,
+    /// Revision, frame and view the minimap layer was built for.
+    uploaded_minimap: Option<(u64, Rect, Rect)>,
+}
+
+impl ShapePass {
+    pub fn new(context: &Gl) -> Result<Self, String> {
+        let sdf_program = ShaderProgram::sdf(context)?;
+        let mesh_program = ShaderProgram::mesh(context)?;
+        let quad = Self::setup_quad_buffer(context)?;
+        let document_layer = ShapeLayer::new(context, &quad)?;
+        let preview_layer = ShapeLayer::new(context, &quad)?;
+        let overlay_layer = ShapeLayer::new(context, &quad)?;
+        let export_layer = ShapeLayer::new(context, &quad)?;
+        let minimap_layer = ShapeLayer::new(context, &quad)?;
+        let minimap_viewport_layer = ShapeLayer::new(context, &quad)?;
+
+        Ok(Self {
+            sdf_program,
+            mesh_program,
+            document_layer,
+            preview_layer,
+            overlay_layer,
+            export_layer,
+            minimap_layer,
+            minimap_viewport_layer,
+            uploaded_minimap: None,
+        })
+    }
+
+    fn setup_quad_buffer(context: &Gl) -> Result<WebGlBuffer, String> {
+        let corners: [f32; 8] = [-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0];
+        let buffer = context.create_buffer()
+            .ok_or("Failed to create buffer")?;
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&buffer));
+        unsafe {
+            let view = js_sys::Float32Array::view(&corners);
+            context.buffer_data_with_array_buffer_view(Gl::ARRAY_BUFFER, &view, Gl::STATIC_DRAW);
+        }
+        Ok(buffer)
+    }
+
+    /// Camera uniforms shared by every world-space program.
+    pub fn set_camera_uniforms(program: &ShaderProgram, context: &Gl, state: &State, width: f32, height: f32) {
+        context.use_program(Some(&program.program));
+        program.set_uniform_1f(context, "u_aspect_ratio", width / height);
+        program.set_uniform_1f(context, "u_zoom", state.zoom);
+        program.set_uniform_2f(context, "u_offset", state.offset_x, state.offset_y);
+        program.set_uniform_1f(context, "u_world_scale", 1.0 / WORLD_UNITS_PER_GRID_UNIT);
+        program.set_uniform_1f(context, "u_pixel_size", state.world_units_per_pixel(width, height));
+    }
+
+    /// Restricts drawing to the world rect `clip`, or lifts the restriction.
+    pub fn set_clip(context: &Gl, state: &State, width: f32, height: f32, clip: Option<Rect>) {
+        let Some(clip) = clip else {
+            context.disable(Gl::SCISSOR_TEST);
+            return;
+        };
+        let top_left = state.world_to_screen(width, height, Point::new(clip.min_x, clip.min_y));
+        let bottom_right = state.world_to_screen(width, height, Point::new(clip.max_x, clip.max_y));
+        // Scissor rows count up from the bottom of the target.
+        let (x0, x1) = (top_left.x.floor(), bottom_right.x.ceil());
+        let (y0, y1) = ((height - bottom_right.y).floor(), (height - top_left.y).ceil());
+        context.enable(Gl::SCISSOR_TEST);
+        context.scissor(x0 as i32, y0 as i32, (x1 - x0).max(0.0) as i32, (y1 - y0).max(0.0) as i32);
+    }
+
+    pub fn set_camera(&self, context: &Gl, state: &State, width: f32, height: f32) {
+        Self::set_camera_uniforms(&self.sdf_program, context, state, width, height);
+        Self::set_camera_uniforms(&self.mesh_program, context, state, width, height);
+    }
+
+    /// Uploads `shapes`, in paint order, for [`Target::Screen`]. Returns the
+    /// batch count after each shape.
+    pub fn upload_document(&mut self, context: &Gl, document: &Document, shapes: &[&Shape]) -> Vec<usize> {
+        self.document_layer.batches.rebuild_painted(shapes.iter().copied(), |shape| document.paint(shape));
+        self.document_layer.upload(context);
+        self.document_layer.batches.ends.clone()
+    }
+
+    /// Uploads `shapes` of `document` for [`Target::Export`].
+    pub fn upload_export(&mut self, context: &Gl, document: &Document, shapes: &[&Shape]) -> Vec<usize> {
+        self.export_layer.batches.rebuild_painted(shapes.iter().copied(), |shape| document.paint(shape));
+        self.export_layer.upload(context);
+        self.export_layer.batches.ends.clone()
+    }
+
+    /// Draws the `batches` of `target`, with the camera from
+    /// [`ShapePass::set_camera`] and blending already set up.
+    pub fn draw(&self, context: &Gl, target: Target, state: &State, width: f32, height: f32, batches: Range<usize>) {
+        let layer = match target {
+            Target::Screen => &self.document_layer,
+            Target::Export => &self.export_layer,
+        };
+        layer.draw(context, &self.sdf_program, &self.mesh_program, state, width, height, Some(batches));
+    }
+
+    /// Draws `preview`, a shape still being drawn, over the document.
+    pub fn render_preview(&mut self, context: &Gl, state: &State, preview: &Shape, width: f32, height: f32) {
+        self.preview_layer.batches.rebuild_from([preview]);
+        self.preview_layer.upload(context);
+        self.set_camera(context, state, width, height);
+        context.enable(Gl::BLEND);
+        context.blend_func(Gl::ONE, Gl::ONE_MINUS_SRC_ALPHA);
+        self.preview_layer.draw(context, &self.sdf_program, &self.mesh_program, state, width, height, None);
+        context.disable(Gl::BLEND);
+    }
+
+    /// Draws the editor overlay; called last so it sits above text too.
+    pub fn render_overlay(&mut self, context: &Gl, state: &State, overlay: &[Shape], width: f32, height: f32) {
+        if overlay.is_empty() {
+            return;
+        }
+        self.overlay_layer.batches.rebuild_from(overlay);
+        self.overlay_layer.upload(context);
+        self.set_camera(context, state, width, height);
+        context.enable(Gl::BLEND);
+        context.blend_func(Gl::ONE, Gl::ONE_MINUS_SRC_ALPHA);
+        self.overlay_layer.draw(context, &self.sdf_program, &self.mesh_program, state, width, height, None);
+        context.disable(Gl::BLEND);
+    }
+
+    /// Draws `minimap` into its corner of a `width` x `height` canvas. Its
+    /// shapes are only rebuilt when the document or the map's layout
+    /// changes; panning within the map only moves the viewport box.
+    pub fn render_minimap(&mut self, context: &Gl, document: &Document, minimap: &Minimap, width: f32, height: f32) {
+        let key = (document.revision(), minimap.frame, minimap.view);
+        if self.uploaded_minimap != Some(key) {
+            self.minimap_layer.batches.rebuild_from(minimap.shapes(document).shapes());
+            self.minimap_layer.upload(context);
+            self.uploaded_minimap = Some(key);
+        }
+        self.minimap_viewport_layer.batches.rebuild_from(minimap.viewport_shapes().shapes());
+        self.minimap_viewport_layer.upload(context);
+        let frame = minimap.frame;
+        let camera = minimap.camera();
+        // Viewport rows count up from the bottom of the canvas.
+        context.viewport(frame.min_x as i32, (height - frame.max_y) as i32, frame.width() as i32, frame.height() as i32);
+        self.set_camera(context, &camera, frame.width(), frame.height());
+        context.enable(Gl::BLEND);
+        context.blend_func(Gl::ONE, Gl::ONE_MINUS_SRC_ALPHA);
+        self.minimap_layer.draw(context, &self.sdf_program, &self.mesh_program, &camera, frame.width(), frame.height(), None);
+        self.minimap_viewport_layer.draw(context, &self.sdf_program, &self.mesh_program, &camera, frame.width(), frame.height(), None);
+        context.disable(Gl::BLEND);
+        context.viewport(0, 0, width as i32, height as i32);
+    }
+}
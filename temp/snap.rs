@@ -0,0 +1,251 @@
+//! Smart snapping for moving and resizing: to grid lines, to the edges and
+//! centres of other shapes, and to equal gaps between shapes. Everything
+//! works on axis-aligned bounds, one axis at a time; the vertical axis is
+//! the horizontal one with x and y swapped.
+
+use crate::document::{Document, ShapeId};
+use crate::geometry::{Point, Rect};
+use crate::spatial::SpatialIndex;
+
+/// How close, in screen pixels, an edge must come to a target to snap.
+pub const SNAP_THRESHOLD_PX: f32 = 6.0;
+
+/// A line to draw while a snap holds, in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Guide {
+    pub start: Point,
+    pub end: Point,
+}
+
+impl Guide {
+    fn transposed(self) -> Self {
+        Self {
+            start: transpose_point(self.start),
+            end: transpose_point(self.end),
+        }
+    }
+}
+
+/// The correction a snap applies and the guides that explain it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snap {
+    pub offset: Point,
+    pub guides: Vec<Guide>,
+}
+
+/// What snapping aligns to, gathered once per gesture.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapTargets {
+    /// Grid spacing; zero disables grid snapping.
+    grid: f32,
+    /// Bounds of the shapes that stay put.
+    bounds: Vec<Rect>,
+}
+
+impl SnapTargets {
+    pub fn new(grid: f32, bounds: Vec<Rect>) -> Self {
+        Self { grid, bounds }
+    }
+
+    /// The visible shapes in `view` other than `moving`, their contents and
+    /// the containers around them.
+    pub fn collect(document: &Document, index: &SpatialIndex, view: &Rect, moving: &[ShapeId], grid: f32) -> Self {
+        let bounds = index
+            .query_rect(view)
+            .into_iter()
+            .filter(|id| {
+                !moving
+                    .iter()
+                    .any(|m| m == id || document.is_descendant(*id, *m) || document.is_descendant(*m, *id))
+            })
+            .filter_map(|id| document.get(id))
+            .filter(|shape| document.is_visible(shape))
+            .map(|shape| shape.transform.bounds())
+            .collect();
+        Self::new(grid, bounds)
+    }
+
+    /// The offset that brings `rect` within `threshold` of the nearest
+    /// target on each axis, with the guides for every target it then meets.
+    pub fn snap_rect(&self, rect: Rect, threshold: f32) -> Snap {
+        self.snap(rect, (true, true), true, threshold)
+    }
+
+    /// Like [`Self::snap_rect`] for a single point, such as a dragged resize
+    /// handle, only along the axes the handle moves.
+    pub fn snap_point(&self, point: Point, axes: (bool, bool), threshold: f32) -> Snap {
+        self.snap(Rect::new(point.x, point.y, point.x, point.y), axes, false, threshold)
+    }
+
+    fn snap(&self, rect: Rect, (along_x, along_y): (bool, bool), gaps: bool, threshold: f32) -> Snap {
+        let transposed = self.transposed();
+        let nearest = |targets: &SnapTargets, rect: Rect| {
+            targets
+                .candidates(rect, gaps)
+                .into_iter()
+                .map(|(offset, _)| offset)
+                .filter(|offset| offset.abs() <= threshold)
+                .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+        };
+        let x = along_x.then(|| nearest(self, rect)).flatten();
+        let y = along_y.then(|| nearest(&transposed, transpose_rect(rect))).flatten();
+        let offset = Point::new(x.unwrap_or(0.0), y.unwrap_or(0.0));
+        // Guides are gathered at the snapped position, so each shows a
+        // match on both axes at once.
+        let snapped = Rect::new(rect.min_x + offset.x, rect.min_y + offset.y, rect.max_x + offset.x, rect.max_y + offset.y);
+        let tolerance = threshold * 1e-3;
+        let met = |targets: &SnapTargets, rect: Rect| {
+            targets
+                .candidates(rect, gaps)
+                .into_iter()
+                .filter(|(offset, _)| offset.abs() <= tolerance)
+                .flat_map(|(_, guides)| guides)
+                .collect::<Vec<_>>()
+        };
+        let mut guides = Vec::new();
+        if x.is_some() {
+            guides.extend(met(self, snapped));
+        }
+        if y.is_some() {
+            guides.extend(met(&transposed, transpose_rect(snapped)).into_iter().map(Guide::transposed));
+        }
+        Snap { offset, guides }
+    }
+
+    fn transposed(&self) -> Self {
+        Self::new(self.grid, self.bounds.iter().copied().map(transpose_rect).collect())
+    }
+
+    /// Horizontal offsets that would align `rect` with something, each with
+    /// the guides to draw once it does.
+    fn candidates(&self, rect: Rect, gaps: bool) -> Vec<(f32, Vec<Guide>)> {
+        let edges = [rect.min_x, rect.center().x, rect.max_x];
+        let mut candidates = Vec::new();
+        if self.grid > 0.0 {
+            // The grid is on screen already; it needs no guides.
+            candidates.extend(edges.iter().map(|e| ((e / self.grid).round() * self.grid - e, Vec::new())));
+        }
+        for other in &self.bounds {
+            let (top, bottom) = (other.min_y.min(rect.min_y), other.max_y.max(rect.max_y));
+            for target in [other.min_x, other.center().x, other.max_x] {
+                let guide = Guide {
+                    start: Point::new(target, top),
+                    end: Point::new(target, bottom),
+                };
+                candidates.extend(edges.iter().map(|e| (target - e, vec![guide])));
+            }
+        }
+        if gaps {
+            candidates.extend(self.gap_candidates(rect));
+        }
+        candidates
+    }
+
+    /// Offsets that leave `rect` as far from its neighbours in the same row
+    /// as they are from each other.
+    fn gap_candidates(&self, rect: Rect) -> Vec<(f32, Vec<Guide>)> {
+        let row: Vec<&Rect> = self
+            .bounds
+            .iter()
+            .filter(|b| b.min_y < rect.max_y && b.max_y > rect.min_y)
+            .collect();
+        let center = rect.center().x;
+        let nearest_left = |limit: f32| row.iter().filter(|b| b.max_x <= limit).max_by(|a, b| a.max_x.total_cmp(&b.max_x));
+        let nearest_right = |limit: f32| row.iter().filter(|b| b.min_x >= limit).min_by(|a, b| a.min_x.total_cmp(&b.min_x));
+        // A gap drawn across the middle of the rows it separates.
+        let gap = |from: f32, to: f32, a: &Rect, b: &Rect| {
+            let y = (a.min_y.max(b.min_y) + a.max_y.min(b.max_y)) * 0.5;
+            Guide {
+                start: Point::new(from, y),
+                end: Point::new(to, y),
+            }
+        };
+        let width = rect.width();
+        let mut candidates = Vec::new();
+        let left = nearest_left(center);
+        let right = nearest_right(center);
+        if let (Some(l), Some(r)) = (left, right) {
+            // Centred between the neighbours.
+            let min_x = (l.max_x + r.min_x - width) * 0.5;
+            let moved = Rect::new(min_x, rect.min_y, min_x + width, rect.max_y);
+            let guides = vec![gap(l.max_x, min_x, l, &moved), gap(min_x + width, r.min_x, &moved, r)];
+            candidates.push((min_x - rect.min_x, guides));
+        }
+        if let Some(l) = left {
+            // Repeating the gap between the two shapes to the left.
+            if let Some(ll) = nearest_left(l.min_x) {
+                let min_x = l.max_x + (l.min_x - ll.max_x);
+                let moved = Rect::new(min_x, rect.min_y, min_x + width, rect.max_y);
+                let guides = vec![gap(ll.max_x, l.min_x, ll, l), gap(l.max_x, min_x, l, &moved)];
+                candidates.push((min_x - rect.min_x, guides));
+            }
+        }
+        if let Some(r) = right {
+            if let Some(rr) = nearest_right(r.max_x) {
+                let max_x = r.min_x - (rr.min_x - r.max_x);
+                let moved = Rect::new(max_x - width, rect.min_y, max_x, rect.max_y);
+                let guides = vec![gap(max_x, r.min_x, &moved, r), gap(r.max_x, rr.min_x, r, rr)];
+                candidates.push((max_x - rect.max_x, guides));
+            }
+        }
+        candidates
+    }
+}
+
+fn transpose_point(p: Point) -> Point {
+    Point::new(p.y, p.x)
+}
+
+fn transpose_rect(r: Rect) -> Rect {
+    Rect::new(r.min_y, r.min_x, r.max_y, r.max_x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32) -> Rect {
+        Rect::new(x, y, x + 10.0, y + 10.0)
+    }
+
+    #[test]
+    fn test_snaps_to_edges_centres_and_grid() {
+        let targets = SnapTargets::new(0.0, vec![square(0.0, 0.0)]);
+        // Left edge 2 units right of the other's right edge; top 1 unit below its centre.
+        let snap = targets.snap_rect(square(12.0, 6.0), 3.0);
+        assert_eq!(snap.offset, Point::new(-2.0, -1.0));
+        assert!(snap.guides.contains(&Guide {
+            start: Point::new(10.0, 0.0),
+            end: Point::new(10.0, 15.0),
+        }));
+        // Out of reach: nothing moves and nothing is drawn.
+        assert_eq!(targets.snap_rect(square(30.0, 30.0), 3.0), Snap::default());
+
+        let grid = SnapTargets::new(40.0, Vec::new());
+        let snap = grid.snap_rect(square(38.0, 81.0), 3.0);
+        assert_eq!(snap.offset, Point::new(2.0, -1.0));
+        assert!(snap.guides.is_empty());
+        let snap = grid.snap_point(Point::new(78.0, 78.0), (true, false), 3.0);
+        assert_eq!(snap.offset, Point::new(2.0, 0.0));
+    }
+
+    #[test]
+    fn test_snaps_to_equal_gaps() {
+        // Centred between two shapes 30 apart.
+        let targets = SnapTargets::new(0.0, vec![square(0.0, 0.0), square(30.0, 0.0)]);
+        let snap = targets.snap_rect(square(16.0, 2.5), 2.0);
+        assert_eq!(snap.offset.x, -1.0);
+        assert_eq!(snap.guides.len(), 2);
+
+        // Repeating the gap between two shapes, on either side.
+        let snap = targets.snap_rect(square(62.0, 2.0), 3.0);
+        assert_eq!(snap.offset.x, -2.0);
+        assert!(snap.guides.iter().any(|g| g.start.x == 10.0 && g.end.x == 30.0));
+        let snap = targets.snap_rect(square(-31.0, 2.0), 3.0);
+        assert_eq!(snap.offset.x, 1.0);
+
+        // The same along the vertical axis.
+        let targets = SnapTargets::new(0.0, vec![square(0.0, 0.0), square(0.0, 30.0)]);
+        assert_eq!(targets.snap_rect(square(2.0, 61.0), 3.0).offset.y, -1.0);
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+
+use crate::document::{Document, DocumentEvent, ShapeId};
+use crate::geometry::{Point, Rect};
+use crate::hit_test::hit_test;
+
+/// Deepest level of the tree. Cells at this depth are `root_size / 2^16`.
+const MAX_DEPTH: u32 = 16;
+
+/// Smallest root cell; avoids rebuilding repeatedly while a board is small.
+const MIN_ROOT_HALF_SIZE: f32 = 1024.0;
+
+#[derive(Debug, Default)]
+struct Node {
+    children: Option<[usize; 4]>,
+    items: Vec<(ShapeId, Rect)>,
+    /// Tight bounds of everything in this node and below it.
+    bounds: Option<Rect>,
+}
+
+/// Loose quadtree over shape world bounds.
+///
+/// Each shape lives in exactly one node: the deepest one whose cell contains
+/// the shape's centre and is at least as large as the shape. With cells
+/// loosened by half their size on every side, that node's loose bounds always
+/// contain the whole shape, so placement needs no splitting or duplication
+/// and updates are a remove plus an insert. The root grows (and the tree is
+/// rebuilt) when a shape lands outside it, which happens rarely.
+#[derive(Debug)]
+pub struct SpatialIndex {
+    nodes: Vec<Node>,
+    root_center: Point,
+    root_half: f32,
+    entries: HashMap<ShapeId, Rect>,
+    /// First indices of runs of four unused nodes, from collapsed subtrees.
+    free: Vec<usize>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpatialIndex {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+            root_center: Point::ZERO,
+            root_half: MIN_ROOT_HALF_SIZE,
+            entries: HashMap::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Builds an index over every shape in `document`.
+    pub fn from_document(document: &Document) -> Self {
+        let mut index = Self::new();
+        for shape in document.shapes() {
+            index.insert(shape.id, shape.bounds());
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn bounds_of(&self, id: ShapeId) -> Option<Rect> {
+        self.entries.get(&id).copied()
+    }
+
+    /// Bounds of every shape in the index, or `None` when it is empty.
+    pub fn bounds(&self) -> Option<Rect> {
+        self.nodes[0].bounds
+    }
+
+    /// Inserts or moves a shape. Bounds with a NaN or infinite side cannot
+    /// be placed, so the shape is left out of the index and `false` returned.
+    pub fn insert(&mut self, id: ShapeId, bounds: Rect) -> bool {
+        if self.entries.contains_key(&id) {
+            self.remove(id);
+        }
+        if !bounds.is_finite() {
+            return false;
+        }
+        if !self.fits(&bounds) {
+            self.grow_to_fit(&bounds);
+        }
+        self.entries.insert(id, bounds);
+        self.add(id, bounds);
+        true
+    }
+
+    pub fn remove(&mut self, id: ShapeId) -> bool {
+        let Some(bounds) = self.entries.remove(&id) else {
+            return false;
+        };
+        let path = self.path(&bounds);
+        let items = &mut self.nodes[*path.last().unwrap()].items;
+        if let Some(i) = items.iter().position(|(item, _)| *item == id) {
+            items.swap_remove(i);
+        }
+        // Collapse the subtrees left empty and shrink the bounds, deepest first.
+        for &node in path.iter().rev() {
+            if let Some(children) = self.nodes[node].children {
+                if children.iter().all(|&child| self.nodes[child].children.is_none() && self.nodes[child].items.is_empty()) {
+                    self.nodes[node].children = None;
+                    self.free.push(children[0]);
+                }
+            }
+            let Node { children, items, .. } = &self.nodes[node];
+            let below = children.iter().flatten().filter_map(|&child| self.nodes[child].bounds);
+            self.nodes[node].bounds = items.iter().map(|(_, rect)| *rect).chain(below).reduce(|a, b| a.union(&b));
+        }
+        true
+    }
+
+    /// Keeps the index in sync with a batch of document changes.
+    pub fn apply_events(&mut self, document: &Document, events: &[DocumentEvent]) {
+        for event in events {
+            match *event {
+                DocumentEvent::Inserted(id) | DocumentEvent::Updated(id) => {
+                    if let Some(shape) = document.get(id) {
+                        self.insert(id, shape.bounds());
+                    }
+                }
+                DocumentEvent::Deleted(id) => {
+                    self.remove(id);
+                }
+            }
+        }
+    }
+
+    /// Shapes whose bounds intersect `rect`, e.g. the visible world rect.
+    pub fn query_rect(&self, rect: &Rect) -> Vec<ShapeId> {
+        let mut out = Vec::new();
+        self.visit(rect, |id, bounds| {
+            if bounds.intersects(rect) {
+                out.push(id);
+            }
+        });
+        out
+    }
+
+    /// Shapes whose bounds lie entirely inside `rect`, e.g. a marquee.
+    pub fn query_contained(&self, rect: &Rect) -> Vec<ShapeId> {
+        let mut out = Vec::new();
+        self.visit(rect, |id, bounds| {
+            if rect.contains_rect(bounds) {
+                out.push(id);
+            }
+        });
+        out
+    }
+
+    /// The topmost shape whose geometry is within `tolerance` of `point`.
+    /// Parts of shapes cut off by a frame, and shapes on hidden or locked
+    /// layers, cannot be hit.
+    pub fn topmost_at(&self, document: &Document, point: Point, tolerance: f32) -> Option<ShapeId> {
+        let probe = Rect::new(point.x, point.y, point.x, point.y).expand(tolerance);
+        self.query_rect(&probe)
+            .into_iter()
+            .filter_map(|id| document.get(id))
+            .filter(|shape| document.is_hittable(shape) && hit_test(shape, point, tolerance))
+            .filter(|shape| document.clip(shape.id).is_none_or(|clip| clip.contains_point(point)))
+            .max_by_key(|shape| document.paint_key(shape))
+            .map(|shape| shape.id)
+    }
+
+    fn visit<F: FnMut(ShapeId, &Rect)>(&self, rect: &Rect, mut f: F) {
+        let mut stack = vec![(0usize, self.root_center, self.root_half)];
+        while let Some((node, center, half)) = stack.pop() {
+            // Loose bounds: the cell grown by half its size on each side.
+            let loose = Rect::new(center.x - half, center.y - half, center.x + half, center.y + half).expand(half);
+            if !loose.intersects(rect) {
+                continue;
+            }
+            let node = &self.nodes[node];
+            for (id, bounds) in &node.items {
+                f(*id, bounds);
+            }
+            if let Some(children) = node.children {
+                let q = half * 0.5;
+                for (i, child) in children.iter().enumerate() {
+                    stack.push((*child, quadrant_center(center, q, i), q));
+                }
+            }
+        }
+    }
+
+    fn fits(&self, bounds: &Rect) -> bool {
+        let c = bounds.center();
+        let size = bounds.width().max(bounds.height());
+        (c.x - self.root_center.x).abs() <= self.root_half
+            && (c.y - self.root_center.y).abs() <= self.root_half
+            && size <= self.root_half * 2.0
+    }
+
+    fn grow_to_fit(&mut self, bounds: &Rect) {
+        if self.entries.is_empty() {
+            self.root_center = bounds.center();
+        }
+        while !self.fits(bounds) {
+            self.root_half *= 2.0;
+        }
+        self.nodes = vec![Node::default()];
+        self.free.clear();
+        let entries: Vec<(ShapeId, Rect)> = self.entries.iter().map(|(id, r)| (*id, *r)).collect();
+        for (id, rect) in entries {
+            self.add(id, rect);
+        }
+    }
+
+    /// Puts a shape in its node, widening the bounds on the way down.
+    fn add(&mut self, id: ShapeId, bounds: Rect) {
+        let node = self.place(&bounds);
+        self.nodes[node].items.push((id, bounds));
+        for node in self.path(&bounds) {
+            let node = &mut self.nodes[node];
+            node.bounds = Some(node.bounds.map_or(bounds, |b| b.union(&bounds)));
+        }
+    }
+
+    /// Finds, building it if need be, the node a shape with `bounds` belongs in.
+    fn place(&mut self, bounds: &Rect) -> usize {
+        let c = bounds.center();
+        let size = bounds.width().max(bounds.height());
+        let mut node = 0;
+        let mut center = self.root_center;
+        let mut half = self.root_half;
+        for _ in 0..MAX_DEPTH {
+            let q = half * 0.5;
+            // Stop when the child cells would be smaller than the shape.
+            if size > q * 2.0 {
+                break;
+            }
+            let quadrant = usize::from(c.x >= center.x) | (usize::from(c.y >= center.y) << 1);
+            let children = match self.nodes[node].children {
+                Some(children) => children,
+                None => {
+                    let first = match self.free.pop() {
+                        Some(first) => first,
+                        None => {
+                            self.nodes.extend((0..4).map(|_| Node::default()));
+                            self.nodes.len() - 4
+                        }
+                    };
+                    let children = [first, first + 1, first + 2, first + 3];
+                    self.nodes[node].children = Some(children);
+                    children
+                }
+            };
+            node = children[quadrant];
+            center = quadrant_center(center, q, quadrant);
+            half = q;
+        }
+        node
+    }
+
+    /// The existing nodes from the root down to where `bounds` belongs.
+    fn path(&self, bounds: &Rect) -> Vec<usize> {
+        let c = bounds.center();
+        let size = bounds.width().max(bounds.height());
+        let mut path = vec![0];
+        let mut center = self.root_center;
+        let mut half = self.root_half;
+        for _ in 0..MAX_DEPTH {
+            let q = half * 0.5;
+            let Some(children) = self.nodes[*path.last().unwrap()].children else {
+                break;
+            };
+            if size > q * 2.0 {
+                break;
+            }
+            let quadrant = usize::from(c.x >= center.x) | (usize::from(c.y >= center.y) << 1);
+            path.push(children[quadrant]);
+            center = quadrant_center(center, q, quadrant);
+            half = q;
+        }
+        path
+    }
+}
+
+fn quadrant_center(center: Point, quarter: f32, quadrant: usize) -> Point {
+    let dx = if quadrant & 1 == 1 { quarter } else { -quarter };
+    let dy = if quadrant & 2 == 2 { quarter } else { -quarter };
+    Point::new(center.x + dx, center.y + dy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Color, ShapeKind, Style, Transform};
+
+    fn rect(x: f32, y: f32, size: f32) -> Rect {
+        Rect::new(x, y, x + size, y + size)
+    }
+
+    /// Deterministic pseudo-random boxes so failures reproduce.
+    fn random_boxes(count: usize) -> Vec<Rect> {
+        let mut seed = 0x2545_f491_u64;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % 10_000) as f32
+        };
+        (0..count)
+            .map(|_| {
+                let (x, y) = (next() * 10.0 - 50_000.0, next() * 10.0 - 50_000.0);
+                rect(x, y, next() * 0.1 + 1.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_queries_match_brute_force() {
+        let boxes = random_boxes(2_000);
+        let mut index = SpatialIndex::new();
+        for (i, b) in boxes.iter().enumerate() {
+            index.insert(ShapeId(i as u64), *b);
+        }
+        let query = Rect::new(-10_000.0, -20_000.0, 15_000.0, 5_000.0);
+
+        let mut found = index.query_rect(&query);
+        found.sort();
+        let expected: Vec<ShapeId> = (0..boxes.len())
+            .filter(|&i| boxes[i].intersects(&query))
+            .map(|i| ShapeId(i as u64))
+            .collect();
+        assert_eq!(found, expected);
+
+        let mut contained = index.query_contained(&query);
+        contained.sort();
+        let expected: Vec<ShapeId> = (0..boxes.len())
+            .filter(|&i| query.contains_rect(&boxes[i]))
+            .map(|i| ShapeId(i as u64))
+            .collect();
+        assert_eq!(contained, expected);
+    }
+
+    #[test]
+    fn test_update_and_remove() {
+        let mut index = SpatialIndex::new();
+        let id = ShapeId(1);
+        index.insert(id, rect(0.0, 0.0, 10.0));
+        index.insert(id, rect(500.0, 500.0, 10.0));
+        assert!(index.query_rect(&rect(0.0, 0.0, 20.0)).is_empty());
+        assert_eq!(index.query_rect(&rect(495.0, 495.0, 10.0)), vec![id]);
+        assert_eq!(index.len(), 1);
+
+        assert!(index.remove(id));
+        assert!(!index.remove(id));
+        assert!(index.query_rect(&rect(495.0, 495.0, 10.0)).is_empty());
+    }
+
+    #[test]
+    fn test_removal_collapses_empty_nodes() {
+        let mut index = SpatialIndex::new();
+        index.insert(ShapeId(1), rect(0.0, 0.0, 1.0));
+        let nodes = index.nodes.len();
+        // A shape dragged across the board leaves no nodes behind.
+        for step in 0..200 {
+            let x = step as f32 * 7.0 - 700.0;
+            index.insert(ShapeId(2), rect(x, -x, 1.0));
+        }
+        index.remove(ShapeId(2));
+        assert_eq!(index.nodes.len() - index.free.len() * 4, nodes);
+        assert_eq!(index.query_rect(&rect(0.0, 0.0, 1.0)), vec![ShapeId(1)]);
+
+        index.remove(ShapeId(1));
+        assert!(index.nodes[0].children.is_none());
+        assert_eq!(index.nodes.len() - index.free.len() * 4, 1);
+    }
+
+    #[test]
+    fn test_bounds_follow_inserts_and_removals() {
+        let boxes = random_boxes(500);
+        let mut index = SpatialIndex::new();
+        assert_eq!(index.bounds(), None);
+        for (i, b) in boxes.iter().enumerate() {
+            index.insert(ShapeId(i as u64), *b);
+        }
+        let union = |boxes: &[Rect]| boxes.iter().copied().reduce(|a, b| a.union(&b));
+        assert_eq!(index.bounds(), union(&boxes));
+
+        for i in 0..400 {
+            index.remove(ShapeId(i as u64));
+        }
+        assert_eq!(index.bounds(), union(&boxes[400..]));
+        // Growing the root keeps them.
+        index.insert(ShapeId(1), rect(1.0e7, 1.0e7, 10.0));
+        assert_eq!(index.bounds().unwrap().max_x, 1.0e7 + 10.0);
+        assert_eq!(index.bounds().unwrap().min_x, union(&boxes[400..]).unwrap().min_x);
+    }
+
+    #[test]
+    fn test_rejects_non_finite_bounds() {
+        let mut index = SpatialIndex::new();
+        let id = ShapeId(1);
+        assert!(index.insert(id, rect(0.0, 0.0, 10.0)));
+        let nan = Rect {
+            min_x: f32::NAN,
+            ..rect(0.0, 0.0, 10.0)
+        };
+        assert!(!index.insert(id, nan));
+        assert!(!index.insert(ShapeId(2), Rect::new(0.0, 0.0, f32::INFINITY, 10.0)));
+        assert!(index.is_empty());
+        assert!(index.query_rect(&rect(0.0, 0.0, 10.0)).is_empty());
+    }
+
+    #[test]
+    fn test_root_grows_for_far_and_huge_shapes() {
+        let mut index = SpatialIndex::new();
+        index.insert(ShapeId(1), rect(0.0, 0.0, 10.0));
+        index.insert(ShapeId(2), rect(1.0e7, -1.0e7, 10.0));
+        index.insert(ShapeId(3), rect(-5.0e6, -5.0e6, 1.0e7));
+        let mut all = index.query_rect(&Rect::new(-1.0e8, -1.0e8, 1.0e8, 1.0e8));
+        all.sort();
+        assert_eq!(all, vec![ShapeId(1), ShapeId(2), ShapeId(3)]);
+        assert_eq!(index.query_rect(&rect(1.0e7, -1.0e7, 1.0)), vec![ShapeId(2)]);
+        assert!(index.remove(ShapeId(1)));
+    }
+
+    #[test]
+    fn test_topmost_at_and_events() {
+        let mut doc = Document::new();
+        let filled = Style {
+            fill: Some(Color::WHITE),
+            ..Style::default()
+        };
+        let bottom = doc.insert(ShapeKind::Rectangle, Transform::new(0.0, 0.0, 100.0, 100.0), filled.clone());
+        let top = doc.insert(ShapeKind::Rectangle, Transform::new(50.0, 50.0, 100.0, 100.0), filled);
+        let mut index = SpatialIndex::new();
+        let events = doc.drain_events();
+        index.apply_events(&doc, &events);
+
+        assert_eq!(index.topmost_at(&doc, Point::new(75.0, 75.0), 1.0), Some(top));
+        assert_eq!(index.topmost_at(&doc, Point::new(25.0, 25.0), 1.0), Some(bottom));
+        assert_eq!(index.topmost_at(&doc, Point::new(500.0, 500.0), 1.0), None);
+
+        doc.delete(top).unwrap();
+        doc.update(bottom, |s| s.transform.x = 1000.0).unwrap();
+        let events = doc.drain_events();
+        index.apply_events(&doc, &events);
+        assert_eq!(index.topmost_at(&doc, Point::new(75.0, 75.0), 1.0), None);
+        assert_eq!(index.topmost_at(&doc, Point::new(1050.0, 50.0), 1.0), Some(bottom));
+    }
+}
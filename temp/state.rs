@@ -1,47 +1,97 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+use crate::geometry::{Point, Rect};
+
+/// World units per unit of the grid shader's coordinate space. With the
+/// 51-point grid buffer this puts grid dots 40 world units apart at zoom 1.
+pub const WORLD_UNITS_PER_GRID_UNIT: f32 = 1000.0;
+
+/// The camera: where the grid and the world are shown on the canvas.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct State {
     pub zoom: f32,
     pub offset_x: f32,
     pub offset_y: f32,
-    pub is_dragging: bool,
-    pub last_mouse_x: f32,
-    pub last_mouse_y: f32,
 }
 
-impl State {
-    pub fn new() -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Self {
+impl Default for State {
+    fn default() -> Self {
+        Self {
             zoom: 1.0,
             offset_x: 0.0,
             offset_y: 0.0,
-            is_dragging: false,
-            last_mouse_x: 0.0,
-            last_mouse_y: 0.0,
-        }))
+        }
     }
+}
 
-    pub fn update_drag(&mut self, canvas_width: f32, canvas_height: f32, new_x: f32, new_y: f32) {
-        if self.is_dragging {
-            let dx = new_x - self.last_mouse_x;
-            let dy = new_y - self.last_mouse_y;
-            self.offset_x += dx / canvas_width * 2.0;
-            self.offset_y -= dy / canvas_height * 2.0;
-            self.last_mouse_x = new_x;
-            self.last_mouse_y = new_y;
-        }
+impl State {
+    pub fn new() -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self::default()))
+    }
+
+    /// Moves the camera so the world follows a pointer that moved `dx`, `dy` pixels.
+    pub fn pan_by_pixels(&mut self, canvas_width: f32, canvas_height: f32, dx: f32, dy: f32) {
+        // The aspect scale maps the shorter canvas side onto the full clip range.
+        let pixels_per_unit = canvas_width.min(canvas_height) * 0.5;
+        self.offset_x += dx / pixels_per_unit;
+        self.offset_y -= dy / pixels_per_unit;
+    }
+
+    /// Moves the camera so `world` is at the centre of the canvas.
+    pub fn center_on(&mut self, world: Point) {
+        self.offset_x = -world.x / WORLD_UNITS_PER_GRID_UNIT * self.zoom;
+        self.offset_y = world.y / WORLD_UNITS_PER_GRID_UNIT * self.zoom;
+    }
+
+    /// Per-axis scale the grid shader applies to keep the grid square.
+    fn aspect_scale(canvas_width: f32, canvas_height: f32) -> (f32, f32) {
+        let aspect_ratio = canvas_width / canvas_height;
+        ((1.0 / aspect_ratio).min(1.0), aspect_ratio.min(1.0))
+    }
+
+    /// Maps a world-space point to clip space. World space is y-down, like the screen.
+    pub fn world_to_clip(&self, canvas_width: f32, canvas_height: f32, world: Point) -> Point {
+        let (sx, sy) = Self::aspect_scale(canvas_width, canvas_height);
+        let gx = world.x / WORLD_UNITS_PER_GRID_UNIT * self.zoom + self.offset_x;
+        let gy = -world.y / WORLD_UNITS_PER_GRID_UNIT * self.zoom + self.offset_y;
+        Point::new(gx * sx, gy * sy)
     }
 
-    pub fn start_drag(&mut self, x: f32, y: f32) {
-        self.is_dragging = true;
-        self.last_mouse_x = x;
-        self.last_mouse_y = y;
+    pub fn world_to_screen(&self, canvas_width: f32, canvas_height: f32, world: Point) -> Point {
+        let clip = self.world_to_clip(canvas_width, canvas_height, world);
+        Point::new(
+            (clip.x + 1.0) * 0.5 * canvas_width,
+            (1.0 - clip.y) * 0.5 * canvas_height,
+        )
     }
 
-    pub fn stop_drag(&mut self) {
-        self.is_dragging = false;
+    /// Maps a canvas-relative pixel position to world space.
+    pub fn screen_to_world(&self, canvas_width: f32, canvas_height: f32, screen: Point) -> Point {
+        let (sx, sy) = Self::aspect_scale(canvas_width, canvas_height);
+        let clip_x = screen.x / canvas_width * 2.0 - 1.0;
+        let clip_y = 1.0 - screen.y / canvas_height * 2.0;
+        let gx = clip_x / sx;
+        let gy = clip_y / sy;
+        Point::new(
+            (gx - self.offset_x) / self.zoom * WORLD_UNITS_PER_GRID_UNIT,
+            -(gy - self.offset_y) / self.zoom * WORLD_UNITS_PER_GRID_UNIT,
+        )
+    }
+
+    /// Size of one screen pixel in world units at the current zoom.
+    pub fn world_units_per_pixel(&self, canvas_width: f32, canvas_height: f32) -> f32 {
+        2.0 * WORLD_UNITS_PER_GRID_UNIT / (self.zoom * canvas_width.min(canvas_height))
+    }
+
+    /// The part of the world currently visible on the canvas.
+    pub fn visible_world_rect(&self, canvas_width: f32, canvas_height: f32) -> Rect {
+        let a = self.screen_to_world(canvas_width, canvas_height, Point::new(0.0, 0.0));
+        let b = self.screen_to_world(canvas_width, canvas_height, Point::new(canvas_width, canvas_height));
+        Rect::new(a.x, a.y, b.x, b.y)
     }
 }
 
@@ -56,29 +106,52 @@ mod tests {
         assert_eq!(state.zoom, 1.0);
         assert_eq!(state.offset_x, 0.0);
         assert_eq!(state.offset_y, 0.0);
-        assert!(!state.is_dragging);
     }
 
     #[test]
-    fn test_drag_operations() {
-        let state = State::new();
-        {
-            let mut state = state.borrow_mut();
-            state.start_drag(10.0, 10.0);
-            assert!(state.is_dragging);
-            assert_eq!(state.last_mouse_x, 10.0);
-            assert_eq!(state.last_mouse_y, 10.0);
-        }
-        {
-            let mut state = state.borrow_mut();
-            state.update_drag(100.0, 100.0, 20.0, 20.0);
-            assert!(state.offset_x > 0.0);
-            assert!(state.offset_y < 0.0);
-        }
-        {
-            let mut state = state.borrow_mut();
-            state.stop_drag();
-            assert!(!state.is_dragging);
-        }
+    fn test_pan_keeps_world_under_pointer() {
+        let mut state = State {
+            zoom: 3.0,
+            ..State::default()
+        };
+        let grabbed = state.screen_to_world(800.0, 600.0, Point::new(100.0, 100.0));
+        state.pan_by_pixels(800.0, 600.0, 25.0, -40.0);
+        let screen = state.world_to_screen(800.0, 600.0, grabbed);
+        assert!((screen.x - 125.0).abs() < 1e-2);
+        assert!((screen.y - 60.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_screen_world_round_trip() {
+        let state = State {
+            zoom: 2.5,
+            offset_x: 0.3,
+            offset_y: -0.1,
+        };
+        let world = state.screen_to_world(800.0, 600.0, Point::new(123.0, 456.0));
+        let screen = state.world_to_screen(800.0, 600.0, world);
+        assert!((screen.x - 123.0).abs() < 1e-2);
+        assert!((screen.y - 456.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_center_on() {
+        let mut state = State {
+            zoom: 2.0,
+            ..State::default()
+        };
+        state.center_on(Point::new(300.0, -120.0));
+        let center = state.screen_to_world(800.0, 600.0, Point::new(400.0, 300.0));
+        assert!(center.distance(Point::new(300.0, -120.0)) < 1e-2);
+    }
+
+    #[test]
+    fn test_world_units_per_pixel() {
+        let state = State::default();
+        let a = state.screen_to_world(800.0, 600.0, Point::new(100.0, 100.0));
+        let b = state.screen_to_world(800.0, 600.0, Point::new(101.0, 101.0));
+        let per_pixel = state.world_units_per_pixel(800.0, 600.0);
+        assert!(((b.x - a.x) - per_pixel).abs() < 1e-3);
+        assert!(((b.y - a.y) - per_pixel).abs() < 1e-3);
     }
 }
\ No newline at end of file
@@ -0,0 +1,619 @@
+//! Standalone SVG export of the document, the selection or a world rect.
+
+use std::fmt::Write;
+
+use crate::document::{Color, Document, Paint, Routing, Shape, ShapeId, ShapeKind, Style, Transform};
+use crate::freehand::{outline, stroke_widths, FreehandOptions};
+use crate::geometry::{Point, Rect};
+use crate::grid::GridStyle;
+use crate::selection::Selection;
+use crate::text::{layout, FontStack, TextAlign};
+
+/// What part of the board to export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportArea {
+    /// Every shape, framed by their bounds.
+    Document,
+    /// The selected shapes, framed by their bounds.
+    Selection,
+    /// Exactly this world rect; shapes crossing its edge are cropped.
+    Rect(Rect),
+    /// A frame and its contents, cropped to the frame.
+    Frame(ShapeId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    pub area: ExportArea,
+    /// Margin around the shapes for [`ExportArea::Document`] and
+    /// [`ExportArea::Selection`], in world units.
+    pub padding: f32,
+    /// Draws the grid as a background pattern.
+    pub grid: Option<GridStyle>,
+    pub background: Option<Color>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            area: ExportArea::Document,
+            padding: 16.0,
+            grid: None,
+            background: None,
+        }
+    }
+}
+
+/// The visible shapes in `area`, bottom to top, and the world rect framing
+/// them. Shared by the SVG and PNG exporters.
+pub fn export_region<'a>(
+    document: &'a Document,
+    selection: &Selection,
+    area: ExportArea,
+    padding: f32,
+) -> Result<(Vec<&'a Shape>, Rect), String> {
+    let frame = match area {
+        ExportArea::Frame(id) => match document.get(id) {
+            Some(frame) if matches!(frame.kind, ShapeKind::Frame { .. }) => Some(frame.transform.bounds()),
+            _ => return Err(format!("Shape {} is not a frame", id.0)),
+        },
+        _ => None,
+    };
+    let shapes: Vec<&Shape> = document
+        .shapes_in_z_order()
+        .into_iter()
+        .filter(|shape| document.is_visible(shape))
+        .filter(|shape| match area {
+            ExportArea::Document => true,
+            ExportArea::Selection => {
+                selection.contains(shape.id) || document.ancestors(shape.id).into_iter().any(|id| selection.contains(id))
+            }
+            ExportArea::Rect(rect) => shape.bounds().intersects(&rect),
+            ExportArea::Frame(id) => shape.id == id || document.is_descendant(shape.id, id),
+        })
+        .collect();
+    let view = match (area, frame) {
+        (ExportArea::Rect(rect), _) => rect,
+        (_, Some(frame)) => frame,
+        _ => shapes
+            .iter()
+            .map(|shape| shape.bounds())
+            .reduce(|a, b| a.union(&b))
+            .ok_or("Nothing to export")?
+            .expand(padding),
+    };
+    if view.width() <= 0.0 || view.height() <= 0.0 {
+        return Err("Export area is empty".to_string());
+    }
+    Ok((shapes, view))
+}
+
+/// Writes the shapes in `options.area` as an SVG document, one world unit
+/// per SVG user unit. Images are referenced by their `source`, so they are
+/// embedded when the source is a data URL. Text breaks into lines as laid
+/// out with `fonts`, as on the canvas.
+pub fn export_svg(document: &Document, selection: &Selection, fonts: &FontStack, options: &SvgOptions) -> Result<String, String> {
+    let (shapes, view) = export_region(document, selection, options.area, options.padding)?;
+
+    let mut svg = String::new();
+    let (x, y, width, height) = (num(view.min_x), num(view.min_y), num(view.width()), num(view.height()));
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{x} {y} {width} {height}" width="{width}" height="{height}">"#
+    );
+    let background = format!(r#"x="{x}" y="{y}" width="{width}" height="{height}""#);
+    if let Some(color) = options.background {
+        let _ = writeln!(svg, "  <rect {}{}/>", background, paint("fill", color));
+    }
+    if let Some(grid) = options.grid {
+        // Offset by half a cell so dots sit on multiples of the spacing.
+        let half = grid.spacing * 0.5;
+        let _ = writeln!(
+            svg,
+            r#"  <defs><pattern id="grid" x="{}" y="{}" width="{}" height="{}" patternUnits="userSpaceOnUse"><circle cx="{}" cy="{}" r="{}"{}/></pattern></defs>"#,
+            num(-half),
+            num(-half),
+            num(grid.spacing),
+            num(grid.spacing),
+            num(half),
+            num(half),
+            num(grid.dot_radius),
+            paint("fill", grid.color)
+        );
+        let _ = writeln!(svg, r#"  <rect {} fill="url(#grid)"/>"#, background);
+    }
+    // Runs of shapes sharing a frame's clip or a translucent layer go into
+    // one group.
+    let mut clips = 0;
+    let mut open = Paint::default();
+    for shape in shapes {
+        let paint = document.paint(shape);
+        if paint != open {
+            if open != Paint::default() {
+                svg.push_str("  </g>\n");
+            }
+            if paint != Paint::default() {
+                let mut attributes = String::new();
+                if let Some(rect) = paint.clip {
+                    clips += 1;
+                    let _ = writeln!(
+                        svg,
+                        r#"  <clipPath id="clip{}"><rect x="{}" y="{}" width="{}" height="{}"/></clipPath>"#,
+                        clips,
+                        num(rect.min_x),
+                        num(rect.min_y),
+                        num(rect.width().max(0.0)),
+                        num(rect.height().max(0.0))
+                    );
+                    let _ = write!(attributes, r#" clip-path="url(#clip{})""#, clips);
+                }
+                if paint.opacity < 1.0 {
+                    let _ = write!(attributes, r#" opacity="{}""#, num(paint.opacity));
+                }
+                let _ = writeln!(svg, "  <g{}>", attributes);
+            }
+            open = paint;
+        }
+        write_shape(&mut svg, fonts, shape);
+    }
+    if open != Paint::default() {
+        svg.push_str("  </g>\n");
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+fn write_shape(svg: &mut String, fonts: &FontStack, shape: &Shape) {
+    let t = &shape.transform;
+    let style = &shape.style;
+    let opacity = if style.opacity < 1.0 {
+        format!(r#" opacity="{}""#, num(style.opacity))
+    } else {
+        String::new()
+    };
+    match &shape.kind {
+        ShapeKind::Rectangle | ShapeKind::Frame { .. } => {
+            let _ = writeln!(
+                svg,
+                r#"  <rect x="{}" y="{}" width="{}" height="{}"{}{}{}/>"#,
+                num(t.x),
+                num(t.y),
+                num(t.width),
+                num(t.height),
+                rotation(t),
+                closed_paint(style),
+                opacity
+            );
+        }
+        ShapeKind::Ellipse => {
+            let center = t.center();
+            let _ = writeln!(
+                svg,
+                r#"  <ellipse cx="{}" cy="{}" rx="{}" ry="{}"{}{}{}/>"#,
+                num(center.x),
+                num(center.y),
+                num(t.width * 0.5),
+                num(t.height * 0.5),
+                rotation(t),
+                closed_paint(style),
+                opacity
+            );
+        }
+        ShapeKind::Line { .. } | ShapeKind::Arrow { .. } => {
+            let Some(points) = shape.path_points() else {
+                return;
+            };
+            let line = format!(
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}"{}/>"#,
+                num(points[0].x),
+                num(points[0].y),
+                num(points[1].x),
+                num(points[1].y),
+                open_paint(style)
+            );
+            match arrow_head(points[0], points[1], style.stroke_width) {
+                Some(head) if matches!(shape.kind, ShapeKind::Arrow { .. }) => {
+                    let _ = writeln!(
+                        svg,
+                        r#"  <g{}>{}<polygon points="{}"{}/></g>"#,
+                        opacity,
+                        line,
+                        polyline(&head),
+                        paint("fill", style.stroke)
+                    );
+                }
+                _ => {
+                    let _ = writeln!(svg, "  {}", line.replacen("/>", &format!("{}/>", opacity), 1));
+                }
+            }
+        }
+        ShapeKind::Connector { route, routing, .. } => {
+            let route: Vec<Point> = route.iter().map(|p| t.to_world(*p)).collect();
+            let Some(path) = shape.path_points() else {
+                return;
+            };
+            let [.., from, tip] = path.as_slice() else {
+                return;
+            };
+            let d = match (routing, route.as_slice()) {
+                (Routing::Curved, [p0, c1, c2, p1]) => format!(
+                    "M{} {} C{} {} {} {} {} {}",
+                    num(p0.x),
+                    num(p0.y),
+                    num(c1.x),
+                    num(c1.y),
+                    num(c2.x),
+                    num(c2.y),
+                    num(p1.x),
+                    num(p1.y)
+                ),
+                _ => route
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| format!("{}{} {}", if i == 0 { "M" } else { "L" }, num(p.x), num(p.y)))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            };
+            let _ = write!(svg, r#"  <g{}><path d="{}"{}/>"#, opacity, d, open_paint(style));
+            // Curves point the head along their last flattened segment, as drawn.
+            if let Some(head) = arrow_head(*from, *tip, style.stroke_width) {
+                let _ = write!(svg, r#"<polygon points="{}"{}/>"#, polyline(&head), paint("fill", style.stroke));
+            }
+            svg.push_str("</g>\n");
+        }
+        ShapeKind::Freehand { points } => {
+            let Some(path) = shape.path_points() else {
+                return;
+            };
+            if !style.dash.is_empty() {
+                // Dashes need a real stroke, which cannot vary in width.
+                let _ = writeln!(svg, r#"  <polyline points="{}"{}{}/>"#, polyline(&path), open_paint(style), opacity);
+                return;
+            }
+            let widths = stroke_widths(points, style.stroke_width, &FreehandOptions::default());
+            let polygon = outline(&path, &widths);
+            if polygon.is_empty() {
+                return;
+            }
+            let mut d = String::new();
+            for (i, p) in polygon.iter().enumerate() {
+                let _ = write!(d, "{}{} {}", if i == 0 { "M" } else { " L" }, num(p.x), num(p.y));
+            }
+            let _ = writeln!(svg, r#"  <path d="{} Z"{}{}/>"#, d, paint("fill", style.stroke), opacity);
+        }
+        ShapeKind::Text { content, font_size, align } => {
+            let (x, anchor) = match align {
+                TextAlign::Left => (t.x, ""),
+                TextAlign::Center => (t.x + t.width * 0.5, r#" text-anchor="middle""#),
+                TextAlign::Right => (t.x + t.width, r#" text-anchor="end""#),
+            };
+            let _ = write!(
+                svg,
+                r#"  <text font-family="sans-serif" font-size="{}"{}{}{}{}>"#,
+                num(*font_size),
+                anchor,
+                rotation(t),
+                paint("fill", style.stroke),
+                opacity
+            );
+            // One line per laid out line, each on its own baseline.
+            let laid_out = layout(fonts, content, *font_size, Some(t.width), *align);
+            for line in &laid_out.lines {
+                let text = content[line.text.clone()].trim_end();
+                let _ = write!(svg, r#"<tspan x="{}" y="{}">{}</tspan>"#, num(x), num(t.y + line.baseline), escape(text));
+            }
+            svg.push_str("</text>\n");
+        }
+        ShapeKind::Image { source } => {
+            let _ = writeln!(
+                svg,
+                r#"  <image href="{}" x="{}" y="{}" width="{}" height="{}" preserveAspectRatio="none"{}{}/>"#,
+                escape(source),
+                num(t.x),
+                num(t.y),
+                num(t.width),
+                num(t.height),
+                rotation(t),
+                opacity
+            );
+        }
+        ShapeKind::Group => {}
+    }
+}
+
+/// The same triangle the renderer draws at an arrow's tip.
+fn arrow_head(from: Point, tip: Point, stroke_width: f32) -> Option<[Point; 3]> {
+    let dir = (tip - from).normalize();
+    if dir == Point::ZERO {
+        return None;
+    }
+    let size = stroke_width * 4.0;
+    let base = tip - dir * size;
+    let side = dir.perp() * (size * 0.5);
+    Some([tip, base + side, base - side])
+}
+
+fn rotation(t: &Transform) -> String {
+    if t.rotation == 0.0 {
+        return String::new();
+    }
+    let center = t.center();
+    format!(
+        r#" transform="rotate({} {} {})""#,
+        num(t.rotation.to_degrees()),
+        num(center.x),
+        num(center.y)
+    )
+}
+
+fn closed_paint(style: &Style) -> String {
+    let fill = match style.fill {
+        Some(color) => paint("fill", color),
+        None => r#" fill="none""#.to_string(),
+    };
+    format!("{}{}{}", fill, paint("stroke", style.stroke), stroke_attributes(style))
+}
+
+fn open_paint(style: &Style) -> String {
+    format!(
+        r#" fill="none"{}{} stroke-linecap="round" stroke-linejoin="round""#,
+        paint("stroke", style.stroke),
+        stroke_attributes(style)
+    )
+}
+
+fn stroke_attributes(style: &Style) -> String {
+    let mut attributes = format!(r#" stroke-width="{}""#, num(style.stroke_width));
+    if !style.dash.is_empty() {
+        let dash: Vec<String> = style.dash.iter().map(|d| num(*d)).collect();
+        let _ = write!(attributes, r#" stroke-dasharray="{}""#, dash.join(" "));
+    }
+    attributes
+}
+
+/// `name="rgb(..)"`, plus `name-opacity` for translucent colours.
+fn paint(name: &str, color: Color) -> String {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut attributes = format!(
+        r#" {}="rgb({},{},{})""#,
+        name,
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    );
+    if color.a < 1.0 {
+        let _ = write!(attributes, r#" {}-opacity="{}""#, name, num(color.a));
+    }
+    attributes
+}
+
+fn polyline(points: &[Point]) -> String {
+    let pairs: Vec<String> = points.iter().map(|p| format!("{},{}", num(p.x), num(p.y))).collect();
+    pairs.join(" ")
+}
+
+/// Numbers rounded to three decimals without trailing zeros.
+fn num(value: f32) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{ShapeId, StrokePoint};
+    use crate::text::test_fonts;
+    use std::path::Path;
+
+    /// Compares against `snapshots/<name>.svg` next to this file. Run with
+    /// `UPDATE_SNAPSHOTS=1` to rewrite the snapshot after a deliberate change.
+    fn assert_snapshot(name: &str, actual: &str) {
+        // `file!()` is relative to the package root.
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join(Path::new(file!()))
+            .with_file_name("snapshots")
+            .join(format!("{}.svg", name));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("missing snapshot {}", path.display()));
+        assert_eq!(actual, expected, "snapshot {} differs", name);
+    }
+
+    fn board() -> (Document, ShapeId, ShapeId) {
+        let mut document = Document::new();
+        let rect = document.insert(
+            ShapeKind::Rectangle,
+            Transform::new(0.0, 0.0, 100.0, 60.0),
+            Style {
+                fill: Some(Color::rgba(1.0, 0.8, 0.2, 0.5)),
+                ..Style::default()
+            },
+        );
+        document.insert(
+            ShapeKind::Ellipse,
+            Transform {
+                rotation: std::f32::consts::FRAC_PI_6,
+                ..Transform::new(120.0, 0.0, 80.0, 40.0)
+            },
+            Style {
+                dash: vec![6.0, 4.0],
+                ..Style::default()
+            },
+        );
+        let arrow = document.insert(
+            ShapeKind::Arrow {
+                start: Point::new(0.0, 0.0),
+                end: Point::new(100.0, 0.0),
+            },
+            Transform::new(0.0, 100.0, 100.0, 0.0),
+            Style {
+                opacity: 0.5,
+                ..Style::default()
+            },
+        );
+        document.insert(
+            ShapeKind::Freehand {
+                points: [(0.0, 0.0), (10.0, 5.0), (20.0, 0.0)]
+                    .iter()
+                    .map(|&(x, y)| StrokePoint { x, y, pressure: 0.5 })
+                    .collect(),
+            },
+            Transform::new(140.0, 80.0, 20.0, 5.0),
+            Style {
+                stroke_width: 4.0,
+                ..Style::default()
+            },
+        );
+        document.insert(
+            ShapeKind::Text {
+                content: "Fish & <chips>\nsecond line".to_string(),
+                font_size: 16.0,
+                align: TextAlign::Left,
+            },
+            Transform::new(0.0, 140.0, 120.0, 40.0),
+            Style::default(),
+        );
+        document.insert(
+            ShapeKind::Image {
+                source: "data:image/png;base64,iVBORw0KGgo=".to_string(),
+            },
+            Transform::new(140.0, 140.0, 32.0, 32.0),
+            Style::default(),
+        );
+        (document, rect, arrow)
+    }
+
+    #[test]
+    fn test_document_snapshot() {
+        let (document, _, _) = board();
+        let svg = export_svg(&document, &Selection::new(), &test_fonts(), &SvgOptions::default()).unwrap();
+        assert_snapshot("document", &svg);
+    }
+
+    #[test]
+    fn test_selection_with_grid_snapshot() {
+        let (document, rect, arrow) = board();
+        let mut selection = Selection::new();
+        selection.set([rect, arrow]);
+        let options = SvgOptions {
+            area: ExportArea::Selection,
+            grid: Some(GridStyle::default()),
+            background: Some(Color::WHITE),
+            ..SvgOptions::default()
+        };
+        assert_snapshot("selection_grid", &export_svg(&document, &selection, &test_fonts(), &options).unwrap());
+    }
+
+    #[test]
+    fn test_rect_area_crops_and_filters() {
+        let (document, _, _) = board();
+        let options = SvgOptions {
+            area: ExportArea::Rect(Rect::new(-10.0, -10.0, 50.0, 50.0)),
+            ..SvgOptions::default()
+        };
+        let svg = export_svg(&document, &Selection::new(), &test_fonts(), &options).unwrap();
+        assert!(svg.contains(r#"viewBox="-10 -10 60 60""#));
+        assert_eq!(svg.matches("  <").count(), 1);
+
+        let selection = Selection::new();
+        let nothing = SvgOptions {
+            area: ExportArea::Selection,
+            ..SvgOptions::default()
+        };
+        assert!(export_svg(&document, &selection, &test_fonts(), &nothing).is_err());
+    }
+
+    #[test]
+    fn test_frame_area_clips_to_the_frame() {
+        let (mut document, rect, _) = board();
+        let frame = document.insert(
+            ShapeKind::Frame { name: "Card".to_string() },
+            Transform::new(100.0, 100.0, 50.0, 40.0),
+            Style::default(),
+        );
+        let inside = document.insert(ShapeKind::Ellipse, Transform::new(120.0, 110.0, 60.0, 10.0), Style::default());
+        document.update(inside, |shape| shape.parent = Some(frame)).unwrap();
+        let options = SvgOptions {
+            area: ExportArea::Frame(frame),
+            ..SvgOptions::default()
+        };
+        let svg = export_svg(&document, &Selection::new(), &test_fonts(), &options).unwrap();
+        assert!(svg.contains(r#"viewBox="100 100 50 40""#));
+        assert_eq!(svg.matches("<clipPath").count(), 1);
+        assert_eq!(svg.matches("<ellipse").count(), 1);
+
+        let not_a_frame = SvgOptions {
+            area: ExportArea::Frame(rect),
+            ..SvgOptions::default()
+        };
+        assert_eq!(export_svg(&document, &Selection::new(), &test_fonts(), &not_a_frame).unwrap_err(), format!("Shape {} is not a frame", rect.0));
+    }
+
+    #[test]
+    fn test_curved_connector_is_a_true_curve() {
+        let mut document = Document::new();
+        let kind = ShapeKind::Connector {
+            route: vec![Point::new(0.0, 0.0), Point::new(40.0, 0.0), Point::new(60.0, 50.0), Point::new(100.0, 50.0)],
+            routing: Routing::Curved,
+            start_binding: None,
+            end_binding: None,
+        };
+        document.insert(kind, Transform::new(10.0, 20.0, 100.0, 50.0), Style::default());
+        let svg = export_svg(&document, &Selection::new(), &test_fonts(), &SvgOptions::default()).unwrap();
+        assert!(svg.contains(r#"<path d="M10 20 C50 20 70 70 110 70""#));
+        assert!(svg.contains("<polygon points=\"110,70 "));
+    }
+
+    #[test]
+    fn test_text_wraps_as_laid_out() {
+        let mut document = Document::new();
+        let content = "one two three four five six".to_string();
+        document.insert(
+            ShapeKind::Text {
+                content: content.clone(),
+                font_size: 20.0,
+                align: TextAlign::Left,
+            },
+            Transform::new(0.0, 0.0, 90.0, 100.0),
+            Style::default(),
+        );
+        let fonts = test_fonts();
+        let laid_out = layout(&fonts, &content, 20.0, Some(90.0), TextAlign::Left);
+        assert!(laid_out.lines.len() > 1);
+        let svg = export_svg(&document, &Selection::new(), &fonts, &SvgOptions::default()).unwrap();
+        assert_eq!(svg.matches("<tspan").count(), laid_out.lines.len());
+        let second = format!(r#"<tspan x="0" y="{}">"#, num(laid_out.lines[1].baseline));
+        assert!(svg.contains(&second), "{}", svg);
+    }
+
+    #[test]
+    fn test_numbers_are_compact() {
+        assert_eq!(num(1.0), "1");
+        assert_eq!(num(-0.0001), "0");
+        assert_eq!(num(2.5), "2.5");
+        assert_eq!(num(1.0 / 3.0), "0.333");
+    }
+}
@@ -0,0 +1,659 @@
+use std::f32::consts::PI;
+
+use crate::geometry::Point;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeOptions {
+    /// Full stroke width, used when no per-vertex widths are given.
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Miters longer than `miter_limit * width / 2` fall back to bevels.
+    pub miter_limit: f32,
+    /// Alternating dash/gap lengths. An odd-length array is repeated, as in SVG.
+    pub dash: Vec<f32>,
+    pub dash_offset: f32,
+    /// Maximum distance between a curve and its flattened approximation.
+    pub tolerance: f32,
+}
+
+impl Default for StrokeOptions {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+            dash: Vec::new(),
+            dash_offset: 0.0,
+            tolerance: 0.25,
+        }
+    }
+}
+
+/// Indexed triangle list.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Point>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    fn vertex(&mut self, p: Point) -> u32 {
+        self.vertices.push(p);
+        (self.vertices.len() - 1) as u32
+    }
+
+    fn triangle(&mut self, a: Point, b: Point, c: Point) {
+        let i = self.vertex(a);
+        let j = self.vertex(b);
+        let k = self.vertex(c);
+        self.indices.extend_from_slice(&[i, j, k]);
+    }
+
+    fn quad(&mut self, a: Point, b: Point, c: Point, d: Point) {
+        let i = self.vertex(a);
+        let j = self.vertex(b);
+        let k = self.vertex(c);
+        let l = self.vertex(d);
+        self.indices.extend_from_slice(&[i, j, k, j, l, k]);
+    }
+
+    /// Triangle fan around `center` sweeping from `from` by `sweep` radians.
+    fn arc(&mut self, center: Point, from: Point, sweep: f32, radius: f32, tolerance: f32) {
+        let steps = arc_segments(radius, sweep.abs(), tolerance);
+        let c = self.vertex(center);
+        let mut prev = self.vertex(center + from);
+        for step in 1..=steps {
+            let angle = sweep * step as f32 / steps as f32;
+            let next = self.vertex(center + from.rotate_around(Point::ZERO, angle));
+            self.indices.extend_from_slice(&[c, prev, next]);
+            prev = next;
+        }
+    }
+}
+
+/// Number of segments needed to approximate an arc within `tolerance`.
+fn arc_segments(radius: f32, sweep: f32, tolerance: f32) -> usize {
+    if radius <= tolerance {
+        return 1.max((sweep / (PI / 2.0)).ceil() as usize);
+    }
+    let step = 2.0 * (1.0 - tolerance / radius).acos();
+    ((sweep / step).ceil() as usize).clamp(1, 128)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    LineTo(Point),
+    QuadTo(Point, Point),
+    CubicTo(Point, Point, Point),
+}
+
+/// A single subpath made of lines and bezier curves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub start: Point,
+    pub segments: Vec<PathSegment>,
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn new(start: Point) -> Self {
+        Self {
+            start,
+            segments: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// Converts curves to line segments within `tolerance`.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Point> {
+        let mut points = vec![self.start];
+        let mut current = self.start;
+        for segment in &self.segments {
+            match *segment {
+                PathSegment::LineTo(p) => {
+                    points.push(p);
+                    current = p;
+                }
+                PathSegment::QuadTo(c, p) => {
+                    flatten_quadratic(current, c, p, tolerance, &mut points);
+                    current = p;
+                }
+                PathSegment::CubicTo(c1, c2, p) => {
+                    flatten_cubic(current, c1, c2, p, tolerance, &mut points);
+                    current = p;
+                }
+            }
+        }
+        points
+    }
+}
+
+/// Appends points of the quadratic bezier `p0 c p1`, excluding `p0`.
+pub fn flatten_quadratic(p0: Point, c: Point, p1: Point, tolerance: f32, out: &mut Vec<Point>) {
+    let dd = (p0 - c * 2.0 + p1).length();
+    let steps = ((dd / (8.0 * tolerance)).sqrt().ceil() as usize).clamp(1, 256);
+    for i in 1..=steps {
+        let t = i as f32 / steps as f32;
+        out.push(p0.lerp(c, t).lerp(c.lerp(p1, t), t));
+    }
+}
+
+/// Appends points of the cubic bezier `p0 c1 c2 p1`, excluding `p0`.
+pub fn flatten_cubic(p0: Point, c1: Point, c2: Point, p1: Point, tolerance: f32, out: &mut Vec<Point>) {
+    let dd = (p0 - c1 * 2.0 + c2).length().max((c1 - c2 * 2.0 + p1).length());
+    let steps = ((0.75 * dd / tolerance).sqrt().ceil() as usize).clamp(1, 256);
+    for i in 1..=steps {
+        let t = i as f32 / steps as f32;
+        let mt = 1.0 - t;
+        out.push(
+            p0 * (mt * mt * mt) + c1 * (3.0 * mt * mt * t) + c2 * (3.0 * mt * t * t) + p1 * (t * t * t),
+        );
+    }
+}
+
+/// Tessellates a stroked path.
+pub fn stroke_path(path: &Path, options: &StrokeOptions) -> Mesh {
+    let points = path.flatten(options.tolerance);
+    stroke_polyline(&points, None, path.closed, options)
+}
+
+/// Tessellates a polyline into triangles.
+///
+/// `widths`, when given, holds one full width per point (e.g. from pen
+/// pressure) and overrides `options.width`. Triangles overlap at joins, so
+/// translucent strokes should be drawn into an opaque layer first.
+pub fn stroke_polyline(points: &[Point], widths: Option<&[f32]>, closed: bool, options: &StrokeOptions) -> Mesh {
+    let (mut points, mut widths) = dedup(points, widths, options.width);
+    let mut mesh = Mesh::default();
+    if points.is_empty() {
+        return mesh;
+    }
+
+    if closed && points.len() > 2 {
+        if has_dashes(&options.dash) {
+            // Dashes run around the closing edge like any other.
+            points.push(points[0]);
+            widths.push(widths[0]);
+        } else {
+            stroke_run(&points, &widths, true, options, &mut mesh);
+            return mesh;
+        }
+    }
+
+    if has_dashes(&options.dash) {
+        for (dash_points, dash_widths) in split_dashes(&points, &widths, options) {
+            stroke_run(&dash_points, &dash_widths, false, options, &mut mesh);
+        }
+    } else {
+        stroke_run(&points, &widths, false, options, &mut mesh);
+    }
+    mesh
+}
+
+fn has_dashes(dash: &[f32]) -> bool {
+    !dash.is_empty() && dash.iter().all(|d| *d >= 0.0 && d.is_finite()) && dash.iter().sum::<f32>() > 0.0
+}
+
+/// Drops consecutive duplicate points and resolves per-point widths.
+fn dedup(points: &[Point], widths: Option<&[f32]>, default_width: f32) -> (Vec<Point>, Vec<f32>) {
+    let mut out_points: Vec<Point> = Vec::with_capacity(points.len());
+    let mut out_widths = Vec::with_capacity(points.len());
+    for (i, &p) in points.iter().enumerate() {
+        let width = widths.and_then(|w| w.get(i).copied()).unwrap_or(default_width).max(0.0);
+        match out_points.last() {
+            Some(&last) if last.distance(p) <= f32::EPSILON * 16.0 => {
+                let last_width = out_widths.last_mut().unwrap();
+                *last_width = f32::max(*last_width, width);
+            }
+            _ => {
+                out_points.push(p);
+                out_widths.push(width);
+            }
+        }
+    }
+    (out_points, out_widths)
+}
+
+/// Splits a polyline into its "on" dash intervals.
+///
+/// Dash boundaries are placed by their distance along the whole polyline
+/// rather than by what is left of the current segment, so rounding does not
+/// build up from one segment to the next. A boundary within rounding of a
+/// vertex lands on that vertex, so no dash gets a sliver of a segment whose
+/// direction is mostly noise.
+fn split_dashes(points: &[Point], widths: &[f32], options: &StrokeOptions) -> Vec<(Vec<Point>, Vec<f32>)> {
+    let mut pattern = options.dash.clone();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_slice(&options.dash);
+    }
+    let period: f32 = pattern.iter().sum();
+
+    // Locate the starting position inside the pattern.
+    let mut phase = options.dash_offset.rem_euclid(period);
+    let mut index = 0;
+    for _ in 0..pattern.len() * 2 {
+        if phase < pattern[index] {
+            break;
+        }
+        phase -= pattern[index];
+        index = (index + 1) % pattern.len();
+    }
+    // Distance along the polyline of the next switch between dash and gap.
+    let mut boundary = pattern[index] - phase;
+    let mut on = index % 2 == 0;
+
+    let mut dashes = Vec::new();
+    let mut current: (Vec<Point>, Vec<f32>) = (Vec::new(), Vec::new());
+    if on {
+        current.0.push(points[0]);
+        current.1.push(widths[0]);
+    }
+
+    let mut start = 0.0;
+    for i in 0..points.len().saturating_sub(1) {
+        let (a, b) = (points[i], points[i + 1]);
+        let length = a.distance(b);
+        let end = start + length;
+        // Points only carry `magnitude * EPSILON` of precision, so a piece
+        // shorter than about a thousand of those has no reliable direction.
+        let snap = b.x.abs().max(b.y.abs()).max(1.0) * f32::EPSILON * 1024.0;
+        // Boundaries this close to `b` wait for the next segment, which
+        // puts them on `b` itself.
+        while boundary < end - snap {
+            let t = ((boundary - start) / length).clamp(0.0, 1.0);
+            push_dash_point(&mut current, a.lerp(b, t), widths[i] + (widths[i + 1] - widths[i]) * t, snap);
+            if on {
+                dashes.push(std::mem::take(&mut current));
+            }
+            on = !on;
+            index = (index + 1) % pattern.len();
+            boundary += pattern[index];
+        }
+        if on {
+            push_dash_point(&mut current, b, widths[i + 1], snap);
+        }
+        start = end;
+    }
+    if on && current.0.len() > 1 {
+        dashes.push(current);
+    }
+    dashes
+}
+
+/// Adds a point to a dash unless it repeats the last one. A zero-length
+/// dash stays a single point, which round and square caps still draw.
+fn push_dash_point(dash: &mut (Vec<Point>, Vec<f32>), p: Point, width: f32, snap: f32) {
+    match (dash.0.last(), dash.1.last_mut()) {
+        (Some(last), Some(last_width)) if last.distance(p) <= snap => *last_width = last_width.max(width),
+        _ => {
+            dash.0.push(p);
+            dash.1.push(width);
+        }
+    }
+}
+
+/// Strokes one continuous run (already deduplicated).
+fn stroke_run(points: &[Point], widths: &[f32], closed: bool, options: &StrokeOptions, mesh: &mut Mesh) {
+    if points.len() == 1 {
+        // A single point only shows up with round or square caps.
+        let half = widths[0] * 0.5;
+        match options.cap {
+            LineCap::Round => mesh.arc(points[0], Point::new(half, 0.0), 2.0 * PI, half, options.tolerance),
+            LineCap::Square => {
+                let p = points[0];
+                mesh.quad(
+                    p + Point::new(-half, -half),
+                    p + Point::new(half, -half),
+                    p + Point::new(-half, half),
+                    p + Point::new(half, half),
+                );
+            }
+            LineCap::Butt => {}
+        }
+        return;
+    }
+
+    let n = points.len();
+    let segment_count = if closed { n } else { n - 1 };
+    for i in 0..segment_count {
+        let j = (i + 1) % n;
+        let (a, b) = (points[i], points[j]);
+        let normal = (b - a).normalize().perp();
+        let (ha, hb) = (widths[i] * 0.5, widths[j] * 0.5);
+        mesh.quad(a + normal * ha, a - normal * ha, b + normal * hb, b - normal * hb);
+    }
+
+    let joins = if closed { 0..n } else { 1..n - 1 };
+    for i in joins {
+        let prev = points[(i + n - 1) % n];
+        let next = points[(i + 1) % n];
+        join(mesh, prev, points[i], next, widths[i] * 0.5, options);
+    }
+
+    if !closed {
+        cap(mesh, points[1], points[0], widths[0] * 0.5, options);
+        cap(mesh, points[n - 2], points[n - 1], widths[n - 1] * 0.5, options);
+    }
+}
+
+/// Fills the wedge on the outer side of the corner at `p`.
+fn join(mesh: &mut Mesh, prev: Point, p: Point, next: Point, half: f32, options: &StrokeOptions) {
+    let d0 = (p - prev).normalize();
+    let d1 = (next - p).normalize();
+    let turn = d0.cross(d1);
+    if turn.abs() < 1e-6 && d0.dot(d1) > 0.0 {
+        return; // Collinear, the segment quads already meet.
+    }
+    // The outer side is opposite the turn direction.
+    let side = if turn > 0.0 { -1.0 } else { 1.0 };
+    let o0 = d0.perp() * (half * side);
+    let o1 = d1.perp() * (half * side);
+
+    match options.join {
+        LineJoin::Bevel => mesh.triangle(p, p + o0, p + o1),
+        LineJoin::Round => {
+            let angle = o0.cross(o1).atan2(o0.dot(o1));
+            mesh.arc(p, o0, angle, half, options.tolerance);
+        }
+        LineJoin::Miter => {
+            let bisector = (o0 + o1).normalize();
+            let cos_half = bisector.dot(o0.normalize());
+            if cos_half <= 1e-4 || 1.0 / cos_half > options.miter_limit {
+                mesh.triangle(p, p + o0, p + o1);
+            } else {
+                let tip = p + bisector * (half / cos_half);
+                mesh.triangle(p, p + o0, tip);
+                mesh.triangle(p, tip, p + o1);
+            }
+        }
+    }
+}
+
+/// Adds the cap at `end`, for a segment coming from `from`.
+fn cap(mesh: &mut Mesh, from: Point, end: Point, half: f32, options: &StrokeOptions) {
+    let dir = (end - from).normalize();
+    let normal = dir.perp() * half;
+    match options.cap {
+        LineCap::Butt => {}
+        LineCap::Square => {
+            let ext = dir * half;
+            mesh.quad(end + normal, end - normal, end + normal + ext, end - normal + ext);
+        }
+        LineCap::Round => mesh.arc(end, normal, -PI, half, options.tolerance),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn point_in_triangle(p: Point, a: Point, b: Point, c: Point) -> bool {
+        // Signed distances to each edge, so the tolerance is in world units.
+        let eps = 1e-3;
+        let d1 = (b - a).normalize().cross(p - a);
+        let d2 = (c - b).normalize().cross(p - b);
+        let d3 = (a - c).normalize().cross(p - c);
+        let has_neg = d1 < -eps || d2 < -eps || d3 < -eps;
+        let has_pos = d1 > eps || d2 > eps || d3 > eps;
+        !(has_neg && has_pos)
+    }
+
+    fn covered(mesh: &Mesh, p: Point) -> bool {
+        mesh.indices.chunks(3).any(|t| {
+            point_in_triangle(
+                p,
+                mesh.vertices[t[0] as usize],
+                mesh.vertices[t[1] as usize],
+                mesh.vertices[t[2] as usize],
+            )
+        })
+    }
+
+    fn assert_well_formed(mesh: &Mesh) {
+        assert_eq!(mesh.indices.len() % 3, 0);
+        assert!(mesh.indices.iter().all(|&i| (i as usize) < mesh.vertices.len()));
+        assert!(mesh.vertices.iter().all(|p| p.x.is_finite() && p.y.is_finite()));
+    }
+
+    /// Samples points strictly inside each segment's quad.
+    fn segment_samples(points: &[Point], widths: &[f32]) -> Vec<Point> {
+        let mut samples = Vec::new();
+        for i in 0..points.len() - 1 {
+            let (a, b) = (points[i], points[i + 1]);
+            let normal = (b - a).normalize().perp();
+            for step in 0..=8 {
+                let t = step as f32 / 8.0;
+                let half = (widths[i] + (widths[i + 1] - widths[i]) * t) * 0.5;
+                for k in [-0.9, -0.5, 0.0, 0.5, 0.9] {
+                    samples.push(a.lerp(b, t) + normal * (half * k));
+                }
+            }
+        }
+        samples
+    }
+
+    fn polyline() -> impl Strategy<Value = Vec<Point>> {
+        prop::collection::vec((-100.0f32..100.0, -100.0f32..100.0), 2..8).prop_map(|v| {
+            let mut points: Vec<Point> = Vec::new();
+            for (x, y) in v {
+                let p = Point::new(x, y);
+                if points.last().is_none_or(|l: &Point| l.distance(p) > 1.0) {
+                    points.push(p);
+                }
+            }
+            points
+        })
+    }
+
+    fn join_strategy() -> impl Strategy<Value = LineJoin> {
+        prop_oneof![Just(LineJoin::Miter), Just(LineJoin::Round), Just(LineJoin::Bevel)]
+    }
+
+    fn cap_strategy() -> impl Strategy<Value = LineCap> {
+        prop_oneof![Just(LineCap::Butt), Just(LineCap::Round), Just(LineCap::Square)]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_segments_are_covered(
+            points in polyline(),
+            width in 0.5f32..20.0,
+            join in join_strategy(),
+            cap in cap_strategy(),
+        ) {
+            prop_assume!(points.len() >= 2);
+            let options = StrokeOptions { width, join, cap, ..StrokeOptions::default() };
+            let mesh = stroke_polyline(&points, None, false, &options);
+            assert_well_formed(&mesh);
+            let widths = vec![width; points.len()];
+            for p in segment_samples(&points, &widths) {
+                prop_assert!(covered(&mesh, p), "gap at {:?}", p);
+            }
+        }
+
+        #[test]
+        fn prop_variable_width_segments_are_covered(
+            points in polyline(),
+            seed_widths in prop::collection::vec(0.5f32..20.0, 8),
+        ) {
+            prop_assume!(points.len() >= 2);
+            let widths: Vec<f32> = (0..points.len()).map(|i| seed_widths[i % seed_widths.len()]).collect();
+            let options = StrokeOptions { join: LineJoin::Round, cap: LineCap::Round, ..StrokeOptions::default() };
+            let mesh = stroke_polyline(&points, Some(&widths), false, &options);
+            assert_well_formed(&mesh);
+            for p in segment_samples(&points, &widths) {
+                prop_assert!(covered(&mesh, p), "gap at {:?}", p);
+            }
+        }
+
+        /// With round joins and caps the stroke is every point within half the
+        /// width of the centreline, so any such point must be covered.
+        #[test]
+        fn prop_round_stroke_is_watertight(
+            points in polyline(),
+            width in 1.0f32..20.0,
+            probes in prop::collection::vec((0.0f32..1.0, 0.0f32..1.0, -0.9f32..0.9), 64),
+        ) {
+            prop_assume!(points.len() >= 2);
+            let options = StrokeOptions { width, join: LineJoin::Round, cap: LineCap::Round, tolerance: 0.01, ..StrokeOptions::default() };
+            let mesh = stroke_polyline(&points, None, false, &options);
+            for (segment, t, offset) in probes {
+                let i = ((segment * (points.len() - 1) as f32) as usize).min(points.len() - 2);
+                let on_line = points[i].lerp(points[i + 1], t);
+                let angle = offset * PI * 2.0;
+                let p = on_line + Point::new(angle.cos(), angle.sin()) * (width * 0.5 * offset.abs() * 0.9);
+                prop_assert!(covered(&mesh, p), "gap at {:?}", p);
+            }
+        }
+
+        #[test]
+        fn prop_dashes_stay_on_the_line(
+            points in polyline(),
+            dash in prop::collection::vec(0.5f32..10.0, 1..4),
+            offset in -20.0f32..20.0,
+        ) {
+            prop_assume!(points.len() >= 2);
+            let width = 2.0;
+            let options = StrokeOptions { width, dash, dash_offset: offset, ..StrokeOptions::default() };
+            let mesh = stroke_polyline(&points, None, false, &options);
+            assert_well_formed(&mesh);
+            let solid = stroke_polyline(&points, None, false, &StrokeOptions { width, ..StrokeOptions::default() });
+            for t in mesh.indices.chunks(3) {
+                let c = (mesh.vertices[t[0] as usize] + mesh.vertices[t[1] as usize] + mesh.vertices[t[2] as usize]) * (1.0 / 3.0);
+                prop_assert!(covered(&solid, c));
+            }
+        }
+    }
+
+    #[test]
+    fn test_dash_lengths() {
+        let points = [Point::new(0.0, 0.0), Point::new(10.0, 0.0)];
+        let widths = [1.0, 1.0];
+        let options = StrokeOptions {
+            dash: vec![2.0, 1.0],
+            ..StrokeOptions::default()
+        };
+        let dashes = split_dashes(&points, &widths, &options);
+        let starts: Vec<f32> = dashes.iter().map(|d| d.0[0].x).collect();
+        assert_eq!(starts, vec![0.0, 3.0, 6.0, 9.0]);
+        assert_eq!(dashes[3].0[1].x, 10.0);
+
+        let shifted = split_dashes(&points, &widths, &StrokeOptions { dash_offset: 1.0, ..options });
+        assert_eq!(shifted[0].0[1].x, 1.0);
+        assert_eq!(shifted[1].0[0].x, 2.0);
+    }
+
+    #[test]
+    fn test_dashes_span_segment_joins() {
+        let points = [Point::new(0.0, 0.0), Point::new(3.0, 0.0), Point::new(3.0, 4.0)];
+        let widths = [1.0, 1.0, 1.0];
+        let options = StrokeOptions {
+            dash: vec![4.0, 1.0],
+            ..StrokeOptions::default()
+        };
+        let dashes = split_dashes(&points, &widths, &options);
+        assert_eq!(dashes[0].0, vec![Point::new(0.0, 0.0), Point::new(3.0, 0.0), Point::new(3.0, 1.0)]);
+        assert_eq!(dashes[1].0, vec![Point::new(3.0, 2.0), Point::new(3.0, 4.0)]);
+
+        // Many short segments: every dash keeps its length, with no slivers
+        // left over where a boundary meets a vertex.
+        let points: Vec<Point> = (0..=200).map(|i| Point::new(i as f32 * 0.05, (i % 2) as f32 * 0.05)).collect();
+        let widths = vec![1.0; points.len()];
+        let options = StrokeOptions {
+            dash: vec![0.3, 0.2],
+            ..StrokeOptions::default()
+        };
+        let dashes = split_dashes(&points, &widths, &options);
+        let length = |dash: &Vec<Point>| dash.windows(2).map(|w| w[0].distance(w[1])).sum::<f32>();
+        let total = length(&points);
+        assert_eq!(dashes.len(), (total / 0.5).ceil() as usize);
+        for (dash, _) in &dashes[..dashes.len() - 1] {
+            assert!((length(dash) - 0.3).abs() < 2e-3, "dash of {}", length(dash));
+            assert!(dash.windows(2).all(|w| w[0].distance(w[1]) > 1e-3));
+        }
+    }
+
+    #[test]
+    fn test_miter_limit_falls_back_to_bevel() {
+        // A hairpin turn whose miter would be very long.
+        let points = [Point::new(0.0, 0.0), Point::new(10.0, 0.0), Point::new(0.0, 0.5)];
+        let options = StrokeOptions {
+            width: 2.0,
+            ..StrokeOptions::default()
+        };
+        let mesh = stroke_polyline(&points, None, false, &options);
+        let max_x = mesh.vertices.iter().map(|p| p.x).fold(f32::MIN, f32::max);
+        assert!(max_x < 11.5);
+    }
+
+    #[test]
+    fn test_closed_path_has_no_caps() {
+        let square = [
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(10.0, 10.0),
+            Point::new(0.0, 10.0),
+        ];
+        let options = StrokeOptions {
+            width: 2.0,
+            cap: LineCap::Square,
+            ..StrokeOptions::default()
+        };
+        let mesh = stroke_polyline(&square, None, true, &options);
+        // Four segments plus four two-triangle miters.
+        assert_eq!(mesh.indices.len() / 3, 4 * 2 + 4 * 2);
+        assert!(covered(&mesh, Point::new(-0.9, -0.9)));
+        assert!(!covered(&mesh, Point::new(5.0, 5.0)));
+    }
+
+    #[test]
+    fn test_flatten_cubic_stays_close() {
+        let mut path = Path::new(Point::new(0.0, 0.0));
+        path.segments.push(PathSegment::CubicTo(
+            Point::new(0.0, 100.0),
+            Point::new(100.0, 100.0),
+            Point::new(100.0, 0.0),
+        ));
+        let points = path.flatten(0.1);
+        assert!(points.len() > 10);
+        assert_eq!(*points.last().unwrap(), Point::new(100.0, 0.0));
+        // The curve's midpoint is at (50, 75).
+        let closest = points
+            .windows(2)
+            .map(|w| Point::new(50.0, 75.0).distance_to_segment(w[0], w[1]))
+            .fold(f32::MAX, f32::min);
+        assert!(closest < 0.1);
+    }
+
+    #[test]
+    fn test_single_point_round_cap() {
+        let mesh = stroke_polyline(
+            &[Point::new(1.0, 1.0)],
+            None,
+            false,
+            &StrokeOptions {
+                width: 4.0,
+                cap: LineCap::Round,
+                ..StrokeOptions::default()
+            },
+        );
+        assert!(covered(&mesh, Point::new(2.5, 1.0)));
+    }
+}
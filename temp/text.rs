@@ -0,0 +1,612 @@
+//! Text layout: font fallback, kerning, line wrapping and alignment.
+//!
+//! Everything is in world units with the origin at the top-left of the text
+//! box and y pointing down, so the renderer and the editor share one
+//! measurement. Glyph rasterization lives in [`crate::glyph_atlas`].
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use ttf_parser::gpos::{PairAdjustment, PositioningSubtable};
+use ttf_parser::{Face, GlyphId, OutlineBuilder, Tag};
+
+use crate::geometry::{Point, Rect};
+
+/// Line metrics used when no font is loaded, as fractions of the font size.
+const FALLBACK_ASCENDER: f32 = 0.8;
+const FALLBACK_DESCENDER: f32 = -0.2;
+const FALLBACK_LINE_GAP: f32 = 0.25;
+
+/// Segments per curve when flattening glyph outlines.
+const CURVE_SEGMENTS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlign {
+    /// Fraction of the spare width placed before a line.
+    fn factor(self) -> f32 {
+        match self {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => 0.5,
+            TextAlign::Right => 1.0,
+        }
+    }
+}
+
+/// A TrueType or OpenType font file.
+pub struct Font {
+    data: Vec<u8>,
+}
+
+impl Font {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, String> {
+        Face::parse(&data, 0).map_err(|e| format!("Invalid font: {}", e))?;
+        Ok(Self { data })
+    }
+
+    fn face(&self) -> Face<'_> {
+        // Cannot fail: checked in `from_bytes`.
+        Face::parse(&self.data, 0).expect("font was validated on load")
+    }
+}
+
+/// A glyph in a particular font of a [`FontStack`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    pub font: usize,
+    pub glyph: u16,
+}
+
+/// The primary font followed by fallbacks, tried in order for each
+/// character.
+#[derive(Default)]
+pub struct FontStack {
+    fonts: Vec<Font>,
+    revision: u64,
+}
+
+impl FontStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a font after the existing ones; the first font pushed is the
+    /// primary and sets the line metrics.
+    pub fn push(&mut self, font: Font) {
+        self.fonts.push(font);
+        self.revision += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.fonts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fonts.is_empty()
+    }
+
+    /// Bumped whenever a font is added, so cached layouts can be redone.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// The glyph's outline as closed polygons in em units, y down, relative
+    /// to the pen position on the baseline. Empty for blank glyphs.
+    pub fn outline(&self, key: GlyphKey) -> Vec<Vec<Point>> {
+        let Some(font) = self.fonts.get(key.font) else {
+            return Vec::new();
+        };
+        let face = font.face();
+        let mut builder = Flattener {
+            scale: 1.0 / face.units_per_em() as f32,
+            contours: Vec::new(),
+        };
+        face.outline_glyph(GlyphId(key.glyph), &mut builder);
+        builder.contours.retain(|contour| contour.len() >= 3);
+        builder.contours
+    }
+}
+
+/// Collects a glyph outline as polylines, flipping y to point down.
+struct Flattener {
+    scale: f32,
+    contours: Vec<Vec<Point>>,
+}
+
+impl Flattener {
+    fn point(&self, x: f32, y: f32) -> Point {
+        Point::new(x * self.scale, -y * self.scale)
+    }
+
+    fn last(&self) -> Point {
+        self.contours.last().and_then(|c| c.last()).copied().unwrap_or(Point::ZERO)
+    }
+
+    fn push(&mut self, point: Point) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.push(point);
+        }
+    }
+}
+
+impl OutlineBuilder for Flattener {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let point = self.point(x, y);
+        self.contours.push(vec![point]);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let point = self.point(x, y);
+        self.push(point);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (p0, p1, p2) = (self.last(), self.point(x1, y1), self.point(x, y));
+        for i in 1..=CURVE_SEGMENTS {
+            let t = i as f32 / CURVE_SEGMENTS as f32;
+            self.push(p0.lerp(p1, t).lerp(p1.lerp(p2, t), t));
+        }
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (p0, p1, p2, p3) = (self.last(), self.point(x1, y1), self.point(x2, y2), self.point(x, y));
+        for i in 1..=CURVE_SEGMENTS {
+            let t = i as f32 / CURVE_SEGMENTS as f32;
+            let (a, b, c) = (p0.lerp(p1, t), p1.lerp(p2, t), p2.lerp(p3, t));
+            self.push(a.lerp(b, t).lerp(b.lerp(c, t), t));
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(contour) = self.contours.last_mut() {
+            if contour.len() > 1 && contour.first() == contour.last() {
+                contour.pop();
+            }
+        }
+    }
+}
+
+/// Horizontal adjustment between two glyphs in font units, from the GPOS
+/// `kern` feature if the font has one, else the legacy `kern` table.
+fn kerning(face: &Face, left: GlyphId, right: GlyphId) -> i16 {
+    if let Some(gpos) = face.tables().gpos {
+        let kern = Tag::from_bytes(b"kern");
+        for feature in gpos.features.into_iter().filter(|f| f.tag == kern) {
+            for index in feature.lookup_indices {
+                let Some(lookup) = gpos.lookups.get(index) else {
+                    continue;
+                };
+                for subtable in lookup.subtables.into_iter::<PositioningSubtable>() {
+                    let PositioningSubtable::Pair(pair) = subtable else {
+                        continue;
+                    };
+                    let Some(coverage) = pair.coverage().get(left) else {
+                        continue;
+                    };
+                    let values = match pair {
+                        PairAdjustment::Format1 { sets, .. } => sets.get(coverage).and_then(|set| set.get(right)),
+                        PairAdjustment::Format2 { classes, matrix, .. } => {
+                            matrix.get((classes.0.get(left), classes.1.get(right)))
+                        }
+                    };
+                    if let Some((first, _)) = values {
+                        return first.x_advance;
+                    }
+                }
+            }
+        }
+    }
+    face.tables()
+        .kern
+        .and_then(|kern| {
+            kern.subtables
+                .into_iter()
+                .filter(|s| s.horizontal && !s.variable && !s.has_cross_stream)
+                .find_map(|s| s.glyphs_kerning(left, right))
+        })
+        .unwrap_or(0)
+}
+
+/// A glyph placed by [`layout`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    pub key: GlyphKey,
+    /// Byte offset of the character in the laid out text.
+    pub byte: usize,
+    /// Pen position: left edge and baseline.
+    pub x: f32,
+    pub y: f32,
+    pub advance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutLine {
+    /// Bytes of the text on this line, including trailing whitespace but not
+    /// the newline.
+    pub text: Range<usize>,
+    /// Indices into [`TextLayout::glyphs`].
+    pub glyphs: Range<usize>,
+    /// Offset from alignment.
+    pub x: f32,
+    /// Width without trailing whitespace.
+    pub width: f32,
+    pub baseline: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub lines: Vec<LayoutLine>,
+    /// Widest line.
+    pub width: f32,
+    pub height: f32,
+    pub line_height: f32,
+}
+
+impl TextLayout {
+    /// The line the caret sits on at byte `index`. At a soft wrap the caret
+    /// belongs to the start of the next line.
+    pub fn line_at(&self, index: usize) -> usize {
+        let last = self.lines.len().saturating_sub(1);
+        self.lines
+            .iter()
+            .position(|line| index < line.text.end || (index == line.text.end && !self.wraps_after(line)))
+            .unwrap_or(last)
+    }
+
+    fn wraps_after(&self, line: &LayoutLine) -> bool {
+        self.lines.iter().any(|next| next.text.start == line.text.end && next != line)
+    }
+
+    /// Horizontal position of byte `index` on `line`.
+    fn x_at(&self, line: &LayoutLine, index: usize) -> f32 {
+        let glyphs = &self.glyphs[line.glyphs.clone()];
+        match glyphs.iter().find(|glyph| glyph.byte >= index) {
+            Some(glyph) => glyph.x,
+            None => glyphs.last().map_or(line.x, |glyph| glyph.x + glyph.advance),
+        }
+    }
+
+    /// The caret at byte `index` as a zero-width box one line tall.
+    pub fn caret(&self, index: usize) -> Rect {
+        let line = self.line_at(index);
+        let top = line as f32 * self.line_height;
+        let x = self.lines.get(line).map_or(0.0, |l| self.x_at(l, index));
+        Rect::new(x, top, x, top + self.line_height)
+    }
+
+    /// Highlight boxes for the bytes in `range`, one per line it touches.
+    pub fn selection_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        if range.is_empty() {
+            return Vec::new();
+        }
+        let mut rects = Vec::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            let start = range.start.max(line.text.start);
+            let end = range.end.min(line.text.end);
+            // Selected line breaks show as a sliver, so empty lines are visible.
+            let newline = range.end > line.text.end && range.start <= line.text.end && !self.wraps_after(line);
+            if start >= end && !newline {
+                continue;
+            }
+            let top = i as f32 * self.line_height;
+            let min_x = self.x_at(line, start);
+            let mut max_x = self.x_at(line, end.max(start));
+            if newline {
+                max_x += self.line_height * 0.25;
+            }
+            rects.push(Rect::new(min_x, top, max_x, top + self.line_height));
+        }
+        rects
+    }
+}
+
+/// Converts a UTF-16 offset, as the DOM reports them, to a byte offset in
+/// `text`. Offsets inside a surrogate pair or past the end are clamped.
+pub fn utf16_to_byte(text: &str, offset: usize) -> usize {
+    let mut units = 0;
+    for (byte, ch) in text.char_indices() {
+        if units + ch.len_utf16() > offset {
+            return byte;
+        }
+        units += ch.len_utf16();
+    }
+    text.len()
+}
+
+/// A character resolved to a glyph, before line breaking.
+struct Cluster {
+    byte: usize,
+    whitespace: bool,
+    key: GlyphKey,
+    advance: f32,
+    /// Kerning against the previous cluster on the same line.
+    kern: f32,
+}
+
+/// Lays out `text` at `font_size`, wrapping at spaces to `max_width` if
+/// given. Words wider than the box are broken between characters; `\n`
+/// always starts a new line.
+pub fn layout(fonts: &FontStack, text: &str, font_size: f32, max_width: Option<f32>, align: TextAlign) -> TextLayout {
+    let faces: Vec<Face> = fonts.fonts.iter().map(Font::face).collect();
+    let (ascender, descender, line_gap) = match faces.first() {
+        Some(face) => {
+            let scale = font_size / face.units_per_em() as f32;
+            (
+                face.ascender() as f32 * scale,
+                face.descender() as f32 * scale,
+                face.line_gap() as f32 * scale,
+            )
+        }
+        None => (
+            FALLBACK_ASCENDER * font_size,
+            FALLBACK_DESCENDER * font_size,
+            FALLBACK_LINE_GAP * font_size,
+        ),
+    };
+    let line_height = ascender - descender + line_gap;
+
+    let mut glyphs = Vec::new();
+    let mut lines = Vec::new();
+    let mut paragraph_start = 0;
+    for paragraph in text.split('\n') {
+        let clusters = shape(&faces, paragraph, paragraph_start, font_size);
+        for range in break_lines(&clusters, max_width) {
+            let baseline = ascender + lines.len() as f32 * line_height;
+            let first_glyph = glyphs.len();
+            let mut pen = 0.0;
+            let mut width = 0.0;
+            for (i, cluster) in clusters[range.clone()].iter().enumerate() {
+                if i > 0 {
+                    pen += cluster.kern;
+                }
+                glyphs.push(PositionedGlyph {
+                    key: cluster.key,
+                    byte: cluster.byte,
+                    x: pen,
+                    y: baseline,
+                    advance: cluster.advance,
+                });
+                pen += cluster.advance;
+                if !cluster.whitespace {
+                    width = pen;
+                }
+            }
+            let end = clusters.get(range.end).map_or(paragraph_start + paragraph.len(), |c| c.byte);
+            let start = clusters.get(range.start).map_or(end, |c| c.byte);
+            lines.push(LayoutLine {
+                text: start..end,
+                glyphs: first_glyph..glyphs.len(),
+                x: 0.0,
+                width,
+                baseline,
+            });
+        }
+        paragraph_start += paragraph.len() + 1;
+    }
+
+    let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+    let box_width = max_width.unwrap_or(width);
+    for line in &mut lines {
+        line.x = (box_width - line.width) * align.factor();
+        for glyph in &mut glyphs[line.glyphs.clone()] {
+            glyph.x += line.x;
+        }
+    }
+    TextLayout {
+        glyphs,
+        height: lines.len() as f32 * line_height,
+        lines,
+        width,
+        line_height,
+    }
+}
+
+/// Resolves each character to the first font that has it, with advances
+/// and kerning in world units.
+fn shape(faces: &[Face], text: &str, offset: usize, font_size: f32) -> Vec<Cluster> {
+    let mut clusters: Vec<Cluster> = Vec::with_capacity(text.len());
+    for (byte, ch) in text.char_indices() {
+        let resolved = faces
+            .iter()
+            .enumerate()
+            .find_map(|(font, face)| face.glyph_index(ch).map(|glyph| (font, glyph)));
+        // Nothing has it: the primary font's missing-glyph box.
+        let (font, glyph) = resolved.unwrap_or((0, GlyphId(0)));
+        let (advance, kern) = match faces.get(font) {
+            Some(face) => {
+                let scale = font_size / face.units_per_em() as f32;
+                let advance = face.glyph_hor_advance(glyph).unwrap_or(0) as f32 * scale;
+                let kern = match clusters.last() {
+                    Some(previous) if previous.key.font == font => {
+                        kerning(face, GlyphId(previous.key.glyph), glyph) as f32 * scale
+                    }
+                    _ => 0.0,
+                };
+                (advance, kern)
+            }
+            None => (0.0, 0.0),
+        };
+        clusters.push(Cluster {
+            byte: offset + byte,
+            whitespace: ch.is_whitespace(),
+            key: GlyphKey { font, glyph: glyph.0 },
+            advance,
+            kern,
+        });
+    }
+    clusters
+}
+
+/// Greedy line breaking. Whitespace never forces a break; it hangs past
+/// the edge instead.
+fn break_lines(clusters: &[Cluster], max_width: Option<f32>) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut pen = 0.0;
+    // Where the next line would start if we broke after the last space.
+    let mut opportunity = None;
+    let mut i = 0;
+    while i < clusters.len() {
+        let cluster = &clusters[i];
+        let end = pen + if i > start { cluster.kern } else { 0.0 } + cluster.advance;
+        let overflows = max_width.is_some_and(|max| end > max + 1e-3);
+        if overflows && !cluster.whitespace && i > start {
+            let next = opportunity.filter(|&at| at > start).unwrap_or(i);
+            lines.push(start..next);
+            start = next;
+            i = next;
+            pen = 0.0;
+            opportunity = None;
+            continue;
+        }
+        pen = end;
+        if cluster.whitespace {
+            opportunity = Some(i + 1);
+        }
+        i += 1;
+    }
+    lines.push(start..clusters.len());
+    lines
+}
+
+#[cfg(test)]
+pub(crate) fn test_fonts() -> FontStack {
+    let mut fonts = FontStack::new();
+    fonts.push(Font::from_bytes(epaint_default_fonts::UBUNTU_LIGHT.to_vec()).unwrap());
+    fonts.push(Font::from_bytes(epaint_default_fonts::NOTO_EMOJI_REGULAR.to_vec()).unwrap());
+    fonts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_texts<'a>(text: &'a str, layout: &TextLayout) -> Vec<&'a str> {
+        layout.lines.iter().map(|line| text[line.text.clone()].trim_end()).collect()
+    }
+
+    #[test]
+    fn test_fallback_font_for_missing_glyphs() {
+        let fonts = test_fonts();
+        let mixed = layout(&fonts, "a\u{1F600}", 20.0, None, TextAlign::Left);
+        assert_eq!(mixed.glyphs[0].key.font, 0);
+        assert_eq!(mixed.glyphs[1].key.font, 1);
+        assert_eq!(mixed.glyphs[1].byte, 1);
+        assert!(mixed.glyphs[1].advance > 0.0);
+        assert!(!fonts.outline(mixed.glyphs[1].key).is_empty());
+        // Nothing has it: the primary font's missing glyph.
+        let missing = layout(&fonts, "\u{E000}", 20.0, None, TextAlign::Left);
+        assert_eq!(missing.glyphs[0].key, GlyphKey { font: 0, glyph: 0 });
+    }
+
+    #[test]
+    fn test_kerning_from_font_tables() {
+        let fonts = test_fonts();
+        let pair = layout(&fonts, "AV", 100.0, None, TextAlign::Left);
+        let a = layout(&fonts, "A", 100.0, None, TextAlign::Left);
+        let v = layout(&fonts, "V", 100.0, None, TextAlign::Left);
+        assert!(pair.width < a.width + v.width - 1.0);
+        assert!(pair.glyphs[1].x < a.glyphs[0].advance);
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let fonts = test_fonts();
+        let text = "the quick brown fox";
+        let single = layout(&fonts, text, 20.0, None, TextAlign::Left);
+        assert_eq!(single.lines.len(), 1);
+
+        let wrapped = layout(&fonts, text, 20.0, Some(single.width * 0.6), TextAlign::Left);
+        assert_eq!(line_texts(text, &wrapped), ["the quick", "brown fox"]);
+        assert!(wrapped.lines.iter().all(|line| line.width <= single.width * 0.6));
+        assert_eq!(wrapped.height, 2.0 * wrapped.line_height);
+        assert_eq!(wrapped.lines[1].glyphs.start, "the quick ".len());
+        assert_eq!(wrapped.glyphs[wrapped.lines[1].glyphs.start].x, 0.0);
+
+        // A word wider than the box is split between characters.
+        let narrow = layout(&fonts, "abcdefgh", 20.0, Some(30.0), TextAlign::Left);
+        assert!(narrow.lines.len() > 1);
+        assert!(narrow.lines.iter().all(|line| line.width <= 30.0 && !line.text.is_empty()));
+
+        let paragraphs = layout(&fonts, "one\n\ntwo", 20.0, None, TextAlign::Left);
+        assert_eq!(line_texts("one\n\ntwo", &paragraphs), ["one", "", "two"]);
+        assert_eq!(paragraphs.lines[2].baseline - paragraphs.lines[0].baseline, 2.0 * paragraphs.line_height);
+    }
+
+    #[test]
+    fn test_alignment() {
+        let fonts = test_fonts();
+        let text = "wide line\nhi";
+        let left = layout(&fonts, text, 20.0, Some(200.0), TextAlign::Left);
+        let center = layout(&fonts, text, 20.0, Some(200.0), TextAlign::Center);
+        let right = layout(&fonts, text, 20.0, Some(200.0), TextAlign::Right);
+        for line in 0..2 {
+            let width = left.lines[line].width;
+            assert_eq!(left.lines[line].x, 0.0);
+            assert!((center.lines[line].x - (200.0 - width) / 2.0).abs() < 1e-3);
+            assert!((right.lines[line].x - (200.0 - width)).abs() < 1e-3);
+        }
+        let first_of_second = right.lines[1].glyphs.start;
+        assert_eq!(right.glyphs[first_of_second].x, right.lines[1].x);
+
+        // Without a box, lines align within the widest one.
+        let unbounded = layout(&fonts, text, 20.0, None, TextAlign::Right);
+        assert_eq!(unbounded.lines[0].x, 0.0);
+        assert!((unbounded.lines[1].x + unbounded.lines[1].width - unbounded.width).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_measurement_without_fonts() {
+        let measured = layout(&FontStack::new(), "a\nb", 10.0, None, TextAlign::Left);
+        assert_eq!(measured.lines.len(), 2);
+        assert_eq!(measured.width, 0.0);
+        assert!((measured.height - 25.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_caret_and_selection_geometry() {
+        let fonts = test_fonts();
+        let text = "the quick\nbrown fox";
+        let laid_out = layout(&fonts, text, 20.0, None, TextAlign::Left);
+        let first = laid_out.caret(0);
+        assert_eq!((first.min_x, first.min_y, first.height()), (0.0, 0.0, laid_out.line_height));
+        assert_eq!(laid_out.caret(4).min_x, laid_out.glyphs[4].x);
+        // End of a paragraph stays on its line; the next byte starts the next.
+        assert_eq!(laid_out.line_at(9), 0);
+        assert_eq!(laid_out.line_at(10), 1);
+        assert_eq!(laid_out.caret(text.len()).min_y, laid_out.line_height);
+
+        let rects = laid_out.selection_rects(4..13);
+        assert_eq!(rects.len(), 2);
+        assert_eq!(rects[0].min_x, laid_out.glyphs[4].x);
+        assert!(rects[0].max_x > laid_out.lines[0].width);
+        assert_eq!(rects[1].min_x, 0.0);
+        assert!(laid_out.selection_rects(3..3).is_empty());
+
+        // At a soft wrap the caret moves down to the next line.
+        let wrapped = layout(&fonts, "the quick brown fox", 20.0, Some(laid_out.lines[0].width + 5.0), TextAlign::Left);
+        let wrap = wrapped.lines[1].text.start;
+        assert_eq!(wrapped.line_at(wrap), 1);
+        assert_eq!(wrapped.caret(wrap).min_x, 0.0);
+    }
+
+    #[test]
+    fn test_utf16_offsets() {
+        let text = "a\u{1F600}é";
+        assert_eq!(utf16_to_byte(text, 0), 0);
+        assert_eq!(utf16_to_byte(text, 1), 1);
+        // Inside the surrogate pair.
+        assert_eq!(utf16_to_byte(text, 2), 1);
+        assert_eq!(utf16_to_byte(text, 3), 5);
+        assert_eq!(utf16_to_byte(text, 4), 7);
+        assert_eq!(utf16_to_byte(text, 99), 7);
+    }
+}
@@ -0,0 +1,187 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CompositionEvent, Document, Event, HtmlCanvasElement, HtmlTextAreaElement, KeyboardEvent};
+
+use crate::document::{ShapeId, ShapeKind};
+use crate::editor::Editor;
+use crate::text::{layout, TextAlign};
+use crate::tools::{InputEvent, Modifiers};
+
+/// CSS family the `n`th loaded font is registered under.
+pub fn font_family(n: usize) -> String {
+    format!("board-font-{}", n)
+}
+
+/// UTF-16 length, the unit of DOM text offsets.
+fn utf16_len(text: &str) -> u32 {
+    text.encode_utf16().count() as u32
+}
+
+/// A transparent textarea kept over the text shape being edited.
+///
+/// The browser does the typing, IME composition, selection, clipboard and
+/// accessibility; every change is forwarded to the editor as
+/// [`InputEvent::TextInput`], and the canvas draws the text, caret and
+/// selection itself.
+pub struct TextField {
+    element: HtmlTextAreaElement,
+    canvas: HtmlCanvasElement,
+    editing: Cell<Option<ShapeId>>,
+}
+
+impl TextField {
+    pub fn new(document: &Document, canvas: &HtmlCanvasElement, editor: Rc<RefCell<Editor>>) -> Result<Self, JsValue> {
+        let element = document
+            .get_element_by_id("text-input")
+            .ok_or("Missing #text-input textarea")?
+            .dyn_into::<HtmlTextAreaElement>()?;
+        // Start of the IME composition and its current extent, UTF-16.
+        let composition: Rc<Cell<Option<(u32, u32)>>> = Rc::new(Cell::new(None));
+
+        let forward = {
+            let editor = editor.clone();
+            let element = element.clone();
+            let composition = composition.clone();
+            move || {
+                let start = element.selection_start().ok().flatten().unwrap_or(0);
+                let end = element.selection_end().ok().flatten().unwrap_or(start);
+                let backwards = element.selection_direction().ok().flatten().as_deref() == Some("backward");
+                let selection = if backwards { (end, start) } else { (start, end) };
+                let composition = composition.get().filter(|(start, end)| start < end);
+                editor.borrow_mut().handle_input(InputEvent::TextInput {
+                    content: element.value(),
+                    selection: (selection.0 as usize, selection.1 as usize),
+                    composition: composition.map(|(start, end)| (start as usize, end as usize)),
+                });
+            }
+        };
+        let forward = Rc::new(forward);
+
+        let forward_clone = forward.clone();
+        let input_callback = Closure::wrap(Box::new(move |_event: Event| {
+            forward_clone();
+        }) as Box<dyn FnMut(Event)>);
+
+        let forward_clone = forward.clone();
+        let element_clone = element.clone();
+        let selectionchange_callback = Closure::wrap(Box::new(move |_event: Event| {
+            let focused = element_clone.owner_document().and_then(|d| d.active_element());
+            if focused.is_some_and(|focused| js_sys::Object::is(&focused, &element_clone)) {
+                forward_clone();
+            }
+        }) as Box<dyn FnMut(Event)>);
+
+        let composition_clone = composition.clone();
+        let element_clone = element.clone();
+        let compositionstart_callback = Closure::wrap(Box::new(move |_event: CompositionEvent| {
+            let start = element_clone.selection_start().ok().flatten().unwrap_or(0);
+            composition_clone.set(Some((start, start)));
+        }) as Box<dyn FnMut(CompositionEvent)>);
+
+        let composition_clone = composition.clone();
+        let compositionupdate_callback = Closure::wrap(Box::new(move |event: CompositionEvent| {
+            if let Some((start, _)) = composition_clone.get() {
+                let length = utf16_len(&event.data().unwrap_or_default());
+                composition_clone.set(Some((start, start + length)));
+            }
+        }) as Box<dyn FnMut(CompositionEvent)>);
+
+        let forward_clone = forward;
+        let compositionend_callback = Closure::wrap(Box::new(move |_event: CompositionEvent| {
+            composition.set(None);
+            forward_clone();
+        }) as Box<dyn FnMut(CompositionEvent)>);
+
+        // Everything but Escape stays with the textarea, including its own
+        // undo while typing; the window's shortcuts ignore it.
+        let keydown_callback = Closure::wrap(Box::new(move |event: KeyboardEvent| {
+            if event.key() == "Escape" && !event.is_composing() {
+                event.prevent_default();
+                editor.borrow_mut().handle_input(InputEvent::KeyDown {
+                    key: event.key(),
+                    modifiers: Modifiers::default(),
+                    repeat: event.repeat(),
+                });
+            }
+        }) as Box<dyn FnMut(KeyboardEvent)>);
+
+        element.add_event_listener_with_callback("input", input_callback.as_ref().unchecked_ref())?;
+        document.add_event_listener_with_callback("selectionchange", selectionchange_callback.as_ref().unchecked_ref())?;
+        element.add_event_listener_with_callback("compositionstart", compositionstart_callback.as_ref().unchecked_ref())?;
+        element.add_event_listener_with_callback("compositionupdate", compositionupdate_callback.as_ref().unchecked_ref())?;
+        element.add_event_listener_with_callback("compositionend", compositionend_callback.as_ref().unchecked_ref())?;
+        element.add_event_listener_with_callback("keydown", keydown_callback.as_ref().unchecked_ref())?;
+
+        input_callback.forget();
+        selectionchange_callback.forget();
+        compositionstart_callback.forget();
+        compositionupdate_callback.forget();
+        compositionend_callback.forget();
+        keydown_callback.forget();
+
+        Ok(Self {
+            element,
+            canvas: canvas.clone(),
+            editing: Cell::new(None),
+        })
+    }
+
+    /// Shows, moves and hides the textarea to follow the editor; call once
+    /// per frame.
+    pub fn sync(&self, editor: &Editor) -> Result<(), JsValue> {
+        let Some((shape, edit)) = editor.text_edit() else {
+            if self.editing.take().is_some() {
+                self.element.blur()?;
+                self.element.set_value("");
+                self.element.style().set_property("display", "none")?;
+            }
+            return Ok(());
+        };
+        let ShapeKind::Text { content, font_size, align } = &shape.kind else {
+            return Ok(());
+        };
+        let style = self.element.style();
+        if self.editing.replace(Some(shape.id)) != Some(shape.id) {
+            self.element.set_value(content);
+            let start = utf16_len(&content[..edit.selection.start]);
+            let end = utf16_len(&content[..edit.selection.end]);
+            self.element.set_selection_range(start, end)?;
+            style.set_property("display", "block")?;
+            self.element.focus()?;
+        }
+
+        let (width, height) = editor.viewport();
+        let scale = 1.0 / editor.state.world_units_per_pixel(width, height);
+        let t = &shape.transform;
+        let center = editor.state.world_to_screen(width, height, t.center());
+        let canvas = self.canvas.get_bounding_client_rect();
+        let left = canvas.left() as f32 + self.canvas.client_left() as f32 + center.x - t.width * scale * 0.5;
+        let top = canvas.top() as f32 + self.canvas.client_top() as f32 + center.y - t.height * scale * 0.5;
+        let line_height = layout(&editor.fonts, "", *font_size, None, *align).line_height;
+        let families: Vec<String> = (0..editor.fonts.len()).map(|n| format!("\"{}\"", font_family(n))).collect();
+        let align = match align {
+            TextAlign::Left => "left",
+            TextAlign::Center => "center",
+            TextAlign::Right => "right",
+        };
+        for (property, value) in [
+            ("left", format!("{}px", left)),
+            ("top", format!("{}px", top)),
+            ("width", format!("{}px", t.width * scale)),
+            ("height", format!("{}px", t.height * scale)),
+            ("transform", format!("rotate({}rad)", t.rotation)),
+            ("font-size", format!("{}px", font_size * scale)),
+            ("line-height", format!("{}px", line_height * scale)),
+            ("font-family", families.into_iter().chain(["sans-serif".to_string()]).collect::<Vec<_>>().join(", ")),
+            ("text-align", align.to_string()),
+            // Placed text grows instead of wrapping.
+            ("white-space", if edit.grows() { "pre" } else { "pre-wrap" }.to_string()),
+        ] {
+            style.set_property(property, &value)?;
+        }
+        Ok(())
+    }
+}
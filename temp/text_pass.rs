@@ -0,0 +1,192 @@
+use std::ops::Range;
+
+use web_sys::{WebGl2RenderingContext, WebGlTexture, WebGlVertexArrayObject};
+
+use crate::buffers::DynamicBuffer;
+use crate::document::{Document, Shape};
+use crate::geometry::Rect;
+use crate::glyph_atlas::{glyph_instances, GlyphAtlas, ATLAS_SIZE, GLYPH_INSTANCE_FLOATS};
+use crate::shaders::ShaderProgram;
+use crate::shape_pass::{ShapePass, Target};
+use crate::state::State;
+use crate::text::FontStack;
+
+type Gl = WebGl2RenderingContext;
+
+/// Glyph instances ready to draw.
+struct GlyphLayer {
+    instances: DynamicBuffer,
+    data: Vec<f32>,
+}
+
+impl GlyphLayer {
+    fn new(context: &Gl) -> Result<Self, String> {
+        Ok(Self {
+            instances: DynamicBuffer::new(context, Gl::ARRAY_BUFFER)?,
+            data: Vec::new(),
+        })
+    }
+
+    /// Lays out the text among `shapes`, in paint order, and uploads the
+    /// instances. Returns the instance count after each shape.
+    fn upload(&mut self, context: &Gl, atlas: &mut GlyphAtlas, fonts: &FontStack, document: &Document, shapes: &[&Shape]) -> Vec<usize> {
+        let ends = glyph_instances(atlas, fonts, shapes, |shape| document.paint(shape), &mut self.data);
+        context.bind_vertex_array(None);
+        self.instances.upload_f32(context, &self.data);
+        ends
+    }
+}
+
+/// Draws text shapes as one instanced quad per glyph, sampling a shared
+/// signed distance field atlas.
+///
+/// Layout and glyph rasterization happen on the CPU when the renderer
+/// uploads the document; it then draws runs of glyphs between the shape
+/// batches, in paint order.
+pub struct TextPass {
+    program: ShaderProgram,
+    vao: WebGlVertexArrayObject,
+    export_vao: WebGlVertexArrayObject,
+    texture: WebGlTexture,
+    atlas: GlyphAtlas,
+    document_layer: GlyphLayer,
+    export_layer: GlyphLayer,
+    uploaded_atlas: Option<u64>,
+}
+
+impl TextPass {
+    pub fn new(context: &Gl) -> Result<Self, String> {
+        let program = ShaderProgram::text(context)?;
+        let quad = context.create_buffer()
+            .ok_or("Failed to create buffer")?;
+        let corners: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&quad));
+        unsafe {
+            let view = js_sys::Float32Array::view(&corners);
+            context.buffer_data_with_array_buffer_view(Gl::ARRAY_BUFFER, &view, Gl::STATIC_DRAW);
+        }
+
+        let document_layer = GlyphLayer::new(context)?;
+        let export_layer = GlyphLayer::new(context)?;
+        let vao = Self::setup_vao(context, &quad, &document_layer)?;
+        let export_vao = Self::setup_vao(context, &quad, &export_layer)?;
+
+        let texture = context.create_texture()
+            .ok_or("Failed to create texture")?;
+        context.bind_texture(Gl::TEXTURE_2D, Some(&texture));
+        for (parameter, value) in [
+            (Gl::TEXTURE_MIN_FILTER, Gl::LINEAR),
+            (Gl::TEXTURE_MAG_FILTER, Gl::LINEAR),
+            (Gl::TEXTURE_WRAP_S, Gl::CLAMP_TO_EDGE),
+            (Gl::TEXTURE_WRAP_T, Gl::CLAMP_TO_EDGE),
+        ] {
+            context.tex_parameteri(Gl::TEXTURE_2D, parameter, value as i32);
+        }
+        context.bind_texture(Gl::TEXTURE_2D, None);
+
+        Ok(Self {
+            program,
+            vao,
+            export_vao,
+            texture,
+            atlas: GlyphAtlas::new(ATLAS_SIZE),
+            document_layer,
+            export_layer,
+            uploaded_atlas: None,
+        })
+    }
+
+    fn setup_vao(context: &Gl, quad: &web_sys::WebGlBuffer, layer: &GlyphLayer) -> Result<WebGlVertexArrayObject, String> {
+        let vao = context.create_vertex_array()
+            .ok_or("Failed to create vertex array")?;
+        context.bind_vertex_array(Some(&vao));
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(quad));
+        context.enable_vertex_attrib_array(0);
+        context.vertex_attrib_pointer_with_i32(0, 2, Gl::FLOAT, false, 0, 0);
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&layer.instances.buffer));
+        for location in 1..=4 {
+            context.enable_vertex_attrib_array(location);
+            context.vertex_attrib_divisor(location, 1);
+        }
+        Self::point_instance_attributes(context, 0);
+        context.bind_vertex_array(None);
+        Ok(vao)
+    }
+
+    /// Points the per-glyph attributes of the bound vertex array at the
+    /// instances from `first` on, so a run can be drawn from instance zero.
+    fn point_instance_attributes(context: &Gl, first: usize) {
+        let stride = (GLYPH_INSTANCE_FLOATS * 4) as i32;
+        let base = (first * GLYPH_INSTANCE_FLOATS * 4) as i32;
+        // (location, components, float offset)
+        let attributes: [(u32, i32, i32); 4] = [(1, 4, 0), (2, 4, 4), (3, 3, 8), (4, 4, 11)];
+        for (location, size, offset) in attributes {
+            context.vertex_attrib_pointer_with_i32(location, size, Gl::FLOAT, false, stride, base + offset * 4);
+        }
+    }
+
+    fn upload_atlas(&mut self, context: &Gl) {
+        if self.uploaded_atlas == Some(self.atlas.revision()) {
+            return;
+        }
+        let size = self.atlas.size() as i32;
+        context.bind_texture(Gl::TEXTURE_2D, Some(&self.texture));
+        context.pixel_storei(Gl::UNPACK_ALIGNMENT, 1);
+        let _ = context.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            Gl::TEXTURE_2D,
+            0,
+            Gl::R8 as i32,
+            size,
+            size,
+            0,
+            Gl::RED,
+            Gl::UNSIGNED_BYTE,
+            Some(self.atlas.pixels()),
+        );
+        context.bind_texture(Gl::TEXTURE_2D, None);
+        self.uploaded_atlas = Some(self.atlas.revision());
+    }
+
+    pub fn set_camera(&self, context: &Gl, state: &State, width: f32, height: f32) {
+        ShapePass::set_camera_uniforms(&self.program, context, state, width, height);
+        self.program.set_uniform_1i(context, "u_atlas", 0);
+    }
+
+    /// Lays out the text among `shapes`, in paint order, for
+    /// [`Target::Screen`]. Returns the glyph count after each shape.
+    pub fn upload_document(&mut self, context: &Gl, fonts: &FontStack, document: &Document, shapes: &[&Shape]) -> Vec<usize> {
+        let ends = self.document_layer.upload(context, &mut self.atlas, fonts, document, shapes);
+        self.upload_atlas(context);
+        ends
+    }
+
+    /// Lays out the text among `shapes` for [`Target::Export`]. This may
+    /// clear the atlas, so the document must be uploaded again after.
+    pub fn upload_export(&mut self, context: &Gl, fonts: &FontStack, document: &Document, shapes: &[&Shape]) -> Vec<usize> {
+        let ends = self.export_layer.upload(context, &mut self.atlas, fonts, document, shapes);
+        self.upload_atlas(context);
+        ends
+    }
+
+    /// Draws the `glyphs` of `target` cut to `clip`, with the camera from
+    /// [`TextPass::set_camera`] and blending already set up.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(&self, context: &Gl, target: Target, state: &State, width: f32, height: f32, glyphs: Range<usize>, clip: Option<Rect>) {
+        let (vao, layer) = match target {
+            Target::Screen => (&self.vao, &self.document_layer),
+            Target::Export => (&self.export_vao, &self.export_layer),
+        };
+        context.use_program(Some(&self.program.program));
+        context.active_texture(Gl::TEXTURE0);
+        context.bind_texture(Gl::TEXTURE_2D, Some(&self.texture));
+        context.bind_vertex_array(Some(vao));
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&layer.instances.buffer));
+        ShapePass::set_clip(context, state, width, height, clip);
+        Self::point_instance_attributes(context, glyphs.start);
+        context.draw_arrays_instanced(Gl::TRIANGLE_STRIP, 0, 4, glyphs.len() as i32);
+        Self::point_instance_attributes(context, 0);
+        ShapePass::set_clip(context, state, width, height, None);
+        context.bind_vertex_array(None);
+        context.bind_texture(Gl::TEXTURE_2D, None);
+    }
+}
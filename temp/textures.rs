@@ -0,0 +1,290 @@
+//! Which image textures live on the GPU, and at what resolution.
+//!
+//! The manager is generic over the texture handle so the bookkeeping can be
+//! tested without a GPU. Each frame the renderer reports every image it
+//! draws and how large it appears on screen; the manager answers with the
+//! texture to draw, queues decodes for images that are missing or resident
+//! at the wrong resolution, and evicts the least recently drawn textures
+//! once the memory budget is exceeded.
+
+use std::collections::HashMap;
+
+/// GPU memory image textures may use by default, in bytes.
+pub const DEFAULT_TEXTURE_BUDGET: usize = 256 * 1024 * 1024;
+
+/// Colour an image is drawn in until its texture is loaded, and wherever
+/// none can be, such as the headless rasterizer.
+pub const PLACEHOLDER_RGBA: [u8; 4] = [224, 224, 224, 255];
+
+/// Smallest longest side an image is uploaded at, in pixels.
+const MIN_TEXTURE_SIDE: u32 = 32;
+
+/// A resident texture this many times larger than needed is swapped for a
+/// smaller one. The gap keeps zooming back and forth from reloading.
+const DOWNSAMPLE_FACTOR: u32 = 4;
+
+/// Bytes used by an RGBA8 texture with its full mipmap chain.
+pub fn texture_bytes(width: u32, height: u32) -> usize {
+    width as usize * height as usize * 4 * 4 / 3
+}
+
+#[derive(Debug)]
+struct Resident<T> {
+    texture: T,
+    width: u32,
+    height: u32,
+}
+
+impl<T> Resident<T> {
+    fn side(&self) -> u32 {
+        self.width.max(self.height)
+    }
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    /// Full size of the decoded image, once known.
+    natural: Option<(u32, u32)>,
+    resident: Option<Resident<T>>,
+    /// Longest side, in pixels, the image was last drawn at.
+    wanted: u32,
+    loading: bool,
+    failed: bool,
+    last_used: u64,
+}
+
+#[derive(Debug)]
+pub struct TextureManager<T> {
+    budget: usize,
+    used: usize,
+    frame: u64,
+    entries: HashMap<String, Entry<T>>,
+    loads: Vec<String>,
+}
+
+impl<T> Default for TextureManager<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_TEXTURE_BUDGET)
+    }
+}
+
+impl<T> TextureManager<T> {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            used: 0,
+            frame: 0,
+            entries: HashMap::new(),
+            loads: Vec::new(),
+        }
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Changes the budget, returning textures evicted to meet it.
+    pub fn set_budget(&mut self, budget: usize) -> Vec<T> {
+        self.budget = budget;
+        self.evict()
+    }
+
+    /// Bytes held by resident textures.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Starts a frame; images drawn in the current frame are never evicted.
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Records that `source` is drawn `screen_side` pixels across (its
+    /// longest side) and returns the texture to draw it with, if any. Queues
+    /// a decode when the image is missing or resident far from that size.
+    pub fn use_image(&mut self, source: &str, screen_side: f32) -> Option<&T> {
+        // Sources can be data URLs megabytes long; only copy one when new.
+        if !self.entries.contains_key(source) {
+            let entry = Entry {
+                natural: None,
+                resident: None,
+                wanted: 0,
+                loading: false,
+                failed: false,
+                last_used: 0,
+            };
+            self.entries.insert(source.to_string(), entry);
+        }
+        let entry = self.entries.get_mut(source)?;
+        entry.last_used = self.frame;
+        entry.wanted = (screen_side.max(1.0).ceil() as u32).next_power_of_two().max(MIN_TEXTURE_SIDE);
+        let target = entry.natural.map_or(entry.wanted, |(w, h)| entry.wanted.min(w.max(h)));
+        let reload = match &entry.resident {
+            None => true,
+            Some(resident) => resident.side() < target || resident.side() >= target * DOWNSAMPLE_FACTOR,
+        };
+        if reload && !entry.loading && !entry.failed {
+            entry.loading = true;
+            self.loads.push(source.to_string());
+        }
+        entry.resident.as_ref().map(|resident| &resident.texture)
+    }
+
+    /// The resident texture for `source`, without counting it as drawn.
+    pub fn texture(&self, source: &str) -> Option<&T> {
+        self.entries.get(source)?.resident.as_ref().map(|resident| &resident.texture)
+    }
+
+    /// Sources to decode, queued since the last call.
+    pub fn take_loads(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.loads)
+    }
+
+    /// The size to upload a freshly decoded `source` of `natural` size at:
+    /// scaled down to what it was last drawn at, keeping the aspect ratio.
+    pub fn upload_size(&mut self, source: &str, natural: (u32, u32)) -> (u32, u32) {
+        let Some(entry) = self.entries.get_mut(source) else {
+            return natural;
+        };
+        entry.natural = Some(natural);
+        let side = natural.0.max(natural.1);
+        if entry.wanted >= side || side == 0 {
+            return natural;
+        }
+        let scale = entry.wanted as f64 / side as f64;
+        let scaled = |n: u32| ((n as f64 * scale).round() as u32).max(1);
+        (scaled(natural.0), scaled(natural.1))
+    }
+
+    /// Makes `texture`, `width` x `height` pixels, the one drawn for
+    /// `source`. Returns textures to delete: the one it replaces and any
+    /// evicted to stay within budget.
+    pub fn loaded(&mut self, source: &str, texture: T, width: u32, height: u32) -> Vec<T> {
+        let mut released = Vec::new();
+        let resident = Resident { texture, width, height };
+        match self.entries.get_mut(source) {
+            Some(entry) => {
+                entry.loading = false;
+                self.used += texture_bytes(width, height);
+                if let Some(old) = entry.resident.replace(resident) {
+                    self.used -= texture_bytes(old.width, old.height);
+                    released.push(old.texture);
+                }
+            }
+            // Forgotten while decoding.
+            None => released.push(resident.texture),
+        }
+        released.extend(self.evict());
+        released
+    }
+
+    /// Decoding `source` failed; it is not retried.
+    pub fn failed(&mut self, source: &str) {
+        if let Some(entry) = self.entries.get_mut(source) {
+            entry.loading = false;
+            entry.failed = true;
+        }
+    }
+
+    /// Drops everything, returning the textures to delete.
+    pub fn clear(&mut self) -> Vec<T> {
+        self.used = 0;
+        self.loads.clear();
+        self.entries.drain().filter_map(|(_, entry)| entry.resident).map(|r| r.texture).collect()
+    }
+
+    /// Evicts the least recently drawn textures until within budget,
+    /// sparing those drawn this frame.
+    fn evict(&mut self) -> Vec<T> {
+        let mut evicted = Vec::new();
+        while self.used > self.budget {
+            let frame = self.frame;
+            let oldest = self
+                .entries
+                .values_mut()
+                .filter(|entry| entry.resident.is_some() && entry.last_used < frame)
+                .min_by_key(|entry| entry.last_used);
+            let Some(resident) = oldest.and_then(|entry| entry.resident.take()) else {
+                break;
+            };
+            self.used -= texture_bytes(resident.width, resident.height);
+            evicted.push(resident.texture);
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loads_once_and_downsamples_thumbnails() {
+        let mut manager = TextureManager::new(usize::MAX);
+        manager.begin_frame();
+        assert!(manager.use_image("a", 100.0).is_none());
+        assert!(manager.use_image("a", 100.0).is_none());
+        assert_eq!(manager.take_loads(), ["a"]);
+
+        // Drawn 100px across: a 4000px image is uploaded at 128px.
+        assert_eq!(manager.upload_size("a", (4000, 2000)), (128, 64));
+        assert!(manager.loaded("a", 1, 128, 64).is_empty());
+        assert_eq!(manager.used(), texture_bytes(128, 64));
+        assert_eq!(manager.use_image("a", 100.0), Some(&1));
+        assert!(manager.take_loads().is_empty());
+
+        // Zooming in asks for more, capped at the natural size.
+        manager.use_image("a", 10_000.0);
+        assert_eq!(manager.take_loads(), ["a"]);
+        assert_eq!(manager.upload_size("a", (4000, 2000)), (4000, 2000));
+        assert_eq!(manager.loaded("a", 2, 4000, 2000), [1]);
+        assert_eq!(manager.use_image("a", 10_000.0), Some(&2));
+        assert!(manager.take_loads().is_empty());
+
+        // Zooming out only reloads well past the needed size.
+        manager.use_image("a", 1500.0);
+        assert!(manager.take_loads().is_empty());
+        manager.use_image("a", 200.0);
+        assert_eq!(manager.take_loads(), ["a"]);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_within_budget() {
+        let budget = texture_bytes(64, 64) * 2;
+        let mut manager = TextureManager::new(budget);
+        for (frame, source) in ["a", "b", "c"].into_iter().enumerate() {
+            manager.begin_frame();
+            manager.use_image(source, 64.0);
+            manager.upload_size(source, (64, 64));
+            let evicted = manager.loaded(source, frame, 64, 64);
+            assert_eq!(evicted, if source == "c" { vec![0] } else { vec![] });
+        }
+        assert_eq!(manager.used(), budget);
+
+        // Everything drawn this frame stays, even over budget.
+        manager.begin_frame();
+        assert!(manager.use_image("a", 64.0).is_none());
+        manager.use_image("b", 64.0);
+        manager.use_image("c", 64.0);
+        manager.take_loads();
+        assert!(manager.loaded("a", 3, 64, 64).is_empty());
+        assert_eq!(manager.used(), budget * 3 / 2);
+        assert_eq!(manager.set_budget(budget / 2).len(), 0);
+        manager.begin_frame();
+        manager.use_image("c", 64.0);
+        assert_eq!(manager.set_budget(budget / 2).len(), 2);
+        assert_eq!(manager.used(), texture_bytes(64, 64));
+    }
+
+    #[test]
+    fn test_failed_images_are_not_retried() {
+        let mut manager: TextureManager<u32> = TextureManager::default();
+        manager.begin_frame();
+        manager.use_image("broken", 50.0);
+        assert_eq!(manager.take_loads().len(), 1);
+        manager.failed("broken");
+        manager.use_image("broken", 50.0);
+        assert!(manager.take_loads().is_empty());
+        assert!(manager.clear().is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+use super::{InputEvent, PointerButton, Tool, ToolContext, ToolKind, DRAG_THRESHOLD_PX};
+use crate::connector::{anchor_position, binding_at, reroute, ANCHORS};
+use crate::document::{Anchor, Binding, Document, LayerId, Routing, Shape, ShapeKind, Style, Transform, PREVIEW_SHAPE_ID};
+use crate::fractional_index::FractionalIndex;
+use crate::geometry::{Point, Rect};
+use crate::overlay::Overlay;
+
+/// How close, in screen pixels, a connector end must come to a shape, or to
+/// one of its anchors, to bind to it.
+const BIND_TOLERANCE_PX: f32 = 8.0;
+
+#[derive(Debug, Clone, PartialEq)]
+enum ConnectorState {
+    Idle,
+    /// Dragging from `origin` to `current`, both in world space. `preview`
+    /// is the connector as it would be placed.
+    Drawing {
+        origin: Point,
+        current: Point,
+        start_binding: Option<Binding>,
+        end_binding: Option<Binding>,
+        preview: Box<Shape>,
+    },
+}
+
+/// Draws connectors by dragging from one shape to another.
+///
+/// An end dropped on a shape binds to it: to the nearest side's middle when
+/// close to one, otherwise to the outline. The shape under the pointer is
+/// highlighted along with its anchors.
+#[derive(Debug, Clone)]
+pub struct ConnectorTool {
+    pub style: Style,
+    pub routing: Routing,
+    /// Binding the pointer would make, for the overlay.
+    hover: Option<Binding>,
+    state: ConnectorState,
+}
+
+impl Default for ConnectorTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectorTool {
+    pub fn new() -> Self {
+        Self {
+            style: Style::default(),
+            routing: Routing::Straight,
+            hover: None,
+            state: ConnectorState::Idle,
+        }
+    }
+
+    fn binding_at(point: Point, context: &ToolContext) -> Option<Binding> {
+        let tolerance = BIND_TOLERANCE_PX * context.world_units_per_pixel();
+        binding_at(context.document, context.index, point, tolerance)
+    }
+
+    /// The connector from `origin` to `current`, routed between its bindings.
+    fn routed(&self, origin: Point, current: Point, start_binding: Option<Binding>, end_binding: Option<Binding>, context: &ToolContext) -> Shape {
+        let bounds = Rect::from_points([origin, current]).unwrap();
+        let corner = Point::new(bounds.min_x, bounds.min_y);
+        let connector = Shape {
+            id: PREVIEW_SHAPE_ID,
+            kind: ShapeKind::Connector {
+                route: vec![origin - corner, current - corner],
+                routing: self.routing,
+                start_binding,
+                end_binding,
+            },
+            transform: Transform::new(bounds.min_x, bounds.min_y, bounds.width(), bounds.height()),
+            style: self.style.clone(),
+            z_index: FractionalIndex::default(),
+            layer: LayerId::default(),
+            parent: None,
+        };
+        reroute(context.document, context.index, &connector)
+    }
+}
+
+impl Tool for ConnectorTool {
+    fn kind(&self) -> ToolKind {
+        ToolKind::Connector
+    }
+
+    fn handle(&mut self, event: &InputEvent, context: &mut ToolContext) {
+        match (&self.state, event) {
+            (ConnectorState::Idle, InputEvent::PointerMove(pointer)) => {
+                self.hover = Self::binding_at(context.to_world(pointer.screen), context);
+            }
+            (ConnectorState::Idle, InputEvent::PointerDown(pointer)) if pointer.button == PointerButton::Primary => {
+                let origin = context.to_world(pointer.screen);
+                let start_binding = Self::binding_at(origin, context);
+                self.state = ConnectorState::Drawing {
+                    origin,
+                    current: origin,
+                    start_binding,
+                    end_binding: None,
+                    preview: Box::new(self.routed(origin, origin, start_binding, None, context)),
+                };
+            }
+            (&ConnectorState::Drawing { origin, start_binding, .. }, InputEvent::PointerMove(pointer)) => {
+                let current = context.to_world(pointer.screen);
+                // Dragging out of the start shape must not bind straight back to it.
+                let end_binding = Self::binding_at(current, context)
+                    .filter(|end| start_binding.is_none_or(|start| start.shape != end.shape));
+                self.hover = end_binding;
+                self.state = ConnectorState::Drawing {
+                    origin,
+                    current,
+                    start_binding,
+                    end_binding,
+                    preview: Box::new(self.routed(origin, current, start_binding, end_binding, context)),
+                };
+            }
+            (ConnectorState::Drawing { origin, preview, .. }, InputEvent::PointerUp(pointer)) => {
+                let dragged = origin.distance(context.to_world(pointer.screen)) >= DRAG_THRESHOLD_PX * context.world_units_per_pixel();
+                if dragged {
+                    context.document.insert(preview.kind.clone(), preview.transform, self.style.clone());
+                }
+                self.state = ConnectorState::Idle;
+            }
+            _ => {}
+        }
+    }
+
+    fn cancel(&mut self, _context: &mut ToolContext) {
+        self.hover = None;
+        self.state = ConnectorState::Idle;
+    }
+
+    fn is_idle(&self) -> bool {
+        self.state == ConnectorState::Idle
+    }
+
+    fn preview(&self) -> Option<Shape> {
+        match &self.state {
+            ConnectorState::Drawing { preview, .. } => Some(preview.as_ref().clone()),
+            ConnectorState::Idle => None,
+        }
+    }
+
+    fn overlay(&self, document: &Document, overlay: &mut Overlay) {
+        let Some(binding) = self.hover else {
+            return;
+        };
+        let Some(target) = document.get(binding.shape) else {
+            return;
+        };
+        overlay.outline(target);
+        for (x, y) in ANCHORS {
+            let active = binding.anchor == Anchor::Point { x, y };
+            overlay.anchor(anchor_position(target, x, y), active);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Editor;
+    use crate::tools::PointerInput;
+
+    fn drag(editor: &mut Editor, from: Point, to: Point) {
+        editor.handle_input(InputEvent::PointerDown(PointerInput::new(from)));
+        editor.handle_input(InputEvent::PointerMove(PointerInput::new(to)));
+        editor.handle_input(InputEvent::PointerUp(PointerInput::new(to)));
+    }
+
+    #[test]
+    fn test_drag_between_shapes_binds_and_follows() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let per_pixel = editor.state.world_units_per_pixel(800.0, 600.0);
+        let screen = |editor: &Editor, p: Point| editor.state.world_to_screen(800.0, 600.0, p);
+        let box_at = |x: f32| Transform::new(x * per_pixel, 0.0, 100.0 * per_pixel, 100.0 * per_pixel);
+        let a = editor.document.insert(ShapeKind::Rectangle, box_at(-300.0), Style::default());
+        let b = editor.document.insert(ShapeKind::Rectangle, box_at(200.0), Style::default());
+        editor.sync_index();
+
+        editor.set_tool(ToolKind::Connector);
+        let from = screen(&editor, editor.document.get(a).unwrap().transform.center());
+        let to = screen(&editor, editor.document.get(b).unwrap().transform.center());
+        drag(&mut editor, from, to);
+        let id = editor.document.shapes().find(|s| matches!(s.kind, ShapeKind::Connector { .. })).unwrap().id;
+        let ShapeKind::Connector { start_binding, end_binding, .. } = &editor.document.get(id).unwrap().kind else {
+            unreachable!();
+        };
+        assert_eq!(start_binding.map(|b| (b.shape, b.anchor)), Some((a, Anchor::Outline)));
+        assert_eq!(end_binding.map(|b| b.shape), Some(b));
+
+        // Moving the target re-routes the connector in the same undo step.
+        let before = editor.document.get(id).unwrap().path_points().unwrap();
+        editor.transaction(|editor| editor.document.update(b, |shape| shape.transform.y += 300.0 * per_pixel).unwrap());
+        let after = editor.document.get(id).unwrap().path_points().unwrap();
+        assert!(after[1].y > before[1].y + 100.0 * per_pixel);
+
+        assert!(editor.undo().unwrap());
+        assert_eq!(editor.document.get(id).unwrap().path_points().unwrap(), before);
+        assert!(editor.can_redo());
+        assert!(editor.redo().unwrap());
+        assert_eq!(editor.document.get(id).unwrap().path_points().unwrap(), after);
+    }
+
+    #[test]
+    fn test_bindings_survive_save_and_load() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let a = editor.document.insert(ShapeKind::Ellipse, Transform::new(-200.0, -50.0, 100.0, 100.0), Style::default());
+        editor.sync_index();
+        editor.tools.connector.routing = Routing::Elbow;
+        editor.set_tool(ToolKind::Connector);
+        let from = editor.state.world_to_screen(800.0, 600.0, Point::new(-150.0, 0.0));
+        drag(&mut editor, from, Point::new(700.0, 500.0));
+
+        let mut loaded = Editor::new(800.0, 600.0);
+        loaded.load(&editor.save().unwrap()).unwrap();
+        assert!(!loaded.can_undo());
+        let connector = |editor: &Editor| editor.document.shapes().find(|s| matches!(s.kind, ShapeKind::Connector { .. })).cloned();
+        assert_eq!(connector(&loaded), connector(&editor));
+        loaded.transaction(|editor| editor.document.update(a, |shape| shape.transform.y -= 300.0).unwrap());
+        let ShapeKind::Connector { route, routing, .. } = connector(&loaded).unwrap().kind else {
+            unreachable!();
+        };
+        assert_eq!(routing, Routing::Elbow);
+        assert!(route.len() > 2);
+        assert_ne!(connector(&loaded), connector(&editor));
+    }
+}
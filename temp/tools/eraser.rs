@@ -0,0 +1,343 @@
+use super::{InputEvent, PointerButton, Tool, ToolContext, ToolKind};
+use crate::arrange::keys_above;
+use crate::document::{Document, Shape, ShapeId, ShapeKind, StrokePoint};
+use crate::freehand::{densify, split_stroke, FreehandOptions, StrokeBuilder, StrokeSplit};
+use crate::geometry::{Point, Rect};
+use crate::hit_test::hit_test;
+use crate::overlay::Overlay;
+
+/// What the eraser removes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EraserMode {
+    /// Every shape the eraser touches.
+    #[default]
+    Whole,
+    /// The parts of freehand strokes under the eraser, splitting them where
+    /// it crosses; other shapes are left alone.
+    Precise,
+}
+
+#[derive(Debug, Clone)]
+enum EraserState {
+    Idle,
+    /// Pointer held down. The document is only changed on release, so what
+    /// will go can be previewed and cancelling has nothing to undo.
+    Erasing {
+        /// The eraser's centre so far, in world space.
+        path: Vec<Point>,
+        /// World units, fixed for the gesture.
+        radius: f32,
+        /// Shapes to delete, in the order they were touched.
+        doomed: Vec<ShapeId>,
+        /// Strokes to cut, with what is left of each.
+        cuts: Vec<(ShapeId, StrokeCut)>,
+    },
+}
+
+/// A stroke the eraser has crossed, resampled in world space so each move
+/// only has to test its points against the newest stretch of the path.
+#[derive(Debug, Clone)]
+struct StrokeCut {
+    points: Vec<StrokePoint>,
+    /// Which of `points` the eraser has passed over so far.
+    gone: Vec<bool>,
+    split: StrokeSplit,
+}
+
+/// Erases what the pointer passes over while pressed, either whole shapes
+/// or parts of freehand strokes.
+#[derive(Debug, Clone)]
+pub struct EraserTool {
+    /// Eraser radius in screen pixels.
+    pub radius: f32,
+    pub mode: EraserMode,
+    state: EraserState,
+}
+
+impl Default for EraserTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EraserTool {
+    pub fn new() -> Self {
+        Self {
+            radius: 8.0,
+            mode: EraserMode::default(),
+            state: EraserState::Idle,
+        }
+    }
+
+    /// Extends the eraser path to `screen` and collects what the new stretch
+    /// touches, looking only at shapes the index puts near it.
+    fn erase_to(&mut self, screen: Point, context: &ToolContext) {
+        let mode = self.mode;
+        let EraserState::Erasing { path, radius, doomed, cuts } = &mut self.state else {
+            return;
+        };
+        let to = context.to_world(screen);
+        let from = path.last().copied().unwrap_or(to);
+        path.push(to);
+        // Half a radius apart, so nothing slips between two samples.
+        let spacing = *radius * 0.5;
+        let steps = (from.distance(to) / spacing).ceil().max(1.0) as usize;
+        let samples: Vec<Point> = (0..=steps).map(|i| from.lerp(to, i as f32 / steps as f32)).collect();
+        let tolerance = FreehandOptions::default().simplify_tolerance * context.world_units_per_pixel();
+        // Cannot fail: two points always have bounds.
+        let reach = Rect::from_points([from, to]).unwrap().expand(*radius);
+        let document = &*context.document;
+        for id in context.index.query_rect(&reach) {
+            let Some(shape) = document.get(id).filter(|shape| document.is_hittable(shape)) else {
+                continue;
+            };
+            // Parts cut off by a frame cannot be erased.
+            let clip = document.clip(id);
+            let visible = |p: Point| clip.is_none_or(|clip| clip.contains_point(p));
+            let touched = samples.iter().any(|p| visible(*p) && hit_test(shape, *p, *radius));
+            match (mode, &shape.kind) {
+                (EraserMode::Whole, _) if touched && !doomed.contains(&id) => doomed.push(id),
+                (EraserMode::Precise, ShapeKind::Freehand { points }) if touched => {
+                    let cut = match cuts.iter().position(|(cut_id, _)| *cut_id == id) {
+                        Some(i) => &mut cuts[i].1,
+                        None => {
+                            let world: Vec<StrokePoint> = points
+                                .iter()
+                                .map(|p| {
+                                    let world = shape.transform.to_world(Point::new(p.x, p.y));
+                                    StrokePoint { x: world.x, y: world.y, pressure: p.pressure }
+                                })
+                                .collect();
+                            let points = densify(&world, spacing);
+                            let gone = vec![false; points.len()];
+                            cuts.push((id, StrokeCut { points, gone, split: StrokeSplit::default() }));
+                            &mut cuts.last_mut().unwrap().1
+                        }
+                    };
+                    // Earlier stretches of the path are already in `gone`.
+                    let reach = *radius + shape.style.stroke_width * 0.5;
+                    let mut changed = false;
+                    for (point, gone) in cut.points.iter().zip(&mut cut.gone) {
+                        let p = Point::new(point.x, point.y);
+                        if !*gone && visible(p) && near(&[from, to], p, reach) {
+                            *gone = true;
+                            changed = true;
+                        }
+                    }
+                    if changed {
+                        cut.split = split_stroke(&cut.points, &cut.gone, tolerance).unwrap_or_default();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Deletes and cuts what the gesture collected.
+    fn commit(&mut self, document: &mut Document) {
+        let EraserState::Erasing { doomed, cuts, .. } = std::mem::replace(&mut self.state, EraserState::Idle) else {
+            return;
+        };
+        // Contents of deleted containers follow when the editor syncs.
+        for id in doomed {
+            let _ = document.delete(id);
+        }
+        for (id, StrokeCut { split, .. }) in cuts {
+            if split.erased.is_empty() {
+                continue;
+            }
+            let Some(shape) = document.get(id).cloned() else {
+                continue;
+            };
+            let mut pieces = split.kept.iter().filter_map(|run| StrokeBuilder::to_shape_parts(run));
+            let Some((kind, transform)) = pieces.next() else {
+                let _ = document.delete(id);
+                continue;
+            };
+            // The first piece keeps the stroke's id; the rest stack just above it.
+            let _ = document.update(id, |stroke| {
+                stroke.kind = kind;
+                stroke.transform = transform;
+            });
+            let rest: Vec<_> = pieces.collect();
+            let keys = keys_above(document, &shape, rest.len());
+            for ((kind, transform), z_index) in rest.into_iter().zip(keys) {
+                let piece = document.insert(kind, transform, shape.style.clone());
+                let _ = document.update(piece, |piece| {
+                    piece.z_index = z_index;
+                    piece.layer = shape.layer;
+                    piece.parent = shape.parent;
+                });
+            }
+        }
+    }
+}
+
+/// Whether `point` is within `reach` of the polyline `path`.
+fn near(path: &[Point], point: Point, reach: f32) -> bool {
+    match path {
+        [] => false,
+        [only] => only.distance(point) <= reach,
+        _ => path.windows(2).any(|w| point.distance_to_segment(w[0], w[1]) <= reach),
+    }
+}
+
+impl Tool for EraserTool {
+    fn kind(&self) -> ToolKind {
+        ToolKind::Eraser
+    }
+
+    fn handle(&mut self, event: &InputEvent, context: &mut ToolContext) {
+        match (&self.state, event) {
+            (EraserState::Idle, InputEvent::PointerDown(pointer)) if pointer.button == PointerButton::Primary => {
+                self.state = EraserState::Erasing {
+                    path: Vec::new(),
+                    radius: self.radius.max(1.0) * context.world_units_per_pixel(),
+                    doomed: Vec::new(),
+                    cuts: Vec::new(),
+                };
+                self.erase_to(pointer.screen, context);
+            }
+            (EraserState::Erasing { .. }, InputEvent::PointerMove(pointer)) => {
+                self.erase_to(pointer.screen, context);
+            }
+            (EraserState::Erasing { .. }, InputEvent::PointerUp(pointer)) => {
+                self.erase_to(pointer.screen, context);
+                self.commit(context.document);
+            }
+            _ => {}
+        }
+    }
+
+    fn cancel(&mut self, _context: &mut ToolContext) {
+        self.state = EraserState::Idle;
+    }
+
+    fn is_idle(&self) -> bool {
+        matches!(self.state, EraserState::Idle)
+    }
+
+    /// Marks what will be erased on release, and the eraser itself.
+    fn overlay(&self, document: &Document, overlay: &mut Overlay) {
+        let EraserState::Erasing { path, radius, doomed, cuts } = &self.state else {
+            return;
+        };
+        for shape in doomed.iter().filter_map(|id| document.get(*id)) {
+            overlay.erased(shape);
+        }
+        for (id, cut) in cuts {
+            let Some(shape) = document.get(*id) else {
+                continue;
+            };
+            for (kind, transform) in cut.split.erased.iter().filter_map(|run| StrokeBuilder::to_shape_parts(run)) {
+                overlay.erased(&Shape {
+                    kind,
+                    transform,
+                    ..shape.clone()
+                });
+            }
+        }
+        if let Some(at) = path.last() {
+            overlay.eraser(*at, *radius);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Color, Style, Transform};
+    use crate::editor::Editor;
+    use crate::tools::PointerInput;
+
+    #[test]
+    fn test_erase_and_cancel_restores() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let filled = Style {
+            fill: Some(Color::WHITE),
+            ..Style::default()
+        };
+        // Two stacked shapes under the screen centre, world origin.
+        for size in [100.0, 50.0] {
+            editor.document.insert(ShapeKind::Rectangle, Transform::new(-size, -size, size * 2.0, size * 2.0), filled.clone());
+        }
+        editor.sync_index();
+        editor.set_tool(ToolKind::Eraser);
+
+        let at = |x, y| PointerInput::new(Point::new(x, y));
+        // Nothing goes before release, but both shapes are marked.
+        editor.handle_input(InputEvent::PointerDown(at(400.0, 300.0)));
+        assert_eq!(editor.document.len(), 2);
+        assert_eq!(editor.overlay().shapes().len(), 3);
+        editor.handle_input(InputEvent::Cancel);
+        assert_eq!(editor.document.len(), 2);
+
+        editor.handle_input(InputEvent::PointerDown(at(10.0, 10.0)));
+        editor.handle_input(InputEvent::PointerMove(at(400.0, 300.0)));
+        editor.handle_input(InputEvent::PointerUp(at(400.0, 300.0)));
+        assert!(editor.document.is_empty());
+    }
+
+    #[test]
+    fn test_precise_mode_splits_strokes() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let stroke = ShapeKind::Freehand {
+            points: vec![StrokePoint { x: 0.0, y: 0.0, pressure: 0.5 }, StrokePoint { x: 200.0, y: 0.0, pressure: 0.5 }],
+        };
+        let id = editor.document.insert(stroke, Transform::new(-100.0, 0.0, 200.0, 0.0), Style::default());
+        let rectangle = editor.document.insert(ShapeKind::Rectangle, Transform::new(-20.0, 20.0, 40.0, 40.0), Style::default());
+        editor.sync_index();
+        editor.set_tool(ToolKind::Eraser);
+        editor.tools.eraser.mode = EraserMode::Precise;
+
+        // Straight down across the middle of the stroke and the rectangle's edge.
+        let at = |y| PointerInput::new(Point::new(400.0, y));
+        editor.handle_input(InputEvent::PointerDown(at(250.0)));
+        editor.handle_input(InputEvent::PointerMove(at(350.0)));
+        assert_eq!(editor.document.len(), 2);
+        editor.handle_input(InputEvent::PointerUp(at(350.0)));
+
+        // Two pieces with a gap of the eraser's width, and the rectangle untouched.
+        assert_eq!(editor.document.len(), 3);
+        assert!(editor.document.contains(id) && editor.document.contains(rectangle));
+        let pieces: Vec<Rect> = editor
+            .document
+            .shapes()
+            .filter(|shape| matches!(shape.kind, ShapeKind::Freehand { .. }))
+            .map(|shape| shape.transform.bounds())
+            .collect();
+        assert_eq!(pieces.len(), 2);
+        assert!(pieces.iter().all(|piece| piece.min_x >= 9.0 || piece.max_x <= -9.0));
+
+        assert!(editor.undo().unwrap());
+        assert_eq!(editor.document.len(), 2);
+    }
+
+    #[test]
+    fn test_precise_cuts_accumulate_across_moves() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let stroke = ShapeKind::Freehand {
+            points: vec![StrokePoint { x: 0.0, y: 0.0, pressure: 0.5 }, StrokePoint { x: 200.0, y: 0.0, pressure: 0.5 }],
+        };
+        editor.document.insert(stroke, Transform::new(-100.0, 0.0, 200.0, 0.0), Style::default());
+        editor.sync_index();
+        editor.set_tool(ToolKind::Eraser);
+        editor.tools.eraser.mode = EraserMode::Precise;
+
+        // Down across the stroke, along below it, then back up across it.
+        let at = |x, y| PointerInput::new(editor.state.world_to_screen(800.0, 600.0, Point::new(x, y)));
+        let moves = [at(-50.0, -30.0), at(-50.0, 30.0), at(50.0, 30.0), at(50.0, -30.0)];
+        editor.handle_input(InputEvent::PointerDown(moves[0]));
+        for pointer in &moves[1..] {
+            editor.handle_input(InputEvent::PointerMove(*pointer));
+        }
+        editor.handle_input(InputEvent::PointerUp(moves[3]));
+
+        let mut pieces: Vec<Rect> = editor.document.shapes().map(|shape| shape.transform.bounds()).collect();
+        pieces.sort_by(|a, b| a.min_x.total_cmp(&b.min_x));
+        assert_eq!(pieces.len(), 3);
+        assert!(pieces[0].max_x < -50.0);
+        assert!(pieces[1].min_x > -50.0 && pieces[1].max_x < 50.0);
+        assert!(pieces[2].min_x > 50.0);
+    }
+}
@@ -0,0 +1,63 @@
+use super::{InputEvent, Tool, ToolContext, ToolKind};
+use crate::geometry::Point;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HandState {
+    Idle,
+    /// Dragging the canvas; `last` is the previous pointer position in pixels.
+    Panning { last: Point },
+}
+
+/// Pans the camera by dragging with any button.
+#[derive(Debug, Clone)]
+pub struct HandTool {
+    state: HandState,
+}
+
+impl Default for HandTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HandTool {
+    pub fn new() -> Self {
+        Self { state: HandState::Idle }
+    }
+
+    pub fn is_panning(&self) -> bool {
+        matches!(self.state, HandState::Panning { .. })
+    }
+}
+
+impl Tool for HandTool {
+    fn kind(&self) -> ToolKind {
+        ToolKind::Hand
+    }
+
+    fn handle(&mut self, event: &InputEvent, context: &mut ToolContext) {
+        match (self.state, event) {
+            (HandState::Idle, InputEvent::PointerDown(pointer)) => {
+                self.state = HandState::Panning { last: pointer.screen };
+            }
+            (HandState::Panning { last }, InputEvent::PointerMove(pointer)) => {
+                let delta = pointer.screen - last;
+                context.state.pan_by_pixels(context.width, context.height, delta.x, delta.y);
+                self.state = HandState::Panning { last: pointer.screen };
+            }
+            (HandState::Panning { .. }, InputEvent::PointerUp(_)) => {
+                self.state = HandState::Idle;
+            }
+            _ => {}
+        }
+    }
+
+    /// Stops panning. The camera stays where it was dragged to.
+    fn cancel(&mut self, _context: &mut ToolContext) {
+        self.state = HandState::Idle;
+    }
+
+    fn is_idle(&self) -> bool {
+        self.state == HandState::Idle
+    }
+}
@@ -0,0 +1,183 @@
+use super::{InputEvent, PointerButton, Tool, ToolContext, ToolKind};
+use crate::document::{Document, ShapeId};
+use crate::geometry::Point;
+use crate::hierarchy::selection_target;
+use crate::overlay::Overlay;
+use crate::selection::{lasso_hits, MarqueeMode};
+
+/// Pointer travel, in screen pixels, before the lasso takes another point.
+const LASSO_SPACING_PX: f32 = 2.0;
+
+#[derive(Debug, Clone, PartialEq)]
+enum LassoState {
+    Idle,
+    /// Pointer held down, tracing `points` in world space. `base` is the
+    /// selection the lasso adds to (empty unless Shift was held).
+    Lassoing {
+        points: Vec<Point>,
+        base: Vec<ShapeId>,
+        mode: MarqueeMode,
+    },
+}
+
+/// Selects the shapes inside a freeform outline drawn with the pointer.
+///
+/// Shift-drag adds to the current selection, and holding Alt flips `mode`,
+/// as with the select tool's marquee.
+#[derive(Debug, Clone)]
+pub struct LassoTool {
+    pub mode: MarqueeMode,
+    /// The group the select tool has entered, whose children the lasso
+    /// picks one by one.
+    pub(super) entered: Option<ShapeId>,
+    state: LassoState,
+}
+
+impl Default for LassoTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LassoTool {
+    pub fn new() -> Self {
+        Self {
+            mode: MarqueeMode::default(),
+            entered: None,
+            state: LassoState::Idle,
+        }
+    }
+
+    /// Selects what the lasso holds so far, on top of `base`.
+    fn select(&self, context: &mut ToolContext) {
+        let LassoState::Lassoing { points, base, mode } = &self.state else {
+            return;
+        };
+        let tolerance = 0.5 * context.world_units_per_pixel();
+        let hits = lasso_hits(context.document, context.index, points, *mode, tolerance);
+        let mut targets = base.clone();
+        for &hit in &hits {
+            let target = selection_target(context.document, hit, self.entered);
+            // A group is only contained once all of it is.
+            let whole = target == hit || *mode == MarqueeMode::Touching || contents_within(context.document, target, &hits);
+            if whole && !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        context.selection.set(targets);
+    }
+}
+
+/// Whether every painted shape inside `group` is among `hits`.
+fn contents_within(document: &Document, group: ShapeId, hits: &[ShapeId]) -> bool {
+    document
+        .descendants(group)
+        .into_iter()
+        .filter(|id| document.get(*id).is_some_and(|shape| !shape.is_container()))
+        .all(|id| hits.contains(&id))
+}
+
+impl Tool for LassoTool {
+    fn kind(&self) -> ToolKind {
+        ToolKind::Lasso
+    }
+
+    fn handle(&mut self, event: &InputEvent, context: &mut ToolContext) {
+        match (&mut self.state, event) {
+            (LassoState::Idle, InputEvent::PointerDown(pointer)) if pointer.button == PointerButton::Primary => {
+                let base = if pointer.modifiers.shift { context.selection.iter().collect() } else { Vec::new() };
+                context.selection.set(base.iter().copied());
+                self.state = LassoState::Lassoing {
+                    points: vec![context.to_world(pointer.screen)],
+                    base,
+                    mode: self.mode,
+                };
+            }
+            (LassoState::Lassoing { points, mode, .. }, InputEvent::PointerMove(pointer)) => {
+                let world = context.to_world(pointer.screen);
+                let spacing = LASSO_SPACING_PX * context.world_units_per_pixel();
+                if points.last().is_some_and(|last| last.distance(world) >= spacing) {
+                    points.push(world);
+                }
+                *mode = if pointer.modifiers.alt { self.mode.toggled() } else { self.mode };
+                self.select(context);
+            }
+            (LassoState::Lassoing { .. }, InputEvent::PointerUp(_)) => {
+                self.state = LassoState::Idle;
+            }
+            _ => {}
+        }
+    }
+
+    /// Puts back the selection from before the lasso.
+    fn cancel(&mut self, context: &mut ToolContext) {
+        if let LassoState::Lassoing { base, .. } = std::mem::replace(&mut self.state, LassoState::Idle) {
+            context.selection.set(base);
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        matches!(self.state, LassoState::Idle)
+    }
+
+    fn overlay(&self, _document: &Document, overlay: &mut Overlay) {
+        if let LassoState::Lassoing { points, .. } = &self.state {
+            overlay.lasso(points);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{ShapeKind, Style, Transform};
+    use crate::editor::Editor;
+    use crate::hierarchy::group;
+    use crate::tools::PointerInput;
+
+    #[test]
+    fn test_lasso_selects_inside_the_outline() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let rect = |editor: &mut Editor, x| editor.document.insert(ShapeKind::Rectangle, Transform::new(x, 0.0, 10.0, 10.0), Style::default());
+        let a = rect(&mut editor, 0.0);
+        let b = rect(&mut editor, 20.0);
+        let c = rect(&mut editor, 40.0);
+        let d = rect(&mut editor, 60.0);
+        let g = group(&mut editor.document, &[c, d]).unwrap();
+        editor.sync_index();
+        editor.set_tool(ToolKind::Lasso);
+
+        // Around a and b, and half of the group.
+        let outline = [(-5.0, -5.0), (35.0, -5.0), (55.0, -5.0), (55.0, 15.0), (-5.0, 15.0)];
+        let camera = editor.state.clone();
+        let at = |(x, y)| PointerInput::new(camera.world_to_screen(800.0, 600.0, Point::new(x, y)));
+        editor.handle_input(InputEvent::PointerDown(at(outline[0])));
+        for point in &outline[1..] {
+            editor.handle_input(InputEvent::PointerMove(at(*point)));
+        }
+        editor.handle_input(InputEvent::PointerUp(at(outline[4])));
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![a, b]);
+
+        // Touching picks up the whole group; cancelling puts the selection back.
+        editor.tools.lasso.mode = MarqueeMode::Touching;
+        editor.handle_input(InputEvent::PointerDown(at(outline[0])));
+        for point in &outline[1..] {
+            editor.handle_input(InputEvent::PointerMove(at(*point)));
+        }
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![a, b, g]);
+        editor.handle_input(InputEvent::Cancel);
+        assert!(editor.selection.is_empty());
+
+        // Inside an entered group its children are picked one by one.
+        editor.set_tool(ToolKind::Select);
+        editor.handle_input(InputEvent::DoubleClick(at((45.0, 5.0))));
+        assert_eq!(editor.tools.select.entered(), Some(g));
+        editor.set_tool(ToolKind::Lasso);
+        editor.tools.lasso.mode = MarqueeMode::default();
+        editor.handle_input(InputEvent::PointerDown(at(outline[0])));
+        for point in &outline[1..] {
+            editor.handle_input(InputEvent::PointerMove(at(*point)));
+        }
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![a, b, c]);
+    }
+}
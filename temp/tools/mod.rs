@@ -0,0 +1,616 @@
+//! Interactive tools.
+//!
+//! Platform events are normalized into [`InputEvent`]s and routed by the
+//! [`ToolManager`] to the active [`Tool`]. Each tool is a small state machine
+//! that is idle between gestures; the manager only switches tools while the
+//! active one is idle, or cancels it first, so a gesture never leaks into the
+//! next tool.
+
+mod connector;
+mod eraser;
+mod hand;
+mod lasso;
+mod pen;
+mod select;
+mod shape;
+mod text;
+
+pub use connector::ConnectorTool;
+pub use eraser::{EraserMode, EraserTool};
+pub use hand::HandTool;
+pub use lasso::LassoTool;
+pub use pen::PenTool;
+pub use select::SelectTool;
+pub use shape::ShapeTool;
+pub use text::{TextEdit, TextTool};
+
+use crate::document::{Document, Shape, ShapeId, ShapeKind};
+use crate::geometry::Point;
+use crate::grid::GridStyle;
+use crate::overlay::Overlay;
+use crate::selection::Selection;
+use crate::spatial::SpatialIndex;
+use crate::state::State;
+use crate::text::FontStack;
+
+/// How far from a shape, in screen pixels, a click still hits it.
+pub const HIT_TOLERANCE_PX: f32 = 4.0;
+
+/// Pointer travel, in screen pixels, below which a press counts as a click.
+pub const DRAG_THRESHOLD_PX: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+    pub meta: bool,
+}
+
+impl Modifiers {
+    /// The platform command key: Ctrl, or Cmd on macOS.
+    pub fn command(&self) -> bool {
+        self.ctrl || self.meta
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerButton {
+    Primary,
+    Middle,
+    Secondary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerKind {
+    Mouse,
+    Pen,
+    Touch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointerInput {
+    /// Canvas-relative position in pixels.
+    pub screen: Point,
+    pub pressure: f32,
+    pub kind: PointerKind,
+    pub button: PointerButton,
+    pub modifiers: Modifiers,
+}
+
+impl PointerInput {
+    /// A primary-button mouse pointer at `screen`.
+    pub fn new(screen: Point) -> Self {
+        Self {
+            screen,
+            pressure: 0.5,
+            kind: PointerKind::Mouse,
+            button: PointerButton::Primary,
+            modifiers: Modifiers::default(),
+        }
+    }
+}
+
+/// Platform-independent input. Keys use DOM `KeyboardEvent.key` names.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    PointerDown(PointerInput),
+    /// One per coalesced sample, so tools see the full device rate.
+    PointerMove(PointerInput),
+    PointerUp(PointerInput),
+    KeyDown { key: String, modifiers: Modifiers, repeat: bool },
+    KeyUp { key: String, modifiers: Modifiers },
+    /// Follows the downs and ups of both clicks.
+    DoubleClick(PointerInput),
+    /// The platform text field over a text shape being edited changed.
+    /// Offsets are UTF-16 code units, as the DOM reports them; `selection`
+    /// may be backwards.
+    TextInput {
+        content: String,
+        selection: (usize, usize),
+        composition: Option<(usize, usize)>,
+    },
+    /// The platform took input away mid-gesture (pointer cancel, window blur).
+    Cancel,
+}
+
+impl InputEvent {
+    fn is_key(&self, name: &str) -> bool {
+        matches!(self, Self::KeyDown { key, .. } | Self::KeyUp { key, .. } if key == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ToolKind {
+    Select,
+    Lasso,
+    Hand,
+    Pen,
+    Rectangle,
+    Ellipse,
+    Arrow,
+    Connector,
+    Frame,
+    Text,
+    Eraser,
+}
+
+impl ToolKind {
+    pub const ALL: [ToolKind; 11] = [
+        Self::Select,
+        Self::Lasso,
+        Self::Hand,
+        Self::Pen,
+        Self::Rectangle,
+        Self::Ellipse,
+        Self::Arrow,
+        Self::Connector,
+        Self::Frame,
+        Self::Text,
+        Self::Eraser,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Select => "select",
+            Self::Lasso => "lasso",
+            Self::Hand => "hand",
+            Self::Pen => "pen",
+            Self::Rectangle => "rectangle",
+            Self::Ellipse => "ellipse",
+            Self::Arrow => "arrow",
+            Self::Connector => "connector",
+            Self::Frame => "frame",
+            Self::Text => "text",
+            Self::Eraser => "eraser",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// The single-key shortcut that activates this tool.
+    pub fn shortcut(self) -> char {
+        match self {
+            Self::Select => 'v',
+            Self::Lasso => 'l',
+            Self::Hand => 'h',
+            Self::Pen => 'p',
+            Self::Rectangle => 'r',
+            Self::Ellipse => 'o',
+            Self::Arrow => 'a',
+            Self::Connector => 'c',
+            Self::Frame => 'f',
+            Self::Text => 't',
+            Self::Eraser => 'e',
+        }
+    }
+
+    fn from_shortcut(key: &str) -> Option<Self> {
+        let mut chars = key.chars();
+        let c = chars.next()?.to_ascii_lowercase();
+        if chars.next().is_some() {
+            return None;
+        }
+        Self::ALL.into_iter().find(|kind| kind.shortcut() == c)
+    }
+}
+
+/// What a tool may read and change while handling an event.
+pub struct ToolContext<'a> {
+    pub state: &'a mut State,
+    pub document: &'a mut Document,
+    pub selection: &'a mut Selection,
+    /// Reflects the document as of the start of the event.
+    pub index: &'a SpatialIndex,
+    pub fonts: &'a FontStack,
+    /// Moving and resizing snap to its lines.
+    pub grid: &'a GridStyle,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ToolContext<'_> {
+    pub fn to_world(&self, screen: Point) -> Point {
+        self.state.screen_to_world(self.width, self.height, screen)
+    }
+
+    pub fn world_units_per_pixel(&self) -> f32 {
+        self.state.world_units_per_pixel(self.width, self.height)
+    }
+}
+
+pub trait Tool {
+    fn kind(&self) -> ToolKind;
+
+    fn handle(&mut self, event: &InputEvent, context: &mut ToolContext);
+
+    /// Abandons the gesture in progress, undoing anything it changed.
+    fn cancel(&mut self, context: &mut ToolContext);
+
+    /// Whether the tool is between gestures and can be switched away from.
+    fn is_idle(&self) -> bool;
+
+    /// A shape to draw on top of the document for the gesture in progress.
+    fn preview(&self) -> Option<Shape> {
+        None
+    }
+
+    /// Adds gesture feedback, such as a marquee, to the editor overlay.
+    fn overlay(&self, _document: &Document, _overlay: &mut Overlay) {}
+}
+
+/// What started a temporary tool, and so what ends it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Trigger {
+    Space,
+    MiddleButton,
+    /// Editing text entered from another tool; ends with the edit.
+    TextEdit,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Temporary {
+    previous: ToolKind,
+    trigger: Trigger,
+}
+
+/// Owns one instance of every tool and routes input to the active one.
+///
+/// Besides forwarding events it handles the global bindings: tool shortcuts,
+/// Escape (cancel the gesture, then fall back to select), spring-loaded
+/// panning with a held space bar or the middle mouse button, and editing
+/// text on double click or Enter.
+pub struct ToolManager {
+    pub select: SelectTool,
+    pub lasso: LassoTool,
+    pub hand: HandTool,
+    pub pen: PenTool,
+    pub rectangle: ShapeTool,
+    pub ellipse: ShapeTool,
+    pub arrow: ShapeTool,
+    pub connector: ConnectorTool,
+    pub frame: ShapeTool,
+    pub text: TextTool,
+    pub eraser: EraserTool,
+    active: ToolKind,
+    temporary: Option<Temporary>,
+}
+
+impl Default for ToolManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToolManager {
+    pub fn new() -> Self {
+        Self {
+            select: SelectTool::new(),
+            lasso: LassoTool::new(),
+            hand: HandTool::new(),
+            pen: PenTool::new(),
+            rectangle: ShapeTool::new(ToolKind::Rectangle),
+            ellipse: ShapeTool::new(ToolKind::Ellipse),
+            arrow: ShapeTool::new(ToolKind::Arrow),
+            connector: ConnectorTool::new(),
+            frame: ShapeTool::new(ToolKind::Frame),
+            text: TextTool::new(),
+            eraser: EraserTool::new(),
+            active: ToolKind::Select,
+            temporary: None,
+        }
+    }
+
+    pub fn active(&self) -> ToolKind {
+        self.active
+    }
+
+    pub fn tool(&self, kind: ToolKind) -> &dyn Tool {
+        match kind {
+            ToolKind::Select => &self.select,
+            ToolKind::Lasso => &self.lasso,
+            ToolKind::Hand => &self.hand,
+            ToolKind::Pen => &self.pen,
+            ToolKind::Rectangle => &self.rectangle,
+            ToolKind::Ellipse => &self.ellipse,
+            ToolKind::Arrow => &self.arrow,
+            ToolKind::Connector => &self.connector,
+            ToolKind::Frame => &self.frame,
+            ToolKind::Text => &self.text,
+            ToolKind::Eraser => &self.eraser,
+        }
+    }
+
+    pub fn tool_mut(&mut self, kind: ToolKind) -> &mut dyn Tool {
+        match kind {
+            ToolKind::Select => &mut self.select,
+            ToolKind::Lasso => &mut self.lasso,
+            ToolKind::Hand => &mut self.hand,
+            ToolKind::Pen => &mut self.pen,
+            ToolKind::Rectangle => &mut self.rectangle,
+            ToolKind::Ellipse => &mut self.ellipse,
+            ToolKind::Arrow => &mut self.arrow,
+            ToolKind::Connector => &mut self.connector,
+            ToolKind::Frame => &mut self.frame,
+            ToolKind::Text => &mut self.text,
+            ToolKind::Eraser => &mut self.eraser,
+        }
+    }
+
+    /// Whether the active tool is between gestures.
+    pub fn is_idle(&self) -> bool {
+        self.tool(self.active).is_idle()
+    }
+
+    pub fn preview(&self) -> Option<Shape> {
+        self.tool(self.active).preview()
+    }
+
+    pub fn overlay(&self, document: &Document, overlay: &mut Overlay) {
+        self.tool(self.active).overlay(document, overlay);
+    }
+
+    /// Switches tools, cancelling any gesture in progress. Also ends a
+    /// temporary tool, so the new choice sticks.
+    pub fn set_active(&mut self, kind: ToolKind, context: &mut ToolContext) {
+        self.tool_mut(self.active).cancel(context);
+        self.temporary = None;
+        self.active = kind;
+    }
+
+    /// Cancels the active gesture and returns from a temporary tool.
+    pub fn cancel(&mut self, context: &mut ToolContext) {
+        self.tool_mut(self.active).cancel(context);
+        if let Some(temporary) = self.temporary.take() {
+            self.active = temporary.previous;
+        }
+    }
+
+    pub fn handle(&mut self, event: &InputEvent, context: &mut ToolContext) {
+        match event {
+            InputEvent::Cancel => return self.cancel(context),
+            InputEvent::KeyDown { key, modifiers, repeat } => {
+                if key == "Escape" {
+                    return self.escape(event, context);
+                }
+                if key == "Enter" && self.active == ToolKind::Select && self.select.is_idle() {
+                    if let Some(id) = self.selected_text(context) {
+                        return self.edit_text(id, context);
+                    }
+                }
+                if event.is_key(" ") {
+                    if !repeat {
+                        self.begin_temporary(ToolKind::Hand, Trigger::Space);
+                    }
+                    return;
+                }
+                let plain = !modifiers.command() && !modifiers.alt;
+                if let Some(kind) = ToolKind::from_shortcut(key).filter(|_| plain && self.temporary.is_none()) {
+                    return self.set_active(kind, context);
+                }
+            }
+            InputEvent::KeyUp { .. } if event.is_key(" ") => {
+                return self.end_temporary(Trigger::Space, context);
+            }
+            InputEvent::PointerDown(pointer) if pointer.button == PointerButton::Middle => {
+                self.begin_temporary(ToolKind::Hand, Trigger::MiddleButton);
+            }
+            InputEvent::DoubleClick(pointer) if self.active == ToolKind::Select && self.select.is_idle() => {
+                // Text inside a group is only edited once the group is entered.
+                let target = self.select.target_at(context.to_world(pointer.screen), context);
+                if let Some(id) = target.filter(|id| context.document.get(*id).is_some_and(|s| matches!(s.kind, ShapeKind::Text { .. }))) {
+                    return self.edit_text(id, context);
+                }
+            }
+            _ => {}
+        }
+
+        if self.active == ToolKind::Lasso {
+            self.lasso.entered = self.select.entered();
+        }
+        self.tool_mut(self.active).handle(event, context);
+
+        if self.temporary.is_some_and(|t| t.trigger == Trigger::TextEdit) && self.text.is_idle() {
+            self.cancel(context);
+        }
+
+        if let InputEvent::PointerUp(pointer) = event {
+            if pointer.button == PointerButton::Middle {
+                self.end_temporary(Trigger::MiddleButton, context);
+            }
+        }
+    }
+
+    fn escape(&mut self, event: &InputEvent, context: &mut ToolContext) {
+        if !self.tool(self.active).is_idle() || self.temporary.is_some() {
+            self.cancel(context);
+        } else if self.active != ToolKind::Select {
+            self.set_active(ToolKind::Select, context);
+        } else {
+            self.select.handle(event, context);
+        }
+    }
+
+    /// The selection's only shape, if it is text.
+    fn selected_text(&self, context: &ToolContext) -> Option<ShapeId> {
+        let mut ids = context.selection.iter();
+        let id = ids.next().filter(|_| ids.next().is_none())?;
+        matches!(context.document.get(id)?.kind, ShapeKind::Text { .. }).then_some(id)
+    }
+
+    /// Edits text from the select tool, returning to it afterwards.
+    fn edit_text(&mut self, id: ShapeId, context: &mut ToolContext) {
+        self.begin_temporary(ToolKind::Text, Trigger::TextEdit);
+        self.text.edit(id, context);
+    }
+
+    /// Temporary tools only start between gestures, so a held space bar
+    /// never interrupts a stroke.
+    fn begin_temporary(&mut self, kind: ToolKind, trigger: Trigger) {
+        if self.temporary.is_some() || self.active == kind || !self.tool(self.active).is_idle() {
+            return;
+        }
+        self.temporary = Some(Temporary {
+            previous: self.active,
+            trigger,
+        });
+        self.active = kind;
+    }
+
+    fn end_temporary(&mut self, trigger: Trigger, context: &mut ToolContext) {
+        if self.temporary.is_some_and(|t| t.trigger == trigger) {
+            self.cancel(context);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Editor;
+
+    fn key_down(key: &str) -> InputEvent {
+        InputEvent::KeyDown {
+            key: key.to_string(),
+            modifiers: Modifiers::default(),
+            repeat: false,
+        }
+    }
+
+    fn key_up(key: &str) -> InputEvent {
+        InputEvent::KeyUp {
+            key: key.to_string(),
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    fn down(x: f32, y: f32) -> InputEvent {
+        InputEvent::PointerDown(PointerInput::new(Point::new(x, y)))
+    }
+
+    fn drag_to(x: f32, y: f32) -> InputEvent {
+        InputEvent::PointerMove(PointerInput::new(Point::new(x, y)))
+    }
+
+    #[test]
+    fn test_shortcuts_switch_tools() {
+        let mut editor = Editor::new(800.0, 600.0);
+        assert_eq!(editor.tools.active(), ToolKind::Select);
+        editor.handle_input(key_down("R"));
+        assert_eq!(editor.tools.active(), ToolKind::Rectangle);
+        editor.handle_input(InputEvent::KeyDown {
+            key: "p".to_string(),
+            modifiers: Modifiers {
+                ctrl: true,
+                ..Modifiers::default()
+            },
+            repeat: false,
+        });
+        assert_eq!(editor.tools.active(), ToolKind::Rectangle);
+        for kind in ToolKind::ALL {
+            assert_eq!(ToolKind::from_name(kind.name()), Some(kind));
+        }
+    }
+
+    #[test]
+    fn test_escape_cancels_then_returns_to_select() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.handle_input(key_down("p"));
+        editor.handle_input(down(100.0, 100.0));
+        editor.handle_input(drag_to(200.0, 150.0));
+        assert!(editor.preview_shape().is_some());
+
+        editor.handle_input(key_down("Escape"));
+        assert!(editor.preview_shape().is_none());
+        assert_eq!(editor.tools.active(), ToolKind::Pen);
+        editor.handle_input(InputEvent::PointerUp(PointerInput::new(Point::new(200.0, 150.0))));
+        assert!(editor.document.is_empty());
+
+        editor.handle_input(key_down("Escape"));
+        assert_eq!(editor.tools.active(), ToolKind::Select);
+    }
+
+    #[test]
+    fn test_hold_space_pans_and_restores_tool() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.handle_input(key_down("r"));
+        editor.handle_input(key_down(" "));
+        assert_eq!(editor.tools.active(), ToolKind::Hand);
+        editor.handle_input(InputEvent::KeyDown {
+            key: " ".to_string(),
+            modifiers: Modifiers::default(),
+            repeat: true,
+        });
+
+        editor.handle_input(down(100.0, 100.0));
+        editor.handle_input(drag_to(160.0, 100.0));
+        assert!(editor.state.offset_x > 0.0);
+        assert!(editor.document.is_empty());
+
+        editor.handle_input(key_up(" "));
+        assert_eq!(editor.tools.active(), ToolKind::Rectangle);
+    }
+
+    #[test]
+    fn test_space_does_not_interrupt_a_gesture() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.handle_input(key_down("r"));
+        editor.handle_input(down(100.0, 100.0));
+        editor.handle_input(key_down(" "));
+        assert_eq!(editor.tools.active(), ToolKind::Rectangle);
+        editor.handle_input(drag_to(200.0, 200.0));
+        editor.handle_input(InputEvent::PointerUp(PointerInput::new(Point::new(200.0, 200.0))));
+        editor.handle_input(key_up(" "));
+        assert_eq!(editor.document.len(), 1);
+        assert_eq!(editor.tools.active(), ToolKind::Rectangle);
+    }
+
+    #[test]
+    fn test_middle_button_pans_temporarily() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.handle_input(key_down("e"));
+        let middle = |x| PointerInput {
+            button: PointerButton::Middle,
+            ..PointerInput::new(Point::new(x, 100.0))
+        };
+        editor.handle_input(InputEvent::PointerDown(middle(100.0)));
+        assert_eq!(editor.tools.active(), ToolKind::Hand);
+        editor.handle_input(InputEvent::PointerMove(middle(50.0)));
+        editor.handle_input(InputEvent::PointerUp(middle(50.0)));
+        assert!(editor.state.offset_x < 0.0);
+        assert_eq!(editor.tools.active(), ToolKind::Eraser);
+    }
+
+    #[test]
+    fn test_double_click_edits_text_then_returns_to_select() {
+        let mut editor = Editor::new(800.0, 600.0);
+        let kind = ShapeKind::Text {
+            content: "hello".to_string(),
+            font_size: 20.0,
+            align: crate::text::TextAlign::Left,
+        };
+        let transform = crate::document::Transform::new(-50.0, -10.0, 100.0, 25.0);
+        let id = editor.document.insert(kind, transform, crate::document::Style::default());
+        editor.sync_index();
+
+        editor.handle_input(InputEvent::DoubleClick(PointerInput::new(Point::new(400.0, 300.0))));
+        assert_eq!(editor.tools.active(), ToolKind::Text);
+        assert_eq!(editor.tools.text.editing().map(|edit| edit.id), Some(id));
+        // Typing letters edits text rather than switching tools.
+        editor.handle_input(InputEvent::TextInput {
+            content: "hello r".to_string(),
+            selection: (7, 7),
+            composition: None,
+        });
+        editor.handle_input(key_down("Escape"));
+        assert_eq!(editor.tools.active(), ToolKind::Select);
+        assert!(editor.selection.contains(id));
+
+        editor.handle_input(key_down("Enter"));
+        assert_eq!(editor.tools.text.editing().map(|edit| edit.id), Some(id));
+        editor.handle_input(down(10.0, 10.0));
+        assert_eq!(editor.tools.active(), ToolKind::Select);
+    }
+}
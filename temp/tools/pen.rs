@@ -0,0 +1,112 @@
+use super::{InputEvent, PointerButton, PointerKind, Tool, ToolContext, ToolKind};
+use crate::document::{Shape, Style};
+use crate::freehand::{FreehandOptions, StrokeBuilder};
+
+#[derive(Debug, Clone)]
+enum PenState {
+    Idle,
+    Drawing(StrokeBuilder),
+}
+
+/// The freehand drawing tool: turns pointer input into freehand shapes.
+#[derive(Debug, Clone)]
+pub struct PenTool {
+    pub style: Style,
+    pub options: FreehandOptions,
+    state: PenState,
+}
+
+impl Default for PenTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PenTool {
+    pub fn new() -> Self {
+        Self {
+            style: Style::default(),
+            options: FreehandOptions::default(),
+            state: PenState::Idle,
+        }
+    }
+
+    pub fn is_drawing(&self) -> bool {
+        matches!(self.state, PenState::Drawing(_))
+    }
+}
+
+impl Tool for PenTool {
+    fn kind(&self) -> ToolKind {
+        ToolKind::Pen
+    }
+
+    fn handle(&mut self, event: &InputEvent, context: &mut ToolContext) {
+        match (&mut self.state, event) {
+            (PenState::Idle, InputEvent::PointerDown(pointer)) if pointer.button == PointerButton::Primary => {
+                // Only styluses report real pressure.
+                let simulate_pressure = pointer.kind != PointerKind::Pen;
+                let mut builder = StrokeBuilder::new(self.style.clone(), self.options.clone(), simulate_pressure);
+                builder.add_sample(context.to_world(pointer.screen), pointer.pressure);
+                self.state = PenState::Drawing(builder);
+            }
+            (PenState::Drawing(builder), InputEvent::PointerMove(pointer)) => {
+                builder.add_sample(context.to_world(pointer.screen), pointer.pressure);
+            }
+            (PenState::Drawing(_), InputEvent::PointerUp(_)) => {
+                let PenState::Drawing(builder) = std::mem::replace(&mut self.state, PenState::Idle) else {
+                    unreachable!();
+                };
+                if let Some((kind, transform, style)) = builder.finish(context.world_units_per_pixel()) {
+                    context.document.insert(kind, transform, style);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn cancel(&mut self, _context: &mut ToolContext) {
+        self.state = PenState::Idle;
+    }
+
+    fn is_idle(&self) -> bool {
+        !self.is_drawing()
+    }
+
+    fn preview(&self) -> Option<Shape> {
+        match &self.state {
+            PenState::Drawing(builder) => builder.preview_shape(),
+            PenState::Idle => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{ShapeKind, PREVIEW_SHAPE_ID};
+    use crate::editor::Editor;
+    use crate::geometry::Point;
+    use crate::tools::PointerInput;
+
+    #[test]
+    fn test_pen_commits_into_document() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.set_tool(ToolKind::Pen);
+        let at = |x, y| PointerInput::new(Point::new(x, y));
+        editor.handle_input(InputEvent::PointerDown(at(100.0, 100.0)));
+        editor.handle_input(InputEvent::PointerMove(at(105.0, 105.0)));
+        assert!(editor.tools.pen.is_drawing());
+        assert_eq!(editor.preview_shape().unwrap().id, PREVIEW_SHAPE_ID);
+
+        editor.handle_input(InputEvent::PointerUp(at(105.0, 105.0)));
+        assert!(!editor.tools.pen.is_drawing());
+        let shape = editor.document.shapes().next().unwrap();
+        assert!(matches!(shape.kind, ShapeKind::Freehand { .. }));
+
+        editor.handle_input(InputEvent::PointerDown(at(100.0, 100.0)));
+        editor.handle_input(InputEvent::Cancel);
+        editor.handle_input(InputEvent::PointerUp(at(100.0, 100.0)));
+        assert_eq!(editor.document.len(), 1);
+    }
+}
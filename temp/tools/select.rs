@@ -0,0 +1,573 @@
+use super::{InputEvent, Modifiers, PointerButton, Tool, ToolContext, ToolKind, DRAG_THRESHOLD_PX, HIT_TOLERANCE_PX};
+use crate::document::{Document, Shape, ShapeId, ShapeKind, Transform};
+use crate::geometry::{Point, Rect};
+use crate::handles::{handle_at, rotate_shape, rotation_delta, selection_frame, Handle, Resize};
+use crate::hierarchy::{drop_into_frames, selection_target};
+use crate::overlay::Overlay;
+use crate::selection::{marquee_hits, MarqueeMode};
+use crate::snap::{Guide, SnapTargets, SNAP_THRESHOLD_PX};
+
+#[derive(Debug, Clone, PartialEq)]
+enum SelectState {
+    Idle,
+    /// Pressed on a selected shape; dragging moves the whole selection.
+    Translating {
+        clicked: ShapeId,
+        grab: Point,
+        originals: Vec<Shape>,
+        moved: bool,
+        targets: SnapTargets,
+        guides: Vec<Guide>,
+    },
+    /// Dragging a resize handle of `frame`, the selection frame at press time.
+    /// `offset` is from the press point to the handle centre, so grabbing a
+    /// handle off-centre does not make it jump.
+    Resizing {
+        handle: Handle,
+        frame: Transform,
+        offset: Point,
+        originals: Vec<Shape>,
+        pointer: Point,
+        modifiers: Modifiers,
+        targets: SnapTargets,
+        guides: Vec<Guide>,
+    },
+    /// Dragging the rotation handle, grabbed at `grab`.
+    Rotating {
+        frame: Transform,
+        grab: Point,
+        originals: Vec<Shape>,
+        pointer: Point,
+        modifiers: Modifiers,
+    },
+    /// Dragging a selection rectangle out from empty canvas. `base` is the
+    /// selection the marquee adds to (empty unless Shift was held).
+    Marquee {
+        origin: Point,
+        current: Point,
+        base: Vec<ShapeId>,
+        mode: MarqueeMode,
+    },
+}
+
+/// Selects shapes by clicking or with a marquee, and moves, resizes and
+/// rotates them.
+///
+/// Shift-click toggles a shape; Shift-drag adds a marquee to the current
+/// selection. Holding Alt while dragging a marquee flips `marquee_mode`.
+/// Resizing keeps the aspect ratio with Shift and grows from the centre with
+/// Alt; rotating snaps to 15° with Shift.
+///
+/// Moving and resizing snap to the grid, to other shapes' edges and centres
+/// and to equal gaps, showing guides; holding Ctrl (Cmd on macOS) suspends
+/// snapping. Resizing a rotated frame does not snap.
+///
+/// Clicks pick whole groups. Double-clicking a group enters it, so clicks
+/// pick its contents until Escape or a click outside it. Shapes dragged onto
+/// a frame move into it, and out again when dragged off.
+#[derive(Debug, Clone)]
+pub struct SelectTool {
+    pub marquee_mode: MarqueeMode,
+    pub snapping: bool,
+    /// The innermost group double-clicked into.
+    entered: Option<ShapeId>,
+    state: SelectState,
+}
+
+impl Default for SelectTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelectTool {
+    pub fn new() -> Self {
+        Self {
+            marquee_mode: MarqueeMode::default(),
+            snapping: true,
+            entered: None,
+            state: SelectState::Idle,
+        }
+    }
+
+    pub fn entered(&self) -> Option<ShapeId> {
+        self.entered
+    }
+
+    /// What a click at `point` selects, see [`selection_target`].
+    pub(super) fn target_at(&self, point: Point, context: &ToolContext) -> Option<ShapeId> {
+        let tolerance = HIT_TOLERANCE_PX * context.world_units_per_pixel();
+        let hit = context.index.topmost_at(context.document, point, tolerance)?;
+        Some(selection_target(context.document, hit, self.entered))
+    }
+
+    fn selected_shapes(context: &ToolContext) -> Vec<Shape> {
+        context
+            .selection
+            .iter()
+            .filter_map(|id| context.document.get(id).cloned())
+            .collect()
+    }
+
+    /// The selection plus the contents of selected groups, so resizing and
+    /// rotating a group reshapes its contents exactly.
+    fn selected_with_contents(context: &ToolContext) -> Vec<Shape> {
+        let mut shapes = Self::selected_shapes(context);
+        let groups: Vec<ShapeId> = shapes.iter().filter(|s| s.kind == ShapeKind::Group).map(|s| s.id).collect();
+        for group in groups {
+            let contents = context.document.descendants(group);
+            shapes.extend(contents.into_iter().filter_map(|id| context.document.get(id).cloned()));
+        }
+        shapes
+    }
+
+    /// What moving or resizing `moving` snaps to: the grid and the shapes
+    /// in view.
+    fn snap_targets(&self, moving: &[Shape], context: &ToolContext) -> SnapTargets {
+        if !self.snapping {
+            return SnapTargets::default();
+        }
+        let view = context.state.visible_world_rect(context.width, context.height);
+        let ids: Vec<ShapeId> = moving.iter().map(|s| s.id).collect();
+        SnapTargets::collect(context.document, context.index, &view, &ids, context.grid.spacing)
+    }
+
+    fn press(&mut self, world: Point, modifiers: Modifiers, context: &mut ToolContext) {
+        let per_pixel = context.world_units_per_pixel();
+        if let Some(frame) = selection_frame(context.document, context.selection) {
+            if let Some(handle) = handle_at(&frame, world, per_pixel) {
+                let originals = Self::selected_with_contents(context);
+                let targets = self.snap_targets(&originals, context);
+                self.state = if handle == Handle::Rotate {
+                    SelectState::Rotating {
+                        frame,
+                        grab: world,
+                        originals,
+                        pointer: world,
+                        modifiers,
+                    }
+                } else {
+                    SelectState::Resizing {
+                        handle,
+                        frame,
+                        offset: handle.position(&frame, per_pixel) - world,
+                        originals,
+                        pointer: world,
+                        modifiers,
+                        targets,
+                        guides: Vec::new(),
+                    }
+                };
+                return;
+            }
+        }
+
+        let mut target = self.target_at(world, context);
+        if let Some(entered) = self.entered {
+            if !target.is_some_and(|id| context.document.is_descendant(id, entered)) {
+                self.entered = None;
+                target = self.target_at(world, context);
+            }
+        }
+        match target {
+            Some(id) if modifiers.shift => {
+                context.selection.toggle(id);
+            }
+            Some(id) => {
+                if !context.selection.contains(id) {
+                    context.selection.select_only(id);
+                }
+                let originals = Self::selected_shapes(context);
+                self.state = SelectState::Translating {
+                    clicked: id,
+                    grab: world,
+                    targets: self.snap_targets(&originals, context),
+                    originals,
+                    moved: false,
+                    guides: Vec::new(),
+                };
+            }
+            None => {
+                let base = if modifiers.shift { context.selection.iter().collect() } else { Vec::new() };
+                context.selection.set(base.iter().copied());
+                self.state = SelectState::Marquee {
+                    origin: world,
+                    current: world,
+                    base,
+                    mode: self.marquee_mode,
+                };
+            }
+        }
+    }
+
+    /// Re-derives the resized or rotated shapes from their originals, so
+    /// modifier changes apply without the pointer moving.
+    fn apply_transform(&mut self, context: &mut ToolContext) {
+        let threshold = SNAP_THRESHOLD_PX * context.world_units_per_pixel();
+        let updated: Vec<Shape> = match &mut self.state {
+            SelectState::Resizing {
+                handle,
+                frame,
+                offset,
+                originals,
+                pointer,
+                modifiers,
+                targets,
+                guides,
+            } => {
+                let mut target = *pointer + *offset;
+                guides.clear();
+                if frame.rotation == 0.0 && !modifiers.command() {
+                    let snap = targets.snap_point(target, handle.axes(), threshold);
+                    target = target + snap.offset;
+                    *guides = snap.guides;
+                }
+                let resize = Resize::from_drag(frame, *handle, target, modifiers.shift, modifiers.alt);
+                originals.iter().map(|shape| resize.apply_to(frame, shape)).collect()
+            }
+            SelectState::Rotating { frame, grab, originals, pointer, modifiers } => {
+                let pivot = frame.center();
+                let angle = rotation_delta(frame, pivot, *grab, *pointer, modifiers.shift);
+                originals.iter().map(|shape| rotate_shape(shape, pivot, angle)).collect()
+            }
+            _ => return,
+        };
+        for shape in updated {
+            let _ = context.document.update(shape.id, |s| *s = shape);
+        }
+    }
+}
+
+impl Tool for SelectTool {
+    fn kind(&self) -> ToolKind {
+        ToolKind::Select
+    }
+
+    fn handle(&mut self, event: &InputEvent, context: &mut ToolContext) {
+        match (&mut self.state, event) {
+            (SelectState::Idle, InputEvent::PointerDown(pointer)) if pointer.button == PointerButton::Primary => {
+                let world = context.to_world(pointer.screen);
+                self.press(world, pointer.modifiers, context);
+            }
+            (SelectState::Idle, InputEvent::DoubleClick(pointer)) => {
+                let world = context.to_world(pointer.screen);
+                let tolerance = HIT_TOLERANCE_PX * context.world_units_per_pixel();
+                let Some(hit) = context.index.topmost_at(context.document, world, tolerance) else {
+                    return;
+                };
+                let target = selection_target(context.document, hit, self.entered);
+                if context.document.get(target).is_some_and(|s| s.kind == ShapeKind::Group) {
+                    self.entered = Some(target);
+                    context.selection.select_only(selection_target(context.document, hit, self.entered));
+                }
+            }
+            // Escape steps out of an entered group, selecting it.
+            (SelectState::Idle, InputEvent::KeyDown { key, .. }) if key == "Escape" => match self.entered.take() {
+                Some(group) if context.document.contains(group) => {
+                    context.selection.select_only(group);
+                    self.entered = context
+                        .document
+                        .ancestors(group)
+                        .into_iter()
+                        .find(|id| context.document.get(*id).is_some_and(|s| s.kind == ShapeKind::Group));
+                }
+                _ => context.selection.clear(),
+            },
+            (
+                SelectState::Translating {
+                    grab,
+                    originals,
+                    moved,
+                    targets,
+                    guides,
+                    ..
+                },
+                InputEvent::PointerMove(pointer),
+            ) => {
+                let per_pixel = context.world_units_per_pixel();
+                let mut delta = context.to_world(pointer.screen) - *grab;
+                if !*moved && delta.length() < DRAG_THRESHOLD_PX * per_pixel {
+                    return;
+                }
+                *moved = true;
+                guides.clear();
+                let bounds = originals.iter().map(|s| s.transform.bounds()).reduce(|a, b| a.union(&b));
+                if let Some(bounds) = bounds.filter(|_| !pointer.modifiers.command()) {
+                    let moving = Rect::new(bounds.min_x + delta.x, bounds.min_y + delta.y, bounds.max_x + delta.x, bounds.max_y + delta.y);
+                    let snap = targets.snap_rect(moving, SNAP_THRESHOLD_PX * per_pixel);
+                    delta = delta + snap.offset;
+                    *guides = snap.guides;
+                }
+                for original in originals.iter() {
+                    let _ = context.document.update(original.id, |shape| {
+                        shape.transform.x = original.transform.x + delta.x;
+                        shape.transform.y = original.transform.y + delta.y;
+                    });
+                }
+            }
+            (SelectState::Translating { clicked, originals, moved, .. }, InputEvent::PointerUp(_)) => {
+                // Clicking one shape of a multi-selection narrows it to that shape.
+                if !*moved {
+                    context.selection.select_only(*clicked);
+                } else {
+                    let ids: Vec<ShapeId> = originals.iter().map(|s| s.id).collect();
+                    drop_into_frames(context.document, &ids);
+                }
+                self.state = SelectState::Idle;
+            }
+            (
+                SelectState::Resizing { pointer, modifiers, .. } | SelectState::Rotating { pointer, modifiers, .. },
+                InputEvent::PointerMove(input),
+            ) => {
+                *pointer = context.to_world(input.screen);
+                *modifiers = input.modifiers;
+                self.apply_transform(context);
+            }
+            (
+                SelectState::Resizing { modifiers, .. } | SelectState::Rotating { modifiers, .. },
+                InputEvent::KeyDown { modifiers: keys, .. } | InputEvent::KeyUp { modifiers: keys, .. },
+            ) => {
+                *modifiers = *keys;
+                self.apply_transform(context);
+            }
+            (SelectState::Resizing { .. } | SelectState::Rotating { .. }, InputEvent::PointerUp(_)) => {
+                self.state = SelectState::Idle;
+            }
+            (SelectState::Marquee { origin, current, base, mode }, InputEvent::PointerMove(pointer)) => {
+                *current = context.to_world(pointer.screen);
+                *mode = if pointer.modifiers.alt { self.marquee_mode.toggled() } else { self.marquee_mode };
+                let rect = Rect::from_points([*origin, *current]).unwrap();
+                let tolerance = 0.5 * context.world_units_per_pixel();
+                let mut targets = Vec::new();
+                for hit in marquee_hits(context.document, context.index, &rect, *mode, tolerance) {
+                    let target = selection_target(context.document, hit, self.entered);
+                    // A group is only contained once all of it is.
+                    let whole = target == hit
+                        || *mode == MarqueeMode::Touching
+                        || context.document.get(target).is_some_and(|g| rect.contains_rect(&g.transform.bounds()));
+                    if whole && !targets.contains(&target) {
+                        targets.push(target);
+                    }
+                }
+                context.selection.set(base.iter().copied().chain(targets));
+            }
+            (SelectState::Marquee { .. }, InputEvent::PointerUp(_)) => {
+                self.state = SelectState::Idle;
+            }
+            _ => {}
+        }
+    }
+
+    /// Puts moved, resized or rotated shapes back, or restores the
+    /// selection from before the marquee.
+    fn cancel(&mut self, context: &mut ToolContext) {
+        match std::mem::replace(&mut self.state, SelectState::Idle) {
+            SelectState::Translating { originals, .. }
+            | SelectState::Resizing { originals, .. }
+            | SelectState::Rotating { originals, .. } => {
+                for original in originals {
+                    let _ = context.document.update(original.id, |shape| *shape = original);
+                }
+            }
+            SelectState::Marquee { base, .. } => context.selection.set(base),
+            SelectState::Idle => {}
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.state == SelectState::Idle
+    }
+
+    fn overlay(&self, _document: &Document, overlay: &mut Overlay) {
+        match &self.state {
+            SelectState::Marquee { origin, current, .. } => overlay.marquee(Rect::from_points([*origin, *current]).unwrap()),
+            SelectState::Translating { guides, .. } | SelectState::Resizing { guides, .. } => {
+                for guide in guides {
+                    overlay.guide(guide);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Color, ShapeKind, Style};
+    use crate::editor::Editor;
+    use crate::tools::{Modifiers, PointerInput};
+
+    fn editor_with_squares() -> (Editor, ShapeId, ShapeId) {
+        let mut editor = Editor::new(800.0, 600.0);
+        let style = Style {
+            fill: Some(Color::WHITE),
+            ..Style::default()
+        };
+        // Screen centre is the world origin; one pixel is 10/3 world units.
+        let a = editor.document.insert(ShapeKind::Rectangle, Transform::new(-50.0, -50.0, 100.0, 100.0), style.clone());
+        let b = editor.document.insert(ShapeKind::Rectangle, Transform::new(200.0, -50.0, 100.0, 100.0), style);
+        editor.sync_index();
+        (editor, a, b)
+    }
+
+    fn at(x: f32, y: f32) -> PointerInput {
+        PointerInput::new(Point::new(x, y))
+    }
+
+    fn shift_at(x: f32, y: f32) -> PointerInput {
+        PointerInput {
+            modifiers: Modifiers {
+                shift: true,
+                ..Modifiers::default()
+            },
+            ..at(x, y)
+        }
+    }
+
+    fn click(editor: &mut Editor, pointer: PointerInput) {
+        editor.handle_input(InputEvent::PointerDown(pointer));
+        editor.handle_input(InputEvent::PointerUp(pointer));
+    }
+
+    #[test]
+    fn test_click_and_shift_click() {
+        let (mut editor, a, b) = editor_with_squares();
+        click(&mut editor, at(400.0, 300.0));
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![a]);
+        click(&mut editor, shift_at(475.0, 300.0));
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![a, b]);
+        click(&mut editor, shift_at(400.0, 300.0));
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![b]);
+        click(&mut editor, at(100.0, 100.0));
+        assert!(editor.selection.is_empty());
+    }
+
+    #[test]
+    fn test_drag_moves_selection_and_cancel_restores() {
+        let (mut editor, a, b) = editor_with_squares();
+        editor.selection.set([a, b]);
+        editor.handle_input(InputEvent::PointerDown(at(400.0, 300.0)));
+        editor.handle_input(InputEvent::PointerMove(at(430.0, 300.0)));
+        let per_pixel = editor.state.world_units_per_pixel(800.0, 600.0);
+        for (id, x) in [(a, -50.0), (b, 200.0)] {
+            let moved = editor.document.get(id).unwrap().transform;
+            assert!((moved.x - (x + 30.0 * per_pixel)).abs() < 1e-2);
+        }
+
+        editor.handle_input(InputEvent::Cancel);
+        assert_eq!(editor.document.get(a).unwrap().transform.x, -50.0);
+        assert_eq!(editor.selection.len(), 2);
+        assert!(editor.tools.tool(ToolKind::Select).is_idle());
+    }
+
+    #[test]
+    fn test_moving_snaps_to_edges_unless_suspended() {
+        let (mut editor, a, b) = editor_with_squares();
+        // 44 px leaves `a`'s right edge 1 px short of `b`'s left edge.
+        editor.handle_input(InputEvent::PointerDown(at(400.0, 300.0)));
+        editor.handle_input(InputEvent::PointerMove(at(444.0, 300.0)));
+        assert_eq!(editor.document.get(a).unwrap().transform.x, 100.0);
+        assert!(editor.overlay().shapes().iter().any(|s| s.style.stroke == crate::overlay::GUIDE));
+
+        let suspended = PointerInput {
+            modifiers: Modifiers {
+                ctrl: true,
+                ..Modifiers::default()
+            },
+            ..at(444.0, 300.0)
+        };
+        editor.handle_input(InputEvent::PointerMove(suspended));
+        let per_pixel = editor.state.world_units_per_pixel(800.0, 600.0);
+        assert!((editor.document.get(a).unwrap().transform.x - (-50.0 + 44.0 * per_pixel)).abs() < 1e-2);
+        assert!(editor.overlay().shapes().iter().all(|s| s.style.stroke != crate::overlay::GUIDE));
+        editor.handle_input(InputEvent::PointerUp(suspended));
+
+        // Resizing snaps the dragged edge; `b`'s left edge is at x = 200.
+        editor.selection.select_only(b);
+        editor.handle_input(InputEvent::PointerDown(at(490.0, 300.0)));
+        editor.handle_input(InputEvent::PointerMove(at(495.0, 300.0)));
+        editor.handle_input(InputEvent::PointerUp(at(495.0, 300.0)));
+        let t = editor.document.get(b).unwrap().transform;
+        assert!((t.x + t.width - 320.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_marquee_selects_and_escape_clears() {
+        let (mut editor, a, b) = editor_with_squares();
+        // From above-left of `a` to the middle of `b`.
+        editor.handle_input(InputEvent::PointerDown(at(370.0, 270.0)));
+        editor.handle_input(InputEvent::PointerMove(at(475.0, 330.0)));
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![a]);
+        // Outline, frame, nine handles and the marquee.
+        assert_eq!(editor.overlay().shapes().len(), 12);
+
+        editor.tools.select.marquee_mode = MarqueeMode::Touching;
+        editor.handle_input(InputEvent::PointerMove(at(476.0, 330.0)));
+        editor.handle_input(InputEvent::PointerUp(at(476.0, 330.0)));
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![a, b]);
+
+        editor.handle_input(InputEvent::KeyDown {
+            key: "Escape".to_string(),
+            modifiers: Modifiers::default(),
+            repeat: false,
+        });
+        assert!(editor.selection.is_empty());
+    }
+
+    #[test]
+    fn test_resize_handle_keeps_screen_size_across_zoom() {
+        let (mut editor, a, _) = editor_with_squares();
+        editor.selection.select_only(a);
+        editor.state.zoom = 4.0;
+        // The east edge of `a` sits at x = 50, i.e. 60 px right of the centre at
+        // zoom 4; 5 px further out still grabs the handle.
+        editor.handle_input(InputEvent::PointerDown(at(465.0, 300.0)));
+        editor.handle_input(InputEvent::PointerMove(at(520.0, 300.0)));
+        editor.handle_input(InputEvent::PointerUp(at(520.0, 300.0)));
+        let t = editor.document.get(a).unwrap().transform;
+        assert!((t.x + 50.0).abs() < 1e-2);
+        assert!((t.width - 100.0 - 55.0 * 10.0 / 12.0).abs() < 1e-2);
+        assert_eq!(t.height, 100.0);
+    }
+
+    #[test]
+    fn test_rotate_handle_snaps_with_shift_and_cancel_restores() {
+        let (mut editor, a, _) = editor_with_squares();
+        editor.selection.select_only(a);
+        // The top edge is 15 px above the centre; the rotation handle 24 px higher.
+        editor.handle_input(InputEvent::PointerDown(at(400.0, 261.0)));
+        editor.handle_input(InputEvent::PointerMove(shift_at(442.0, 280.0)));
+        let rotation = editor.document.get(a).unwrap().transform.rotation;
+        let step = std::f32::consts::PI / 12.0;
+        assert!(rotation > 0.0);
+        assert!(((rotation / step).round() * step - rotation).abs() < 1e-4);
+
+        editor.handle_input(InputEvent::Cancel);
+        assert_eq!(editor.document.get(a).unwrap().transform.rotation, 0.0);
+    }
+
+    #[test]
+    fn test_groups_select_whole_until_entered() {
+        let (mut editor, a, b) = editor_with_squares();
+        editor.selection.set([a, b]);
+        let group = editor.group_selection().unwrap();
+        click(&mut editor, at(100.0, 100.0));
+        click(&mut editor, at(400.0, 300.0));
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![group]);
+
+        editor.handle_input(InputEvent::DoubleClick(at(400.0, 300.0)));
+        assert_eq!(editor.tools.select.entered(), Some(group));
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![a]);
+        click(&mut editor, at(475.0, 300.0));
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![b]);
+
+        editor.handle_input(InputEvent::KeyDown {
+            key: "Escape".to_string(),
+            modifiers: Modifiers::default(),
+            repeat: false,
+        });
+        assert_eq!(editor.tools.select.entered(), None);
+        assert_eq!(editor.selection.iter().collect::<Vec<_>>(), vec![group]);
+    }
+}
@@ -0,0 +1,243 @@
+use std::f32::consts::PI;
+
+use super::{InputEvent, Modifiers, PointerButton, Tool, ToolContext, ToolKind, DRAG_THRESHOLD_PX};
+use crate::document::{Color, LayerId, Shape, ShapeKind, Style, Transform, PREVIEW_SHAPE_ID};
+use crate::fractional_index::FractionalIndex;
+use crate::geometry::{Point, Rect};
+use crate::hierarchy::adopt_contained;
+
+/// Arrow angles snap to multiples of this while Shift is held.
+const ANGLE_STEP: f32 = PI / 12.0;
+
+const FRAME_STROKE: Color = Color::rgba(0.8, 0.8, 0.8, 1.0);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ShapeState {
+    Idle,
+    /// Dragging out a shape from `origin` to `current`, both in world space.
+    Sizing {
+        origin: Point,
+        current: Point,
+        modifiers: Modifiers,
+    },
+}
+
+/// Creates rectangles, ellipses, arrows or frames by dragging.
+///
+/// Shift keeps boxes square and snaps arrows to 15° steps; Alt grows boxes
+/// from the press point outwards. A new frame takes in the shapes lying
+/// entirely inside it.
+#[derive(Debug, Clone)]
+pub struct ShapeTool {
+    kind: ToolKind,
+    pub style: Style,
+    state: ShapeState,
+}
+
+impl ShapeTool {
+    /// `kind` must be [`ToolKind::Rectangle`], [`ToolKind::Ellipse`],
+    /// [`ToolKind::Arrow`] or [`ToolKind::Frame`].
+    pub fn new(kind: ToolKind) -> Self {
+        debug_assert!(matches!(kind, ToolKind::Rectangle | ToolKind::Ellipse | ToolKind::Arrow | ToolKind::Frame));
+        let style = if kind == ToolKind::Frame {
+            Style {
+                stroke: FRAME_STROKE,
+                fill: Some(Color::WHITE),
+                stroke_width: 1.0,
+                ..Style::default()
+            }
+        } else {
+            Style::default()
+        };
+        Self {
+            kind,
+            style,
+            state: ShapeState::Idle,
+        }
+    }
+
+    fn shape_parts(&self, origin: Point, current: Point, modifiers: Modifiers) -> (ShapeKind, Transform) {
+        if self.kind == ToolKind::Arrow {
+            let end = if modifiers.shift { snap_angle(origin, current) } else { current };
+            let bounds = Rect::from_points([origin, end]).unwrap();
+            let corner = Point::new(bounds.min_x, bounds.min_y);
+            let kind = ShapeKind::Arrow {
+                start: origin - corner,
+                end: end - corner,
+            };
+            return (kind, Transform::new(bounds.min_x, bounds.min_y, bounds.width(), bounds.height()));
+        }
+
+        let mut delta = current - origin;
+        if modifiers.shift {
+            let side = delta.x.abs().max(delta.y.abs());
+            delta = Point::new(side.copysign(delta.x), side.copysign(delta.y));
+        }
+        let (a, b) = if modifiers.alt {
+            (origin - delta, origin + delta)
+        } else {
+            (origin, origin + delta)
+        };
+        let bounds = Rect::from_points([a, b]).unwrap();
+        let kind = match self.kind {
+            ToolKind::Ellipse => ShapeKind::Ellipse,
+            // Named when placed, see `Tool::handle`.
+            ToolKind::Frame => ShapeKind::Frame { name: String::new() },
+            _ => ShapeKind::Rectangle,
+        };
+        (kind, Transform::new(bounds.min_x, bounds.min_y, bounds.width(), bounds.height()))
+    }
+}
+
+/// Rotates `to` around `from` onto the nearest [`ANGLE_STEP`].
+fn snap_angle(from: Point, to: Point) -> Point {
+    let delta = to - from;
+    let angle = (delta.y.atan2(delta.x) / ANGLE_STEP).round() * ANGLE_STEP;
+    from + Point::new(angle.cos(), angle.sin()) * delta.length()
+}
+
+impl Tool for ShapeTool {
+    fn kind(&self) -> ToolKind {
+        self.kind
+    }
+
+    fn handle(&mut self, event: &InputEvent, context: &mut ToolContext) {
+        match (self.state, event) {
+            (ShapeState::Idle, InputEvent::PointerDown(pointer)) if pointer.button == PointerButton::Primary => {
+                let world = context.to_world(pointer.screen);
+                self.state = ShapeState::Sizing {
+                    origin: world,
+                    current: world,
+                    modifiers: pointer.modifiers,
+                };
+            }
+            (ShapeState::Sizing { origin, .. }, InputEvent::PointerMove(pointer)) => {
+                self.state = ShapeState::Sizing {
+                    origin,
+                    current: context.to_world(pointer.screen),
+                    modifiers: pointer.modifiers,
+                };
+            }
+            // Modifier changes apply without waiting for the pointer to move.
+            (ShapeState::Sizing { origin, current, .. }, InputEvent::KeyDown { modifiers, .. } | InputEvent::KeyUp { modifiers, .. }) => {
+                self.state = ShapeState::Sizing {
+                    origin,
+                    current,
+                    modifiers: *modifiers,
+                };
+            }
+            (ShapeState::Sizing { origin, .. }, InputEvent::PointerUp(pointer)) => {
+                self.state = ShapeState::Idle;
+                let current = context.to_world(pointer.screen);
+                if origin.distance(current) < DRAG_THRESHOLD_PX * context.world_units_per_pixel() {
+                    return;
+                }
+                let (mut kind, transform) = self.shape_parts(origin, current, pointer.modifiers);
+                if let ShapeKind::Frame { name } = &mut kind {
+                    let count = context.document.shapes().filter(|s| matches!(s.kind, ShapeKind::Frame { .. })).count();
+                    *name = format!("Frame {}", count + 1);
+                }
+                let id = context.document.insert(kind, transform, self.style.clone());
+                if self.kind == ToolKind::Frame {
+                    adopt_contained(context.document, context.index, id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn cancel(&mut self, _context: &mut ToolContext) {
+        self.state = ShapeState::Idle;
+    }
+
+    fn is_idle(&self) -> bool {
+        self.state == ShapeState::Idle
+    }
+
+    fn preview(&self) -> Option<Shape> {
+        let ShapeState::Sizing { origin, current, modifiers } = self.state else {
+            return None;
+        };
+        let (kind, transform) = self.shape_parts(origin, current, modifiers);
+        Some(Shape {
+            id: PREVIEW_SHAPE_ID,
+            kind,
+            transform,
+            style: self.style.clone(),
+            z_index: FractionalIndex::default(),
+            layer: LayerId::default(),
+            parent: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Editor;
+    use crate::tools::PointerInput;
+
+    fn drag(editor: &mut Editor, from: Point, to: Point, modifiers: Modifiers) {
+        let at = |screen| PointerInput {
+            modifiers,
+            ..PointerInput::new(screen)
+        };
+        editor.handle_input(InputEvent::PointerDown(at(from)));
+        editor.handle_input(InputEvent::PointerMove(at(to)));
+        editor.handle_input(InputEvent::PointerUp(at(to)));
+    }
+
+    #[test]
+    fn test_drag_creates_rectangle() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.set_tool(ToolKind::Rectangle);
+        drag(&mut editor, Point::new(400.0, 300.0), Point::new(430.0, 360.0), Modifiers::default());
+        let shape = editor.document.shapes().next().unwrap();
+        assert_eq!(shape.kind, ShapeKind::Rectangle);
+        let per_pixel = editor.state.world_units_per_pixel(800.0, 600.0);
+        assert!((shape.transform.width - 30.0 * per_pixel).abs() < 1e-2);
+        assert!((shape.transform.height - 60.0 * per_pixel).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_modifiers_constrain_boxes() {
+        let tool = ShapeTool::new(ToolKind::Ellipse);
+        let square = Modifiers {
+            shift: true,
+            ..Modifiers::default()
+        };
+        let (_, t) = tool.shape_parts(Point::new(0.0, 0.0), Point::new(-10.0, 40.0), square);
+        assert_eq!((t.x, t.y, t.width, t.height), (-40.0, 0.0, 40.0, 40.0));
+
+        let centered = Modifiers {
+            alt: true,
+            ..Modifiers::default()
+        };
+        let (_, t) = tool.shape_parts(Point::new(0.0, 0.0), Point::new(10.0, 20.0), centered);
+        assert_eq!((t.x, t.y, t.width, t.height), (-10.0, -20.0, 20.0, 40.0));
+    }
+
+    #[test]
+    fn test_arrow_snaps_angle_with_shift() {
+        let tool = ShapeTool::new(ToolKind::Arrow);
+        let shift = Modifiers {
+            shift: true,
+            ..Modifiers::default()
+        };
+        let (kind, t) = tool.shape_parts(Point::new(0.0, 0.0), Point::new(100.0, 3.0), shift);
+        let ShapeKind::Arrow { start, end } = kind else {
+            panic!("expected an arrow");
+        };
+        let (start, end) = (t.to_world(start), t.to_world(end));
+        assert!(start.distance(Point::ZERO) < 1e-3);
+        assert!(end.y.abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_click_without_drag_creates_nothing() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.set_tool(ToolKind::Arrow);
+        drag(&mut editor, Point::new(100.0, 100.0), Point::new(101.0, 100.0), Modifiers::default());
+        assert!(editor.document.is_empty());
+    }
+}
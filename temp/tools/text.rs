@@ -0,0 +1,330 @@
+use std::ops::Range;
+
+use super::{InputEvent, PointerButton, Tool, ToolContext, ToolKind, HIT_TOLERANCE_PX};
+use crate::document::{Shape, ShapeId, ShapeKind, Style, Transform};
+use crate::geometry::Point;
+use crate::text::{layout, utf16_to_byte, FontStack, TextAlign};
+
+/// An in-place edit of a text shape's content.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub id: ShapeId,
+    /// Selected bytes of the content; empty for a bare caret.
+    pub selection: Range<usize>,
+    /// Bytes of uncommitted IME input, shown underlined.
+    pub composition: Option<Range<usize>>,
+    /// The shape as it was before editing; `None` if it was just placed.
+    original: Option<Box<Shape>>,
+}
+
+impl TextEdit {
+    /// Freshly placed text grows with its content instead of wrapping.
+    pub fn grows(&self) -> bool {
+        self.original.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TextState {
+    Idle,
+    /// Pressed at `at` (world space); the shape is placed on release.
+    Placing { at: Point },
+    Editing(TextEdit),
+}
+
+/// Places text shapes with a click and edits them in place.
+///
+/// Editing keeps the tool busy, so the whole edit, from placement to the
+/// last keystroke, is one undo step. The content arrives as
+/// [`InputEvent::TextInput`] from a text field the platform keeps over the
+/// shape. Unlike other tools, cancelling keeps what was typed: it ends the
+/// edit rather than undoing it.
+#[derive(Debug, Clone)]
+pub struct TextTool {
+    pub style: Style,
+    /// Font size in world units.
+    pub font_size: f32,
+    pub align: TextAlign,
+    state: TextState,
+}
+
+impl Default for TextTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextTool {
+    pub fn new() -> Self {
+        Self {
+            style: Style::default(),
+            font_size: 24.0,
+            align: TextAlign::Left,
+            state: TextState::Idle,
+        }
+    }
+
+    /// The edit in progress, if any.
+    pub fn editing(&self) -> Option<&TextEdit> {
+        match &self.state {
+            TextState::Editing(edit) => Some(edit),
+            _ => None,
+        }
+    }
+
+    /// Starts editing the text shape `id`, with the caret at the end.
+    /// Does nothing if `id` is not a text shape.
+    pub fn edit(&mut self, id: ShapeId, context: &mut ToolContext) {
+        self.cancel(context);
+        let Some(shape) = context.document.get(id) else {
+            return;
+        };
+        let ShapeKind::Text { content, .. } = &shape.kind else {
+            return;
+        };
+        self.state = TextState::Editing(TextEdit {
+            id,
+            selection: content.len()..content.len(),
+            composition: None,
+            original: Some(Box::new(shape.clone())),
+        });
+        context.selection.select_only(id);
+    }
+
+    /// A box fitting `content` unwrapped, or a rough estimate until a font
+    /// is loaded.
+    pub(crate) fn fitted_transform(at: Point, content: &str, font_size: f32, fonts: &FontStack) -> Transform {
+        let measured = layout(fonts, content, font_size, None, TextAlign::Left);
+        let width = if fonts.is_empty() {
+            content.chars().count() as f32 * font_size * 0.6
+        } else {
+            measured.width
+        };
+        Transform::new(at.x, at.y, width, measured.height)
+    }
+
+    /// The topmost text shape at `point`.
+    pub(super) fn text_at(point: Point, context: &ToolContext) -> Option<ShapeId> {
+        let tolerance = HIT_TOLERANCE_PX * context.world_units_per_pixel();
+        context
+            .index
+            .topmost_at(context.document, point, tolerance)
+            .filter(|&id| matches!(context.document.get(id), Some(Shape { kind: ShapeKind::Text { .. }, .. })))
+    }
+
+    /// Replaces the content and refits the box: placed text grows to fit,
+    /// existing text keeps its width and rewraps.
+    fn apply_input(edit: &mut TextEdit, text: &str, selection: (usize, usize), composition: Option<(usize, usize)>, context: &mut ToolContext) {
+        edit.selection = byte_range(text, selection);
+        edit.composition = composition.map(|range| byte_range(text, range));
+        let grows = edit.grows();
+        let fonts = context.fonts;
+        let _ = context.document.update(edit.id, |shape| {
+            let ShapeKind::Text { content, font_size, align } = &mut shape.kind else {
+                return;
+            };
+            content.clear();
+            content.push_str(text);
+            let t = &mut shape.transform;
+            if grows {
+                let fitted = Self::fitted_transform(Point::new(t.x, t.y), text, *font_size, fonts);
+                t.width = fitted.width;
+                t.height = fitted.height;
+            } else {
+                t.height = layout(fonts, text, *font_size, Some(t.width), *align).height;
+            }
+        });
+    }
+
+    /// Ends the edit, keeping the content. Emptied text is removed.
+    fn commit(&mut self, context: &mut ToolContext) {
+        let TextState::Editing(edit) = std::mem::replace(&mut self.state, TextState::Idle) else {
+            return;
+        };
+        let empty = matches!(
+            context.document.get(edit.id),
+            Some(Shape { kind: ShapeKind::Text { content, .. }, .. }) if content.trim().is_empty()
+        );
+        if empty {
+            let _ = context.document.delete(edit.id);
+        } else if context.document.contains(edit.id) {
+            context.selection.select_only(edit.id);
+        }
+    }
+}
+
+/// A possibly backwards UTF-16 range as an ordered byte range.
+fn byte_range(text: &str, (start, end): (usize, usize)) -> Range<usize> {
+    let (start, end) = (utf16_to_byte(text, start), utf16_to_byte(text, end));
+    start.min(end)..start.max(end)
+}
+
+impl Tool for TextTool {
+    fn kind(&self) -> ToolKind {
+        ToolKind::Text
+    }
+
+    fn handle(&mut self, event: &InputEvent, context: &mut ToolContext) {
+        match (&mut self.state, event) {
+            (TextState::Idle, InputEvent::PointerDown(pointer)) if pointer.button == PointerButton::Primary => {
+                let at = context.to_world(pointer.screen);
+                match Self::text_at(at, context) {
+                    Some(id) => self.edit(id, context),
+                    None => self.state = TextState::Placing { at },
+                }
+            }
+            (TextState::Placing { at }, InputEvent::PointerUp(_)) => {
+                let kind = ShapeKind::Text {
+                    content: String::new(),
+                    font_size: self.font_size,
+                    align: self.align,
+                };
+                let transform = Self::fitted_transform(*at, "", self.font_size, context.fonts);
+                let id = context.document.insert(kind, transform, self.style.clone());
+                context.selection.select_only(id);
+                self.state = TextState::Editing(TextEdit {
+                    id,
+                    selection: 0..0,
+                    composition: None,
+                    original: None,
+                });
+            }
+            (TextState::Editing(edit), InputEvent::TextInput { content, selection, composition }) => {
+                Self::apply_input(edit, content, *selection, *composition, context);
+            }
+            // Clicking anywhere on the canvas finishes the edit; the text
+            // field itself sits over the shape and takes clicks inside it.
+            (TextState::Editing(_), InputEvent::PointerDown(_)) => self.commit(context),
+            _ => {}
+        }
+    }
+
+    fn cancel(&mut self, context: &mut ToolContext) {
+        match self.state {
+            TextState::Editing(_) => self.commit(context),
+            _ => self.state = TextState::Idle,
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.state == TextState::Idle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Editor;
+    use crate::tools::{Modifiers, PointerInput};
+
+    fn click(editor: &mut Editor, x: f32, y: f32) {
+        let pointer = PointerInput::new(Point::new(x, y));
+        editor.handle_input(InputEvent::PointerDown(pointer));
+        editor.handle_input(InputEvent::PointerUp(pointer));
+    }
+
+    fn type_text(editor: &mut Editor, content: &str) {
+        let end = content.encode_utf16().count();
+        editor.handle_input(InputEvent::TextInput {
+            content: content.to_string(),
+            selection: (end, end),
+            composition: None,
+        });
+    }
+
+    fn content(editor: &Editor, id: ShapeId) -> Option<String> {
+        match &editor.document.get(id)?.kind {
+            ShapeKind::Text { content, .. } => Some(content.clone()),
+            _ => None,
+        }
+    }
+
+    fn escape() -> InputEvent {
+        InputEvent::KeyDown {
+            key: "Escape".to_string(),
+            modifiers: Modifiers::default(),
+            repeat: false,
+        }
+    }
+
+    #[test]
+    fn test_typing_is_one_undo_step() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.fonts = crate::text::test_fonts();
+        editor.set_tool(ToolKind::Text);
+        click(&mut editor, 100.0, 100.0);
+        let id = editor.tools.text.editing().unwrap().id;
+        for typed in ["H", "Hi", "Hi\nthere"] {
+            type_text(&mut editor, typed);
+        }
+        assert!(!editor.can_undo());
+        let shape = editor.document.get(id).unwrap();
+        assert_eq!(shape.transform.height, 2.0 * layout(&editor.fonts, "x", 24.0, None, TextAlign::Left).height);
+        assert!(shape.transform.width > 0.0);
+
+        editor.handle_input(escape());
+        assert!(editor.tools.text.editing().is_none());
+        assert!(editor.selection.contains(id));
+        assert_eq!(content(&editor, id).as_deref(), Some("Hi\nthere"));
+        assert!(editor.undo().unwrap());
+        assert!(editor.document.is_empty());
+        assert!(!editor.can_undo());
+    }
+
+    #[test]
+    fn test_editing_existing_text_keeps_width() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.fonts = crate::text::test_fonts();
+        let kind = ShapeKind::Text {
+            content: "short".to_string(),
+            font_size: 20.0,
+            align: TextAlign::Left,
+        };
+        let id = editor.document.insert(kind, Transform::new(-50.0, -10.0, 100.0, 25.0), Style::default());
+        editor.sync_index();
+        editor.set_tool(ToolKind::Text);
+        // The camera is centred on the origin, so the shape is under the canvas centre.
+        click(&mut editor, 400.0, 300.0);
+        let edit = editor.tools.text.editing().unwrap();
+        assert_eq!((edit.id, edit.selection.clone()), (id, 5..5));
+
+        type_text(&mut editor, "a much longer line that wraps");
+        let shape = editor.document.get(id).unwrap();
+        assert_eq!(shape.transform.width, 100.0);
+        assert!(shape.transform.height > 25.0);
+
+        // Clicking elsewhere commits rather than placing new text.
+        click(&mut editor, 700.0, 500.0);
+        assert!(editor.tools.text.is_idle());
+        assert_eq!(editor.document.len(), 1);
+        assert!(editor.undo().unwrap());
+        assert_eq!(content(&editor, id).as_deref(), Some("short"));
+    }
+
+    #[test]
+    fn test_composition_and_selection_offsets() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.set_tool(ToolKind::Text);
+        click(&mut editor, 100.0, 100.0);
+        // UTF-16 offsets: the emoji is two units, "にほ" one each.
+        editor.handle_input(InputEvent::TextInput {
+            content: "\u{1F600}にほ".to_string(),
+            selection: (4, 2),
+            composition: Some((2, 4)),
+        });
+        let edit = editor.tools.text.editing().unwrap();
+        assert_eq!(edit.selection, 4..10);
+        assert_eq!(edit.composition, Some(4..10));
+    }
+
+    #[test]
+    fn test_empty_text_is_discarded() {
+        let mut editor = Editor::new(800.0, 600.0);
+        editor.set_tool(ToolKind::Text);
+        click(&mut editor, 100.0, 100.0);
+        type_text(&mut editor, "  ");
+        editor.set_tool(ToolKind::Select);
+        assert!(editor.document.is_empty());
+        assert!(!editor.can_undo());
+    }
+}